@@ -3,6 +3,9 @@
 # Build script for NEAR cross-chain escrow contracts
 set -e
 
+# Embedded in contract_version() by each contract
+export GIT_COMMIT_HASH=$(git rev-parse --short HEAD 2>/dev/null || echo unknown)
+
 echo "🔨 Building NEAR Cross-Chain Escrow Contracts..."
 
 # Colors for output
@@ -1,30 +1,95 @@
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
 use near_sdk::collections::Vector;
+use near_sdk::json_types::{Base64VecU8, U128, U64};
 use near_sdk::serde::{Deserialize, Serialize};
 use near_sdk::{
-    env, ext_contract, near_bindgen, AccountId, Gas, NearToken, PanicOnDefault, Promise,
+    env, ext_contract, is_promise_success, near_bindgen, AccountId, Gas, NearToken, PanicOnDefault,
+    Promise, PromiseOrValue,
 };
 use sha2::{Digest, Sha256};
 
-/// Get the contract WASM code for deployment
-fn get_contract_wasm() -> Vec<u8> {
-    #[cfg(feature = "include-wasm")]
-    {
-        include_bytes!("../../target/near/escrow_dst/escrow_dst.wasm").to_vec()
+#[cfg(any(test, feature = "test-time"))]
+thread_local! {
+    static TIME_OVERRIDE: std::cell::Cell<Option<u64>> = const { std::cell::Cell::new(None) };
+}
+
+/// Pin the clock seen by the phase checks, None restores the block timestamp (tests and test-time builds only)
+#[cfg(any(test, feature = "test-time"))]
+pub fn set_time_override(timestamp_ms: Option<u64>) {
+    TIME_OVERRIDE.with(|time| time.set(timestamp_ms));
+}
+
+/// Current time in ms for timelock checks, the block timestamp unless a test clock is set
+fn now_ms() -> u64 {
+    #[cfg(any(test, feature = "test-time"))]
+    if let Some(timestamp_ms) = TIME_OVERRIDE.with(|time| time.get()) {
+        return timestamp_ms;
     }
-    #[cfg(not(feature = "include-wasm"))]
-    {
-        // This should not be called in practice without the WASM
-        env::panic_str("Contract WASM not available - rebuild with include-wasm feature")
+    env::block_timestamp_ms()
+}
+
+// The safety deposit recipient lives under its own key rather than in the Borsh state, so escrows
+// created by an older template still deserialize after the template is upgraded
+const SAFETY_DEPOSIT_RECIPIENT_KEY: &[u8] = b"safety_deposit_recipient";
+
+fn stored_safety_deposit_recipient() -> Option<AccountId> {
+    env::storage_read(SAFETY_DEPOSIT_RECIPIENT_KEY)
+        .map(|bytes| AccountId::try_from_slice(&bytes).expect("Invalid safety deposit recipient"))
+}
+
+fn store_safety_deposit_recipient(recipient: Option<AccountId>) {
+    match recipient {
+        Some(recipient) => {
+            env::storage_write(SAFETY_DEPOSIT_RECIPIENT_KEY, &borsh::to_vec(&recipient).unwrap());
+        }
+        None => {
+            env::storage_remove(SAFETY_DEPOSIT_RECIPIENT_KEY);
+        }
     }
 }
 
+// Per-swap correlation id echoed into every log line, also kept out of the Borsh state
+const CORRELATION_ID_KEY: &[u8] = b"correlation_id";
+
+fn stored_correlation_id() -> Option<String> {
+    env::storage_read(CORRELATION_ID_KEY).map(|bytes| String::from_utf8(bytes).expect("Invalid correlation id"))
+}
+
+fn store_correlation_id(correlation_id: Option<String>) {
+    match correlation_id {
+        Some(correlation_id) => {
+            env::storage_write(CORRELATION_ID_KEY, correlation_id.as_bytes());
+        }
+        None => {
+            env::storage_remove(CORRELATION_ID_KEY);
+        }
+    }
+}
+
+/// Correlation id for log lines, "none" for swaps created without one
+fn correlation_label() -> String {
+    stored_correlation_id().unwrap_or_else(|| "none".to_string())
+}
+
 #[cfg(not(target_arch = "wasm32"))]
 use near_sdk::schemars::{self, JsonSchema};
 
 // Gas constants for cross-contract calls
-const NEP141_TRANSFER_GAS: Gas = Gas::from_tgas(5); // 5 TGas
-const CALLBACK_GAS: Gas = Gas::from_tgas(2); // 2 TGas
+pub const NEP141_TRANSFER_GAS: Gas = Gas::from_tgas(5); // 5 TGas
+pub const CALLBACK_GAS: Gas = Gas::from_tgas(2); // 2 TGas
+
+// Time the taker has after deployment to fund the escrow before anyone may cancel it as abandoned
+const DEFAULT_FUNDING_DEADLINE_SECS: u32 = 600;
+
+// Length of a hashlock preimage, matching the EVM escrows' bytes32 secret
+const SECRET_LEN: usize = 32;
+
+// Preimages anyone could guess, a hashlock committing to one of them is free to withdraw
+const WEAK_PREIMAGES: [&[u8]; 4] = [b"", &[0u8; SECRET_LEN], &[0xffu8; SECRET_LEN], &[1u8; SECRET_LEN]];
+
+// Upper bound on partial-fill parts, keeps Merkle proofs (and withdrawal gas) bounded
+const MAX_MERKLE_PARTS: u32 = 1024;
+const USED_INDEX_WORDS: usize = (MAX_MERKLE_PARTS as usize + 1).div_ceil(128);
 
 /// Copy of Immutables struct from factory
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug)]
@@ -36,9 +101,46 @@ pub struct Immutables {
     pub maker: AccountId,
     pub taker: AccountId, // Resolver address
     pub token: AccountId, // Token contract address ("near" for native NEAR)
-    pub amount: u128,     // Using u128 instead of Balance
-    pub safety_deposit: u128,
+    pub amount: U128, // Serialized as a string to avoid JSON precision loss
+    pub safety_deposit: U128,
     pub timelocks: Timelocks,
+    /// Accounts allowed to complete the B3 public withdrawal, None keeps it fully public
+    #[serde(default)]
+    pub public_withdrawal_allowlist: Option<Vec<AccountId>>,
+    /// Source and destination chain ids, None for escrows created without an order extension
+    #[serde(default)]
+    pub chain_context: Option<ChainContext>,
+    /// Hash of the secret behind `hashlock` (and Merkle leaves), sha256 unless the order says otherwise
+    #[serde(default)]
+    pub hash_algorithm: HashAlgo,
+}
+
+/// Hash function a hashlock commits with, declared by the order so both legs verify alike
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(JsonSchema))]
+#[serde(crate = "near_sdk::serde", rename_all = "lowercase")]
+pub enum HashAlgo {
+    #[default]
+    Sha256,
+    Keccak256,
+}
+
+impl HashAlgo {
+    fn digest(self, data: &[u8]) -> Vec<u8> {
+        match self {
+            HashAlgo::Sha256 => Sha256::digest(data).to_vec(),
+            HashAlgo::Keccak256 => env::keccak256(data),
+        }
+    }
+}
+
+/// Chains an escrow bridges, copied from the order extension for monitoring
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(JsonSchema))]
+#[serde(crate = "near_sdk::serde")]
+pub struct ChainContext {
+    pub src_chain_id: u64,
+    pub dst_chain_id: u64,
 }
 
 /// Timelock configuration
@@ -54,6 +156,9 @@ pub struct Timelocks {
     pub dst_withdrawal: u32,          // Destination chain withdrawal delay (B1)
     pub dst_public_withdrawal: u32,   // Destination chain public withdrawal delay (B2→B3)
     pub dst_cancellation: u32,        // Destination chain cancellation delay (B4)
+    /// Leniency around phase boundaries in ms, 0 disables it (see the escrow phase checks)
+    #[serde(default)]
+    pub grace_ms: u32,
 }
 
 /// Arguments for creating new escrow instances
@@ -63,6 +168,12 @@ pub struct Timelocks {
 pub struct CreateEscrowArgs {
     pub immutables: Immutables,
     pub factory: AccountId,
+    /// Funding deadline in seconds from `deployed_at`, DEFAULT_FUNDING_DEADLINE_SECS when omitted
+    #[serde(default)]
+    pub funding_deadline: Option<u32>,
+    /// Per-swap id echoed into this escrow's events, for tracing a swap across contracts
+    #[serde(default)]
+    pub correlation_id: Option<String>,
 }
 
 /// Escrow state tracking
@@ -74,8 +185,88 @@ pub struct EscrowState {
     pub is_withdrawn: bool,
     pub is_cancelled: bool,
     pub revealed_secret: Option<String>,
-    pub withdrawn_at: Option<u64>,
-    pub cancelled_at: Option<u64>,
+    pub withdrawn_at: Option<U64>,
+    pub cancelled_at: Option<U64>,
+    /// Who the terminal withdrawal or cancellation paid the safety deposit to (view only, stored
+    /// under SAFETY_DEPOSIT_RECIPIENT_KEY)
+    #[serde(default)]
+    #[borsh(skip)]
+    pub safety_deposit_recipient: Option<AccountId>,
+}
+
+/// Actions an account could perform right now, for front-ends deciding which buttons to show
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(JsonSchema))]
+#[serde(crate = "near_sdk::serde")]
+pub struct AuthorizedActions {
+    pub can_withdraw: bool,
+    pub can_public_withdraw: bool,
+    pub can_cancel: bool,
+}
+
+/// Phase, state and next phase opening in one view, for dashboards polling many escrows
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(JsonSchema))]
+#[serde(crate = "near_sdk::serde")]
+pub struct EscrowStatus {
+    pub phase: String,
+    pub state: EscrowState,
+    pub next_action: Option<(String, U64)>,
+}
+
+/// Settlement evidence for the opposite chain's leg, available once the escrow is withdrawn
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(JsonSchema))]
+#[serde(crate = "near_sdk::serde")]
+pub struct CompletionProof {
+    pub order_hash: String,
+    pub revealed_secret: String,
+    pub withdrawn_at: U64,
+    pub recipient: AccountId,
+}
+
+/// How a submitted secret's `value` is encoded
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(JsonSchema))]
+#[serde(crate = "near_sdk::serde")]
+pub enum SecretEncoding {
+    Hex,
+    Base64,
+    Raw, // The UTF-8 bytes of `value` itself
+}
+
+/// Secret preimage submitted to withdraw, decoded to exactly SECRET_LEN bytes before hashing
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(JsonSchema))]
+#[serde(crate = "near_sdk::serde")]
+pub struct Secret {
+    pub encoding: SecretEncoding,
+    pub value: String,
+}
+
+impl Secret {
+    /// Decoded preimage. A wrong encoding fails here instead of burning the withdrawal on a hash mismatch.
+    pub fn decode(&self) -> Vec<u8> {
+        let bytes = match self.encoding {
+            SecretEncoding::Hex => hex::decode(self.value.trim_start_matches("0x"))
+                .unwrap_or_else(|_| env::panic_str("E_INVALID_SECRET: not valid hex")),
+            SecretEncoding::Base64 => {
+                near_sdk::serde_json::from_value::<Base64VecU8>(near_sdk::serde_json::Value::String(
+                    self.value.clone(),
+                ))
+                .unwrap_or_else(|_| env::panic_str("E_INVALID_SECRET: not valid base64"))
+                .0
+            }
+            SecretEncoding::Raw => self.value.as_bytes().to_vec(),
+        };
+        assert_eq!(
+            bytes.len(),
+            SECRET_LEN,
+            "E_INVALID_SECRET: decoded secret must be {} bytes",
+            SECRET_LEN
+        );
+        bytes
+    }
 }
 
 /// Merkle proof for partial fills
@@ -96,11 +287,137 @@ pub struct EscrowInfo {
     pub maker: AccountId,
     pub taker: AccountId,
     pub token: AccountId,
-    pub amount: u128,
-    pub safety_deposit: u128,
+    pub amount: U128,
+    pub safety_deposit: U128,
     pub current_phase: String,
     pub state: EscrowState,
-    pub time_remaining: Option<u64>,
+    pub time_remaining: Option<U64>,
+    pub chain_context: Option<ChainContext>,
+}
+
+/// Merkle secret indices already consumed, one bit per index in 0..=MAX_MERKLE_PARTS
+#[derive(BorshDeserialize, BorshSerialize, Clone, Debug, Default, PartialEq)]
+pub struct UsedIndexBitmap {
+    words: [u128; USED_INDEX_WORDS],
+}
+
+impl UsedIndexBitmap {
+    fn bit(index: u32) -> (usize, u128) {
+        assert!(
+            index <= MAX_MERKLE_PARTS,
+            "Secret index exceeds maximum of {} parts",
+            MAX_MERKLE_PARTS
+        );
+        ((index / 128) as usize, 1u128 << (index % 128))
+    }
+
+    /// Mark `index` used, false if it already was
+    pub fn mark(&mut self, index: u32) -> bool {
+        let (word, mask) = Self::bit(index);
+        let fresh = self.words[word] & mask == 0;
+        self.words[word] |= mask;
+        fresh
+    }
+
+    /// Free `index` again, for a fill whose transfer failed
+    pub fn unmark(&mut self, index: u32) {
+        let (word, mask) = Self::bit(index);
+        self.words[word] &= !mask;
+    }
+
+    pub fn is_used(&self, index: u32) -> bool {
+        let (word, mask) = Self::bit(index);
+        self.words[word] & mask != 0
+    }
+
+    /// Number of parts filled so far
+    pub fn count(&self) -> u32 {
+        self.words.iter().map(|w| w.count_ones()).sum()
+    }
+}
+
+// Layout version of the escrow state, written by init and migrate. Escrows created before it
+// existed carry the original layout, which `migrate` converts once
+const STATE_VERSION_KEY: &[u8] = b"state_version";
+const STATE_VERSION: u8 = 1;
+
+fn store_state_version() {
+    env::storage_write(STATE_VERSION_KEY, &[STATE_VERSION]);
+}
+
+/// Immutables as laid out before amounts became U128 and the allowlist, chain context, hash
+/// algorithm and grace were added, read once by `migrate`
+#[derive(BorshDeserialize, BorshSerialize)]
+struct ImmutablesV0 {
+    order_hash: String,
+    hashlock: String,
+    maker: AccountId,
+    taker: AccountId,
+    token: AccountId,
+    amount: u128,
+    safety_deposit: u128,
+    timelocks: TimelocksV0,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+struct TimelocksV0 {
+    deployed_at: u64,
+    src_withdrawal: u32,
+    src_public_withdrawal: u32,
+    src_cancellation: u32,
+    src_public_cancellation: u32,
+    dst_withdrawal: u32,
+    dst_public_withdrawal: u32,
+    dst_cancellation: u32,
+}
+
+impl ImmutablesV0 {
+    fn upgrade(self) -> Immutables {
+        let timelocks = self.timelocks;
+        Immutables {
+            order_hash: self.order_hash,
+            hashlock: self.hashlock,
+            maker: self.maker,
+            taker: self.taker,
+            token: self.token,
+            amount: U128(self.amount),
+            safety_deposit: U128(self.safety_deposit),
+            timelocks: Timelocks {
+                deployed_at: timelocks.deployed_at,
+                src_withdrawal: timelocks.src_withdrawal,
+                src_public_withdrawal: timelocks.src_public_withdrawal,
+                src_cancellation: timelocks.src_cancellation,
+                src_public_cancellation: timelocks.src_public_cancellation,
+                dst_withdrawal: timelocks.dst_withdrawal,
+                dst_public_withdrawal: timelocks.dst_public_withdrawal,
+                dst_cancellation: timelocks.dst_cancellation,
+                grace_ms: 0,
+            },
+            public_withdrawal_allowlist: None,
+            chain_context: None,
+            hash_algorithm: HashAlgo::Sha256,
+        }
+    }
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+struct EscrowStateV0 {
+    is_funded: bool,
+    is_withdrawn: bool,
+    is_cancelled: bool,
+    revealed_secret: Option<String>,
+    withdrawn_at: Option<u64>,
+    cancelled_at: Option<u64>,
+}
+
+/// Escrow state as laid out before the used-index bitmap
+#[derive(BorshDeserialize, BorshSerialize)]
+struct EscrowDstV0 {
+    immutables: ImmutablesV0,
+    factory: AccountId,
+    state: EscrowStateV0,
+    merkle_root: Option<String>,
+    used_secret_indices: Vector<u32>,
 }
 
 // NEP-141 token interface
@@ -123,111 +440,190 @@ pub struct EscrowDst {
     pub factory: AccountId,
     pub state: EscrowState,
     pub merkle_root: Option<String>,      // For multiple fills
-    pub used_secret_indices: Vector<u32>, // Track used secrets for partial fills
+    pub used_secret_indices: UsedIndexBitmap, // Track used secrets for partial fills
+    pub funding_deadline: u32,            // Seconds from deployed_at the taker has to fund
 }
 
 #[near_bindgen]
 impl EscrowDst {
-    /// Create a new escrow instance (template factory method)
-    #[payable]
-    pub fn create_escrow(
-        &mut self,
-        escrow_account: AccountId,
-        immutables: Immutables,
-        factory: AccountId,
-    ) -> Promise {
-        // Only the factory should call this
-        Promise::new(escrow_account.clone())
-            .create_account()
-            .add_full_access_key(env::signer_account_pk())
-            .transfer(env::attached_deposit())
-            .deploy_contract(
-                get_contract_wasm(),
-            )
-            .function_call(
-                "init".to_string(),
-                near_sdk::serde_json::to_vec(&CreateEscrowArgs {
-                    immutables,
-                    factory,
-                })
-                .unwrap(),
-                NearToken::from_yoctonear(0),
-                Gas::from_tgas(30),
-            )
-    }
-
     /// Initialize the escrow contract (called by factory)
     #[init]
     #[payable]
     pub fn init(args: CreateEscrowArgs) -> Self {
         let immutables = args.immutables;
         let factory = args.factory;
+        let funding_deadline = args.funding_deadline.unwrap_or(DEFAULT_FUNDING_DEADLINE_SECS);
         // Verify that this is being called during contract deployment
         assert_eq!(
             env::predecessor_account_id(),
             factory,
             "Only factory can initialize escrow"
         );
+        // A zero deposit leaves nobody paid to finish or unwind the swap
+        assert!(
+            immutables.safety_deposit.0 > 0,
+            "E_ZERO_SAFETY_DEPOSIT: safety deposit must be non-zero"
+        );
+        assert_hashlock_not_weak(&immutables.hashlock, immutables.hash_algorithm);
 
         // Extract Merkle root if this supports multiple fills
-        let merkle_root = if immutables.hashlock.starts_with("merkle:") {
-            Some(immutables.hashlock[7..].to_string()) // Remove "merkle:" prefix
-        } else {
-            None
-        };
+        let merkle_root = merkle_root_for_init(&immutables.hashlock);
 
-        // Initialize state
+        // Native NEAR principal arrives with the factory's create transfer, NEP-141 through deposit_funds
         let state = EscrowState {
-            is_funded: false,
+            is_funded: immutables.token.as_str() == "near",
             is_withdrawn: false,
             is_cancelled: false,
             revealed_secret: None,
             withdrawn_at: None,
             cancelled_at: None,
+            safety_deposit_recipient: None,
         };
+        store_safety_deposit_recipient(None);
+        store_correlation_id(args.correlation_id);
+        store_state_version();
 
         Self {
             immutables,
             factory,
             state,
             merkle_root,
-            used_secret_indices: Vector::new("used_secrets".as_bytes()),
+            used_secret_indices: UsedIndexBitmap::default(),
+            funding_deadline,
         }
     }
 
-    /// Deposit funds after contract creation (for NEP-141 tokens)
+    /// Initialize from a Borsh-encoded immutables blob (compact alternative to `init`)
+    #[init]
+    #[payable]
+    pub fn init_from_blob(immutables_blob: Base64VecU8, factory: AccountId, correlation_id: Option<String>) -> Self {
+        let immutables =
+            Immutables::try_from_slice(&immutables_blob.0).expect("Invalid immutables blob");
+        Self::init(CreateEscrowArgs {
+            immutables,
+            factory,
+            funding_deadline: None,
+            correlation_id,
+        })
+    }
+
+    /// Upgrade hook for an escrow created before the used-index bitmap, called by the escrow
+    /// account once the template it runs is republished. Used indices, funding and progress carry
+    /// over; the version key makes a second run fail instead of misreading the new layout. A
+    /// Merkle escrow keeps its hashlock without a part count and withdraws as before, with any
+    /// index up to MAX_MERKLE_PARTS.
+    #[private]
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        assert!(env::storage_read(STATE_VERSION_KEY).is_none(), "E_ALREADY_MIGRATED: state is already at the current layout");
+        let mut old: EscrowDstV0 =
+            env::state_read().unwrap_or_else(|| env::panic_str("E_NO_STATE: nothing to migrate"));
+
+        let mut used_secret_indices = UsedIndexBitmap::default();
+        for index in old.used_secret_indices.iter() {
+            used_secret_indices.mark(index);
+        }
+        old.used_secret_indices.clear();
+
+        let state = EscrowState {
+            is_funded: old.state.is_funded,
+            is_withdrawn: old.state.is_withdrawn,
+            is_cancelled: old.state.is_cancelled,
+            revealed_secret: old.state.revealed_secret,
+            withdrawn_at: old.state.withdrawn_at.map(U64),
+            cancelled_at: old.state.cancelled_at.map(U64),
+            safety_deposit_recipient: None,
+        };
+        store_safety_deposit_recipient(None);
+        store_state_version();
+
+        Self {
+            immutables: old.immutables.upgrade(),
+            factory: old.factory,
+            state,
+            merkle_root: old.merkle_root,
+            used_secret_indices,
+            funding_deadline: DEFAULT_FUNDING_DEADLINE_SECS,
+        }
+    }
+
+    /// Deposit funds after contract creation (for NEP-141 tokens, a native escrow is funded at init)
     pub fn deposit_funds(&mut self) -> Promise {
         self.assert_taker();
+        self.assert_not_cancelled();
         assert!(!self.state.is_funded, "Already funded");
+        self.assert_funding_open();
+
+        // For NEP-141 tokens, transfer from taker
+        self.state.is_funded = true;
+        ext_nep141::ext(self.immutables.token.clone())
+            .with_static_gas(NEP141_TRANSFER_GAS)
+            .with_attached_deposit(NearToken::from_yoctonear(1)) // Yocto NEAR for storage
+            .ft_transfer_from(
+                self.immutables.taker.clone(),
+                env::current_account_id(),
+                self.immutables.amount.0.to_string(),
+                Some("Escrow deposit".to_string()),
+            )
+    }
 
-        if self.immutables.token.as_str() == "near" {
-            // For native NEAR, funds should already be attached during init
-            self.state.is_funded = true;
-            Promise::new(env::current_account_id()) // No-op promise
+    /// NEP-141 receiver, funds the escrow with a transfer of `immutables.token`.
+    /// Returns the amount to refund: everything for the wrong token, an underfunded transfer or an
+    /// escrow that no longer accepts funds, otherwise whatever exceeds `immutables.amount`
+    pub fn ft_on_transfer(
+        &mut self,
+        sender_id: AccountId,
+        amount: U128,
+        msg: String,
+    ) -> PromiseOrValue<U128> {
+        let token = env::predecessor_account_id();
+        let refund_reason = if self.immutables.token.as_str() == "near" || token != self.immutables.token {
+            Some("wrong token")
+        } else if self.state.is_funded || self.state.is_withdrawn || self.state.is_cancelled {
+            Some("escrow not accepting funds")
+        } else if now_ms() >= self.get_funding_deadline().0 {
+            Some("funding deadline passed")
+        } else if amount.0 < self.immutables.amount.0 {
+            Some("amount below escrow amount")
         } else {
-            // For NEP-141 tokens, transfer from taker
-            self.state.is_funded = true;
-            ext_nep141::ext(self.immutables.token.clone())
-                .with_static_gas(NEP141_TRANSFER_GAS)
-                .with_attached_deposit(NearToken::from_yoctonear(1)) // Yocto NEAR for storage
-                .ft_transfer_from(
-                    self.immutables.taker.clone(),
-                    env::current_account_id(),
-                    self.immutables.amount.to_string(),
-                    Some("Escrow deposit".to_string()),
-                )
+            None
+        };
+
+        if let Some(reason) = refund_reason {
+            env::log_str(&format!(
+                "DstEscrowFundingRefunded: order_hash={}, correlation_id={}, token={}, sender={}, amount={}, reason={}, msg={}",
+                self.immutables.order_hash, correlation_label(), token, sender_id, amount.0, reason, msg
+            ));
+            return PromiseOrValue::Value(amount);
         }
+
+        self.state.is_funded = true;
+        let excess = amount.0 - self.immutables.amount.0;
+        env::log_str(&format!(
+            "DstEscrowFunded: order_hash={}, correlation_id={}, sender={}, amount={}, refunded={}",
+            self.immutables.order_hash, correlation_label(), sender_id, self.immutables.amount.0, excess
+        ));
+        PromiseOrValue::Value(U128(excess))
     }
 
     /// Withdraw with secret (taker only, B2 phase)
-    pub fn withdraw(&mut self, secret: String, merkle_proof: Option<MerkleProof>) -> Promise {
+    /// `expected_immutables`, when given, must equal the stored immutables byte for byte, so a caller that
+    /// knows the swap (the resolver) refuses to release funds from an escrow whose state does not match
+    pub fn withdraw(
+        &mut self,
+        secret: Secret,
+        merkle_proof: Option<MerkleProof>,
+        expected_immutables: Option<Immutables>,
+    ) -> Promise {
         self.assert_taker();
+        self.assert_expected_immutables(expected_immutables.as_ref());
         self.assert_funded();
+        self.assert_secret_not_revealed();
         self.assert_not_withdrawn();
         self.assert_not_cancelled();
 
         // Check timelock - must be after finality lock, before cancellation
-        let current_time = env::block_timestamp_ms();
+        let current_time = now_ms();
         let withdrawal_start = self.get_timelock_timestamp(TimelockStage::DstWithdrawal);
         let cancellation_start = self.get_timelock_timestamp(TimelockStage::DstCancellation);
 
@@ -236,44 +632,64 @@ impl EscrowDst {
             "Finality lock not expired"
         );
         assert!(
-            current_time < cancellation_start,
+            current_time < cancellation_start + self.grace_ms(),
             "Cancellation period started"
         );
 
         // Verify secret
-        self.verify_secret(&secret, merkle_proof.as_ref());
+        let secret_bytes = secret.decode();
+        let fill_index = self.verify_secret(&secret_bytes, merkle_proof.as_ref());
+        let secret = hex::encode(&secret_bytes); // Revealed in canonical hex whatever the input encoding
 
-        // Update state
+        // Update state, reserving the Merkle index so a racing withdrawal for it is rejected
+        if let Some(index) = fill_index {
+            self.used_secret_indices.mark(index);
+        }
         self.state.is_withdrawn = true;
         self.state.revealed_secret = Some(secret.clone());
-        self.state.withdrawn_at = Some(current_time);
+        self.state.withdrawn_at = Some(U64(current_time));
+        store_safety_deposit_recipient(Some(env::predecessor_account_id()));
 
         // Log withdrawal event
         env::log_str(&format!(
-            "EscrowWithdrawal: order_hash={}, secret={}, withdrawn_by={}",
+            "EscrowWithdrawal: order_hash={}, correlation_id={}, secret={}, withdrawn_by={}",
             self.immutables.order_hash,
+            correlation_label(),
             secret,
             env::predecessor_account_id()
         ));
 
         // Transfer funds to maker and safety deposit to caller
+        // The callback pays the safety deposit only if the principal moved; a failed transfer
+        // rolls the withdrawal back and frees the Merkle index, escrow_withdrawn is logged on success
         self.transfer_funds_to_maker()
-            .then(self.transfer_safety_deposit())
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(CALLBACK_GAS)
+                    .on_withdrawal_transfer(fill_index, env::predecessor_account_id()),
+            )
     }
 
     /// Public withdraw with secret (anyone with access token, B3 phase)
     pub fn public_withdraw(
         &mut self,
-        secret: String,
+        secret: Secret,
         merkle_proof: Option<MerkleProof>,
     ) -> Promise {
-        // Note: In EVM, this requires access token. For NEAR, we'll allow anyone during public phase
+        // Note: In EVM, this requires access token. For NEAR, anyone may call unless an allowlist is set
+        if let Some(allowlist) = &self.immutables.public_withdrawal_allowlist {
+            assert!(
+                allowlist.contains(&env::predecessor_account_id()),
+                "Caller is not allowed to public withdraw"
+            );
+        }
         self.assert_funded();
+        self.assert_secret_not_revealed();
         self.assert_not_withdrawn();
         self.assert_not_cancelled();
 
         // Check timelock - must be in public withdrawal phase
-        let current_time = env::block_timestamp_ms();
+        let current_time = now_ms();
         let public_withdrawal_start =
             self.get_timelock_timestamp(TimelockStage::DstPublicWithdrawal);
         let cancellation_start = self.get_timelock_timestamp(TimelockStage::DstCancellation);
@@ -283,29 +699,42 @@ impl EscrowDst {
             "Public withdrawal period not started"
         );
         assert!(
-            current_time < cancellation_start,
+            current_time < cancellation_start + self.grace_ms(),
             "Cancellation period started"
         );
 
         // Verify secret
-        self.verify_secret(&secret, merkle_proof.as_ref());
+        let secret_bytes = secret.decode();
+        let fill_index = self.verify_secret(&secret_bytes, merkle_proof.as_ref());
+        let secret = hex::encode(&secret_bytes); // Revealed in canonical hex whatever the input encoding
 
-        // Update state
+        // Update state, reserving the Merkle index so a racing withdrawal for it is rejected
+        if let Some(index) = fill_index {
+            self.used_secret_indices.mark(index);
+        }
         self.state.is_withdrawn = true;
         self.state.revealed_secret = Some(secret.clone());
-        self.state.withdrawn_at = Some(current_time);
+        self.state.withdrawn_at = Some(U64(current_time));
+        store_safety_deposit_recipient(Some(env::predecessor_account_id()));
 
         // Log withdrawal event
         env::log_str(&format!(
-            "EscrowPublicWithdrawal: order_hash={}, secret={}, withdrawn_by={}",
+            "EscrowPublicWithdrawal: order_hash={}, correlation_id={}, secret={}, withdrawn_by={}",
             self.immutables.order_hash,
+            correlation_label(),
             secret,
             env::predecessor_account_id()
         ));
 
         // Transfer funds to maker and safety deposit to caller
+        // The callback pays the safety deposit only if the principal moved; a failed transfer
+        // rolls the withdrawal back and frees the Merkle index, escrow_withdrawn is logged on success
         self.transfer_funds_to_maker()
-            .then(self.transfer_safety_deposit())
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(CALLBACK_GAS)
+                    .on_withdrawal_transfer(fill_index, env::predecessor_account_id()),
+            )
     }
 
     /// Cancel escrow (taker only, B4 phase)
@@ -316,22 +745,25 @@ impl EscrowDst {
         self.assert_not_cancelled();
 
         // Check timelock - must be in cancellation phase
-        let current_time = env::block_timestamp_ms();
+        let current_time = now_ms();
         let cancellation_start = self.get_timelock_timestamp(TimelockStage::DstCancellation);
 
+        // Opens grace_ms late so it never overlaps a lenient withdrawal
         assert!(
-            current_time >= cancellation_start,
+            current_time >= cancellation_start + self.grace_ms(),
             "Cancellation period not started"
         );
 
         // Update state
         self.state.is_cancelled = true;
-        self.state.cancelled_at = Some(current_time);
+        self.state.cancelled_at = Some(U64(current_time));
+        store_safety_deposit_recipient(Some(env::predecessor_account_id()));
 
         // Log cancellation event
         env::log_str(&format!(
-            "EscrowCancelled: order_hash={}, cancelled_by={}",
+            "EscrowCancelled: order_hash={}, correlation_id={}, cancelled_by={}",
             self.immutables.order_hash,
+            correlation_label(),
             env::predecessor_account_id()
         ));
 
@@ -340,33 +772,104 @@ impl EscrowDst {
             .then(self.transfer_safety_deposit())
     }
 
-    /// Emergency fund rescue (taker only, after rescue delay)
-    pub fn rescue_funds(&mut self, token: AccountId, amount: u128) -> Promise {
+    /// Cancel an escrow the taker never funded (anyone, after the funding deadline). Nothing is
+    /// held but the safety deposit, which goes to the caller; the event signals the abandonment.
+    pub fn cancel_unfunded(&mut self) -> Promise {
+        assert!(!self.state.is_funded, "Escrow is funded");
+        self.assert_not_cancelled();
+        let current_time = now_ms();
+        assert!(
+            current_time >= self.get_funding_deadline().0,
+            "Funding deadline not reached"
+        );
+
+        self.state.is_cancelled = true;
+        self.state.cancelled_at = Some(U64(current_time));
+        store_safety_deposit_recipient(Some(env::predecessor_account_id()));
+
+        env::log_str(&format!(
+            "DstEscrowAbandoned: order_hash={}, correlation_id={}, taker={}, cancelled_by={}",
+            self.immutables.order_hash,
+            correlation_label(),
+            self.immutables.taker,
+            env::predecessor_account_id()
+        ));
+
+        self.transfer_safety_deposit()
+    }
+
+    /// Emergency fund rescue (taker only, after rescue delay). NEAR up to `get_excess_balance` was
+    /// never part of the swap and can be rescued at any time while the escrow is open.
+    pub fn rescue_funds(&mut self, token: AccountId, amount: U128) -> Promise {
         self.assert_taker();
 
         // Check rescue delay (similar to EVM BaseEscrow.rescueFunds)
-        let current_time = env::block_timestamp_ms();
+        let current_time = now_ms();
         let rescue_start = self.immutables.timelocks.deployed_at + (30 * 24 * 60 * 60 * 1000); // 30 days in milliseconds
+        let is_excess = token.as_str() == "near" && amount.0 <= self.get_excess_balance().0;
 
-        assert!(current_time >= rescue_start, "Rescue delay not expired");
+        assert!(is_excess || current_time >= rescue_start, "Rescue delay not expired");
 
-        env::log_str(&format!("FundsRescued: token={}, amount={}", token, amount));
+        env::log_str(&format!(
+            "FundsRescued: token={}, amount={}, correlation_id={}",
+            token,
+            amount.0,
+            correlation_label()
+        ));
 
         if token.as_str() == "near" {
-            Promise::new(self.immutables.taker.clone()).transfer(NearToken::from_yoctonear(amount))
+            Promise::new(self.immutables.taker.clone()).transfer(NearToken::from_yoctonear(amount.0))
         } else {
             ext_nep141::ext(token)
                 .with_static_gas(NEP141_TRANSFER_GAS)
                 .with_attached_deposit(NearToken::from_yoctonear(1))
                 .ft_transfer(
                     self.immutables.taker.clone(),
-                    amount.to_string(),
+                    amount.0.to_string(),
                     Some("Emergency rescue".to_string()),
                 )
         }
     }
 
+    /// Pays the safety deposit once the principal transfer succeeded. A failed transfer pays nothing
+    /// and rolls the withdrawal back, freeing its Merkle index for a retry.
+    #[private]
+    pub fn on_withdrawal_transfer(
+        &mut self,
+        fill_index: Option<u32>,
+        safety_deposit_recipient: AccountId,
+    ) -> PromiseOrValue<()> {
+        if is_promise_success() {
+            self.log_withdrawn(fill_index, &safety_deposit_recipient);
+            return PromiseOrValue::Promise(
+                Promise::new(safety_deposit_recipient)
+                    .transfer(NearToken::from_yoctonear(self.immutables.safety_deposit.0)),
+            );
+        }
+        if let Some(index) = fill_index {
+            self.used_secret_indices.unmark(index);
+        }
+        self.state.is_withdrawn = false;
+        self.state.revealed_secret = None;
+        self.state.withdrawn_at = None;
+        store_safety_deposit_recipient(None);
+        env::log_str(&format!(
+            "WithdrawalTransferFailed: order_hash={}, correlation_id={}, index {:?} freed, safety deposit kept",
+            self.immutables.order_hash, correlation_label(), fill_index
+        ));
+        PromiseOrValue::Value(())
+    }
+
     // View methods
+    /// Deployed code version, `<package version>+<git hash>` for upgrade coordination
+    pub fn contract_version(&self) -> String {
+        format!(
+            "{}+{}",
+            env!("CARGO_PKG_VERSION"),
+            option_env!("GIT_COMMIT_HASH").unwrap_or("unknown")
+        )
+    }
+
     pub fn get_escrow_info(&self) -> EscrowInfo {
         let current_phase = self.get_current_phase();
         let time_remaining = self.get_time_remaining();
@@ -379,13 +882,19 @@ impl EscrowDst {
             amount: self.immutables.amount,
             safety_deposit: self.immutables.safety_deposit,
             current_phase,
-            state: self.state.clone(),
+            state: self.get_state(),
             time_remaining,
+            chain_context: self.immutables.chain_context.clone(),
         }
     }
 
+    /// Timestamp (ms) after which an unfunded escrow may be cancelled as abandoned
+    pub fn get_funding_deadline(&self) -> U64 {
+        U64(self.immutables.timelocks.deployed_at + self.funding_deadline as u64 * 1000)
+    }
+
     pub fn get_current_phase(&self) -> String {
-        let current_time = env::block_timestamp_ms();
+        let current_time = now_ms();
         let withdrawal_start = self.get_timelock_timestamp(TimelockStage::DstWithdrawal);
         let public_withdrawal_start =
             self.get_timelock_timestamp(TimelockStage::DstPublicWithdrawal);
@@ -402,44 +911,152 @@ impl EscrowDst {
         }
     }
 
-    pub fn get_time_remaining(&self) -> Option<u64> {
-        let current_time = env::block_timestamp_ms();
+    pub fn get_time_remaining(&self) -> Option<U64> {
+        let current_time = now_ms();
         let current_phase = self.get_current_phase();
 
         match current_phase.as_str() {
             "B1_FINALITY_LOCK" => {
                 let next_phase_start = self.get_timelock_timestamp(TimelockStage::DstWithdrawal);
-                Some(next_phase_start.saturating_sub(current_time))
+                Some(U64(next_phase_start.saturating_sub(current_time)))
             }
             "B2_RESOLVER_EXCLUSIVE" => {
                 let next_phase_start =
                     self.get_timelock_timestamp(TimelockStage::DstPublicWithdrawal);
-                Some(next_phase_start.saturating_sub(current_time))
+                Some(U64(next_phase_start.saturating_sub(current_time)))
             }
             "B3_PUBLIC_WITHDRAWAL" => {
                 let next_phase_start = self.get_timelock_timestamp(TimelockStage::DstCancellation);
-                Some(next_phase_start.saturating_sub(current_time))
+                Some(U64(next_phase_start.saturating_sub(current_time)))
             }
             _ => None,
         }
     }
 
+    /// Soonest upcoming phase opening as (event, timestamp ms), None once settled or past the last phase
+    pub fn next_action_timestamp(&self) -> Option<(String, U64)> {
+        if self.state.is_withdrawn || self.state.is_cancelled {
+            return None;
+        }
+
+        let current_time = now_ms();
+        [
+            ("withdrawal", TimelockStage::DstWithdrawal),
+            ("public_withdrawal", TimelockStage::DstPublicWithdrawal),
+            ("cancellation", TimelockStage::DstCancellation),
+        ]
+        .into_iter()
+        .map(|(event, stage)| (event, self.get_timelock_timestamp(stage)))
+        .find(|(_, timestamp)| *timestamp > current_time)
+        .map(|(event, timestamp)| (event.to_string(), U64(timestamp)))
+    }
+
+    /// `get_current_phase`, `get_state` and `next_action_timestamp` in a single call
+    pub fn get_status(&self) -> EscrowStatus {
+        EscrowStatus {
+            phase: self.get_current_phase(),
+            state: self.get_state(),
+            next_action: self.next_action_timestamp(),
+        }
+    }
+
+    /// Mirrors the checks in withdraw, public_withdraw and cancel for `account` at the current time
+    pub fn authorized_actions(&self, account: AccountId) -> AuthorizedActions {
+        let current_time = now_ms();
+        let grace = self.grace_ms();
+        let open = self.state.is_funded && !self.state.is_withdrawn && !self.state.is_cancelled;
+        let withdrawal_start = self.get_timelock_timestamp(TimelockStage::DstWithdrawal);
+        let public_withdrawal_start =
+            self.get_timelock_timestamp(TimelockStage::DstPublicWithdrawal);
+        let cancellation_start = self.get_timelock_timestamp(TimelockStage::DstCancellation);
+        let is_taker = account == self.immutables.taker;
+        let allowed_public = match &self.immutables.public_withdrawal_allowlist {
+            Some(allowlist) => allowlist.contains(&account),
+            None => true,
+        };
+
+        AuthorizedActions {
+            can_withdraw: open
+                && is_taker
+                && current_time >= withdrawal_start
+                && current_time < cancellation_start + grace,
+            can_public_withdraw: open
+                && allowed_public
+                && current_time >= public_withdrawal_start
+                && current_time < cancellation_start + grace,
+            can_cancel: open && is_taker && current_time >= cancellation_start + grace,
+        }
+    }
+
+    pub fn get_completion_proof(&self) -> Option<CompletionProof> {
+        if !self.state.is_withdrawn {
+            return None;
+        }
+        Some(CompletionProof {
+            order_hash: self.immutables.order_hash.clone(),
+            revealed_secret: self.state.revealed_secret.clone()?,
+            withdrawn_at: self.state.withdrawn_at?,
+            recipient: self.immutables.maker.clone(),
+        })
+    }
+
+    /// Account paid the safety deposit by the terminal withdrawal or cancellation, None while the
+    /// escrow is open or when no safety deposit was paid out
+    pub fn get_safety_deposit_recipient(&self) -> Option<AccountId> {
+        stored_safety_deposit_recipient()
+    }
+
+    /// NEAR held beyond the native principal and safety deposit attached at init and the storage
+    /// stake, e.g. sent straight to the escrow account outside the funding flow. 0 once withdrawn or
+    /// cancelled, while the payouts may still bounce back.
+    pub fn get_excess_balance(&self) -> U128 {
+        if self.state.is_withdrawn || self.state.is_cancelled {
+            return U128(0);
+        }
+        let principal = if self.immutables.token.as_str() == "near" {
+            self.immutables.amount.0
+        } else {
+            0
+        };
+        let safety_deposit = self.immutables.safety_deposit.0;
+        let storage_stake = env::storage_usage() as u128 * env::storage_byte_cost().as_yoctonear();
+        U128(
+            env::account_balance()
+                .as_yoctonear()
+                .saturating_sub(principal + safety_deposit + storage_stake),
+        )
+    }
+
     pub fn supports_partial_fills(&self) -> bool {
         self.merkle_root.is_some()
     }
 
+    /// Partial-fill parts consumed so far, 0 for single-fill escrows
+    pub fn get_filled_parts(&self) -> u32 {
+        self.used_secret_indices.count()
+    }
+
+    pub fn get_state(&self) -> EscrowState {
+        EscrowState { safety_deposit_recipient: stored_safety_deposit_recipient(), ..self.state.clone() }
+    }
+
+    /// Immutables the escrow was initialized with, for checking the factory passed them on unchanged
+    pub fn get_immutables(&self) -> Immutables {
+        self.immutables.clone()
+    }
+
     // Private helper methods
-    fn verify_secret(&mut self, secret: &str, merkle_proof: Option<&MerkleProof>) {
+    /// Checks the secret without touching state, returns the Merkle index a partial fill will consume
+    fn verify_secret(&self, secret: &[u8], merkle_proof: Option<&MerkleProof>) -> Option<u32> {
         if let Some(merkle_root) = &self.merkle_root {
             // Partial fill with Merkle proof
             let proof = merkle_proof.expect("Merkle proof required for partial fills");
 
+            let parts = parse_merkle_hashlock(&self.immutables.hashlock).and_then(|(parts, _)| parts);
+            assert_merkle_proof_shape(proof, parts);
             // Check if this secret index was already used
             assert!(
-                !self
-                    .used_secret_indices
-                    .iter()
-                    .any(|used_idx| used_idx == proof.index),
+                !self.used_secret_indices.is_used(proof.index),
                 "Secret index already used"
             );
 
@@ -450,8 +1067,7 @@ impl EscrowDst {
                 "Invalid Merkle proof"
             );
 
-            // Mark secret index as used
-            self.used_secret_indices.push(&proof.index);
+            Some(proof.index)
         } else {
             // Single fill - direct hash comparison
             let secret_hash = self.hash_secret(secret);
@@ -459,12 +1075,12 @@ impl EscrowDst {
                 hex::decode(&self.immutables.hashlock).expect("Invalid hashlock format");
 
             assert_eq!(secret_hash, expected_hash, "Invalid secret");
+            None
         }
     }
 
-    fn hash_secret(&self, secret: &str) -> Vec<u8> {
-        let secret_bytes = hex::decode(secret).expect("Invalid secret format");
-        Sha256::digest(&secret_bytes).to_vec()
+    fn hash_secret(&self, secret: &[u8]) -> Vec<u8> {
+        self.immutables.hash_algorithm.digest(secret)
     }
 
     fn verify_merkle_proof(&self, leaf: &[u8], proof: &[String], index: u32, root: &str) -> bool {
@@ -489,10 +1105,21 @@ impl EscrowDst {
             current_index /= 2;
         }
 
+        // A proof shorter than the tree depth stops at an intermediate node, leaving index bits unconsumed
+        assert_eq!(current_index, 0, "Merkle proof does not reach the root for index {}", index);
+
         let computed_root = hex::encode(&hash);
         computed_root == root
     }
 
+    /// Grace applied to phase boundaries, in the safe direction only: withdrawals with a valid
+    /// secret stay open up to grace_ms past cancellation start and cancellation opens grace_ms late.
+    /// Trade-off: the maker-side src escrow keeps its own deadlines, so the resolver must leave
+    /// more than grace_ms between dst cancellation and src cancellation.
+    fn grace_ms(&self) -> u64 {
+        self.immutables.timelocks.grace_ms as u64
+    }
+
     fn get_timelock_timestamp(&self, stage: TimelockStage) -> u64 {
         let delay_seconds = match stage {
             TimelockStage::DstWithdrawal => self.immutables.timelocks.dst_withdrawal,
@@ -506,14 +1133,14 @@ impl EscrowDst {
     fn transfer_funds_to_maker(&self) -> Promise {
         if self.immutables.token.as_str() == "near" {
             Promise::new(self.immutables.maker.clone())
-                .transfer(NearToken::from_yoctonear(self.immutables.amount))
+                .transfer(NearToken::from_yoctonear(self.immutables.amount.0))
         } else {
             ext_nep141::ext(self.immutables.token.clone())
                 .with_static_gas(NEP141_TRANSFER_GAS)
                 .with_attached_deposit(NearToken::from_yoctonear(1))
                 .ft_transfer(
                     self.immutables.maker.clone(),
-                    self.immutables.amount.to_string(),
+                    self.immutables.amount.0.to_string(),
                     Some("Escrow withdrawal to maker".to_string()),
                 )
         }
@@ -522,14 +1149,14 @@ impl EscrowDst {
     fn transfer_funds_to_taker(&self) -> Promise {
         if self.immutables.token.as_str() == "near" {
             Promise::new(self.immutables.taker.clone())
-                .transfer(NearToken::from_yoctonear(self.immutables.amount))
+                .transfer(NearToken::from_yoctonear(self.immutables.amount.0))
         } else {
             ext_nep141::ext(self.immutables.token.clone())
                 .with_static_gas(NEP141_TRANSFER_GAS)
                 .with_attached_deposit(NearToken::from_yoctonear(1))
                 .ft_transfer(
                     self.immutables.taker.clone(),
-                    self.immutables.amount.to_string(),
+                    self.immutables.amount.0.to_string(),
                     Some("Escrow cancellation to taker".to_string()),
                 )
         }
@@ -537,7 +1164,39 @@ impl EscrowDst {
 
     fn transfer_safety_deposit(&self) -> Promise {
         Promise::new(env::predecessor_account_id())
-            .transfer(NearToken::from_yoctonear(self.immutables.safety_deposit))
+            .transfer(NearToken::from_yoctonear(self.immutables.safety_deposit.0))
+    }
+
+    /// NEP-297 `escrow_withdrawn` event carrying the amounts the withdrawal transferred, for off-chain
+    /// reconciliation, logged once the principal moved. A partial fill releases the whole escrow
+    /// amount as its increment.
+    fn log_withdrawn(&self, fill_index: Option<u32>, safety_deposit_recipient: &AccountId) {
+        let event = near_sdk::serde_json::json!({
+            "standard": "1prime-escrow",
+            "version": "1.0.0",
+            "event": "escrow_withdrawn",
+            "data": [{
+                "order_hash": self.immutables.order_hash,
+                "token": self.immutables.token,
+                "recipient": self.immutables.maker,
+                "principal": self.immutables.amount,
+                "safety_deposit": self.immutables.safety_deposit,
+                "safety_deposit_recipient": safety_deposit_recipient,
+                "fill_index": fill_index,
+                "fill_amount": fill_index.map(|_| self.immutables.amount),
+                "correlation_id": stored_correlation_id(),
+            }],
+        });
+        env::log_str(&format!("EVENT_JSON:{}", event));
+    }
+
+    fn assert_expected_immutables(&self, expected: Option<&Immutables>) {
+        if let Some(expected) = expected {
+            assert!(
+                borsh::to_vec(expected).unwrap() == borsh::to_vec(&self.immutables).unwrap(),
+                "E_IMMUTABLES_MISMATCH: expected immutables differ from the escrow's"
+            );
+        }
     }
 
     // Access control helpers
@@ -557,9 +1216,96 @@ impl EscrowDst {
         assert!(!self.state.is_withdrawn, "Already withdrawn");
     }
 
+    /// A single-fill escrow is terminal once its secret is public, whatever secret is submitted next
+    fn assert_secret_not_revealed(&self) {
+        if self.merkle_root.is_none() {
+            assert!(
+                self.state.revealed_secret.is_none(),
+                "E_SECRET_ALREADY_REVEALED: escrow is terminal"
+            );
+        }
+    }
+
     fn assert_not_cancelled(&self) {
         assert!(!self.state.is_cancelled, "Already cancelled");
     }
+
+    /// Past the deadline the escrow may be cancelled as abandoned, funding it then would race that
+    fn assert_funding_open(&self) {
+        assert!(
+            now_ms() < self.get_funding_deadline().0,
+            "E_FUNDING_DEADLINE_PASSED: funding closed at {}",
+            self.get_funding_deadline().0
+        );
+    }
+}
+
+/// Levels of the tree over the `parts + 1` secrets of a partial fill, the length of every proof
+pub fn merkle_proof_depth(parts: u32) -> usize {
+    (parts + 1).next_power_of_two().trailing_zeros() as usize
+}
+
+/// Bounds `proof` by the order's part count. A legacy hashlock without one accepts any index and
+/// depth a MAX_MERKLE_PARTS tree allows, as escrows did before the count was recorded
+fn assert_merkle_proof_shape(proof: &MerkleProof, parts: Option<u32>) {
+    match parts {
+        Some(parts) => {
+            assert!(proof.index <= parts, "Secret index exceeds the order's {} parts", parts);
+            // A shorter proof would stop at an inner node, a longer one climb past the root
+            let depth = merkle_proof_depth(parts);
+            assert_eq!(proof.proof.len(), depth, "Merkle proof must be {} levels deep", depth);
+        }
+        None => {
+            assert!(
+                proof.index <= MAX_MERKLE_PARTS,
+                "Secret index exceeds maximum of {} parts",
+                MAX_MERKLE_PARTS
+            );
+            let max_depth = merkle_proof_depth(MAX_MERKLE_PARTS);
+            assert!(proof.proof.len() <= max_depth, "Merkle proof deeper than {}", max_depth);
+        }
+    }
+}
+
+/// Splits a partial-fill hashlock `merkle:<parts>:<root hex>` into its parts and root,
+/// None for a single-fill hashlock. The parts are None for the legacy `merkle:<root hex>` that
+/// migrated escrows still hold
+fn parse_merkle_hashlock(hashlock: &str) -> Option<(Option<u32>, String)> {
+    let rest = hashlock.strip_prefix("merkle:")?;
+    let Some((parts, root)) = rest.split_once(':') else {
+        return Some((None, rest.to_string()));
+    };
+    let parts: u32 = parts
+        .parse()
+        .unwrap_or_else(|_| env::panic_str("E_INVALID_MERKLE_HASHLOCK: parts is not a number"));
+    assert!(
+        parts > 0 && parts <= MAX_MERKLE_PARTS,
+        "E_INVALID_MERKLE_HASHLOCK: parts must be in 1..={}",
+        MAX_MERKLE_PARTS
+    );
+    Some((Some(parts), root.to_string()))
+}
+
+/// Root of a new escrow's partial-fill hashlock, which must carry its part count
+fn merkle_root_for_init(hashlock: &str) -> Option<String> {
+    parse_merkle_hashlock(hashlock).map(|(parts, root)| {
+        if parts.is_none() {
+            env::panic_str("E_INVALID_MERKLE_HASHLOCK: expected merkle:<parts>:<root>");
+        }
+        root
+    })
+}
+
+/// Rejects a single-fill hashlock committing to one of WEAK_PREIMAGES, a handful of hashes at init
+fn assert_hashlock_not_weak(hashlock: &str, hash_algorithm: HashAlgo) {
+    // "merkle:" roots are not a secret's hash, malformed hex is rejected at withdraw
+    let Ok(hashlock) = hex::decode(hashlock) else {
+        return;
+    };
+    assert!(
+        !WEAK_PREIMAGES.iter().any(|preimage| hash_algorithm.digest(preimage) == hashlock),
+        "E_WEAK_HASHLOCK: hashlock commits to a publicly known preimage"
+    );
 }
 
 /// Timelock stages for destination chain
@@ -568,3 +1314,599 @@ pub enum TimelockStage {
     DstPublicWithdrawal,
     DstCancellation,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+    use near_sdk::PromiseResult;
+
+    const DEPLOYED_AT: u64 = 1_700_000_000_000;
+    const SECRET: [u8; 32] = [7u8; 32];
+
+    fn immutables() -> Immutables {
+        Immutables {
+            order_hash: "0xorder".to_string(),
+            hashlock: hex::encode(Sha256::digest(SECRET)),
+            maker: "maker.testnet".parse().unwrap(),
+            taker: "resolver.testnet".parse().unwrap(),
+            token: "near".parse().unwrap(),
+            amount: U128(1_000),
+            safety_deposit: U128(100),
+            timelocks: Timelocks {
+                deployed_at: DEPLOYED_AT,
+                src_withdrawal: 60,
+                src_public_withdrawal: 120,
+                src_cancellation: 180,
+                src_public_cancellation: 240,
+                dst_withdrawal: 30,
+                dst_public_withdrawal: 90,
+                dst_cancellation: 150,
+                grace_ms: 0,
+            },
+            public_withdrawal_allowlist: None,
+            chain_context: None,
+            hash_algorithm: HashAlgo::Sha256,
+        }
+    }
+
+    fn escrow() -> EscrowDst {
+        let factory: AccountId = "factory.testnet".parse().unwrap();
+        testing_env!(VMContextBuilder::new().predecessor_account_id(factory.clone()).build());
+        EscrowDst::init(CreateEscrowArgs { immutables: immutables(), factory, funding_deadline: Some(60), correlation_id: None })
+    }
+
+    #[test]
+    fn test_next_action_follows_timeline() {
+        let mut escrow = escrow();
+        for (time, next) in [
+            (DEPLOYED_AT, Some(("withdrawal", DEPLOYED_AT + 30_000))),
+            (DEPLOYED_AT + 30_000, Some(("public_withdrawal", DEPLOYED_AT + 90_000))),
+            (DEPLOYED_AT + 120_000, Some(("cancellation", DEPLOYED_AT + 150_000))),
+            (DEPLOYED_AT + 150_000, None),
+        ] {
+            set_time_override(Some(time));
+            let expected = next.map(|(event, timestamp)| (event.to_string(), U64(timestamp)));
+            assert_eq!(escrow.next_action_timestamp(), expected, "at {}", time);
+        }
+
+        set_time_override(Some(DEPLOYED_AT));
+        escrow.state.is_cancelled = true;
+        assert_eq!(escrow.next_action_timestamp(), None);
+        set_time_override(None);
+    }
+
+    #[test]
+    fn test_contract_version_matches_package_version() {
+        let version = escrow().contract_version();
+        let (package, commit) = version.split_once('+').unwrap();
+        assert_eq!(package, env!("CARGO_PKG_VERSION"));
+        assert!(!commit.is_empty());
+    }
+
+    /// Writes an escrow in the original layout for `hashlock`, before the part count and bitmap
+    fn write_v0_escrow(hashlock: String, merkle_root: Option<String>, used_indices: &[u32]) {
+        testing_env!(VMContextBuilder::new().build());
+        let current = immutables();
+        let timelocks = current.timelocks;
+        let mut used_secret_indices = Vector::new("used_secrets".as_bytes());
+        for index in used_indices {
+            used_secret_indices.push(index);
+        }
+        env::state_write(&EscrowDstV0 {
+            immutables: ImmutablesV0 {
+                order_hash: current.order_hash,
+                hashlock,
+                maker: current.maker,
+                taker: current.taker,
+                token: current.token,
+                amount: current.amount.0,
+                safety_deposit: current.safety_deposit.0,
+                timelocks: TimelocksV0 {
+                    deployed_at: timelocks.deployed_at,
+                    src_withdrawal: timelocks.src_withdrawal,
+                    src_public_withdrawal: timelocks.src_public_withdrawal,
+                    src_cancellation: timelocks.src_cancellation,
+                    src_public_cancellation: timelocks.src_public_cancellation,
+                    dst_withdrawal: timelocks.dst_withdrawal,
+                    dst_public_withdrawal: timelocks.dst_public_withdrawal,
+                    dst_cancellation: timelocks.dst_cancellation,
+                },
+            },
+            factory: "factory.testnet".parse().unwrap(),
+            state: EscrowStateV0 {
+                is_funded: true,
+                is_withdrawn: false,
+                is_cancelled: false,
+                revealed_secret: None,
+                withdrawn_at: None,
+                cancelled_at: None,
+            },
+            merkle_root,
+            used_secret_indices,
+        });
+    }
+
+    #[test]
+    fn test_migrate_converts_original_layout() {
+        write_v0_escrow(immutables().hashlock, None, &[0]);
+        let escrow = EscrowDst::migrate();
+        assert!(escrow.used_secret_indices.is_used(0));
+        assert!(escrow.state.is_funded);
+        assert_eq!(escrow.immutables.amount, U128(1_000));
+        assert_eq!(escrow.immutables.timelocks.deployed_at, DEPLOYED_AT);
+        assert_eq!(escrow.funding_deadline, DEFAULT_FUNDING_DEADLINE_SECS);
+    }
+
+    #[test]
+    #[should_panic(expected = "E_ALREADY_MIGRATED")]
+    fn test_migrate_refuses_current_layout() {
+        escrow();
+        EscrowDst::migrate();
+    }
+
+    #[test]
+    fn test_migrated_legacy_merkle_hashlock_still_withdraws() {
+        // Single-leaf tree: the root is SECRET's hash and index 0 needs no siblings
+        let root = hex::encode(Sha256::digest(SECRET));
+        write_v0_escrow(format!("merkle:{}", root), Some(root), &[]);
+        let mut escrow = EscrowDst::migrate();
+
+        testing_env!(VMContextBuilder::new().predecessor_account_id("resolver.testnet".parse().unwrap()).build());
+        set_time_override(Some(DEPLOYED_AT + 30_000));
+        let secret = Secret { encoding: SecretEncoding::Hex, value: hex::encode(SECRET) };
+        escrow.withdraw(secret, Some(MerkleProof { proof: vec![], index: 0 }), None);
+        set_time_override(None);
+
+        assert!(escrow.state.is_withdrawn);
+        assert_eq!(escrow.state.revealed_secret, Some(hex::encode(SECRET)));
+    }
+
+    #[test]
+    fn test_phase_boundaries() {
+        let escrow = escrow();
+        for (time, phase, remaining) in [
+            (DEPLOYED_AT, "B1_FINALITY_LOCK", Some(30_000)),
+            (DEPLOYED_AT + 29_999, "B1_FINALITY_LOCK", Some(1)),
+            (DEPLOYED_AT + 30_000, "B2_RESOLVER_EXCLUSIVE", Some(60_000)),
+            (DEPLOYED_AT + 89_999, "B2_RESOLVER_EXCLUSIVE", Some(1)),
+            (DEPLOYED_AT + 90_000, "B3_PUBLIC_WITHDRAWAL", Some(60_000)),
+            (DEPLOYED_AT + 149_999, "B3_PUBLIC_WITHDRAWAL", Some(1)),
+            (DEPLOYED_AT + 150_000, "B4_CANCELLATION", None),
+        ] {
+            set_time_override(Some(time));
+            assert_eq!(escrow.get_current_phase(), phase, "at {}", time);
+            assert_eq!(escrow.get_time_remaining(), remaining.map(U64), "at {}", time);
+        }
+        set_time_override(None);
+    }
+
+    fn funded_escrow_with_allowlist() -> EscrowDst {
+        let mut escrow = escrow();
+        escrow.state.is_funded = true;
+        escrow.immutables.public_withdrawal_allowlist = Some(vec!["backup.testnet".parse().unwrap()]);
+        escrow
+    }
+
+    #[test]
+    fn test_allowlisted_caller_public_withdraws_in_b3() {
+        let mut escrow = funded_escrow_with_allowlist();
+        testing_env!(VMContextBuilder::new().predecessor_account_id("backup.testnet".parse().unwrap()).build());
+        set_time_override(Some(DEPLOYED_AT + 90_000));
+        assert!(escrow.authorized_actions("backup.testnet".parse().unwrap()).can_public_withdraw);
+        escrow.public_withdraw(Secret { encoding: SecretEncoding::Hex, value: hex::encode(SECRET) }, None);
+        assert!(escrow.state.is_withdrawn);
+        set_time_override(None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Caller is not allowed to public withdraw")]
+    fn test_unlisted_caller_public_withdraw_rejected_in_b3() {
+        let mut escrow = funded_escrow_with_allowlist();
+        testing_env!(VMContextBuilder::new().predecessor_account_id("random.testnet".parse().unwrap()).build());
+        set_time_override(Some(DEPLOYED_AT + 90_000));
+        assert!(!escrow.authorized_actions("random.testnet".parse().unwrap()).can_public_withdraw);
+        escrow.public_withdraw(Secret { encoding: SecretEncoding::Hex, value: hex::encode(SECRET) }, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "E_WEAK_HASHLOCK")]
+    fn test_hashlock_of_zero_preimage_rejected() {
+        let factory: AccountId = "factory.testnet".parse().unwrap();
+        testing_env!(VMContextBuilder::new().predecessor_account_id(factory.clone()).build());
+        let immutables = Immutables {
+            hashlock: hex::encode(env::keccak256(&[0u8; 32])),
+            hash_algorithm: HashAlgo::Keccak256,
+            ..immutables()
+        };
+        EscrowDst::init(CreateEscrowArgs { immutables, factory, funding_deadline: Some(60), correlation_id: None });
+    }
+
+    #[test]
+    fn test_authorized_actions_by_role_and_phase() {
+        let mut escrow = escrow();
+        escrow.state.is_funded = true;
+        let taker: AccountId = "resolver.testnet".parse().unwrap();
+        let maker: AccountId = "maker.testnet".parse().unwrap();
+        let random: AccountId = "random.testnet".parse().unwrap();
+        let actions = |withdraw, public_withdraw, cancel| AuthorizedActions {
+            can_withdraw: withdraw,
+            can_public_withdraw: public_withdraw,
+            can_cancel: cancel,
+        };
+
+        for (time, account, expected) in [
+            (DEPLOYED_AT, &taker, actions(false, false, false)),
+            (DEPLOYED_AT + 30_000, &taker, actions(true, false, false)),
+            (DEPLOYED_AT + 30_000, &maker, actions(false, false, false)),
+            (DEPLOYED_AT + 90_000, &random, actions(false, true, false)),
+            (DEPLOYED_AT + 90_000, &taker, actions(true, true, false)),
+            (DEPLOYED_AT + 150_000, &taker, actions(false, false, true)),
+            (DEPLOYED_AT + 150_000, &maker, actions(false, false, false)),
+        ] {
+            set_time_override(Some(time));
+            assert_eq!(escrow.authorized_actions(account.clone()), expected, "{} at {}", account, time);
+        }
+        set_time_override(None);
+    }
+
+    #[test]
+    #[should_panic(expected = "E_SECRET_ALREADY_REVEALED")]
+    fn test_second_withdraw_rejected_with_correct_secret() {
+        let mut escrow = escrow();
+        escrow.state.is_funded = true;
+        testing_env!(VMContextBuilder::new().predecessor_account_id("resolver.testnet".parse().unwrap()).build());
+        set_time_override(Some(DEPLOYED_AT + 30_000));
+
+        let secret = Secret { encoding: SecretEncoding::Hex, value: hex::encode(SECRET) };
+        escrow.withdraw(secret.clone(), None, None);
+        assert_eq!(escrow.state.revealed_secret, Some(hex::encode(SECRET)));
+
+        escrow.withdraw(secret, None, None);
+    }
+
+    #[test]
+    fn test_completion_proof_populated_after_withdrawal() {
+        let mut escrow = escrow();
+        escrow.state.is_funded = true;
+        assert!(escrow.get_completion_proof().is_none());
+
+        testing_env!(VMContextBuilder::new().predecessor_account_id("resolver.testnet".parse().unwrap()).build());
+        set_time_override(Some(DEPLOYED_AT + 30_000));
+        escrow.withdraw(Secret { encoding: SecretEncoding::Hex, value: hex::encode(SECRET) }, None, None);
+        set_time_override(None);
+
+        let proof = escrow.get_completion_proof().expect("no proof after withdrawal");
+        assert_eq!(proof.order_hash, "0xorder");
+        assert_eq!(proof.revealed_secret, hex::encode(SECRET));
+        assert_eq!(proof.withdrawn_at, U64(DEPLOYED_AT + 30_000));
+        assert_eq!(proof.recipient.as_str(), "maker.testnet");
+    }
+
+    #[test]
+    fn test_safety_deposit_recipient_recorded_on_withdrawal() {
+        let secret = Secret { encoding: SecretEncoding::Hex, value: hex::encode(SECRET) };
+        for (time, caller, public) in [
+            (DEPLOYED_AT + 30_000, "resolver.testnet", false),
+            (DEPLOYED_AT + 90_000, "random.testnet", true),
+        ] {
+            let mut escrow = escrow();
+            escrow.state.is_funded = true;
+            assert_eq!(escrow.get_safety_deposit_recipient(), None);
+            testing_env!(VMContextBuilder::new().predecessor_account_id(caller.parse().unwrap()).build());
+            set_time_override(Some(time));
+            if public {
+                escrow.public_withdraw(secret.clone(), None);
+            } else {
+                escrow.withdraw(secret.clone(), None, None);
+            }
+            let info = escrow.get_escrow_info();
+            set_time_override(None);
+
+            assert_eq!(escrow.get_safety_deposit_recipient(), Some(caller.parse().unwrap()));
+            assert_eq!(info.state.safety_deposit_recipient, Some(caller.parse().unwrap()));
+            // Reported once the principal transfer succeeded
+            assert!(!near_sdk::test_utils::get_logs().iter().any(|log| log.starts_with("EVENT_JSON:")));
+            testing_env!(
+                VMContextBuilder::new().build(),
+                near_sdk::test_vm_config(),
+                near_sdk::RuntimeFeesConfig::test(),
+                Default::default(),
+                vec![PromiseResult::Successful(vec![])],
+            );
+            escrow.on_withdrawal_transfer(None, caller.parse().unwrap());
+            let logs = near_sdk::test_utils::get_logs();
+            let event = logs.iter().find_map(|log| log.strip_prefix("EVENT_JSON:")).expect("no event logged");
+            let event: near_sdk::serde_json::Value = near_sdk::serde_json::from_str(event).unwrap();
+            assert_eq!(event["event"], "escrow_withdrawn");
+            assert_eq!(event["data"][0]["safety_deposit_recipient"], caller);
+        }
+    }
+
+    #[test]
+    fn test_secret_encodings_hash_alike() {
+        let raw = "0123456789abcdef0123456789abcdef";
+        let decoded: Vec<Vec<u8>> = [
+            (SecretEncoding::Hex, "0x3031323334353637383961626364656630313233343536373839616263646566"),
+            (SecretEncoding::Base64, "MDEyMzQ1Njc4OWFiY2RlZjAxMjM0NTY3ODlhYmNkZWY="),
+            (SecretEncoding::Raw, raw),
+        ]
+        .into_iter()
+        .map(|(encoding, value)| Secret { encoding, value: value.to_string() }.decode())
+        .collect();
+
+        assert!(decoded.iter().all(|bytes| bytes.as_slice() == raw.as_bytes()));
+        assert_eq!(Sha256::digest(&decoded[0]), Sha256::digest(&decoded[1]));
+    }
+
+    #[test]
+    #[should_panic(expected = "E_INVALID_SECRET")]
+    fn test_short_secret_rejected() {
+        Secret { encoding: SecretEncoding::Hex, value: hex::encode(b"secret") }.decode();
+    }
+
+    #[test]
+    #[should_panic(expected = "Merkle proof does not reach the root")]
+    fn test_truncated_merkle_proof_rejected() {
+        let escrow = escrow();
+        let leaves: Vec<Vec<u8>> = (0u8..4).map(|i| Sha256::digest([i; 32]).to_vec()).collect();
+        let node = |left: &[u8], right: &[u8]| Sha256::digest([left, right].concat()).to_vec();
+        let right_subtree = node(&leaves[2], &leaves[3]);
+
+        // Leaf 2 with only its sibling: one level short of the depth-2 root
+        escrow.verify_merkle_proof(&leaves[2], &[hex::encode(&leaves[3])], 2, &hex::encode(right_subtree));
+    }
+
+    #[test]
+    #[should_panic(expected = "Merkle proof must be 2 levels deep")]
+    fn test_short_merkle_proof_rejected() {
+        let mut escrow = escrow();
+        // Three parts make four secrets, two levels; index 0 proven only up to its parent
+        let leaves: Vec<Vec<u8>> = (0u8..2).map(|i| Sha256::digest([i; 32]).to_vec()).collect();
+        let parent = hex::encode(Sha256::digest([leaves[0].as_slice(), &leaves[1]].concat()));
+        escrow.immutables.hashlock = format!("merkle:3:{}", parent);
+        escrow.merkle_root = Some(parent);
+        escrow.verify_secret(&[0u8; 32], Some(&MerkleProof { proof: vec![hex::encode(&leaves[1])], index: 0 }));
+    }
+
+    #[test]
+    fn test_failed_partial_withdrawal_frees_index_for_retry() {
+        let mut escrow = escrow();
+        escrow.state.is_funded = true;
+        // Two-leaf tree, index 0 proven by its sibling
+        let sibling = Sha256::digest([9u8; 32]).to_vec();
+        let root = hex::encode(Sha256::digest([Sha256::digest(SECRET).as_slice(), &sibling].concat()));
+        escrow.immutables.hashlock = format!("merkle:1:{}", root);
+        escrow.merkle_root = Some(root);
+        let secret = Secret { encoding: SecretEncoding::Hex, value: hex::encode(SECRET) };
+        let proof = MerkleProof { proof: vec![hex::encode(sibling)], index: 0 };
+        let as_taker = || {
+            testing_env!(VMContextBuilder::new().predecessor_account_id("resolver.testnet".parse().unwrap()).build());
+        };
+        set_time_override(Some(DEPLOYED_AT + 30_000));
+
+        as_taker();
+        escrow.withdraw(secret.clone(), Some(proof.clone()), None);
+        assert!(escrow.used_secret_indices.is_used(0));
+
+        testing_env!(
+            VMContextBuilder::new().build(),
+            near_sdk::test_vm_config(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed],
+        );
+        let payout = escrow.on_withdrawal_transfer(Some(0), "resolver.testnet".parse().unwrap());
+        assert!(matches!(payout, PromiseOrValue::Value(())), "safety deposit paid despite failed transfer");
+        assert!(!escrow.used_secret_indices.is_used(0));
+        assert!(!escrow.state.is_withdrawn);
+
+        as_taker();
+        escrow.withdraw(secret, Some(proof), None);
+        assert!(escrow.used_secret_indices.is_used(0));
+        assert!(escrow.state.is_withdrawn);
+        set_time_override(None);
+    }
+
+    /// Escrow over a NEP-141 token, unfunded until the taker deposits
+    fn nep141_escrow() -> EscrowDst {
+        let factory: AccountId = "factory.testnet".parse().unwrap();
+        testing_env!(VMContextBuilder::new().predecessor_account_id(factory.clone()).build());
+        let immutables = Immutables { token: "usdc.testnet".parse().unwrap(), ..immutables() };
+        EscrowDst::init(CreateEscrowArgs { immutables, factory, funding_deadline: Some(60), correlation_id: None })
+    }
+
+    #[test]
+    fn test_native_escrow_funded_at_init() {
+        assert!(escrow().state.is_funded);
+        assert!(!nep141_escrow().state.is_funded);
+    }
+
+    #[test]
+    #[should_panic(expected = "E_FUNDING_DEADLINE_PASSED")]
+    fn test_deposit_after_funding_deadline_rejected() {
+        let mut escrow = nep141_escrow();
+        testing_env!(VMContextBuilder::new().predecessor_account_id("resolver.testnet".parse().unwrap()).build());
+        set_time_override(Some(DEPLOYED_AT + 60_000));
+        escrow.deposit_funds();
+    }
+
+    #[test]
+    fn test_token_transfer_after_funding_deadline_refunded() {
+        let mut escrow = nep141_escrow();
+        testing_env!(VMContextBuilder::new().predecessor_account_id("usdc.testnet".parse().unwrap()).build());
+        set_time_override(Some(DEPLOYED_AT + 60_000));
+
+        let refund = escrow.ft_on_transfer("resolver.testnet".parse().unwrap(), U128(1_000), String::new());
+        set_time_override(None);
+        assert!(matches!(refund, PromiseOrValue::Value(U128(1_000))));
+        assert!(!escrow.state.is_funded);
+    }
+
+    /// `amount` of `token` transferred to `escrow` by the resolver before the funding deadline
+    fn transfer_call(escrow: &mut EscrowDst, token: &str, amount: u128) -> u128 {
+        testing_env!(VMContextBuilder::new().predecessor_account_id(token.parse().unwrap()).build());
+        set_time_override(Some(DEPLOYED_AT + 1_000));
+        let refund = escrow.ft_on_transfer("resolver.testnet".parse().unwrap(), U128(amount), String::new());
+        set_time_override(None);
+        match refund {
+            PromiseOrValue::Value(refund) => refund.0,
+            PromiseOrValue::Promise(_) => panic!("expected a refund value"),
+        }
+    }
+
+    #[test]
+    fn test_wrong_token_transfer_fully_refunded() {
+        let mut escrow = nep141_escrow();
+        assert_eq!(transfer_call(&mut escrow, "fake-usdc.testnet", 1_000), 1_000);
+        assert!(!escrow.state.is_funded);
+    }
+
+    #[test]
+    fn test_underfunding_transfer_fully_refunded() {
+        let mut escrow = nep141_escrow();
+        assert_eq!(transfer_call(&mut escrow, "usdc.testnet", 999), 999);
+        assert!(!escrow.state.is_funded);
+    }
+
+    #[test]
+    fn test_overfunding_transfer_refunds_excess_and_funds() {
+        let mut escrow = nep141_escrow();
+        assert_eq!(transfer_call(&mut escrow, "usdc.testnet", 1_250), 250);
+        assert!(escrow.state.is_funded);
+
+        // A second transfer to the funded escrow goes back in full
+        assert_eq!(transfer_call(&mut escrow, "usdc.testnet", 1_000), 1_000);
+    }
+
+    #[test]
+    fn test_exact_transfer_funds_without_refund() {
+        let mut escrow = nep141_escrow();
+        assert_eq!(transfer_call(&mut escrow, "usdc.testnet", 1_000), 0);
+        assert!(escrow.state.is_funded);
+    }
+
+    #[test]
+    #[should_panic(expected = "Funding deadline not reached")]
+    fn test_unfunded_cancel_before_deadline_rejected() {
+        let mut escrow = nep141_escrow();
+        assert_eq!(escrow.get_funding_deadline(), U64(DEPLOYED_AT + 60_000));
+        set_time_override(Some(DEPLOYED_AT + 59_999));
+        escrow.cancel_unfunded();
+    }
+
+    #[test]
+    fn test_unfunded_cancel_after_deadline() {
+        let mut escrow = nep141_escrow();
+        testing_env!(VMContextBuilder::new().predecessor_account_id("maker.testnet".parse().unwrap()).build());
+        set_time_override(Some(DEPLOYED_AT + 60_000));
+
+        escrow.cancel_unfunded();
+        set_time_override(None);
+        assert!(escrow.state.is_cancelled);
+        assert!(near_sdk::test_utils::get_logs().iter().any(|log| log.starts_with("DstEscrowAbandoned")));
+    }
+
+    #[test]
+    fn test_correlation_id_in_cancel_logs() {
+        let factory: AccountId = "factory.testnet".parse().unwrap();
+        testing_env!(VMContextBuilder::new().predecessor_account_id(factory.clone()).build());
+        let mut escrow = EscrowDst::init(CreateEscrowArgs {
+            immutables: immutables(),
+            factory,
+            funding_deadline: Some(60),
+            correlation_id: Some("swap-7".to_string()),
+        });
+        testing_env!(VMContextBuilder::new().predecessor_account_id("resolver.testnet".parse().unwrap()).build());
+        set_time_override(Some(DEPLOYED_AT + 150_000));
+
+        escrow.cancel();
+        set_time_override(None);
+        let logs = near_sdk::test_utils::get_logs();
+        assert!(
+            logs.iter().any(|log| log.starts_with("EscrowCancelled: order_hash=0xorder, correlation_id=swap-7,")),
+            "{:?}",
+            logs
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Escrow is funded")]
+    fn test_funded_escrow_not_cancellable_as_abandoned() {
+        // The native principal came with the factory's create transfer, nobody called deposit_funds
+        let mut escrow = escrow();
+        testing_env!(VMContextBuilder::new().predecessor_account_id("maker.testnet".parse().unwrap()).build());
+        set_time_override(Some(DEPLOYED_AT + 60_000));
+        escrow.cancel_unfunded();
+    }
+
+    #[test]
+    fn test_failed_principal_transfer_keeps_safety_deposit() {
+        let mut escrow = escrow();
+        let callback_with = |result: PromiseResult| {
+            testing_env!(
+                VMContextBuilder::new().build(),
+                near_sdk::test_vm_config(),
+                near_sdk::RuntimeFeesConfig::test(),
+                Default::default(),
+                vec![result],
+            );
+        };
+
+        callback_with(PromiseResult::Failed);
+        let payout = escrow.on_withdrawal_transfer(None, "resolver.testnet".parse().unwrap());
+        assert!(matches!(payout, PromiseOrValue::Value(())));
+        assert!(!near_sdk::test_utils::get_logs().iter().any(|log| log.starts_with("EVENT_JSON:")));
+
+        callback_with(PromiseResult::Successful(vec![]));
+        let payout = escrow.on_withdrawal_transfer(None, "resolver.testnet".parse().unwrap());
+        assert!(matches!(payout, PromiseOrValue::Promise(_)));
+    }
+
+    #[test]
+    fn test_excess_near_rescued_before_delay() {
+        let mut escrow = escrow();
+        let storage_stake = env::storage_usage() as u128 * env::storage_byte_cost().as_yoctonear();
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id("resolver.testnet".parse().unwrap())
+            .account_balance(NearToken::from_yoctonear(1_100 + storage_stake + 500))
+            .storage_usage(env::storage_usage())
+            .build());
+        set_time_override(Some(DEPLOYED_AT + 60_000));
+
+        assert_eq!(escrow.get_excess_balance(), U128(500));
+        escrow.rescue_funds("near".parse().unwrap(), U128(500));
+        assert!(near_sdk::test_utils::get_logs().iter().any(|log| log == "FundsRescued: token=near, amount=500, correlation_id=none"));
+        set_time_override(None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Rescue delay not expired")]
+    fn test_rescue_beyond_excess_waits_for_delay() {
+        let mut escrow = escrow();
+        let storage_stake = env::storage_usage() as u128 * env::storage_byte_cost().as_yoctonear();
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id("resolver.testnet".parse().unwrap())
+            .account_balance(NearToken::from_yoctonear(1_100 + storage_stake + 500))
+            .storage_usage(env::storage_usage())
+            .build());
+        set_time_override(Some(DEPLOYED_AT + 60_000));
+
+        escrow.rescue_funds("near".parse().unwrap(), U128(501));
+    }
+
+    #[test]
+    fn test_timestamps_round_trip_as_strings() {
+        let escrow = escrow();
+        set_time_override(Some(DEPLOYED_AT));
+        let info = near_sdk::serde_json::to_value(escrow.get_escrow_info()).unwrap();
+        let next = near_sdk::serde_json::to_value(escrow.next_action_timestamp()).unwrap();
+        set_time_override(None);
+
+        assert_eq!(info["time_remaining"], "30000");
+        assert_eq!(next, near_sdk::serde_json::json!(["withdrawal", (DEPLOYED_AT + 30_000).to_string()]));
+        let deadline = near_sdk::serde_json::to_value(escrow.get_funding_deadline()).unwrap();
+        assert_eq!(near_sdk::serde_json::from_value::<U64>(deadline).unwrap(), U64(DEPLOYED_AT + 60_000));
+    }
+}
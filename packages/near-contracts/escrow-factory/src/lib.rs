@@ -1,17 +1,66 @@
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::collections::LookupMap;
+use near_sdk::collections::{LookupMap, LookupSet, Vector};
 use near_sdk::env::promise_batch_action_use_global_contract_by_account_id;
+use near_sdk::json_types::{Base58CryptoHash, Base64VecU8, U128, U64};
 use near_sdk::serde::{Deserialize, Serialize};
 use near_sdk::serde_json;
-use near_sdk::{env, log, near_bindgen, AccountId, Gas, NearToken, PanicOnDefault, Promise};
+use near_sdk::{
+    env, log, near_bindgen, AccountId, CryptoHash, Gas, NearToken, PanicOnDefault, Promise,
+    PromiseOrValue,
+};
 
 #[cfg(not(target_arch = "wasm32"))]
 use near_sdk::schemars::{self, JsonSchema};
 use sha2::{Digest, Sha256};
 
-// Gas constants
-const CREATE_ESCROW_GAS: Gas = Gas::from_tgas(50); // 50 TGas
-const CALLBACK_GAS: Gas = Gas::from_tgas(10); // 10 TGas
+// Gas constants, public so the sandbox gas tests measure against these exact budgets
+pub const CREATE_ESCROW_GAS: Gas = Gas::from_tgas(50); // 50 TGas
+pub const CALLBACK_GAS: Gas = Gas::from_tgas(10); // 10 TGas
+pub const SRC_SETUP_OVERHEAD_GAS: Gas = Gas::from_tgas(30); // Setup callback itself, token transfer/approve and verification callback
+pub const SRC_CREATION_OVERHEAD_GAS: Gas = Gas::from_tgas(30); // Balance check, account creation and dispatch
+pub const SRC_CREATED_CALLBACK_GAS: Gas = Gas::from_tgas(5); // on_src_escrow_created, verification and refund
+// Escrow init gas: a plain native single-fill init plus extras for the heavier setups
+pub const SRC_INIT_BASE_GAS: Gas = Gas::from_tgas(30);
+pub const NEP141_INIT_EXTRA_GAS: Gas = Gas::from_tgas(5); // NEP-141 transfer_from on init
+pub const MERKLE_INIT_EXTRA_GAS: Gas = Gas::from_tgas(10); // Merkle root and used-index set setup
+pub const DST_CREATION_OVERHEAD_GAS: Gas = Gas::from_tgas(10); // Validation and state writes in create_dst_escrow
+
+// NEAR account id length limit, bounds the factory id since escrows are its sub-accounts
+const MAX_ACCOUNT_ID_LEN: usize = 64;
+
+// Correlation ids are stored in the escrow's state, a UUID needs 36 bytes
+const MAX_CORRELATION_ID_LEN: usize = 64;
+
+// Storage staked on a new src escrow account: account record, access key, global contract
+// reference and the escrow's own state (immutables, used secret indices)
+const SRC_ESCROW_STORAGE_BYTES: u128 = 2_000;
+
+// Storage of one maker index entry kept on the factory: the escrow id under its own vector
+// slot key and the rewritten per-maker vector header
+const MAKER_INDEX_STORAGE_BYTES: u128 = 200;
+
+// Largest page get_escrows_by_maker returns
+const MAX_MAKER_ESCROWS_PAGE: u64 = 100;
+
+// NEAR's SLIP-44 coin type, the chain id orders use for NEAR. Any other dst chain is an EVM chain
+const NEAR_CHAIN_ID: u64 = 397;
+
+// Upper bound for the phase boundary grace window
+const MAX_GRACE_MS: u32 = 60_000;
+
+// Upper bound on partial-fill parts, must match the escrows' MAX_MERKLE_PARTS
+const MAX_MERKLE_PARTS: u32 = 1024;
+
+// Default buffer between dst and src cancellation, time for the resolver to cancel the dst
+// escrow before the maker can reclaim the src funds
+const DEFAULT_MIN_CANCELLATION_GAP_MS: u64 = 5 * 60 * 1000; // 5 minutes
+
+// Least time a dst escrow created from a pinned deployed_at must have before its cancellation opens,
+// for the resolver to fund it and withdraw
+const MIN_DST_TIME_TO_CANCELLATION_MS: u64 = 5 * 60 * 1000; // 5 minutes
+
+// Default upper bound for a single timelock offset
+const DEFAULT_MAX_TIMELOCK_OFFSET: u32 = 30 * 24 * 60 * 60; // 30 days in seconds
 
 /// Immutables struct matching EVM BaseEscrow.Immutables
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug)]
@@ -23,9 +72,37 @@ pub struct Immutables {
     pub maker: AccountId,
     pub taker: AccountId, // Resolver address
     pub token: AccountId, // Token contract address ("near" for native NEAR)
-    pub amount: u128,     // Using u128 instead of Balance
-    pub safety_deposit: u128,
+    pub amount: U128, // Serialized as a string to avoid JSON precision loss
+    pub safety_deposit: U128,
     pub timelocks: Timelocks,
+    /// Accounts allowed to complete the B3 public withdrawal, None keeps it fully public
+    #[serde(default)]
+    pub public_withdrawal_allowlist: Option<Vec<AccountId>>,
+    /// Source and destination chain ids, None for escrows created without an order extension
+    #[serde(default)]
+    pub chain_context: Option<ChainContext>,
+    /// Hash of the secret behind `hashlock` (and Merkle leaves), sha256 unless the order says otherwise
+    #[serde(default)]
+    pub hash_algorithm: HashAlgo,
+}
+
+/// Hash function a hashlock commits with, declared by the order so both legs verify alike
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(JsonSchema))]
+#[serde(crate = "near_sdk::serde", rename_all = "lowercase")]
+pub enum HashAlgo {
+    #[default]
+    Sha256,
+    Keccak256,
+}
+
+/// Chains an escrow bridges, copied from the order extension for monitoring
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(JsonSchema))]
+#[serde(crate = "near_sdk::serde")]
+pub struct ChainContext {
+    pub src_chain_id: u64,
+    pub dst_chain_id: u64,
 }
 
 /// Arguments for creating new escrow instances
@@ -42,6 +119,8 @@ pub struct CreateEscrowArgs {
 #[cfg_attr(not(target_arch = "wasm32"), derive(JsonSchema))]
 #[serde(crate = "near_sdk::serde")]
 pub struct Timelocks {
+    // Stays a JSON number unlike the U64 timestamps elsewhere, it is part of the immutables hash
+    // preimage and changing its encoding moves every escrow address (see immutables_hash_preimage)
     pub deployed_at: u64,             // Deployment timestamp
     pub src_withdrawal: u32,          // Source chain withdrawal delay
     pub src_public_withdrawal: u32,   // Source chain public withdrawal delay
@@ -50,6 +129,18 @@ pub struct Timelocks {
     pub dst_withdrawal: u32,          // Destination chain withdrawal delay (B1)
     pub dst_public_withdrawal: u32,   // Destination chain public withdrawal delay (B2→B3)
     pub dst_cancellation: u32,        // Destination chain cancellation delay (B4)
+    /// Leniency around phase boundaries in ms, 0 disables it (see the escrow phase checks)
+    #[serde(default)]
+    pub grace_ms: u32,
+}
+
+/// Token on the destination chain, an EVM address is not a valid NEAR AccountId
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(JsonSchema))]
+#[serde(crate = "near_sdk::serde")]
+pub enum CrossChainAsset {
+    Near(AccountId),
+    Evm(String), // 0x-prefixed, lowercase
 }
 
 /// Destination chain immutables complement (from EVM)
@@ -58,10 +149,14 @@ pub struct Timelocks {
 #[serde(crate = "near_sdk::serde")]
 pub struct DstImmutablesComplement {
     pub maker: AccountId,
-    pub amount: u128,
-    pub token: AccountId,
-    pub safety_deposit: u128,
-    pub chain_id: String,
+    pub taker: CrossChainAsset, // Resolver identity on the destination chain
+    pub amount: U128,
+    pub token: CrossChainAsset,
+    pub safety_deposit: U128,
+    pub chain_id: U64,
+    /// Copied from the src immutables, both legs share the hashlock
+    #[serde(default)]
+    pub hash_algorithm: HashAlgo,
 }
 
 /// Escrow creation result
@@ -72,6 +167,53 @@ pub struct EscrowCreationResult {
     pub escrow_account: AccountId,
     pub order_hash: String,
     pub success: bool,
+    /// Timelock reference time the escrow was initialized with, which callers must use for the
+    /// phase deadlines instead of the deployed_at they passed in
+    pub deployed_at: U64,
+}
+
+/// Outcome of a single `validate_order` check, `error` carries the message creation would panic with
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(JsonSchema))]
+#[serde(crate = "near_sdk::serde")]
+pub struct ValidationCheck {
+    pub name: String,
+    pub passed: bool,
+    pub error: Option<String>,
+}
+
+/// Verdict of `validate_order`, `valid` only when every check passed
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(JsonSchema))]
+#[serde(crate = "near_sdk::serde")]
+pub struct ValidationReport {
+    pub valid: bool,
+    pub checks: Vec<ValidationCheck>,
+}
+
+impl ValidationReport {
+    fn from_results(results: Vec<(&str, Result<(), String>)>) -> Self {
+        let checks: Vec<ValidationCheck> = results
+            .into_iter()
+            .map(|(name, result)| ValidationCheck {
+                name: name.to_string(),
+                passed: result.is_ok(),
+                error: result.err(),
+            })
+            .collect();
+        Self { valid: checks.iter().all(|check| check.passed), checks }
+    }
+}
+
+/// Factory state layout before the configuration and index fields, only read back by `migrate`
+#[derive(BorshDeserialize, BorshSerialize)]
+struct EscrowFactoryV1 {
+    owner: AccountId,
+    escrow_src_template: AccountId,
+    escrow_dst_template: AccountId,
+    deployed_escrows: LookupMap<String, AccountId>,
+    escrow_counter: u64,
+    rescue_delay: u32,
 }
 
 #[near_bindgen]
@@ -81,8 +223,19 @@ pub struct EscrowFactory {
     pub escrow_src_template: AccountId, // Template contract for source escrows
     pub escrow_dst_template: AccountId, // Template contract for destination escrows
     pub deployed_escrows: LookupMap<String, AccountId>, // orderHash -> escrow_account
-    pub escrow_counter: u64,
+    pub escrow_counter: u64, // Escrow creations started, never decremented as dst names are drawn from it
     pub rescue_delay: u32, // Delay for emergency fund rescue
+    pub borsh_immutables: bool, // Pass immutables to escrow init as a Borsh blob instead of JSON
+    pub max_timelock_offset: u32, // Maximum timelock offset in seconds
+    pub allowed_tokens: LookupSet<AccountId>, // NEP-141 contracts accepted in escrows
+    pub permissionless_tokens: bool, // Accept any NEP-141 token, skipping the allowlist
+    pub escrow_src_code_hash: Option<CryptoHash>, // Pinned global contract code, overrides the template account
+    pub escrow_dst_code_hash: Option<CryptoHash>, // Same for destination escrows
+    pub min_cancellation_gap_ms: u64, // Required lead of dst cancellation over src cancellation
+    pub maker_escrows: LookupMap<AccountId, Vector<AccountId>>, // maker -> escrows created for their orders
+    pub amount_limits: LookupMap<AccountId, (u128, u128)>, // token ("near" for native) -> (min, max) escrow amount
+    pub src_paused: bool, // Halts create_src_escrow only, e.g. while the src template is patched
+    pub dst_paused: bool, // Halts create_dst_escrow only
 }
 
 #[near_bindgen]
@@ -101,9 +254,133 @@ impl EscrowFactory {
             deployed_escrows: LookupMap::new("escrows".as_bytes()),
             escrow_counter: 0,
             rescue_delay,
+            borsh_immutables: false,
+            max_timelock_offset: DEFAULT_MAX_TIMELOCK_OFFSET,
+            allowed_tokens: LookupSet::new("tokens".as_bytes()),
+            permissionless_tokens: false,
+            escrow_src_code_hash: None,
+            escrow_dst_code_hash: None,
+            min_cancellation_gap_ms: DEFAULT_MIN_CANCELLATION_GAP_MS,
+            maker_escrows: LookupMap::new("makers".as_bytes()),
+            amount_limits: LookupMap::new("limits".as_bytes()),
+            src_paused: false,
+            dst_paused: false,
+        }
+    }
+
+    /// Upgrade hook for a factory deployed with the original state layout. Escrows and the counter
+    /// carry over, every setting added since starts at the `new` default.
+    #[private]
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        let old: EscrowFactoryV1 =
+            env::state_read().unwrap_or_else(|| env::panic_str("E_NO_STATE: nothing to migrate"));
+        Self {
+            deployed_escrows: old.deployed_escrows,
+            escrow_counter: old.escrow_counter,
+            ..Self::new(old.owner, old.rescue_delay, old.escrow_src_template, old.escrow_dst_template)
         }
     }
 
+    /// Update the maximum allowed timelock offset in seconds (only owner)
+    pub fn set_max_timelock_offset(&mut self, max_offset: u32) {
+        self.assert_owner();
+        self.max_timelock_offset = max_offset;
+        env::log_str(&format!("Max timelock offset set to {}s", max_offset));
+    }
+
+    /// Update the minimum gap between dst and src cancellation in milliseconds (only owner)
+    pub fn set_min_cancellation_gap_ms(&mut self, gap_ms: u64) {
+        self.assert_owner();
+        self.min_cancellation_gap_ms = gap_ms;
+        env::log_str(&format!("Min cancellation gap set to {}ms", gap_ms));
+    }
+
+    /// Allow a NEP-141 token contract to be used in escrows (only owner)
+    pub fn add_token(&mut self, token: AccountId) {
+        self.assert_owner();
+        self.allowed_tokens.insert(&token);
+        env::log_str(&format!("Token allowed: {}", token));
+    }
+
+    /// Remove a NEP-141 token contract from the allowlist (only owner)
+    pub fn remove_token(&mut self, token: AccountId) {
+        self.assert_owner();
+        self.allowed_tokens.remove(&token);
+        env::log_str(&format!("Token removed: {}", token));
+    }
+
+    /// Bound the escrow amount for `token` ("near" for native NEAR), inclusive on both ends (only owner)
+    pub fn set_amount_limits(&mut self, token: AccountId, min: U128, max: U128) {
+        self.assert_owner();
+        assert!(min.0 <= max.0, "Minimum {} exceeds maximum {}", min.0, max.0);
+        self.amount_limits.insert(&token, &(min.0, max.0));
+        env::log_str(&format!("Amount limits for {} set to [{}, {}]", token, min.0, max.0));
+    }
+
+    /// Lift the amount limits for `token` (only owner)
+    pub fn remove_amount_limits(&mut self, token: AccountId) {
+        self.assert_owner();
+        self.amount_limits.remove(&token);
+        env::log_str(&format!("Amount limits for {} removed", token));
+    }
+
+    /// (min, max) escrow amount for `token`, None when unbounded
+    pub fn get_amount_limits(&self, token: AccountId) -> Option<(U128, U128)> {
+        self.amount_limits.get(&token).map(|(min, max)| (U128(min), U128(max)))
+    }
+
+    /// Accept any NEP-141 token without consulting the allowlist (only owner)
+    pub fn set_permissionless_tokens(&mut self, enabled: bool) {
+        self.assert_owner();
+        self.permissionless_tokens = enabled;
+        env::log_str(&format!("Permissionless tokens set to {}", enabled));
+    }
+
+    /// Whether escrows may be created for `token`, native NEAR is always accepted
+    pub fn is_token_allowed(&self, token: AccountId) -> bool {
+        token.as_str() == "near" || self.permissionless_tokens || self.allowed_tokens.contains(&token)
+    }
+
+    /// Pin source escrows to a global contract code hash, None follows the template account (only owner)
+    /// The template account can be upgraded at any time, a pinned hash keeps escrows on audited code
+    pub fn set_escrow_src_code_hash(&mut self, code_hash: Option<Base58CryptoHash>) {
+        self.assert_owner();
+        let label = code_hash.as_ref().map(String::from).unwrap_or_else(|| "none".to_string());
+        self.escrow_src_code_hash = code_hash.map(CryptoHash::from);
+        env::log_str(&format!("Source escrow code hash set to {}", label));
+    }
+
+    /// Pin destination escrows to a global contract code hash, None follows the template account (only owner)
+    pub fn set_escrow_dst_code_hash(&mut self, code_hash: Option<Base58CryptoHash>) {
+        self.assert_owner();
+        let label = code_hash.as_ref().map(String::from).unwrap_or_else(|| "none".to_string());
+        self.escrow_dst_code_hash = code_hash.map(CryptoHash::from);
+        env::log_str(&format!("Destination escrow code hash set to {}", label));
+    }
+
+    /// Toggle compact Borsh encoding of immutables for escrow init calls (only owner)
+    /// JSON stays the default as it is easier to inspect in explorers
+    pub fn set_borsh_immutables(&mut self, enabled: bool) {
+        self.assert_owner();
+        self.borsh_immutables = enabled;
+        env::log_str(&format!("Borsh immutables encoding set to {}", enabled));
+    }
+
+    /// Stop or resume source escrow creation without touching destination escrows (only owner)
+    pub fn set_src_paused(&mut self, paused: bool) {
+        self.assert_owner();
+        self.src_paused = paused;
+        env::log_str(&format!("Source escrow creation paused set to {}", paused));
+    }
+
+    /// Stop or resume destination escrow creation without touching source escrows (only owner)
+    pub fn set_dst_paused(&mut self, paused: bool) {
+        self.assert_owner();
+        self.dst_paused = paused;
+        env::log_str(&format!("Destination escrow creation paused set to {}", paused));
+    }
+
     /// Update the source escrow template contract (only owner)
     pub fn set_escrow_src_template(&mut self, template: AccountId) {
         self.assert_owner();
@@ -123,14 +400,33 @@ impl EscrowFactory {
     pub fn create_dst_escrow(
         &mut self,
         dst_immutables: Immutables,
-        src_cancellation_timestamp: u64,
+        src_cancellation_timestamp: U64,
+        correlation_id: Option<String>,
     ) -> Promise {
-        // Validate payment for safety deposit and native tokens
+        assert!(!self.dst_paused, "E_DST_PAUSED: destination escrow creation is paused");
+        assert_correlation_id_len(correlation_id.as_deref());
+        let src_cancellation_timestamp = src_cancellation_timestamp.0;
+        let mut dst_immutables = dst_immutables;
+        dst_immutables.order_hash = normalize_order_hash(&dst_immutables.order_hash);
+
+        // The template call and callback must be affordable before any state is written,
+        // otherwise the mapping is stored while the escrow is never created
+        let required_gas = CREATE_ESCROW_GAS
+            .saturating_add(CALLBACK_GAS)
+            .saturating_add(DST_CREATION_OVERHEAD_GAS);
+        assert!(
+            env::prepaid_gas() >= required_gas,
+            "Insufficient gas: required {}, got {}",
+            required_gas,
+            env::prepaid_gas()
+        );
+
+        // Validate payment for safety deposit and native tokens, plus the maker index entry
         let required_deposit = if dst_immutables.token.as_str() == "near" {
-            dst_immutables.amount + dst_immutables.safety_deposit
+            dst_immutables.amount.0 + dst_immutables.safety_deposit.0
         } else {
-            dst_immutables.safety_deposit // Only safety deposit for NEP-141 tokens
-        };
+            dst_immutables.safety_deposit.0 // Only safety deposit for NEP-141 tokens
+        } + maker_index_storage_stake();
 
         assert!(
             env::attached_deposit().as_yoctonear() >= required_deposit,
@@ -139,23 +435,44 @@ impl EscrowFactory {
             env::attached_deposit().as_yoctonear()
         );
 
-        // Create immutables with current timestamp
+        dst_immutables.timelocks.validate(self.max_timelock_offset);
+        self.assert_token_allowed(&dst_immutables.token);
+        assert_amount_in_range(dst_immutables.amount.0, self.amount_limits.get(&dst_immutables.token));
+        assert_safety_deposit(dst_immutables.safety_deposit.0);
+
         let mut immutables = dst_immutables;
-        immutables.timelocks.deployed_at = env::block_timestamp_ms();
+        immutables.timelocks.deployed_at = dst_deployed_at(
+            immutables.timelocks.deployed_at,
+            immutables.timelocks.dst_cancellation,
+            env::block_timestamp_ms(),
+        );
 
-        // Validate cancellation timing
+        // A src cancellation already open (or about to) would let the maker reclaim while the
+        // dst funds are still locked, whatever the ordering check below says
+        assert_src_cancellation_ahead(
+            src_cancellation_timestamp,
+            env::block_timestamp_ms(),
+            self.min_cancellation_gap_ms,
+        );
+
+        // Validate cancellation timing, the dst must become cancellable at least
+        // min_cancellation_gap_ms before the maker can reclaim on the src chain
         let dst_cancellation_start = immutables.timelocks.deployed_at
             + (immutables.timelocks.dst_cancellation as u64 * 1000);
         assert!(
-            dst_cancellation_start <= src_cancellation_timestamp,
-            "Invalid creation time: dst cancellation would start after src"
+            dst_cancellation_start.saturating_add(self.min_cancellation_gap_ms) <= src_cancellation_timestamp,
+            "Invalid creation time: dst cancellation at {} must precede src cancellation at {} by at least {}ms",
+            dst_cancellation_start,
+            src_cancellation_timestamp,
+            self.min_cancellation_gap_ms
         );
 
         // Generate unique escrow account
+        check_order_hash(&immutables.order_hash).unwrap_or_else(|e| env::panic_str(&e));
         let escrow_account = format!(
             "escrow-{}-{}.{}",
             self.escrow_counter,
-            &immutables.order_hash[..8], // Use first 8 chars of order hash
+            &immutables.order_hash[..8], // Use first 8 chars of order hash, ASCII once checked
             env::current_account_id()
         );
         self.escrow_counter += 1;
@@ -164,30 +481,38 @@ impl EscrowFactory {
         self.deployed_escrows
             .insert(&immutables.order_hash, &escrow_account.parse().unwrap());
 
-        // Create escrow using template factory pattern
         let escrow_id: AccountId = escrow_account.parse().unwrap();
+        let deposit = if immutables.token.as_str() == "near" {
+            NearToken::from_yoctonear(immutables.amount.0 + immutables.safety_deposit.0)
+        } else {
+            NearToken::from_yoctonear(immutables.safety_deposit.0)
+        };
+        let order_hash = immutables.order_hash.clone();
+        let maker = immutables.maker.clone();
+        let deployed_at = U64(immutables.timelocks.deployed_at);
 
-        // Call the template contract to create a new escrow instance
-        Promise::new(self.escrow_dst_template.clone())
-            .function_call(
-                "create_escrow".to_string(),
-                near_sdk::serde_json::to_vec(&serde_json::json!({
-                    "escrow_account": escrow_id.to_string(),
-                    "immutables": immutables,
-                    "factory": env::current_account_id().to_string(),
-                }))
-                .unwrap(),
-                if immutables.token.as_str() == "near" {
-                    NearToken::from_yoctonear(immutables.amount + immutables.safety_deposit)
-                } else {
-                    NearToken::from_yoctonear(immutables.safety_deposit)
-                },
-                CREATE_ESCROW_GAS,
-            )
+        // Same mechanism as src escrows: a fresh account running the template's global
+        // contract, initialized directly by the factory in the same batch
+        let (init_method, init_args) = self.escrow_init_call(immutables, correlation_id.clone());
+        let create_account = Promise::new(escrow_id.clone()).create_account().transfer(deposit);
+        let create_account = match self.escrow_dst_code_hash {
+            Some(code_hash) => create_account.use_global_contract(code_hash),
+            None => create_account.use_global_contract_by_account_id(self.escrow_dst_template.clone()),
+        };
+        create_account
+            .function_call(init_method, init_args, NearToken::from_yoctonear(0), CREATE_ESCROW_GAS)
             .then(
                 Self::ext(env::current_account_id())
                     .with_static_gas(CALLBACK_GAS)
-                    .on_escrow_created(immutables.order_hash, escrow_id),
+                    .on_escrow_created(
+                        order_hash,
+                        escrow_id,
+                        maker,
+                        deployed_at,
+                        correlation_id,
+                        env::predecessor_account_id(),
+                        deposit,
+                    ),
             )
     }
 
@@ -199,18 +524,41 @@ impl EscrowFactory {
         order_hash: String,
         immutables: Immutables,
         dst_complement: DstImmutablesComplement,
+        correlation_id: Option<String>,
     ) -> Promise {
+        self.check_src_not_paused().unwrap_or_else(|e| env::panic_str(&e));
+        assert_correlation_id_len(correlation_id.as_deref());
+        immutables.timelocks.validate(self.max_timelock_offset);
+        self.assert_token_allowed(&immutables.token);
+        assert_amount_in_range(immutables.amount.0, self.amount_limits.get(&immutables.token));
+        assert_safety_deposit(immutables.safety_deposit.0);
+        assert_dst_hash_algorithm(&immutables, &dst_complement);
 
+        let required_gas = src_setup_gas(&immutables).saturating_add(SRC_CREATION_OVERHEAD_GAS);
+        assert!(
+            env::prepaid_gas() >= required_gas,
+            "Insufficient gas: required {} for this escrow's init, got {}",
+            required_gas,
+            env::prepaid_gas()
+        );
 
-        // Set deployed timestamp
+        let order_hash = normalize_order_hash(&order_hash);
         let mut immutables = immutables;
-        immutables.timelocks.deployed_at = env::block_timestamp_ms() / 1000; // Convert to seconds
+        immutables.order_hash = normalize_order_hash(&immutables.order_hash);
 
-        let required_deposit = if immutables.token.as_str() == "near" {
-            immutables.amount + immutables.safety_deposit
-        } else {
-            immutables.safety_deposit // For NEP-141 tokens
-        };
+        // Set deployed timestamp
+        immutables.timelocks.deployed_at = env::block_timestamp_ms(); // Escrow timelocks count from ms
+
+        // Checked before any promise: a panic in the deployment self-call would refund the factory,
+        // not the caller, keeping the escrow funds and storage stake
+        let principal = if immutables.token.as_str() == "near" { immutables.amount.0 } else { 0 };
+        try_src_init_deposit(
+            principal,
+            immutables.safety_deposit.0,
+            env::attached_deposit().as_yoctonear(),
+            self.src_escrow_storage_stake().0,
+        )
+        .unwrap_or_else(|e| env::panic_str(&e));
 
         // For NEP-141 tokens, check balance and allowance
         if immutables.token.as_str() != "near" {
@@ -231,14 +579,27 @@ impl EscrowFactory {
             token_balance_check.then(
                 Self::ext(env::current_account_id())
                     .with_attached_deposit(env::attached_deposit())
-                    .on_token_balance_checked_result_received(order_hash, immutables, dst_complement)
+                    .on_token_balance_checked_result_received(
+                        order_hash,
+                        immutables,
+                        dst_complement,
+                        correlation_id,
+                        env::predecessor_account_id(),
+                    )
             )
             // Note: In a real implementation, you'd need to handle this asynchronously
             // For now, we assume the balance check will be done in the escrow contract
         } else {
             Self::ext(env::current_account_id())
                 .with_attached_deposit(env::attached_deposit())
-                .src_contract_deployment(order_hash, immutables, dst_complement, 0)
+                .src_contract_deployment(
+                    order_hash,
+                    immutables,
+                    dst_complement,
+                    U128(0),
+                    correlation_id,
+                    env::predecessor_account_id(),
+                )
         }
     }
 
@@ -249,6 +610,8 @@ impl EscrowFactory {
         order_hash: String,
         immutables: Immutables,
         dst_complement: DstImmutablesComplement,
+        correlation_id: Option<String>,
+        caller: AccountId,
         #[callback_result] call_result: Result<String, near_sdk::PromiseError>,
     ) -> Promise {
         match call_result {
@@ -261,7 +624,9 @@ impl EscrowFactory {
                     order_hash.clone(),
                     immutables.clone(),
                     dst_complement.clone(),
-                    balance.parse::<u128>().unwrap()
+                    U128(balance.parse::<u128>().unwrap()),
+                    correlation_id,
+                    caller,
                 )
             }
             Err(e) => {
@@ -272,7 +637,9 @@ impl EscrowFactory {
                     order_hash.clone(),
                     immutables.clone(),
                     dst_complement.clone(),
-                    NearToken::from_yoctonear(0).as_yoctonear()
+                    U128(0),
+                    correlation_id,
+                    caller,
                 )
             }
         }
@@ -280,101 +647,166 @@ impl EscrowFactory {
 
     #[private]
     #[payable]
-    pub fn src_contract_deployment(&mut self, order_hash: String, immutables: Immutables, dst_complement: DstImmutablesComplement, attached_non_native_token: u128) -> Promise {
+    /// `caller` is the account that called `create_src_escrow`, refunded if creation fails
+    pub fn src_contract_deployment(&mut self, order_hash: String, immutables: Immutables, dst_complement: DstImmutablesComplement, attached_non_native_token: U128, correlation_id: Option<String>, caller: AccountId) -> Promise {
         
         let mut immutables = immutables;
-        immutables.timelocks.deployed_at = env::block_timestamp_ms() / 1000; // Convert to seconds
+        immutables.timelocks.deployed_at = env::block_timestamp_ms(); // Escrow timelocks count from ms
 
+        // The safety deposit may be attached now or later by the resolver via deposit_safety
         let required_deposit = if immutables.token.as_str() == "near" {
-            immutables.amount + immutables.safety_deposit
+            immutables.amount.0
         } else {
-            immutables.safety_deposit // For NEP-141 tokens
+            0 // For NEP-141 tokens
         };
 
         let required_approval = if immutables.token.as_str() != "near" {
-            immutables.amount
+            immutables.amount.0
         } else {
             0
         };
 
-        assert!(attached_non_native_token >= required_approval, "Insufficient attached deposit for non-native token: required {}, got {}", required_approval, attached_non_native_token);
+        assert!(attached_non_native_token.0 >= required_approval, "Insufficient attached deposit for non-native token: required {}, got {}", required_approval, attached_non_native_token.0);
 
-        assert!(
-            env::attached_deposit().as_yoctonear() >= required_deposit,
-            "Insufficient deposit: required {}, got {}",
+        // The account creation moves only the account's storage stake, init receives exactly what it
+        // asserts and the maker index share stays on the factory
+        let storage_stake = self.src_escrow_storage_stake().0;
+        let init_deposit = NearToken::from_yoctonear(src_init_deposit(
             required_deposit,
-            env::attached_deposit().as_yoctonear()
-        );
-        
+            immutables.safety_deposit.0,
+            env::attached_deposit().as_yoctonear(),
+            storage_stake,
+        ));
+
         // Generate deterministic escrow account
         let escrow_account = self.compute_escrow_address(&immutables);
 
-        // Log event similar to EVM's SrcEscrowCreated
-        log!(
-            "SrcEscrowCreated: {{\"immutables\": {:?}, \"complement\": {:?}}}",
-            immutables,
-            dst_complement
-        );
+        // Event similar to EVM's SrcEscrowCreated
+        env::log_str(&src_escrow_created_event(&immutables, &dst_complement, correlation_id.as_deref()));
 
-        let mut promise = Promise::new(escrow_account.clone())
+        // Create the account first so a name collision can be told apart from an init failure
+        let create_account = Promise::new(escrow_account.clone())
             .create_account()
             .add_full_access_key(env::signer_account_pk())
-            .transfer(env::attached_deposit())
-            .use_global_contract_by_account_id(self.escrow_src_template.clone());
-
-        if required_approval > 0 {
-            promise = promise.then(
-                Promise::new(immutables.token.clone())
-                    .function_call(
-                        "transfer_from".to_string(),
-                        near_sdk::serde_json::to_vec(&serde_json::json!({
-                            "from": immutables.maker.clone(),
-                            "to": immutables.taker.clone(),
-                            "value": required_approval.to_string(),
-                        }))
-                        .unwrap(),
-                        NearToken::from_yoctonear(0), // No additional deposit
-                        Gas::from_tgas(5), // 5 TGas for transfer call
-                    )
-            ).then(
-                Promise::new(immutables.token.clone()).function_call(
-                    "approve".to_string(),
-                    near_sdk::serde_json::to_vec(
-                        &serde_json::json!({
-                            "spender_id": escrow_account.clone(),
-                            "value": required_approval.to_string(),
-                        })
-                    ).unwrap(),
-                    NearToken::from_yoctonear(0),
-                    Gas::from_tgas(5),
-                )
+            .transfer(NearToken::from_yoctonear(storage_stake - maker_index_storage_stake()));
+        let create_account = match self.escrow_src_code_hash {
+            Some(code_hash) => create_account.use_global_contract(code_hash),
+            None => create_account.use_global_contract_by_account_id(self.escrow_src_template.clone()),
+        };
+
+        create_account
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(src_setup_gas(&immutables))
+                    .on_src_account_created(
+                        order_hash,
+                        escrow_account,
+                        immutables,
+                        U128(required_approval),
+                        init_deposit,
+                        NearToken::from_yoctonear(storage_stake),
+                        correlation_id,
+                        caller,
+                    ),
+            )
+    }
+
+    #[private]
+    pub fn on_src_account_created(
+        &mut self,
+        order_hash: String,
+        escrow_account: AccountId,
+        immutables: Immutables,
+        required_approval: U128,
+        init_deposit: NearToken,
+        storage_stake: NearToken,
+        correlation_id: Option<String>,
+        caller: AccountId,
+        #[callback_result] call_result: Result<(), near_sdk::PromiseError>,
+    ) -> PromiseOrValue<EscrowCreationResult> {
+        if let Err(e) = call_result {
+            // Most likely the escrow account already exists, nothing was recorded for it yet. The
+            // failed creation returned the account's stake here, the maker index share never left
+            // and init never ran, so the caller gets back everything it attached
+            log!(
+                "SrcEscrowAccountCreationFailed: escrow={}, order_hash={}, correlation_id={}, error={:?}",
+                escrow_account,
+                order_hash,
+                correlation_id.as_deref().unwrap_or("none"),
+                e
             );
+            Promise::new(caller).transfer(init_deposit.saturating_add(storage_stake));
+            return PromiseOrValue::Value(EscrowCreationResult {
+                escrow_account,
+                order_hash,
+                success: false,
+                deployed_at: U64(immutables.timelocks.deployed_at),
+            });
         }
+
         // Store mapping
         self.deployed_escrows.insert(&order_hash, &escrow_account);
         self.escrow_counter += 1;
 
-        promise = promise.then(Promise::new(escrow_account.clone()).function_call(
-            "init".to_string(),
-            near_sdk::serde_json::to_vec(
-                        &serde_json::json!({
-                            "args": &InitEscrowArgs {
-                                immutables,
-                                factory: env::current_account_id(),
-                }
-                        }))
-            .unwrap(),
-            env::attached_deposit(), // No additional deposit
-            Gas::from_tgas(50), // 50 TGas for init call
-        ));
+        let required_approval = required_approval.0;
+        let token = immutables.token.clone();
+        let maker = immutables.maker.clone();
+        let taker = immutables.taker.clone();
+        let deployed_at = U64(immutables.timelocks.deployed_at);
+
+        let init_gas = src_init_gas(&immutables);
+        let (init_method, init_args) = self.escrow_init_call(immutables, correlation_id.clone());
+        let init = Promise::new(escrow_account.clone()).function_call(
+            init_method,
+            init_args,
+            init_deposit,
+            init_gas,
+        );
+
+        let promise = if required_approval > 0 {
+            Promise::new(token.clone())
+                .function_call(
+                    "transfer_from".to_string(),
+                    near_sdk::serde_json::to_vec(&serde_json::json!({
+                        "from": maker,
+                        "to": taker,
+                        "value": required_approval.to_string(),
+                    }))
+                    .unwrap(),
+                    NearToken::from_yoctonear(0), // No additional deposit
+                    Gas::from_tgas(5), // 5 TGas for transfer call
+                )
+                .then(Promise::new(token).function_call(
+                    "approve".to_string(),
+                    near_sdk::serde_json::to_vec(&serde_json::json!({
+                        "spender_id": escrow_account.clone(),
+                        "value": required_approval.to_string(),
+                    }))
+                    .unwrap(),
+                    NearToken::from_yoctonear(0),
+                    Gas::from_tgas(5),
+                ))
+                .then(init)
+        } else {
+            init
+        };
 
         // Callback for verification
-        promise.then(Promise::new(env::current_account_id()).function_call(
-            "on_src_escrow_created".to_string(),
-            near_sdk::serde_json::to_vec(&(order_hash.clone(), escrow_account.clone())).unwrap(),
-            NearToken::from_yoctonear(0),
-            Gas::from_tgas(5),
-        ))
+        PromiseOrValue::Promise(
+            promise.then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(SRC_CREATED_CALLBACK_GAS)
+                    .on_src_escrow_created(
+                        order_hash,
+                        escrow_account,
+                        maker,
+                        deployed_at,
+                        correlation_id,
+                        caller,
+                        init_deposit,
+                    ),
+            ),
+        )
     }
 
     #[private]
@@ -382,92 +814,210 @@ impl EscrowFactory {
         &mut self,
         order_hash: String,
         escrow_account: AccountId,
+        maker: AccountId,
+        deployed_at: U64,
+        correlation_id: Option<String>,
+        caller: AccountId,
+        init_deposit: NearToken,
         #[callback_result] call_result: Result<(), near_sdk::PromiseError>,
     ) -> EscrowCreationResult {
         match call_result {
             Ok(_) => {
-                log!("Source escrow created: {}", escrow_account);
+                log!(
+                    "Source escrow created: {}, correlation_id={}",
+                    escrow_account,
+                    correlation_id.as_deref().unwrap_or("none")
+                );
+                self.index_maker_escrow(maker, escrow_account.clone());
                 EscrowCreationResult {
                     escrow_account,
                     order_hash,
                     success: true,
+                    deployed_at,
                 }
             }
             Err(e) => {
-                log!("Failed to create source escrow: {:?}", e);
+                // The account exists at this point, only the init failed
+                log!(
+                    "Failed to initialize source escrow {} (correlation_id={}): {:?}",
+                    escrow_account,
+                    correlation_id.as_deref().unwrap_or("none"),
+                    e
+                );
                 self.deployed_escrows.remove(&order_hash);
-                self.escrow_counter -= 1;
+                // The failed init returned its deposit here, the account's stake stays on the account
+                // and the unused maker index share goes back with the deposit
+                Promise::new(caller).transfer(init_deposit.saturating_add(NearToken::from_yoctonear(maker_index_storage_stake())));
                 EscrowCreationResult {
                     escrow_account,
                     order_hash,
                     success: false,
+                    deployed_at,
                 }
             }
         }
     }
 
+    /// `caller` is the account that called `create_dst_escrow`, refunded `deposit` if creation fails
     #[private]
     pub fn on_escrow_created(
         &mut self,
         order_hash: String,
         escrow_account: AccountId,
+        maker: AccountId,
+        deployed_at: U64,
+        correlation_id: Option<String>,
+        caller: AccountId,
+        deposit: NearToken,
         #[callback_result] call_result: Result<(), near_sdk::PromiseError>,
     ) -> EscrowCreationResult {
         match call_result {
             Ok(_) => {
                 env::log_str(&format!(
-                    "DstEscrowCreated: escrow={}, order_hash={}, taker={}",
+                    "DstEscrowCreated: escrow={}, order_hash={}, taker={}, correlation_id={}",
                     escrow_account,
                     order_hash,
-                    env::predecessor_account_id()
+                    caller,
+                    correlation_id.as_deref().unwrap_or("none")
                 ));
+                self.index_maker_escrow(maker, escrow_account.clone());
 
                 EscrowCreationResult {
                     escrow_account,
                     order_hash,
                     success: true,
+                    deployed_at,
                 }
             }
             Err(e) => {
                 env::log_str(&format!(
-                    "Failed to create escrow for order {}: {:?}",
-                    order_hash, e
+                    "Failed to create escrow for order {} (correlation_id={}): {:?}",
+                    order_hash,
+                    correlation_id.as_deref().unwrap_or("none"),
+                    e
                 ));
 
-                // Remove from mapping on failure
+                // Remove from mapping on failure. The counter stays: a create started since then may
+                // hold the next name, and rolling back would hand it out twice
                 self.deployed_escrows.remove(&order_hash);
+                // The failed batch returned the escrow's funds here, nothing reached the account and
+                // the maker index share was never used
+                Promise::new(caller).transfer(deposit.saturating_add(NearToken::from_yoctonear(maker_index_storage_stake())));
 
                 EscrowCreationResult {
                     escrow_account,
                     order_hash,
                     success: false,
+                    deployed_at,
                 }
             }
         }
     }
 
+    /// Dry run of `create_src_escrow`'s checks for `immutables`, `dst_complement` and `attached_deposit`
+    /// (principal, safety deposit and storage stake as they would be attached). Deploys nothing and
+    /// writes no state, gas is not checked as a view call has no prepaid gas to compare. An escrow
+    /// already recorded for the order hash is no failure, as each fill of an order deploys its own.
+    pub fn validate_order(
+        &self,
+        immutables: Immutables,
+        dst_complement: DstImmutablesComplement,
+        attached_deposit: U128,
+    ) -> ValidationReport {
+        let principal = if immutables.token.as_str() == "near" { immutables.amount.0 } else { 0 };
+
+        ValidationReport::from_results(vec![
+            (
+                "deposit",
+                try_src_init_deposit(
+                    principal,
+                    immutables.safety_deposit.0,
+                    attached_deposit.0,
+                    self.src_escrow_storage_stake().0,
+                )
+                .map(|_| ()),
+            ),
+            ("safety_deposit", check_safety_deposit(immutables.safety_deposit.0)),
+            ("timelocks", immutables.timelocks.check(self.max_timelock_offset)),
+            ("token_allowed", self.check_token_allowed(&immutables.token)),
+            (
+                "amount_limits",
+                check_amount_in_range(immutables.amount.0, self.amount_limits.get(&immutables.token)),
+            ),
+            ("hash_algorithm", check_dst_hash_algorithm(&immutables, &dst_complement)),
+            ("not_paused", self.check_src_not_paused()),
+        ])
+    }
+
     /// Get escrow address for a given order hash
     pub fn get_escrow_address(&self, order_hash: String) -> Option<AccountId> {
-        self.deployed_escrows.get(&order_hash)
+        self.deployed_escrows.get(&normalize_order_hash(&order_hash))
+    }
+
+    /// Escrows created for a maker's orders, oldest first, whether or not they settled since.
+    /// Paged from `from_index` (default 0), at most `limit` (default and cap MAX_MAKER_ESCROWS_PAGE)
+    pub fn get_escrows_by_maker(&self, maker: AccountId, from_index: Option<U64>, limit: Option<U64>) -> Vec<AccountId> {
+        let Some(escrows) = self.maker_escrows.get(&maker) else {
+            return Vec::new();
+        };
+        let from_index = from_index.map_or(0, |index| index.0);
+        let limit = limit.map_or(MAX_MAKER_ESCROWS_PAGE, |limit| limit.0.min(MAX_MAKER_ESCROWS_PAGE));
+        (from_index..escrows.len().min(from_index.saturating_add(limit)))
+            .filter_map(|index| escrows.get(index))
+            .collect()
     }
 
     /// Compute deterministic escrow address (similar to EVM addressOfEscrowDst)
     pub fn compute_escrow_address(&self, immutables: &Immutables) -> AccountId {
         // Use hash of immutables for deterministic address generation
         let hash = self.compute_immutables_hash(immutables);
-        format!(
+        // 16 hex chars (64 bits) are kept even for long factory ids, fewer would make collisions practical
+        let escrow_account = format!(
             "escrow-{}.{}",
             &hex::encode(&hash)[..16], // Use first 16 hex chars
             env::current_account_id()
-        )
-        .parse()
-        .unwrap()
+        );
+        assert!(
+            escrow_account.len() <= MAX_ACCOUNT_ID_LEN,
+            "E_ACCOUNT_ID_TOO_LONG: {} is {} characters, NEAR allows at most {}",
+            escrow_account,
+            escrow_account.len(),
+            MAX_ACCOUNT_ID_LEN
+        );
+        escrow_account.parse().unwrap()
     }
 
     /// Compute hash of immutables (similar to EVM ImmutablesLib.hash)
     pub fn compute_immutables_hash(&self, immutables: &Immutables) -> Vec<u8> {
-        let serialized = near_sdk::serde_json::to_vec(immutables).unwrap();
-        Sha256::digest(&serialized).to_vec()
+        Sha256::digest(immutables_hash_preimage(immutables)).to_vec()
+    }
+
+    /// Build the escrow init method and arguments, either JSON `init` or compact Borsh `init_from_blob`
+    fn escrow_init_call(&self, immutables: Immutables, correlation_id: Option<String>) -> (String, Vec<u8>) {
+        if self.borsh_immutables {
+            let immutables_blob = Base64VecU8::from(borsh::to_vec(&immutables).unwrap());
+            (
+                "init_from_blob".to_string(),
+                near_sdk::serde_json::to_vec(&serde_json::json!({
+                    "immutables_blob": immutables_blob,
+                    "factory": env::current_account_id(),
+                    "correlation_id": correlation_id,
+                }))
+                .unwrap(),
+            )
+        } else {
+            (
+                "init".to_string(),
+                near_sdk::serde_json::to_vec(&serde_json::json!({
+                    "args": &InitEscrowArgs {
+                        immutables,
+                        factory: env::current_account_id(),
+                        correlation_id,
+                    }
+                }))
+                .unwrap(),
+            )
+        }
     }
 
     /// Check if an order supports multiple fills (Merkle tree)
@@ -478,25 +1028,47 @@ impl EscrowFactory {
         hashlock.starts_with("merkle:") || hashlock.len() > 64
     }
 
+    /// Compute the Merkle secret index a fill of `making_amount` must use, one above the secret's
+    /// leaf index as the EVM's validated index counts it
+    /// Lets off-chain coordinators pick the index `validate_partial_fill` expects
+    pub fn compute_fill_index(
+        &self,
+        making_amount: U128,
+        remaining_making_amount: U128,
+        order_making_amount: U128,
+        parts: u32,
+    ) -> u32 {
+        try_compute_fill_index(making_amount.0, remaining_making_amount.0, order_making_amount.0, parts)
+            .unwrap_or_else(|e| env::panic_str(&e))
+    }
+
     /// Validate partial fill (similar to EVM _isValidPartialFill)
     pub fn validate_partial_fill(
         &self,
-        making_amount: u128,
-        remaining_making_amount: u128,
-        order_making_amount: u128,
+        making_amount: U128,
+        remaining_making_amount: U128,
+        order_making_amount: U128,
         parts_amount: u32,
         validated_index: u32,
     ) -> bool {
-        let calculated_index = ((order_making_amount - remaining_making_amount + making_amount
-            - 1)
-            * parts_amount as u128)
-            / order_making_amount;
-
-        if remaining_making_amount == making_amount {
-            // Order filled to completion - use secret with index i + 1
-            return (calculated_index + 2) as u32 == validated_index;
-        } else if order_making_amount != remaining_making_amount {
+        let Ok(expected_index) = try_compute_fill_index(
+            making_amount.0,
+            remaining_making_amount.0,
+            order_making_amount.0,
+            parts_amount,
+        ) else {
+            return false;
+        };
+
+        let (making_amount, remaining_making_amount, order_making_amount) =
+            (making_amount.0, remaining_making_amount.0, order_making_amount.0);
+
+        if remaining_making_amount != making_amount && order_making_amount != remaining_making_amount {
             // Calculate previous fill index if not first fill
+            let calculated_index = ((order_making_amount - remaining_making_amount + making_amount
+                - 1)
+                * parts_amount as u128)
+                / order_making_amount;
             let prev_calculated_index = ((order_making_amount - remaining_making_amount - 1)
                 * parts_amount as u128)
                 / order_making_amount;
@@ -505,7 +1077,27 @@ impl EscrowFactory {
             }
         }
 
-        (calculated_index + 1) as u32 == validated_index
+        expected_index == validated_index
+    }
+
+    /// Deployed code version, `<package version>+<git hash>` for upgrade coordination
+    pub fn contract_version(&self) -> String {
+        format!(
+            "{}+{}",
+            env!("CARGO_PKG_VERSION"),
+            option_env!("GIT_COMMIT_HASH").unwrap_or("unknown")
+        )
+    }
+
+    /// Deposit a src escrow account and its maker index entry need for storage staking, attached
+    /// on top of the escrow funds
+    pub fn src_escrow_storage_stake(&self) -> U128 {
+        U128(SRC_ESCROW_STORAGE_BYTES * env::storage_byte_cost().as_yoctonear() + maker_index_storage_stake())
+    }
+
+    /// Deposit `create_dst_escrow` requires on top of the escrow funds for the maker index entry
+    pub fn maker_index_storage_stake(&self) -> U128 {
+        U128(maker_index_storage_stake())
     }
 
     /// Get factory statistics
@@ -516,6 +1108,14 @@ impl EscrowFactory {
             rescue_delay: self.rescue_delay,
             escrow_src_template: self.escrow_src_template.clone(),
             escrow_dst_template: self.escrow_dst_template.clone(),
+            escrow_src_code_hash: self
+                .escrow_src_code_hash
+                .map(|hash| String::from(&Base58CryptoHash::from(hash))),
+            escrow_dst_code_hash: self
+                .escrow_dst_code_hash
+                .map(|hash| String::from(&Base58CryptoHash::from(hash))),
+            src_paused: self.src_paused,
+            dst_paused: self.dst_paused,
         }
     }
 
@@ -527,6 +1127,36 @@ impl EscrowFactory {
             "Only owner can call this method"
         );
     }
+
+    /// Appends one slot to the maker's vector, constant work however many escrows the maker has
+    fn index_maker_escrow(&mut self, maker: AccountId, escrow_account: AccountId) {
+        let mut escrows = self.maker_escrows.get(&maker).unwrap_or_else(|| {
+            let mut prefix = b"m".to_vec();
+            prefix.extend(env::sha256(maker.as_bytes()));
+            Vector::new(prefix)
+        });
+        escrows.push(&escrow_account);
+        // The vector's length lives in the map entry
+        self.maker_escrows.insert(&maker, &escrows);
+    }
+
+    fn check_src_not_paused(&self) -> Result<(), String> {
+        if self.src_paused {
+            return Err("E_SRC_PAUSED: source escrow creation is paused".to_string());
+        }
+        Ok(())
+    }
+
+    fn check_token_allowed(&self, token: &AccountId) -> Result<(), String> {
+        if !self.is_token_allowed(token.clone()) {
+            return Err(format!("Token {} is not on the allowlist", token));
+        }
+        Ok(())
+    }
+
+    fn assert_token_allowed(&self, token: &AccountId) {
+        self.check_token_allowed(token).unwrap_or_else(|e| env::panic_str(&e));
+    }
 }
 
 /// Arguments for escrow initialization
@@ -536,6 +1166,7 @@ impl EscrowFactory {
 pub struct InitEscrowArgs {
     pub immutables: Immutables,
     pub factory: AccountId,
+    pub correlation_id: Option<String>,
 }
 
 /// Factory statistics
@@ -548,45 +1179,291 @@ pub struct FactoryStats {
     pub rescue_delay: u32,
     pub escrow_src_template: AccountId,
     pub escrow_dst_template: AccountId,
+    pub escrow_src_code_hash: Option<String>, // base58
+    pub escrow_dst_code_hash: Option<String>, // base58
+    pub src_paused: bool,
+    pub dst_paused: bool,
 }
 
-impl Timelocks {
-    /// Get timestamp for a specific timelock stage
-    pub fn get_timestamp(&self, stage: TimelockStage) -> u64 {
-        let delay_seconds = match stage {
-            TimelockStage::DstWithdrawal => self.dst_withdrawal,
-            TimelockStage::DstPublicWithdrawal => self.dst_public_withdrawal,
-            TimelockStage::DstCancellation => self.dst_cancellation,
-            TimelockStage::SrcWithdrawal => self.src_withdrawal,
-            TimelockStage::SrcPublicWithdrawal => self.src_public_withdrawal,
-            TimelockStage::SrcCancellation => self.src_cancellation,
-            TimelockStage::SrcPublicCancellation => self.src_public_cancellation,
-        };
+/// Storage stake of one maker index entry, charged to the escrow creator
+fn maker_index_storage_stake() -> u128 {
+    MAKER_INDEX_STORAGE_BYTES * env::storage_byte_cost().as_yoctonear()
+}
 
-        self.deployed_at + (delay_seconds as u64 * 1000)
+/// Part of the deposit attached to a src escrow creation forwarded to the escrow's init, the rest
+/// being the storage stake. Init accepts the principal alone or principal plus safety
+/// deposit, so anything else attached is rejected rather than stranded on the escrow account.
+pub fn src_init_deposit(principal: u128, safety_deposit: u128, attached: u128, storage_stake: u128) -> u128 {
+    try_src_init_deposit(principal, safety_deposit, attached, storage_stake).unwrap_or_else(|e| env::panic_str(&e))
+}
+
+/// `src_init_deposit` returning the mismatch instead of panicking
+pub fn try_src_init_deposit(principal: u128, safety_deposit: u128, attached: u128, storage_stake: u128) -> Result<u128, String> {
+    let with_safety = principal + safety_deposit;
+    match attached.checked_sub(storage_stake) {
+        Some(init_deposit) if init_deposit == principal || init_deposit == with_safety => Ok(init_deposit),
+        _ => Err(format!(
+            "E_SRC_DEPOSIT_MISMATCH: attach {} or {} ({} principal, {} safety deposit, {} storage), got {}",
+            principal + storage_stake,
+            with_safety + storage_stake,
+            principal,
+            safety_deposit,
+            storage_stake,
+            attached
+        )),
     }
+}
 
-    /// Get current timelock phase for destination chain
-    pub fn get_current_dst_phase(&self) -> String {
-        let current_time = env::block_timestamp_ms();
-        let withdrawal_start = self.get_timestamp(TimelockStage::DstWithdrawal);
-        let public_withdrawal_start = self.get_timestamp(TimelockStage::DstPublicWithdrawal);
-        let cancellation_start = self.get_timestamp(TimelockStage::DstCancellation);
+/// `compute_fill_index` returning bad inputs as an error instead of panicking
+pub fn try_compute_fill_index(
+    making_amount: u128,
+    remaining_making_amount: u128,
+    order_making_amount: u128,
+    parts: u32,
+) -> Result<u32, String> {
+    if parts == 0 || parts > MAX_MERKLE_PARTS {
+        return Err(format!("E_INVALID_PARTS: parts must be in 1..={}, got {}", MAX_MERKLE_PARTS, parts));
+    }
+    if making_amount == 0 {
+        return Err("E_ZERO_FILL: making amount must be non-zero".to_string());
+    }
+    if making_amount > remaining_making_amount || remaining_making_amount > order_making_amount {
+        return Err(format!(
+            "E_INVALID_FILL_AMOUNTS: fill {} of remaining {} of order {}",
+            making_amount, remaining_making_amount, order_making_amount
+        ));
+    }
 
-        if current_time < withdrawal_start {
-            "B1_FINALITY_LOCK".to_string()
-        } else if current_time < public_withdrawal_start {
-            "B2_RESOLVER_EXCLUSIVE".to_string()
-        } else if current_time < cancellation_start {
-            "B3_PUBLIC_WITHDRAWAL".to_string()
-        } else {
-            "B4_CANCELLATION".to_string()
-        }
+    let calculated_index = (order_making_amount - remaining_making_amount + making_amount - 1)
+        .checked_mul(parts as u128)
+        .ok_or_else(|| format!("E_FILL_OVERFLOW: order of {} too large to split in {} parts", order_making_amount, parts))?
+        / order_making_amount;
+
+    if remaining_making_amount == making_amount {
+        // Order filled to completion - use secret with index i + 1
+        Ok((calculated_index + 2) as u32)
+    } else {
+        Ok((calculated_index + 1) as u32)
     }
 }
 
-/// Timelock stages matching EVM TimelocksLib.Stage
-pub enum TimelockStage {
+/// NEP-297 `src_escrow_created` log carrying the immutables the escrow is initialized with, so
+/// off-chain services can check them against what they submitted, and the swap's correlation id
+pub fn src_escrow_created_event(
+    immutables: &Immutables,
+    dst_complement: &DstImmutablesComplement,
+    correlation_id: Option<&str>,
+) -> String {
+    let event = serde_json::json!({
+        "standard": "1prime-factory",
+        "version": "1.0.0",
+        "event": "src_escrow_created",
+        "data": [{ "immutables": immutables, "complement": dst_complement, "correlation_id": correlation_id }],
+    });
+    format!("EVENT_JSON:{}", event)
+}
+
+/// `amount` must lie within the token's (min, max) limits when it has any
+pub fn assert_amount_in_range(amount: u128, limits: Option<(u128, u128)>) {
+    check_amount_in_range(amount, limits).unwrap_or_else(|e| env::panic_str(&e));
+}
+
+pub fn check_amount_in_range(amount: u128, limits: Option<(u128, u128)>) -> Result<(), String> {
+    match limits {
+        Some((min, max)) if amount < min || amount > max => {
+            Err(format!("E_AMOUNT_OUT_OF_RANGE: amount {} outside [{}, {}]", amount, min, max))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Without a safety deposit nobody is paid to finish a stalled swap, so every escrow must carry one
+pub fn assert_safety_deposit(safety_deposit: u128) {
+    check_safety_deposit(safety_deposit).unwrap_or_else(|e| env::panic_str(&e));
+}
+
+pub fn check_safety_deposit(safety_deposit: u128) -> Result<(), String> {
+    if safety_deposit == 0 {
+        return Err("E_ZERO_SAFETY_DEPOSIT: safety deposit must be non-zero".to_string());
+    }
+    Ok(())
+}
+
+/// A correlation id ends up in the escrow's state, keep it within what its storage stake covers
+pub fn assert_correlation_id_len(correlation_id: Option<&str>) {
+    check_correlation_id_len(correlation_id).unwrap_or_else(|e| env::panic_str(&e));
+}
+
+pub fn check_correlation_id_len(correlation_id: Option<&str>) -> Result<(), String> {
+    match correlation_id {
+        Some(id) if id.len() > MAX_CORRELATION_ID_LEN => Err(format!(
+            "E_CORRELATION_ID_TOO_LONG: {} bytes, at most {}",
+            id.len(),
+            MAX_CORRELATION_ID_LEN
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Both legs verify the same preimage against the shared hashlock, so they must hash it alike
+pub fn assert_dst_hash_algorithm(immutables: &Immutables, dst_complement: &DstImmutablesComplement) {
+    check_dst_hash_algorithm(immutables, dst_complement).unwrap_or_else(|e| env::panic_str(&e));
+}
+
+/// The src hashlock must be verifiable on the dst chain: an EVM escrow only checks keccak256
+/// preimages, a NEAR escrow checks either
+pub fn check_dst_hash_algorithm(immutables: &Immutables, dst_complement: &DstImmutablesComplement) -> Result<(), String> {
+    if dst_complement.chain_id.0 != NEAR_CHAIN_ID && immutables.hash_algorithm != HashAlgo::Keccak256 {
+        return Err(format!(
+            "E_HASH_ALGORITHM_MISMATCH: dst chain {} only verifies Keccak256, src escrow uses {:?}",
+            dst_complement.chain_id.0, immutables.hash_algorithm
+        ));
+    }
+    Ok(())
+}
+
+/// Reference time for a dst escrow's timelocks. A non-zero `requested` pins the dst timelocks to
+/// the src escrow's reference time so both legs measure their windows from the same instant. It may
+/// only lie in the past, which shortens the dst windows and never extends them, and not so far
+/// back that the escrow would start at or near its cancellation stage. Zero means now.
+pub fn dst_deployed_at(requested: u64, dst_cancellation: u32, now_ms: u64) -> u64 {
+    if requested == 0 {
+        return now_ms;
+    }
+    assert!(
+        requested <= now_ms,
+        "E_DEPLOYED_AT_IN_FUTURE: dst deployed_at {} is after the current block time",
+        requested
+    );
+    let dst_cancellation_start = requested.saturating_add(dst_cancellation as u64 * 1000);
+    assert!(
+        dst_cancellation_start > now_ms.saturating_add(MIN_DST_TIME_TO_CANCELLATION_MS),
+        "E_DEPLOYED_AT_TOO_OLD: dst cancellation at {} leaves less than {}ms after the current block time {}",
+        dst_cancellation_start,
+        MIN_DST_TIME_TO_CANCELLATION_MS,
+        now_ms
+    );
+    requested
+}
+
+/// `src_cancellation_timestamp` must lie more than `margin_ms` after `now_ms`
+pub fn assert_src_cancellation_ahead(src_cancellation_timestamp: u64, now_ms: u64, margin_ms: u64) {
+    assert!(
+        src_cancellation_timestamp > now_ms.saturating_add(margin_ms),
+        "E_SRC_CANCELLATION_NOT_AHEAD: src cancellation at {} must be more than {}ms after now ({})",
+        src_cancellation_timestamp,
+        margin_ms,
+        now_ms
+    );
+}
+
+/// Gas forwarded to a src escrow's init, scaled with the setup the immutables require
+pub fn src_init_gas(immutables: &Immutables) -> Gas {
+    let mut gas = SRC_INIT_BASE_GAS;
+    if immutables.token.as_str() != "near" {
+        gas = gas.saturating_add(NEP141_INIT_EXTRA_GAS);
+    }
+    if immutables.hashlock.starts_with("merkle:") {
+        gas = gas.saturating_add(MERKLE_INIT_EXTRA_GAS);
+    }
+    gas
+}
+
+/// Static gas for the on_src_account_created callback, which forwards `src_init_gas` to init
+fn src_setup_gas(immutables: &Immutables) -> Gas {
+    src_init_gas(immutables).saturating_add(SRC_SETUP_OVERHEAD_GAS)
+}
+
+/// Bytes `compute_immutables_hash` digests: the immutables' compact JSON, fields in declaration order,
+/// integers as decimal digits and U128 amounts as strings. Every escrow address derives from these bytes
+/// (and the resolver service recomputes them off-chain), so adding, removing, renaming or reordering a
+/// field of `Immutables`, `Timelocks` or `ChainContext`, or changing how one serializes, moves the address
+/// of every future escrow and is a breaking change. `test_immutables_hash_bytes_are_pinned` guards them.
+pub fn immutables_hash_preimage(immutables: &Immutables) -> Vec<u8> {
+    near_sdk::serde_json::to_vec(immutables).unwrap()
+}
+
+/// Canonical order hash string, 0x-prefixed lowercase hex, so lookups match however the caller formats it
+pub fn normalize_order_hash(order_hash: &str) -> String {
+    let hex = order_hash
+        .strip_prefix("0x")
+        .or_else(|| order_hash.strip_prefix("0X"))
+        .unwrap_or(order_hash);
+    format!("0x{}", hex.to_lowercase())
+}
+
+/// A normalized order hash is 0x-prefixed hex, long enough for the 8 characters a dst escrow
+/// name takes from it
+pub fn check_order_hash(order_hash: &str) -> Result<(), String> {
+    match order_hash.strip_prefix("0x") {
+        Some(hex) if hex.len() >= 6 && hex.bytes().all(|b| b.is_ascii_hexdigit()) => Ok(()),
+        _ => Err(format!("E_INVALID_ORDER_HASH: expected 0x-prefixed hex of at least 6 digits, got {}", order_hash)),
+    }
+}
+
+impl Timelocks {
+    /// Reject offsets above `max_offset` seconds, which usually means milliseconds were passed
+    pub fn validate(&self, max_offset: u32) {
+        self.check(max_offset).unwrap_or_else(|e| env::panic_str(&e));
+    }
+
+    /// `validate` returning the first violation instead of panicking
+    pub fn check(&self, max_offset: u32) -> Result<(), String> {
+        for offset in [
+            self.src_withdrawal,
+            self.src_public_withdrawal,
+            self.src_cancellation,
+            self.src_public_cancellation,
+            self.dst_withdrawal,
+            self.dst_public_withdrawal,
+            self.dst_cancellation,
+        ] {
+            if offset > max_offset {
+                return Err(format!("Timelock offset {}s exceeds maximum of {}s", offset, max_offset));
+            }
+        }
+        if self.grace_ms > MAX_GRACE_MS {
+            return Err(format!("Grace window {}ms exceeds maximum of {}ms", self.grace_ms, MAX_GRACE_MS));
+        }
+        Ok(())
+    }
+
+    /// Get timestamp for a specific timelock stage
+    pub fn get_timestamp(&self, stage: TimelockStage) -> u64 {
+        let delay_seconds = match stage {
+            TimelockStage::DstWithdrawal => self.dst_withdrawal,
+            TimelockStage::DstPublicWithdrawal => self.dst_public_withdrawal,
+            TimelockStage::DstCancellation => self.dst_cancellation,
+            TimelockStage::SrcWithdrawal => self.src_withdrawal,
+            TimelockStage::SrcPublicWithdrawal => self.src_public_withdrawal,
+            TimelockStage::SrcCancellation => self.src_cancellation,
+            TimelockStage::SrcPublicCancellation => self.src_public_cancellation,
+        };
+
+        self.deployed_at + (delay_seconds as u64 * 1000)
+    }
+
+    /// Get current timelock phase for destination chain
+    pub fn get_current_dst_phase(&self) -> String {
+        let current_time = env::block_timestamp_ms();
+        let withdrawal_start = self.get_timestamp(TimelockStage::DstWithdrawal);
+        let public_withdrawal_start = self.get_timestamp(TimelockStage::DstPublicWithdrawal);
+        let cancellation_start = self.get_timestamp(TimelockStage::DstCancellation);
+
+        if current_time < withdrawal_start {
+            "B1_FINALITY_LOCK".to_string()
+        } else if current_time < public_withdrawal_start {
+            "B2_RESOLVER_EXCLUSIVE".to_string()
+        } else if current_time < cancellation_start {
+            "B3_PUBLIC_WITHDRAWAL".to_string()
+        } else {
+            "B4_CANCELLATION".to_string()
+        }
+    }
+}
+
+/// Timelock stages matching EVM TimelocksLib.Stage
+pub enum TimelockStage {
     SrcWithdrawal,
     SrcPublicWithdrawal,
     SrcCancellation,
@@ -595,3 +1472,1129 @@ pub enum TimelockStage {
     DstPublicWithdrawal,
     DstCancellation,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn immutables(token: &str, hashlock: &str) -> Immutables {
+        Immutables {
+            order_hash: "0xorder".to_string(),
+            hashlock: hashlock.to_string(),
+            maker: "maker.testnet".parse().unwrap(),
+            taker: "resolver.testnet".parse().unwrap(),
+            token: token.parse().unwrap(),
+            amount: U128(1_000),
+            safety_deposit: U128(100),
+            timelocks: Timelocks {
+                deployed_at: 0,
+                src_withdrawal: 60,
+                src_public_withdrawal: 120,
+                src_cancellation: 180,
+                src_public_cancellation: 240,
+                dst_withdrawal: 30,
+                dst_public_withdrawal: 90,
+                dst_cancellation: 150,
+                grace_ms: 0,
+            },
+            public_withdrawal_allowlist: None,
+            chain_context: None,
+            // The test orders go to an EVM chain, which only verifies keccak256
+            hash_algorithm: HashAlgo::Keccak256,
+        }
+    }
+
+    #[test]
+    fn test_nep141_merkle_order_forwards_more_init_gas() {
+        let plain = src_init_gas(&immutables("near", &"ab".repeat(32)));
+        let complex = src_init_gas(&immutables("usdc.testnet", &format!("merkle:4:{}", "ab".repeat(32))));
+
+        assert_eq!(plain, SRC_INIT_BASE_GAS);
+        assert_eq!(
+            complex,
+            SRC_INIT_BASE_GAS.saturating_add(NEP141_INIT_EXTRA_GAS).saturating_add(MERKLE_INIT_EXTRA_GAS)
+        );
+        assert!(complex > plain);
+    }
+
+    #[test]
+    fn test_dst_deployed_at_defaults_to_block_time() {
+        let now = 1_700_000_000_000;
+        assert_eq!(dst_deployed_at(0, 1_500, now), now);
+        // A pinned src reference time is kept as is
+        assert_eq!(dst_deployed_at(now - 60_000, 1_500, now), now - 60_000);
+    }
+
+    #[test]
+    #[should_panic(expected = "E_DEPLOYED_AT_IN_FUTURE")]
+    fn test_future_dst_deployed_at_rejected() {
+        let now = 1_700_000_000_000;
+        dst_deployed_at(now + 1, 1_500, now);
+    }
+
+    #[test]
+    #[should_panic(expected = "E_DEPLOYED_AT_TOO_OLD")]
+    fn test_dst_deployed_at_close_to_cancellation_rejected() {
+        let now = 1_700_000_000_000;
+        // Cancellation would open 4 minutes from now
+        dst_deployed_at(now - 1_260_000, 1_500, now);
+    }
+
+    #[test]
+    #[should_panic(expected = "E_AMOUNT_OUT_OF_RANGE")]
+    fn test_amount_below_min_rejected() {
+        assert_amount_in_range(99, Some((100, 1_000)));
+    }
+
+    #[test]
+    fn test_amount_in_range_accepted() {
+        assert_amount_in_range(100, Some((100, 1_000)));
+        assert_amount_in_range(1_000, Some((100, 1_000)));
+        assert_amount_in_range(u128::MAX, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "E_AMOUNT_OUT_OF_RANGE")]
+    fn test_amount_above_max_rejected() {
+        assert_amount_in_range(1_001, Some((100, 1_000)));
+    }
+
+    #[test]
+    fn test_future_src_cancellation_accepted() {
+        let now = 1_700_000_000_000;
+        assert_src_cancellation_ahead(now + DEFAULT_MIN_CANCELLATION_GAP_MS + 1, now, DEFAULT_MIN_CANCELLATION_GAP_MS);
+    }
+
+    #[test]
+    #[should_panic(expected = "E_SRC_CANCELLATION_NOT_AHEAD")]
+    fn test_past_src_cancellation_rejected() {
+        let now = 1_700_000_000_000;
+        assert_src_cancellation_ahead(now - 60_000, now, DEFAULT_MIN_CANCELLATION_GAP_MS);
+    }
+
+    #[test]
+    #[should_panic(expected = "E_HASH_ALGORITHM_MISMATCH")]
+    fn test_sha256_order_to_evm_chain_rejected() {
+        // The complement copies the src algorithm, only the dst chain decides
+        let src = Immutables { hash_algorithm: HashAlgo::Sha256, ..immutables("near", &"ab".repeat(32)) };
+        let dst_complement = DstImmutablesComplement { hash_algorithm: HashAlgo::Sha256, ..complement() };
+        assert_dst_hash_algorithm(&src, &dst_complement);
+    }
+
+    #[test]
+    fn test_near_dst_accepts_either_hash_algorithm() {
+        let near_dst = DstImmutablesComplement { chain_id: U64(NEAR_CHAIN_ID), ..complement() };
+        for hash_algorithm in [HashAlgo::Sha256, HashAlgo::Keccak256] {
+            let src = Immutables { hash_algorithm, ..immutables("near", &"ab".repeat(32)) };
+            assert!(check_dst_hash_algorithm(&src, &near_dst).is_ok());
+        }
+        assert!(check_dst_hash_algorithm(&immutables("near", &"ab".repeat(32)), &complement()).is_ok());
+    }
+
+    #[test]
+    fn test_src_escrow_created_event_round_trips() {
+        let immutables = Immutables {
+            chain_context: Some(ChainContext { src_chain_id: 397, dst_chain_id: 11155111 }),
+            ..immutables("usdc.testnet", &"ab".repeat(32))
+        };
+        let complement = DstImmutablesComplement {
+            maker: "maker.testnet".parse().unwrap(),
+            taker: CrossChainAsset::Evm("0x1111111111111111111111111111111111111111".to_string()),
+            amount: U128(990),
+            token: CrossChainAsset::Evm("0x2222222222222222222222222222222222222222".to_string()),
+            safety_deposit: U128(50),
+            chain_id: U64(11155111),
+            hash_algorithm: HashAlgo::Sha256,
+        };
+
+        let log = src_escrow_created_event(&immutables, &complement, None);
+        let event: serde_json::Value =
+            serde_json::from_str(log.strip_prefix("EVENT_JSON:").unwrap()).unwrap();
+        assert_eq!(event["event"], "src_escrow_created");
+
+        let data = &event["data"][0];
+        let parsed: Immutables = serde_json::from_value(data["immutables"].clone()).unwrap();
+        let parsed_complement: DstImmutablesComplement =
+            serde_json::from_value(data["complement"].clone()).unwrap();
+        assert_eq!(serde_json::to_value(&parsed).unwrap(), serde_json::to_value(&immutables).unwrap());
+        assert_eq!(
+            serde_json::to_value(&parsed_complement).unwrap(),
+            serde_json::to_value(&complement).unwrap()
+        );
+        assert_eq!(parsed.amount.0, 1_000);
+        assert_eq!(parsed_complement.safety_deposit.0, 50);
+        assert!(data["correlation_id"].is_null());
+    }
+
+    #[test]
+    fn test_src_escrow_created_event_carries_correlation_id() {
+        let complement = DstImmutablesComplement {
+            maker: "maker.testnet".parse().unwrap(),
+            taker: CrossChainAsset::Evm("0x1111111111111111111111111111111111111111".to_string()),
+            amount: U128(990),
+            token: CrossChainAsset::Evm("0x2222222222222222222222222222222222222222".to_string()),
+            safety_deposit: U128(50),
+            chain_id: U64(11155111),
+            hash_algorithm: HashAlgo::Sha256,
+        };
+
+        let log = src_escrow_created_event(
+            &immutables("usdc.testnet", &"ab".repeat(32)),
+            &complement,
+            Some("5f0c9a7e-2b1d-4c3e-9f8a-0d6e4b2a1c77"),
+        );
+        let event: serde_json::Value =
+            serde_json::from_str(log.strip_prefix("EVENT_JSON:").unwrap()).unwrap();
+        assert_eq!(event["data"][0]["correlation_id"], "5f0c9a7e-2b1d-4c3e-9f8a-0d6e4b2a1c77");
+        assert!(check_correlation_id_len(Some("5f0c9a7e-2b1d-4c3e-9f8a-0d6e4b2a1c77")).is_ok());
+        assert!(check_correlation_id_len(Some(&"x".repeat(MAX_CORRELATION_ID_LEN + 1))).is_err());
+    }
+
+    #[test]
+    fn test_src_init_deposit_excludes_storage_stake() {
+        let stake = SRC_ESCROW_STORAGE_BYTES * 10u128.pow(19);
+        let (principal, safety) = (1_000, 100);
+
+        // The escrow's real balance after creation is checked in tests/gas.rs
+        assert_eq!(
+            src_init_deposit(principal, safety, principal + safety + stake, stake),
+            principal + safety
+        );
+
+        assert_eq!(src_init_deposit(principal, safety, principal + stake, stake), principal);
+    }
+
+    #[test]
+    #[should_panic(expected = "E_SRC_DEPOSIT_MISMATCH")]
+    fn test_src_overpayment_rejected() {
+        let stake = SRC_ESCROW_STORAGE_BYTES * 10u128.pow(19);
+        src_init_deposit(1_000, 100, 1_000 + 100 + stake + 1, stake);
+    }
+
+    #[test]
+    fn test_immutables_hash_bytes_are_pinned() {
+        let mut immutables = immutables("near", &"ab".repeat(32));
+        immutables.timelocks.deployed_at = 1_700_000_000_000;
+        immutables.chain_context = Some(ChainContext { src_chain_id: 397, dst_chain_id: 11155111 });
+        immutables.hash_algorithm = HashAlgo::Sha256;
+
+        let expected = format!(
+            concat!(
+                r#"{{"order_hash":"0xorder","hashlock":"{}","maker":"maker.testnet","taker":"resolver.testnet","#,
+                r#""token":"near","amount":"1000","safety_deposit":"100","timelocks":{{"deployed_at":1700000000000,"#,
+                r#""src_withdrawal":60,"src_public_withdrawal":120,"src_cancellation":180,"src_public_cancellation":240,"#,
+                r#""dst_withdrawal":30,"dst_public_withdrawal":90,"dst_cancellation":150,"grace_ms":0}},"#,
+                r#""public_withdrawal_allowlist":null,"chain_context":{{"src_chain_id":397,"dst_chain_id":11155111}},"#,
+                r#""hash_algorithm":"sha256"}}"#,
+            ),
+            "ab".repeat(32)
+        );
+        assert_eq!(String::from_utf8(immutables_hash_preimage(&immutables)).unwrap(), expected);
+        // A change here moves every escrow address, see immutables_hash_preimage
+        assert_eq!(
+            hex::encode(Sha256::digest(immutables_hash_preimage(&immutables))),
+            "216d1d0c4f6ea80f5ca37a31700818d7f93c561582bcefc6448af3e3c35c0d65"
+        );
+    }
+
+    fn factory() -> EscrowFactory {
+        near_sdk::testing_env!(near_sdk::test_utils::VMContextBuilder::new().build());
+        EscrowFactory::new(
+            "owner.testnet".parse().unwrap(),
+            86_400,
+            "src-template.testnet".parse().unwrap(),
+            "dst-template.testnet".parse().unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_contract_version_matches_package_version() {
+        let version = factory().contract_version();
+        let (package, commit) = version.split_once('+').unwrap();
+        assert_eq!(package, env!("CARGO_PKG_VERSION"));
+        assert!(!commit.is_empty());
+    }
+
+    #[test]
+    fn test_fill_indices_match_partial_fill_validation() {
+        let factory = factory();
+        // 100 split in 4 parts, filled 25 at a time: indices 1..=3, then 5 for the completing fill
+        for (fill, remaining, index) in [(25, 100, 1), (25, 75, 2), (25, 50, 3), (25, 25, 5), (100, 100, 5), (60, 100, 3)] {
+            assert_eq!(factory.compute_fill_index(U128(fill), U128(remaining), U128(100), 4), index, "{} of {}", fill, remaining);
+            assert!(factory.validate_partial_fill(U128(fill), U128(remaining), U128(100), 4, index), "{} of {}", fill, remaining);
+            assert!(!factory.validate_partial_fill(U128(fill), U128(remaining), U128(100), 4, index + 1));
+        }
+    }
+
+    #[test]
+    fn test_fill_index_rejects_degenerate_inputs() {
+        for (fill, remaining, order, parts, error) in [
+            (25, 100, 100, 0, "E_INVALID_PARTS"),
+            (25, 100, 100, MAX_MERKLE_PARTS + 1, "E_INVALID_PARTS"),
+            (0, 100, 100, 4, "E_ZERO_FILL"),
+            (25, 100, 0, 4, "E_INVALID_FILL_AMOUNTS"),
+            (50, 25, 100, 4, "E_INVALID_FILL_AMOUNTS"),
+            (u128::MAX, u128::MAX, u128::MAX, 4, "E_FILL_OVERFLOW"),
+        ] {
+            let result = try_compute_fill_index(fill, remaining, order, parts);
+            assert!(result.as_ref().is_err_and(|e| e.starts_with(error)), "{:?} for {} of {} of {} in {}", result, fill, remaining, order, parts);
+        }
+        assert!(!factory().validate_partial_fill(U128(0), U128(100), U128(100), 4, 1));
+    }
+
+    #[test]
+    #[should_panic(expected = "E_INVALID_PARTS")]
+    fn test_fill_index_view_rejects_zero_parts() {
+        factory().compute_fill_index(U128(25), U128(100), U128(100), 0);
+    }
+
+    fn complement() -> DstImmutablesComplement {
+        DstImmutablesComplement {
+            maker: "maker.testnet".parse().unwrap(),
+            taker: CrossChainAsset::Evm("0x1111111111111111111111111111111111111111".to_string()),
+            amount: U128(990),
+            token: CrossChainAsset::Evm("0x2222222222222222222222222222222222222222".to_string()),
+            safety_deposit: U128(50),
+            chain_id: U64(11155111),
+            hash_algorithm: HashAlgo::Keccak256,
+        }
+    }
+
+    fn failed_checks(report: &ValidationReport) -> Vec<&str> {
+        report.checks.iter().filter(|check| !check.passed).map(|check| check.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_validate_order_accepts_valid_order() {
+        let factory = factory();
+        let stake = factory.src_escrow_storage_stake().0;
+
+        let report = factory.validate_order(immutables("near", &"ab".repeat(32)), complement(), U128(1_100 + stake));
+        assert!(report.valid, "{:?}", report);
+        assert_eq!(report.checks.len(), 7);
+        // Principal alone is accepted too, the resolver may attach the safety deposit later
+        assert!(factory.validate_order(immutables("near", &"ab".repeat(32)), complement(), U128(1_000 + stake)).valid);
+    }
+
+    #[test]
+    fn test_validate_order_reports_each_failure() {
+        let mut factory = factory();
+        let stake = factory.src_escrow_storage_stake().0;
+        let valid = || immutables("near", &"ab".repeat(32));
+
+        let report = factory.validate_order(valid(), complement(), U128(1_100 + stake + 1));
+        assert_eq!(failed_checks(&report), ["deposit"]);
+        assert!(report.checks[0].error.as_ref().unwrap().contains("E_SRC_DEPOSIT_MISMATCH"));
+
+        let mut bad_timelocks = valid();
+        bad_timelocks.timelocks.src_cancellation = 180_000_000; // ms passed as seconds
+        assert_eq!(failed_checks(&factory.validate_order(bad_timelocks, complement(), U128(1_100 + stake))), ["timelocks"]);
+
+        // NEP-141 principal is pulled from the maker, only the safety deposit is attached
+        let unlisted = immutables("usdc.testnet", &"ab".repeat(32));
+        assert_eq!(failed_checks(&factory.validate_order(unlisted, complement(), U128(100 + stake))), ["token_allowed"]);
+
+        factory.amount_limits.insert(&"near".parse().unwrap(), &(2_000, 10_000));
+        assert_eq!(failed_checks(&factory.validate_order(valid(), complement(), U128(1_100 + stake))), ["amount_limits"]);
+        factory.amount_limits.remove(&"near".parse().unwrap());
+
+        let no_safety = Immutables { safety_deposit: U128(0), ..valid() };
+        let report = factory.validate_order(no_safety, complement(), U128(1_000 + stake));
+        assert_eq!(failed_checks(&report), ["safety_deposit"]);
+        assert!(report.checks[1].error.as_ref().unwrap().starts_with("E_ZERO_SAFETY_DEPOSIT"));
+
+        let sha256 = Immutables { hash_algorithm: HashAlgo::Sha256, ..valid() };
+        assert_eq!(failed_checks(&factory.validate_order(sha256, complement(), U128(1_100 + stake))), ["hash_algorithm"]);
+
+        // A later fill of an order that already has an escrow deploys its own, as create_src_escrow does
+        factory.deployed_escrows.insert(&"0xorder".to_string(), &"escrow-0-0xorder.factory.testnet".parse().unwrap());
+        assert!(factory.validate_order(valid(), complement(), U128(1_100 + stake)).valid);
+        factory.deployed_escrows.remove(&"0xorder".to_string());
+
+        factory.src_paused = true;
+        let report = factory.validate_order(valid(), complement(), U128(1_100 + stake));
+        assert_eq!(failed_checks(&report), ["not_paused"]);
+        assert!(report.checks.last().unwrap().error.as_ref().unwrap().starts_with("E_SRC_PAUSED"));
+    }
+
+    #[test]
+    fn test_migrate_keeps_escrows_and_defaults_new_settings() {
+        as_factory_callback();
+        let mut deployed_escrows = LookupMap::new("escrows".as_bytes());
+        deployed_escrows.insert(&"0xorder".to_string(), &"escrow-0-0xorder.factory.testnet".parse().unwrap());
+        env::state_write(&EscrowFactoryV1 {
+            owner: "owner.testnet".parse().unwrap(),
+            escrow_src_template: "src-template.testnet".parse().unwrap(),
+            escrow_dst_template: "dst-template.testnet".parse().unwrap(),
+            deployed_escrows,
+            escrow_counter: 3,
+            rescue_delay: 86_400,
+        });
+
+        let factory = EscrowFactory::migrate();
+        assert_eq!(factory.owner, "owner.testnet".parse::<AccountId>().unwrap());
+        assert_eq!(factory.escrow_counter, 3);
+        assert!(factory.get_escrow_address("0xorder".to_string()).is_some());
+        assert!(!factory.src_paused && !factory.dst_paused);
+        assert_eq!(factory.min_cancellation_gap_ms, DEFAULT_MIN_CANCELLATION_GAP_MS);
+        assert!(factory.get_escrows_by_maker("maker.testnet".parse().unwrap(), None, None).is_empty());
+    }
+
+    #[test]
+    fn test_creation_result_timestamps_round_trip_as_strings() {
+        let result = EscrowCreationResult {
+            escrow_account: "escrow-0-0xorder.factory.testnet".parse().unwrap(),
+            order_hash: "0xorder".to_string(),
+            success: true,
+            deployed_at: U64(u64::MAX),
+        };
+        let json = serde_json::to_value(&result).unwrap();
+        assert_eq!(json["deployed_at"], u64::MAX.to_string());
+
+        let parsed: EscrowCreationResult = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed.deployed_at, U64(u64::MAX));
+
+        // create_dst_escrow's argument decodes from a string the same way
+        let args: serde_json::Value = serde_json::json!({ "src_cancellation_timestamp": "1700001800000" });
+        let timestamp: U64 = serde_json::from_value(args["src_cancellation_timestamp"].clone()).unwrap();
+        assert_eq!(timestamp.0, 1_700_001_800_000);
+    }
+
+    fn as_owner_with_gas() {
+        as_owner_with_prepaid_gas(Gas::from_tgas(300));
+    }
+
+    fn pausable_order() -> Immutables {
+        Immutables { order_hash: "0xabcdef0123".to_string(), ..immutables("near", &"ab".repeat(32)) }
+    }
+
+    /// Owner call attaching exactly what a native src escrow for `pausable_order` needs
+    fn as_owner_funding_src(factory: &EscrowFactory) {
+        let stake = factory.src_escrow_storage_stake().0;
+        near_sdk::testing_env!(near_sdk::test_utils::VMContextBuilder::new()
+            .predecessor_account_id("owner.testnet".parse().unwrap())
+            .block_timestamp(1_700_000_000_000 * 1_000_000)
+            .prepaid_gas(Gas::from_tgas(300))
+            .attached_deposit(NearToken::from_yoctonear(1_100 + stake))
+            .build());
+    }
+
+    #[test]
+    fn test_dst_pause_leaves_src_creation_open() {
+        let mut factory = factory();
+        as_owner_with_gas();
+        factory.set_dst_paused(true);
+        as_owner_funding_src(&factory);
+
+        factory.create_src_escrow("0xabcdef0123".to_string(), pausable_order(), complement(), None);
+        assert!(factory.get_stats().dst_paused && !factory.get_stats().src_paused);
+    }
+
+    #[test]
+    #[should_panic(expected = "E_DST_PAUSED")]
+    fn test_dst_pause_blocks_dst_creation() {
+        let mut factory = factory();
+        as_owner_with_gas();
+        factory.set_dst_paused(true);
+
+        factory.create_dst_escrow(pausable_order(), U64(1_700_000_000_000 + 86_400_000), None);
+    }
+
+    fn as_owner_with_prepaid_gas(gas: Gas) {
+        near_sdk::testing_env!(near_sdk::test_utils::VMContextBuilder::new()
+            .predecessor_account_id("owner.testnet".parse().unwrap())
+            .block_timestamp(1_700_000_000_000 * 1_000_000)
+            .prepaid_gas(gas)
+            .attached_deposit(NearToken::from_yoctonear(1_100 + MAKER_INDEX_STORAGE_BYTES * 10u128.pow(19)))
+            .build());
+    }
+
+    #[test]
+    fn test_under_gassed_dst_creation_panics_before_state_changes() {
+        let mut factory = factory();
+        let required = CREATE_ESCROW_GAS.saturating_add(CALLBACK_GAS).saturating_add(DST_CREATION_OVERHEAD_GAS);
+        as_owner_with_prepaid_gas(required.saturating_sub(Gas::from_gas(1)));
+
+        let panic = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            factory.create_dst_escrow(pausable_order(), U64(1_700_000_000_000 + 86_400_000), None);
+        }))
+        .unwrap_err();
+        let message = panic.downcast_ref::<String>().cloned().unwrap_or_default();
+        assert!(message.contains("Insufficient gas"), "{}", message);
+        assert!(factory.get_escrow_address("0xabcdef0123".to_string()).is_none());
+        assert_eq!(factory.escrow_counter, 0);
+    }
+
+    #[test]
+    fn test_dst_creation_with_exact_required_gas_accepted() {
+        let mut factory = factory();
+        let required = CREATE_ESCROW_GAS.saturating_add(CALLBACK_GAS).saturating_add(DST_CREATION_OVERHEAD_GAS);
+        as_owner_with_prepaid_gas(required);
+
+        factory.create_dst_escrow(pausable_order(), U64(1_700_000_000_000 + 86_400_000), None);
+        assert!(factory.get_escrow_address("0xabcdef0123".to_string()).is_some());
+    }
+
+    fn usdc_order() -> Immutables {
+        Immutables { token: "usdc.testnet".parse().unwrap(), ..pausable_order() }
+    }
+
+    #[test]
+    fn test_allowlisted_token_accepted() {
+        let mut factory = factory();
+        as_owner_with_gas();
+        factory.add_token("usdc.testnet".parse().unwrap());
+
+        factory.create_dst_escrow(usdc_order(), U64(1_700_000_000_000 + 86_400_000), None);
+        assert!(factory.get_escrow_address("0xabcdef0123".to_string()).is_some());
+    }
+
+    #[test]
+    #[should_panic(expected = "Token usdc.testnet is not on the allowlist")]
+    fn test_unlisted_token_rejected() {
+        let mut factory = factory();
+        as_owner_with_gas();
+        factory.add_token("other.testnet".parse().unwrap());
+
+        factory.create_src_escrow("0xabcdef0123".to_string(), usdc_order(), complement(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Token usdc.testnet is not on the allowlist")]
+    fn test_removed_token_rejected() {
+        let mut factory = factory();
+        as_owner_with_gas();
+        factory.add_token("usdc.testnet".parse().unwrap());
+        factory.remove_token("usdc.testnet".parse().unwrap());
+
+        factory.create_dst_escrow(usdc_order(), U64(1_700_000_000_000 + 86_400_000), None);
+    }
+
+    #[test]
+    fn test_permissionless_mode_accepts_unlisted_token() {
+        let mut factory = factory();
+        as_owner_with_gas();
+        factory.set_permissionless_tokens(true);
+
+        factory.create_dst_escrow(usdc_order(), U64(1_700_000_000_000 + 86_400_000), None);
+        assert!(factory.get_escrow_address("0xabcdef0123".to_string()).is_some());
+        // Native NEAR never needs an entry
+        assert!(factory.is_token_allowed("near".parse().unwrap()));
+    }
+
+    #[test]
+    #[should_panic(expected = "Only owner can call this method")]
+    fn test_add_token_requires_owner() {
+        let mut factory = factory();
+        near_sdk::testing_env!(near_sdk::test_utils::VMContextBuilder::new()
+            .predecessor_account_id("resolver.testnet".parse().unwrap())
+            .build());
+        factory.add_token("usdc.testnet".parse().unwrap());
+    }
+
+    #[test]
+    fn test_escrow_lookup_ignores_order_hash_prefix_and_case() {
+        let mut factory = factory();
+        as_owner_with_gas();
+        let order = Immutables { order_hash: "0XABCDEF0123".to_string(), ..pausable_order() };
+        factory.create_dst_escrow(order, U64(1_700_000_000_000 + 86_400_000), None);
+
+        let escrow = factory.get_escrow_address("0xabcdef0123".to_string()).expect("escrow not found");
+        for order_hash in ["abcdef0123", "ABCDEF0123", "0XAbCdEf0123", "0xABCDEF0123"] {
+            assert_eq!(factory.get_escrow_address(order_hash.to_string()), Some(escrow.clone()), "{}", order_hash);
+        }
+    }
+
+    fn pinned_dst_order(deployed_at: u64) -> Immutables {
+        let mut order = pausable_order();
+        order.timelocks.dst_cancellation = 1_500;
+        order.timelocks.deployed_at = deployed_at;
+        order
+    }
+
+    #[test]
+    fn test_create_dst_escrow_keeps_pinned_deployed_at() {
+        let mut factory = factory();
+        as_owner_with_gas();
+        let pinned = 1_700_000_000_000 - 600_000;
+
+        factory.create_dst_escrow(pinned_dst_order(pinned), U64(pinned + 1_800_000), None);
+        assert!(factory.get_escrow_address("0xabcdef0123".to_string()).is_some());
+    }
+
+    #[test]
+    fn test_src_escrow_stamps_deployed_at_in_ms_like_dst() {
+        let mut factory = factory();
+        let stake = factory.src_escrow_storage_stake().0;
+        near_sdk::testing_env!(near_sdk::test_utils::VMContextBuilder::new()
+            .current_account_id("factory.testnet".parse().unwrap())
+            .predecessor_account_id("factory.testnet".parse().unwrap())
+            .block_timestamp(1_700_000_000_000 * 1_000_000)
+            .prepaid_gas(Gas::from_tgas(300))
+            .attached_deposit(NearToken::from_yoctonear(1_100 + stake))
+            .build());
+
+        factory.src_contract_deployment(
+            "0xabcdef0123".to_string(),
+            pausable_order(),
+            complement(),
+            U128(0),
+            None,
+            "resolver.testnet".parse().unwrap(),
+        );
+
+        let logs = near_sdk::test_utils::get_logs();
+        let event: serde_json::Value =
+            serde_json::from_str(logs[0].strip_prefix("EVENT_JSON:").unwrap()).unwrap();
+        // Same unit as a dst escrow created in this block without a pinned deployed_at
+        assert_eq!(event["data"][0]["immutables"]["timelocks"]["deployed_at"], 1_700_000_000_000u64);
+        assert_eq!(dst_deployed_at(0, 1_500, 1_700_000_000_000), 1_700_000_000_000);
+    }
+
+    #[test]
+    #[should_panic(expected = "E_DEPLOYED_AT_TOO_OLD")]
+    fn test_create_dst_escrow_rejects_deployed_at_past_cancellation() {
+        let mut factory = factory();
+        as_owner_with_gas();
+        // The dst would be born in its cancellation stage
+        let pinned = 1_700_000_000_000 - 1_600_000;
+
+        factory.create_dst_escrow(pinned_dst_order(pinned), U64(1_700_000_000_000 + 86_400_000), None);
+    }
+
+    #[test]
+    fn test_src_pause_leaves_dst_creation_open() {
+        let mut factory = factory();
+        as_owner_with_gas();
+        factory.set_src_paused(true);
+
+        factory.create_dst_escrow(pausable_order(), U64(1_700_000_000_000 + 86_400_000), None);
+        assert!(factory.get_escrow_address("0xabcdef0123".to_string()).is_some());
+    }
+
+    /// Panic message of `create_src_escrow` for `pausable_order` attaching `attached`, with no
+    /// receipt created before it
+    fn create_src_escrow_panic(attached: u128) -> String {
+        let mut factory = factory();
+        near_sdk::testing_env!(near_sdk::test_utils::VMContextBuilder::new()
+            .predecessor_account_id("resolver.testnet".parse().unwrap())
+            .block_timestamp(1_700_000_000_000 * 1_000_000)
+            .prepaid_gas(Gas::from_tgas(300))
+            .attached_deposit(NearToken::from_yoctonear(attached))
+            .build());
+        let panic = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            factory.create_src_escrow("0xabcdef0123".to_string(), pausable_order(), complement(), None);
+        }))
+        .unwrap_err();
+        assert!(near_sdk::test_utils::get_created_receipts().is_empty());
+        panic.downcast_ref::<String>().cloned().unwrap_or_default()
+    }
+
+    #[test]
+    fn test_mismatched_src_attachment_panics_in_create_src_escrow() {
+        let stake = factory().src_escrow_storage_stake().0;
+        let message = create_src_escrow_panic(1_100 + stake + 1);
+        assert!(message.contains("E_SRC_DEPOSIT_MISMATCH"), "{}", message);
+    }
+
+    #[test]
+    fn test_src_attachment_without_storage_stake_panics_in_create_src_escrow() {
+        let message = create_src_escrow_panic(1_100);
+        assert!(message.contains("E_SRC_DEPOSIT_MISMATCH"), "{}", message);
+    }
+
+    #[test]
+    #[should_panic(expected = "E_SRC_PAUSED")]
+    fn test_src_pause_blocks_src_creation() {
+        let mut factory = factory();
+        as_owner_with_gas();
+        factory.set_src_paused(true);
+
+        factory.create_src_escrow("0xabcdef0123".to_string(), pausable_order(), complement(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "E_ZERO_SAFETY_DEPOSIT")]
+    fn test_create_src_escrow_rejects_zero_safety_deposit() {
+        let mut factory = factory();
+        as_owner_with_gas();
+
+        let order = Immutables { safety_deposit: U128(0), ..pausable_order() };
+        factory.create_src_escrow("0xabcdef0123".to_string(), order, complement(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "E_ZERO_SAFETY_DEPOSIT")]
+    fn test_create_dst_escrow_rejects_zero_safety_deposit() {
+        let mut factory = factory();
+        as_owner_with_gas();
+
+        let order = Immutables { safety_deposit: U128(0), ..pausable_order() };
+        factory.create_dst_escrow(order, U64(1_700_000_000_000 + 86_400_000), None);
+    }
+
+    #[test]
+    fn test_check_safety_deposit() {
+        assert!(check_safety_deposit(0).unwrap_err().starts_with("E_ZERO_SAFETY_DEPOSIT"));
+        assert!(check_safety_deposit(1).is_ok());
+    }
+
+    #[test]
+    fn test_src_deployment_covers_account_storage() {
+        let mut factory = factory();
+        let stake = factory.src_escrow_storage_stake().0;
+        assert_eq!(stake, SRC_ESCROW_STORAGE_BYTES * 10u128.pow(19) + maker_index_storage_stake());
+
+        near_sdk::testing_env!(near_sdk::test_utils::VMContextBuilder::new()
+            .current_account_id("factory.testnet".parse().unwrap())
+            .predecessor_account_id("factory.testnet".parse().unwrap())
+            .prepaid_gas(Gas::from_tgas(300))
+            .attached_deposit(NearToken::from_yoctonear(1_100 + stake))
+            .build());
+        factory.src_contract_deployment(
+            "0xabcdef0123".to_string(),
+            pausable_order(),
+            complement(),
+            U128(0),
+            None,
+            "resolver.testnet".parse().unwrap(),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "E_SRC_DEPOSIT_MISMATCH")]
+    fn test_src_deployment_without_storage_stake_rejected() {
+        let mut factory = factory();
+        // Escrow funds alone, nothing for the new account's storage
+        near_sdk::testing_env!(near_sdk::test_utils::VMContextBuilder::new()
+            .current_account_id("factory.testnet".parse().unwrap())
+            .predecessor_account_id("factory.testnet".parse().unwrap())
+            .prepaid_gas(Gas::from_tgas(300))
+            .attached_deposit(NearToken::from_yoctonear(1_100))
+            .build());
+        factory.src_contract_deployment(
+            "0xabcdef0123".to_string(),
+            pausable_order(),
+            complement(),
+            U128(0),
+            None,
+            "resolver.testnet".parse().unwrap(),
+        );
+    }
+
+    #[test]
+    fn test_create_src_escrow_accepts_offset_at_max() {
+        let mut factory = factory();
+        as_owner_with_gas();
+        // pausable_order's largest offset is src_public_cancellation at 240s
+        factory.set_max_timelock_offset(240);
+        as_owner_funding_src(&factory);
+
+        factory.create_src_escrow("0xabcdef0123".to_string(), pausable_order(), complement(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Timelock offset 240s exceeds maximum of 239s")]
+    fn test_create_src_escrow_rejects_offset_above_max() {
+        let mut factory = factory();
+        as_owner_with_gas();
+        factory.set_max_timelock_offset(239);
+
+        factory.create_src_escrow("0xabcdef0123".to_string(), pausable_order(), complement(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "exceeds maximum of 2592000s")]
+    fn test_create_dst_escrow_rejects_offset_in_ms() {
+        let mut factory = factory();
+        as_owner_with_gas();
+        let mut order = pausable_order();
+        order.timelocks.dst_cancellation = 150_000_000; // ms passed as seconds
+
+        factory.create_dst_escrow(order, U64(1_700_000_000_000 + 86_400_000), None);
+    }
+
+    /// pausable_order's dst cancellation opens 150s after the block time as_owner_with_gas sets
+    const DST_CANCELLATION_START: u64 = 1_700_000_000_000 + 150_000;
+
+    #[test]
+    fn test_create_dst_escrow_accepts_exact_cancellation_gap() {
+        let mut factory = factory();
+        as_owner_with_gas();
+
+        let src_cancellation = DST_CANCELLATION_START + DEFAULT_MIN_CANCELLATION_GAP_MS;
+        factory.create_dst_escrow(pausable_order(), U64(src_cancellation), None);
+        assert!(factory.get_escrow_address("0xabcdef0123".to_string()).is_some());
+    }
+
+    #[test]
+    #[should_panic(expected = "must precede src cancellation")]
+    fn test_create_dst_escrow_rejects_insufficient_cancellation_gap() {
+        let mut factory = factory();
+        as_owner_with_gas();
+
+        let src_cancellation = DST_CANCELLATION_START + DEFAULT_MIN_CANCELLATION_GAP_MS - 1;
+        factory.create_dst_escrow(pausable_order(), U64(src_cancellation), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "must precede src cancellation")]
+    fn test_create_dst_escrow_applies_configured_cancellation_gap() {
+        let mut factory = factory();
+        as_owner_with_gas();
+        factory.set_min_cancellation_gap_ms(DEFAULT_MIN_CANCELLATION_GAP_MS * 2);
+        // A later dst cancellation keeps the src one ahead of now by more than the gap
+        let mut order = pausable_order();
+        order.timelocks.dst_cancellation = 1_000;
+
+        // Enough for the default gap, not the configured one
+        let src_cancellation = 1_700_000_000_000 + 1_000_000 + DEFAULT_MIN_CANCELLATION_GAP_MS;
+        factory.create_dst_escrow(order, U64(src_cancellation), None);
+    }
+
+    /// Debug rendering of the actions sent to new escrow accounts, global contract use included
+    fn escrow_deploy_actions() -> String {
+        let actions: Vec<_> = near_sdk::test_utils::get_created_receipts()
+            .into_iter()
+            .filter(|receipt| receipt.receiver_id.as_str().starts_with("escrow-"))
+            .flat_map(|receipt| receipt.actions)
+            .collect();
+        format!("{:?}", actions)
+    }
+
+    fn deploy_src_escrow(factory: &mut EscrowFactory) {
+        let stake = factory.src_escrow_storage_stake().0;
+        near_sdk::testing_env!(near_sdk::test_utils::VMContextBuilder::new()
+            .current_account_id("factory.testnet".parse().unwrap())
+            .predecessor_account_id("factory.testnet".parse().unwrap())
+            .prepaid_gas(Gas::from_tgas(300))
+            .attached_deposit(NearToken::from_yoctonear(1_100 + stake))
+            .build());
+        factory.src_contract_deployment(
+            "0xabcdef0123".to_string(),
+            pausable_order(),
+            complement(),
+            U128(0),
+            None,
+            "resolver.testnet".parse().unwrap(),
+        );
+    }
+
+    /// Method and arguments of the function calls sent to new escrow accounts
+    fn escrow_init_calls() -> Vec<(String, Vec<u8>)> {
+        near_sdk::test_utils::get_created_receipts()
+            .into_iter()
+            .filter(|receipt| receipt.receiver_id.as_str().starts_with("escrow-"))
+            .flat_map(|receipt| receipt.actions)
+            .filter_map(|action| match action {
+                near_sdk::mock::MockAction::FunctionCallWeight { method_name, args, .. } => {
+                    Some((String::from_utf8(method_name).unwrap(), args))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_borsh_immutables_round_trip_in_fewer_bytes() {
+        let mut factory = factory();
+        let order = immutables("near", &"ab".repeat(32));
+        let (_, json_args) = factory.escrow_init_call(order.clone(), None);
+        factory.borsh_immutables = true;
+        let (method, blob_args) = factory.escrow_init_call(order.clone(), None);
+        assert_eq!(method, "init_from_blob");
+
+        let args: serde_json::Value = serde_json::from_slice(&blob_args).unwrap();
+        let blob: Base64VecU8 = serde_json::from_value(args["immutables_blob"].clone()).unwrap();
+        let decoded = Immutables::try_from_slice(&blob.0).unwrap();
+        assert_eq!(format!("{:?}", decoded), format!("{:?}", order));
+        assert!(blob_args.len() < json_args.len(), "blob {} bytes, json {} bytes", blob_args.len(), json_args.len());
+    }
+
+    #[test]
+    fn test_src_deploy_uses_configured_immutables_encoding() {
+        let mut factory = factory();
+        deploy_src_escrow(&mut factory);
+        let calls = escrow_init_calls();
+        assert_eq!(calls.iter().map(|(method, _)| method.as_str()).collect::<Vec<_>>(), vec!["init"]);
+
+        near_sdk::testing_env!(near_sdk::test_utils::VMContextBuilder::new()
+            .predecessor_account_id("owner.testnet".parse().unwrap())
+            .build());
+        factory.set_borsh_immutables(true);
+        deploy_src_escrow(&mut factory);
+        let calls = escrow_init_calls();
+        assert_eq!(calls.iter().map(|(method, _)| method.as_str()).collect::<Vec<_>>(), vec!["init_from_blob"]);
+
+        let args: serde_json::Value = serde_json::from_slice(&calls[0].1).unwrap();
+        let blob: Base64VecU8 = serde_json::from_value(args["immutables_blob"].clone()).unwrap();
+        assert_eq!(Immutables::try_from_slice(&blob.0).unwrap().maker.as_str(), "maker.testnet");
+    }
+
+    fn on_factory_account(factory_id: &str) {
+        near_sdk::testing_env!(near_sdk::test_utils::VMContextBuilder::new()
+            .current_account_id(factory_id.parse().unwrap())
+            .build());
+    }
+
+    #[test]
+    fn test_escrow_address_at_account_id_limit_accepted() {
+        let factory = factory();
+        // "escrow-" + 16 hex chars + "." leaves 40 characters for the factory id
+        on_factory_account(&format!("{}.testnet", "a".repeat(32)));
+        let escrow = factory.compute_escrow_address(&pausable_order());
+        assert_eq!(escrow.as_str().len(), MAX_ACCOUNT_ID_LEN);
+    }
+
+    #[test]
+    #[should_panic(expected = "E_ACCOUNT_ID_TOO_LONG")]
+    fn test_long_factory_account_id_rejected() {
+        let factory = factory();
+        on_factory_account(&format!("{}.testnet", "a".repeat(33)));
+        factory.compute_escrow_address(&pausable_order());
+    }
+
+    #[test]
+    #[should_panic(expected = "E_ACCOUNT_ID_TOO_LONG")]
+    fn test_src_deploy_on_long_factory_account_rejected() {
+        let mut factory = factory();
+        let long_factory = format!("{}.testnet", "a".repeat(33));
+        let stake = factory.src_escrow_storage_stake().0;
+        near_sdk::testing_env!(near_sdk::test_utils::VMContextBuilder::new()
+            .current_account_id(long_factory.parse().unwrap())
+            .predecessor_account_id(long_factory.parse().unwrap())
+            .prepaid_gas(Gas::from_tgas(300))
+            .attached_deposit(NearToken::from_yoctonear(1_100 + stake))
+            .build());
+        factory.src_contract_deployment(
+            "0xabcdef0123".to_string(),
+            pausable_order(),
+            complement(),
+            U128(0),
+            None,
+            "resolver.testnet".parse().unwrap(),
+        );
+    }
+
+    #[test]
+    fn test_pinned_src_code_hash_used_in_deploy() {
+        let mut unpinned = factory();
+        deploy_src_escrow(&mut unpinned);
+        assert!(escrow_deploy_actions().contains("src-template.testnet"));
+
+        let mut pinned = factory();
+        pinned.escrow_src_code_hash = Some([7u8; 32]);
+        deploy_src_escrow(&mut pinned);
+        let actions = escrow_deploy_actions();
+        assert!(actions.contains(&format!("{:?}", [7u8; 32])), "{}", actions);
+        assert!(!actions.contains("src-template.testnet"), "{}", actions);
+    }
+
+    #[test]
+    fn test_pinned_dst_code_hash_used_in_deploy() {
+        let mut unpinned = factory();
+        as_owner_with_gas();
+        unpinned.create_dst_escrow(pausable_order(), U64(1_700_000_000_000 + 86_400_000), None);
+        assert!(escrow_deploy_actions().contains("dst-template.testnet"));
+
+        let mut pinned = factory();
+        as_owner_with_gas();
+        pinned.set_escrow_dst_code_hash(Some(Base58CryptoHash::from([7u8; 32])));
+        pinned.create_dst_escrow(pausable_order(), U64(1_700_000_000_000 + 86_400_000), None);
+        let actions = escrow_deploy_actions();
+        assert!(actions.contains(&format!("{:?}", [7u8; 32])), "{}", actions);
+        assert!(!actions.contains("dst-template.testnet"), "{}", actions);
+    }
+
+    /// Runs a factory callback as the factory itself, as #[private] requires
+    fn as_factory_callback() {
+        near_sdk::testing_env!(near_sdk::test_utils::VMContextBuilder::new()
+            .current_account_id("factory.testnet".parse().unwrap())
+            .predecessor_account_id("factory.testnet".parse().unwrap())
+            .build());
+    }
+
+    fn refunds_to(account: &str) -> Vec<NearToken> {
+        near_sdk::test_utils::get_created_receipts()
+            .into_iter()
+            .filter(|receipt| receipt.receiver_id.as_str() == account)
+            .flat_map(|receipt| receipt.actions)
+            .filter_map(|action| match action {
+                near_sdk::mock::MockAction::Transfer { deposit, .. } => Some(deposit),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_src_account_already_exists_refunds_caller_and_records_nothing() {
+        let mut factory = factory();
+        as_factory_callback();
+
+        let result = factory.on_src_account_created(
+            "0xabcdef0123".to_string(),
+            "escrow-0.factory.testnet".parse().unwrap(),
+            pausable_order(),
+            U128(0),
+            NearToken::from_yoctonear(1_100),
+            NearToken::from_yoctonear(20),
+            None,
+            "resolver.testnet".parse().unwrap(),
+            Err(near_sdk::PromiseError::Failed),
+        );
+
+        let PromiseOrValue::Value(result) = result else { panic!("a failed creation must not init") };
+        assert!(!result.success);
+        assert_eq!(factory.get_stats().total_escrows_created, 0);
+        assert!(factory.get_escrow_address("0xabcdef0123".to_string()).is_none());
+        assert_eq!(refunds_to("resolver.testnet"), vec![NearToken::from_yoctonear(1_120)]);
+    }
+
+    #[test]
+    fn test_src_init_failure_refunds_init_deposit_to_caller() {
+        let mut factory = factory();
+        as_factory_callback();
+        factory.deployed_escrows.insert(&"0xabcdef0123".to_string(), &"escrow-0.factory.testnet".parse().unwrap());
+        factory.escrow_counter = 1;
+
+        let result = factory.on_src_escrow_created(
+            "0xabcdef0123".to_string(),
+            "escrow-0.factory.testnet".parse().unwrap(),
+            "maker.testnet".parse().unwrap(),
+            U64(1_700_000_000_000),
+            None,
+            "resolver.testnet".parse().unwrap(),
+            NearToken::from_yoctonear(1_100),
+            Err(near_sdk::PromiseError::Failed),
+        );
+
+        assert!(!result.success);
+        assert_eq!(factory.get_stats().total_escrows_created, 1);
+        assert!(factory.get_escrow_address("0xabcdef0123".to_string()).is_none());
+        assert_eq!(
+            refunds_to("resolver.testnet"),
+            vec![NearToken::from_yoctonear(1_100 + MAKER_INDEX_STORAGE_BYTES * 10u128.pow(19))]
+        );
+    }
+
+    #[test]
+    fn test_dst_creation_failure_refunds_caller_and_keeps_counter() {
+        let mut factory = factory();
+        as_owner_with_gas();
+        factory.create_dst_escrow(pausable_order(), U64(1_700_000_000_000 + 86_400_000), None);
+        assert_eq!(factory.get_stats().total_escrows_created, 1);
+        let escrow = factory.get_escrow_address("0xabcdef0123".to_string()).unwrap();
+
+        as_factory_callback();
+        let result = factory.on_escrow_created(
+            "0xabcdef0123".to_string(),
+            escrow,
+            "maker.testnet".parse().unwrap(),
+            U64(1_700_000_000_000),
+            None,
+            "resolver.testnet".parse().unwrap(),
+            NearToken::from_yoctonear(1_100),
+            Err(near_sdk::PromiseError::Failed),
+        );
+
+        assert!(!result.success);
+        assert_eq!(factory.get_stats().total_escrows_created, 1);
+        assert!(factory.get_escrow_address("0xabcdef0123".to_string()).is_none());
+        assert_eq!(
+            refunds_to("resolver.testnet"),
+            vec![NearToken::from_yoctonear(1_100 + MAKER_INDEX_STORAGE_BYTES * 10u128.pow(19))]
+        );
+    }
+
+    #[test]
+    fn test_failed_dst_creation_does_not_reuse_in_flight_name() {
+        let mut factory = factory();
+        as_owner_with_gas();
+        factory.create_dst_escrow(pausable_order(), U64(1_700_000_000_000 + 86_400_000), None);
+        let first = factory.get_escrow_address("0xabcdef0123".to_string()).unwrap();
+        let second_order = Immutables { order_hash: "0x1234567890".to_string(), ..pausable_order() };
+        factory.create_dst_escrow(second_order, U64(1_700_000_000_000 + 86_400_000), None);
+        let in_flight = factory.get_escrow_address("0x1234567890".to_string()).unwrap();
+
+        // The first creation fails while the second is still in flight
+        as_factory_callback();
+        factory.on_escrow_created(
+            "0xabcdef0123".to_string(),
+            first.clone(),
+            "maker.testnet".parse().unwrap(),
+            U64(1_700_000_000_000),
+            None,
+            "resolver.testnet".parse().unwrap(),
+            NearToken::from_yoctonear(1_100),
+            Err(near_sdk::PromiseError::Failed),
+        );
+
+        as_owner_with_gas();
+        let third_order = Immutables { order_hash: "0x1234560000".to_string(), ..pausable_order() };
+        factory.create_dst_escrow(third_order, U64(1_700_000_000_000 + 86_400_000), None);
+        let third = factory.get_escrow_address("0x1234560000".to_string()).unwrap();
+        assert_eq!(third.as_str(), format!("escrow-2-0x123456.{}", env::current_account_id()));
+        assert_ne!(third, in_flight);
+        assert_ne!(third, first);
+    }
+
+    #[test]
+    #[should_panic(expected = "E_INVALID_ORDER_HASH")]
+    fn test_dst_creation_rejects_short_order_hash() {
+        let mut factory = factory();
+        as_owner_with_gas();
+        let order = Immutables { order_hash: "0xab".to_string(), ..pausable_order() };
+        factory.create_dst_escrow(order, U64(1_700_000_000_000 + 86_400_000), None);
+    }
+
+    #[test]
+    fn test_check_order_hash_rejects_non_hex() {
+        assert!(check_order_hash("0xabcdef").is_ok());
+        for order_hash in ["0xabcde", "abcdef0123", "0xorder", "0xéabcdef", "0xabcdef01g3"] {
+            assert!(check_order_hash(order_hash).is_err(), "{}", order_hash);
+        }
+    }
+
+    #[test]
+    fn test_dst_creation_success_indexes_escrow_without_refund() {
+        let mut factory = factory();
+        as_factory_callback();
+        let escrow: AccountId = "escrow-0-0xabcdef.factory.testnet".parse().unwrap();
+
+        let result = factory.on_escrow_created(
+            "0xabcdef0123".to_string(),
+            escrow.clone(),
+            "maker.testnet".parse().unwrap(),
+            U64(1_700_000_000_000),
+            None,
+            "resolver.testnet".parse().unwrap(),
+            NearToken::from_yoctonear(1_100),
+            Ok(()),
+        );
+
+        assert!(result.success);
+        assert!(refunds_to("resolver.testnet").is_empty());
+        assert!(near_sdk::test_utils::get_logs().iter().any(|log| log.contains("taker=resolver.testnet")));
+        assert_eq!(factory.get_escrows_by_maker("maker.testnet".parse().unwrap(), None, None), vec![escrow]);
+    }
+
+    #[test]
+    fn test_maker_escrows_paged_oldest_first() {
+        let mut factory = factory();
+        let maker: AccountId = "maker.testnet".parse().unwrap();
+        let escrows: Vec<AccountId> = (0..MAX_MAKER_ESCROWS_PAGE + 5)
+            .map(|i| format!("escrow-{}.factory.testnet", i).parse().unwrap())
+            .collect();
+        for escrow in &escrows {
+            factory.index_maker_escrow(maker.clone(), escrow.clone());
+        }
+
+        let first_page = factory.get_escrows_by_maker(maker.clone(), None, None);
+        assert_eq!(first_page, escrows[..MAX_MAKER_ESCROWS_PAGE as usize]);
+        let rest = factory.get_escrows_by_maker(maker.clone(), Some(U64(MAX_MAKER_ESCROWS_PAGE)), Some(U64(1_000)));
+        assert_eq!(rest, escrows[MAX_MAKER_ESCROWS_PAGE as usize..]);
+        assert_eq!(factory.get_escrows_by_maker(maker.clone(), Some(U64(2)), Some(U64(2))), escrows[2..4]);
+        assert!(factory.get_escrows_by_maker(maker, Some(U64(u64::MAX)), None).is_empty());
+        assert!(factory.get_escrows_by_maker("other.testnet".parse().unwrap(), None, None).is_empty());
+    }
+}
@@ -0,0 +1,411 @@
+//! Gas budgets of the factory, src and dst escrow flows, measured in a near-workspaces sandbox.
+//! Each test fails once an operation burns more than the constant allotted to it, so a change
+//! that pushes an operation over budget is caught before the constant silently becomes a guess.
+//! The src escrow's balance after creation is checked here too, as only a real account shows it.
+//!
+//! Needs the wasm output of build.sh (override with ESCROW_FACTORY_WASM / ESCROW_SRC_WASM /
+//! ESCROW_DST_WASM) and,
+//! for the NEP-141 path, network access to import wrap.testnet:
+//!
+//!     ./build.sh && cargo test -p escrow-factory --test gas -- --ignored
+
+use near_sdk::json_types::U128;
+use near_workspaces::network::Sandbox;
+use near_workspaces::result::{ExecutionFinalResult, ExecutionSuccess};
+use near_workspaces::types::{Gas, NearToken};
+use near_workspaces::{Account, AccountId, Contract, Worker};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use sha3::Keccak256;
+
+use escrow_dst::{CALLBACK_GAS as DST_CALLBACK_GAS, NEP141_TRANSFER_GAS};
+use escrow_factory::{
+    CALLBACK_GAS as FACTORY_CALLBACK_GAS, CREATE_ESCROW_GAS, MERKLE_INIT_EXTRA_GAS, SRC_CREATED_CALLBACK_GAS,
+    SRC_INIT_BASE_GAS, SRC_SETUP_OVERHEAD_GAS,
+};
+use escrow_src::CALLBACK_GAS as SRC_CALLBACK_GAS;
+
+// Budget the resolver service gives cancel_near_escrow for the whole cancel
+const CANCEL_CALL_GAS: Gas = Gas::from_tgas(100);
+
+const SRC_WITHDRAWAL_SECS: u32 = 10;
+const DST_WITHDRAWAL_SECS: u32 = 10;
+const DST_CANCELLATION_SECS: u32 = 2_000;
+
+struct Setup {
+    worker: Worker<Sandbox>,
+    factory: Contract,
+    resolver: Account,
+    maker: Account,
+}
+
+fn wasm(env_var: &str, default_path: &str) -> Vec<u8> {
+    let path = std::env::var(env_var).unwrap_or_else(|_| default_path.to_string());
+    std::fs::read(&path).unwrap_or_else(|e| panic!("Failed to read {} ({}): {}, run build.sh first", path, env_var, e))
+}
+
+async fn setup() -> anyhow::Result<Setup> {
+    let worker = near_workspaces::sandbox().await?;
+    let root = worker.root_account()?;
+
+    let src_template = root
+        .create_subaccount("escrow-src-template")
+        .initial_balance(NearToken::from_near(50))
+        .transact()
+        .await?
+        .into_result()?;
+    src_template
+        .deploy_global_contract_by_account_id(&wasm("ESCROW_SRC_WASM", "../target/near/escrow_src/escrow_src.wasm"))
+        .await?
+        .into_result()?;
+    let dst_template = root
+        .create_subaccount("escrow-dst-template")
+        .initial_balance(NearToken::from_near(50))
+        .transact()
+        .await?
+        .into_result()?;
+    dst_template
+        .deploy_global_contract_by_account_id(&wasm("ESCROW_DST_WASM", "../target/near/escrow_dst/escrow_dst.wasm"))
+        .await?
+        .into_result()?;
+
+    // Short parent id, escrow accounts are `escrow-<n>-<hash prefix>.<factory>`
+    let factory = root
+        .create_subaccount("factory")
+        .initial_balance(NearToken::from_near(50))
+        .transact()
+        .await?
+        .into_result()?
+        .deploy(&wasm("ESCROW_FACTORY_WASM", "../target/near/escrow_factory/escrow_factory.wasm"))
+        .await?
+        .into_result()?;
+    factory
+        .call("new")
+        .args_json(json!({
+            "owner": root.id(),
+            "rescue_delay": 86_400,
+            "escrow_src_template": src_template.id(),
+            "escrow_dst_template": dst_template.id(),
+        }))
+        .transact()
+        .await?
+        .into_result()?;
+
+    let resolver = root.create_subaccount("resolver").initial_balance(NearToken::from_near(100)).transact().await?.into_result()?;
+    let maker = root.create_subaccount("maker").initial_balance(NearToken::from_near(10)).transact().await?.into_result()?;
+    Ok(Setup { worker, factory, resolver, maker })
+}
+
+fn immutables(setup: &Setup, order_hash: &str, hashlock: &str, token: &str) -> Value {
+    json!({
+        "order_hash": order_hash,
+        "hashlock": hashlock,
+        "maker": setup.maker.id(),
+        "taker": setup.resolver.id(),
+        "token": token,
+        "amount": NearToken::from_near(1).as_yoctonear().to_string(),
+        "safety_deposit": NearToken::from_millinear(100).as_yoctonear().to_string(),
+        "timelocks": {
+            "deployed_at": 0,
+            "src_withdrawal": SRC_WITHDRAWAL_SECS,
+            "src_public_withdrawal": 1_000,
+            "src_cancellation": 10_000,
+            "src_public_cancellation": 20_000,
+            "dst_withdrawal": DST_WITHDRAWAL_SECS,
+            "dst_public_withdrawal": 1_000,
+            "dst_cancellation": DST_CANCELLATION_SECS,
+        },
+    })
+}
+
+/// Creates the dst escrow with `deposit` plus the maker index stake and checks the init and
+/// callback budgets, returns the escrow account
+async fn create_dst(setup: &Setup, immutables: Value, deposit: NearToken) -> anyhow::Result<AccountId> {
+    let now_ms = setup.worker.view_block().await?.timestamp() / 1_000_000;
+    let index_stake: U128 = setup.factory.view("maker_index_storage_stake").await?.json()?;
+    let result = setup
+        .resolver
+        .call(setup.factory.id(), "create_dst_escrow")
+        .args_json(json!({
+            "dst_immutables": immutables,
+            "src_cancellation_timestamp": (now_ms + 3 * 60 * 60 * 1000).to_string(),
+        }))
+        .deposit(deposit.saturating_add(NearToken::from_yoctonear(index_stake.0)))
+        .max_gas()
+        .transact()
+        .await?;
+
+    let escrow: AccountId = setup
+        .factory
+        .view("get_escrow_address")
+        .args_json(json!({ "order_hash": immutables["order_hash"] }))
+        .await?
+        .json::<Option<AccountId>>()?
+        .expect("escrow not recorded");
+    let result = result.into_result()?;
+
+    assert_within_budget(&result, &escrow, 0, CREATE_ESCROW_GAS, "escrow init");
+    assert_within_budget(&result, setup.factory.id(), 1, FACTORY_CALLBACK_GAS, "on_escrow_created");
+
+    // The escrow pays out everything it holds, leave it enough to cover its own storage
+    setup.resolver.transfer_near(&escrow, NearToken::from_near(1)).await?.into_result()?;
+    Ok(escrow)
+}
+
+/// Creates a native src escrow as the maker, bound for an EVM chain, and checks the account
+/// setup, init and callback budgets, returns the escrow account
+async fn create_src(setup: &Setup, immutables: Value) -> anyhow::Result<AccountId> {
+    let storage_stake: U128 = setup.factory.view("src_escrow_storage_stake").await?.json()?;
+    let deposit = NearToken::from_near(1)
+        .saturating_add(NearToken::from_millinear(100))
+        .saturating_add(NearToken::from_yoctonear(storage_stake.0));
+    let init_budget = if immutables["hashlock"].as_str().is_some_and(|hashlock| hashlock.starts_with("merkle:")) {
+        SRC_INIT_BASE_GAS.saturating_add(MERKLE_INIT_EXTRA_GAS)
+    } else {
+        SRC_INIT_BASE_GAS
+    };
+    let result = setup
+        .maker
+        .call(setup.factory.id(), "create_src_escrow")
+        .args_json(json!({
+            "order_hash": immutables["order_hash"],
+            "immutables": immutables,
+            "dst_complement": {
+                "maker": setup.maker.id(),
+                "taker": { "Evm": format!("0x{}", "11".repeat(20)) },
+                "amount": NearToken::from_near(1).as_yoctonear().to_string(),
+                "token": { "Evm": format!("0x{}", "22".repeat(20)) },
+                "safety_deposit": NearToken::from_millinear(100).as_yoctonear().to_string(),
+                "chain_id": "1",
+                "hash_algorithm": "keccak256",
+            },
+        }))
+        .deposit(deposit)
+        .max_gas()
+        .transact()
+        .await?;
+
+    let escrow: AccountId = setup
+        .factory
+        .view("get_escrow_address")
+        .args_json(json!({ "order_hash": immutables["order_hash"] }))
+        .await?
+        .json::<Option<AccountId>>()?
+        .expect("escrow not recorded");
+    let result = result.into_result()?;
+
+    // The escrow's first receipt is the account creation, which runs no contract code
+    assert_within_budget(&result, &escrow, 1, init_budget, "src escrow init");
+    assert_within_budget(&result, setup.factory.id(), 2, SRC_SETUP_OVERHEAD_GAS, "on_src_account_created");
+    assert_within_budget(&result, setup.factory.id(), 3, SRC_CREATED_CALLBACK_GAS, "on_src_escrow_created");
+    Ok(escrow)
+}
+
+/// Every receipt `executor` ran, after the first `skip`, burnt at most `budget`
+fn assert_within_budget(
+    result: &ExecutionSuccess,
+    executor: &AccountId,
+    skip: usize,
+    budget: Gas,
+    operation: &str,
+) {
+    let burnt: Vec<Gas> = result
+        .receipt_outcomes()
+        .iter()
+        .filter(|outcome| &outcome.executor_id == executor)
+        .skip(skip)
+        .map(|outcome| outcome.gas_burnt)
+        .collect();
+    assert!(!burnt.is_empty(), "{}: no receipt executed by {}", operation, executor);
+    for gas in burnt {
+        assert!(gas <= budget, "{} burnt {} over its budget of {}", operation, gas, budget);
+    }
+}
+
+fn secret_hex(seed: u8) -> String {
+    hex::encode([seed; 32])
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    hex::encode(Sha256::digest(bytes))
+}
+
+fn keccak256_hex(bytes: &[u8]) -> String {
+    hex::encode(Keccak256::digest(bytes))
+}
+
+async fn withdraw(setup: &Setup, escrow: &AccountId, secret: &str, proof: Value) -> anyhow::Result<ExecutionFinalResult> {
+    Ok(setup
+        .resolver
+        .call(escrow, "withdraw")
+        .args_json(json!({
+            "secret": { "encoding": "Hex", "value": secret },
+            "merkle_proof": proof,
+        }))
+        .max_gas()
+        .transact()
+        .await?)
+}
+
+#[tokio::test]
+#[ignore = "needs build.sh output and a sandbox"]
+async fn test_native_src_create_and_withdraw_within_budget() -> anyhow::Result<()> {
+    let setup = setup().await?;
+    let secret = secret_hex(6);
+    let mut immutables = immutables(&setup, &format!("0x{}", "e5".repeat(32)), &keccak256_hex(&[6; 32]), "near");
+    immutables["hash_algorithm"] = json!("keccak256");
+    let escrow = create_src(&setup, immutables).await?;
+
+    setup.worker.fast_forward(SRC_WITHDRAWAL_SECS as u64 * 2).await?;
+
+    let result = withdraw(&setup, &escrow, &secret, Value::Null).await?.into_result()?;
+    assert_within_budget(&result, &escrow, 1, SRC_CALLBACK_GAS, "on_withdrawal_transfer");
+    Ok(())
+}
+
+#[tokio::test]
+#[ignore = "needs build.sh output and a sandbox"]
+async fn test_src_escrow_holds_exactly_principal_safety_deposit_and_storage() -> anyhow::Result<()> {
+    let setup = setup().await?;
+    let mut immutables = immutables(&setup, &format!("0x{}", "e6".repeat(32)), &keccak256_hex(&[7; 32]), "near");
+    immutables["hash_algorithm"] = json!("keccak256");
+    let escrow = create_src(&setup, immutables).await?;
+
+    // The maker index share of the stake stays on the factory
+    let storage_stake: U128 = setup.factory.view("src_escrow_storage_stake").await?.json()?;
+    let index_stake: U128 = setup.factory.view("maker_index_storage_stake").await?.json()?;
+    let expected = NearToken::from_near(1).as_yoctonear()
+        + NearToken::from_millinear(100).as_yoctonear()
+        + storage_stake.0
+        - index_stake.0;
+    let held = setup.worker.view_account(&escrow).await?.balance.as_yoctonear();
+
+    // Init's gas reward is the only other credit, a fraction of a millinear at sandbox gas prices.
+    // A double-forwarded stake or a dropped safety deposit is orders of magnitude off.
+    assert!(held >= expected, "escrow holds {} below the expected {}", held, expected);
+    assert!(
+        held - expected < NearToken::from_millinear(1).as_yoctonear(),
+        "escrow holds {} above the expected {}",
+        held,
+        expected
+    );
+    Ok(())
+}
+
+#[tokio::test]
+#[ignore = "needs build.sh output and a sandbox"]
+async fn test_partial_fill_src_create_and_withdraw_within_budget() -> anyhow::Result<()> {
+    let setup = setup().await?;
+    // Two-part Merkle tree over keccak256 leaves (inner nodes are always sha256), withdrawing part 0
+    // with its sibling as proof
+    let leaves = [Keccak256::digest([7; 32]).to_vec(), Keccak256::digest([8; 32]).to_vec()];
+    let root = sha256_hex(&[leaves[0].as_slice(), leaves[1].as_slice()].concat());
+    let mut immutables = immutables(&setup, &format!("0x{}", "f6".repeat(32)), &format!("merkle:1:{}", root), "near");
+    immutables["hash_algorithm"] = json!("keccak256");
+    let escrow = create_src(&setup, immutables).await?;
+
+    setup.worker.fast_forward(SRC_WITHDRAWAL_SECS as u64 * 2).await?;
+
+    let proof = json!({ "proof": [hex::encode(&leaves[1])], "index": 0 });
+    let result = withdraw(&setup, &escrow, &secret_hex(7), proof).await?.into_result()?;
+    assert_within_budget(&result, &escrow, 1, SRC_CALLBACK_GAS, "on_withdrawal_transfer");
+    Ok(())
+}
+
+#[tokio::test]
+#[ignore = "needs build.sh output and a sandbox"]
+async fn test_native_dst_create_and_withdraw_within_budget() -> anyhow::Result<()> {
+    let setup = setup().await?;
+    let secret = secret_hex(1);
+    let immutables = immutables(&setup, &format!("0x{}", "a1".repeat(32)), &sha256_hex(&[1; 32]), "near");
+    let escrow = create_dst(&setup, immutables, NearToken::from_millinear(1_100)).await?;
+
+    setup.worker.fast_forward(DST_WITHDRAWAL_SECS as u64 * 2).await?;
+
+    let result = withdraw(&setup, &escrow, &secret, Value::Null).await?.into_result()?;
+    assert_within_budget(&result, &escrow, 1, DST_CALLBACK_GAS, "on_withdrawal_transfer");
+    Ok(())
+}
+
+#[tokio::test]
+#[ignore = "needs build.sh output and a sandbox"]
+async fn test_partial_fill_withdraw_within_budget() -> anyhow::Result<()> {
+    let setup = setup().await?;
+    // Two-part Merkle tree over sha256 leaves, withdrawing part 0 with its sibling as proof
+    let leaves = [Sha256::digest([2; 32]).to_vec(), Sha256::digest([3; 32]).to_vec()];
+    let root = sha256_hex(&[leaves[0].as_slice(), leaves[1].as_slice()].concat());
+    let immutables = immutables(&setup, &format!("0x{}", "b2".repeat(32)), &format!("merkle:1:{}", root), "near");
+    let escrow = create_dst(&setup, immutables, NearToken::from_millinear(1_100)).await?;
+
+    setup.worker.fast_forward(DST_WITHDRAWAL_SECS as u64 * 2).await?;
+
+    let proof = json!({ "proof": [hex::encode(&leaves[1])], "index": 0 });
+    let result = withdraw(&setup, &escrow, &secret_hex(2), proof).await?.into_result()?;
+    assert_within_budget(&result, &escrow, 1, DST_CALLBACK_GAS, "on_withdrawal_transfer");
+    Ok(())
+}
+
+#[tokio::test]
+#[ignore = "needs build.sh output and a sandbox"]
+async fn test_native_dst_cancel_within_budget() -> anyhow::Result<()> {
+    let setup = setup().await?;
+    let immutables = immutables(&setup, &format!("0x{}", "c3".repeat(32)), &sha256_hex(&[4; 32]), "near");
+    let escrow = create_dst(&setup, immutables, NearToken::from_millinear(1_100)).await?;
+
+    setup.worker.fast_forward(DST_CANCELLATION_SECS as u64 * 2).await?;
+
+    let result = setup.resolver.call(&escrow, "cancel").gas(CANCEL_CALL_GAS).transact().await?.into_result()?;
+    assert!(
+        result.total_gas_burnt <= CANCEL_CALL_GAS,
+        "cancel burnt {} over its budget of {}",
+        result.total_gas_burnt,
+        CANCEL_CALL_GAS
+    );
+    Ok(())
+}
+
+#[tokio::test]
+#[ignore = "needs build.sh output, a sandbox and network access to import wrap.testnet"]
+async fn test_nep141_dst_create_and_withdraw_within_budget() -> anyhow::Result<()> {
+    let setup = setup().await?;
+    let testnet = near_workspaces::testnet().await?;
+    let wrap_id: AccountId = "wrap.testnet".parse()?;
+    let wrap = setup
+        .worker
+        .import_contract(&wrap_id, &testnet)
+        .initial_balance(NearToken::from_near(10))
+        .transact()
+        .await?;
+    wrap.call("new").transact().await?.into_result()?;
+    setup.worker.root_account()?.call(setup.factory.id(), "add_token").args_json(json!({ "token": wrap.id() })).transact().await?.into_result()?;
+
+    let immutables = immutables(&setup, &format!("0x{}", "d4".repeat(32)), &sha256_hex(&[5; 32]), wrap.id().as_str());
+    let escrow = create_dst(&setup, immutables, NearToken::from_millinear(100)).await?;
+
+    for account in [setup.resolver.id(), setup.maker.id(), &escrow] {
+        setup
+            .resolver
+            .call(wrap.id(), "storage_deposit")
+            .args_json(json!({ "account_id": account }))
+            .deposit(NearToken::from_millinear(125))
+            .transact()
+            .await?
+            .into_result()?;
+    }
+    setup.resolver.call(wrap.id(), "near_deposit").deposit(NearToken::from_near(1)).transact().await?.into_result()?;
+    setup
+        .resolver
+        .call(wrap.id(), "ft_transfer_call")
+        .args_json(json!({ "receiver_id": escrow, "amount": NearToken::from_near(1).as_yoctonear().to_string(), "msg": "" }))
+        .deposit(NearToken::from_yoctonear(1))
+        .max_gas()
+        .transact()
+        .await?
+        .into_result()?;
+    setup.worker.fast_forward(DST_WITHDRAWAL_SECS as u64 * 2).await?;
+
+    let result = withdraw(&setup, &escrow, &secret_hex(5), Value::Null).await?.into_result()?;
+    assert_within_budget(&result, wrap.id(), 0, NEP141_TRANSFER_GAS, "ft_transfer");
+    assert_within_budget(&result, &escrow, 1, DST_CALLBACK_GAS, "on_withdrawal_transfer");
+    Ok(())
+}
@@ -1,8 +1,10 @@
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
 use near_sdk::collections::Vector;
+use near_sdk::json_types::{Base64VecU8, U128, U64};
 use near_sdk::serde::{Deserialize, Serialize};
 use near_sdk::{
-    env, ext_contract, near_bindgen, AccountId, Gas, NearToken, PanicOnDefault, Promise,
+    env, ext_contract, is_promise_success, near_bindgen, AccountId, Gas, NearToken, PanicOnDefault,
+    Promise, PromiseOrValue,
 };
 use sha2::{Digest, Sha256};
 
@@ -10,8 +12,99 @@ use sha2::{Digest, Sha256};
 use near_sdk::schemars::{self, JsonSchema};
 
 // Gas constants
-const NEP141_TRANSFER_GAS: Gas = Gas::from_tgas(5);
-const CALLBACK_GAS: Gas = Gas::from_tgas(2);
+pub const NEP141_TRANSFER_GAS: Gas = Gas::from_tgas(5);
+pub const CALLBACK_GAS: Gas = Gas::from_tgas(2);
+
+// Length of a hashlock preimage, matching the EVM escrows' bytes32 secret
+const SECRET_LEN: usize = 32;
+
+// Preimages anyone could guess, a hashlock committing to one of them is free to withdraw
+const WEAK_PREIMAGES: [&[u8]; 4] = [b"", &[0u8; SECRET_LEN], &[0xffu8; SECRET_LEN], &[1u8; SECRET_LEN]];
+
+// Upper bound on partial-fill parts, keeps Merkle proofs (and withdrawal gas) bounded
+const MAX_MERKLE_PARTS: u32 = 1024;
+const USED_INDEX_WORDS: usize = (MAX_MERKLE_PARTS as usize + 1).div_ceil(128);
+
+#[cfg(any(test, feature = "test-time"))]
+thread_local! {
+    static TIME_OVERRIDE: std::cell::Cell<Option<u64>> = const { std::cell::Cell::new(None) };
+}
+
+/// Pin the clock seen by the phase checks, None restores the block timestamp (tests and test-time builds only)
+#[cfg(any(test, feature = "test-time"))]
+pub fn set_time_override(timestamp_ms: Option<u64>) {
+    TIME_OVERRIDE.with(|time| time.set(timestamp_ms));
+}
+
+/// Current time in ms for timelock checks, the block timestamp unless a test clock is set
+fn now_ms() -> u64 {
+    #[cfg(any(test, feature = "test-time"))]
+    if let Some(timestamp_ms) = TIME_OVERRIDE.with(|time| time.get()) {
+        return timestamp_ms;
+    }
+    env::block_timestamp_ms()
+}
+
+// The safety deposit recipient lives under its own key rather than in the Borsh state, so escrows
+// created by an older template still deserialize after the template is upgraded
+const SAFETY_DEPOSIT_RECIPIENT_KEY: &[u8] = b"safety_deposit_recipient";
+
+fn stored_safety_deposit_recipient() -> Option<AccountId> {
+    env::storage_read(SAFETY_DEPOSIT_RECIPIENT_KEY)
+        .map(|bytes| AccountId::try_from_slice(&bytes).expect("Invalid safety deposit recipient"))
+}
+
+fn store_safety_deposit_recipient(recipient: Option<AccountId>) {
+    match recipient {
+        Some(recipient) => {
+            env::storage_write(SAFETY_DEPOSIT_RECIPIENT_KEY, &borsh::to_vec(&recipient).unwrap());
+        }
+        None => {
+            env::storage_remove(SAFETY_DEPOSIT_RECIPIENT_KEY);
+        }
+    }
+}
+
+// Per-swap correlation id echoed into every log line, also kept out of the Borsh state
+const CORRELATION_ID_KEY: &[u8] = b"correlation_id";
+
+fn stored_correlation_id() -> Option<String> {
+    env::storage_read(CORRELATION_ID_KEY).map(|bytes| String::from_utf8(bytes).expect("Invalid correlation id"))
+}
+
+fn store_correlation_id(correlation_id: Option<String>) {
+    match correlation_id {
+        Some(correlation_id) => {
+            env::storage_write(CORRELATION_ID_KEY, correlation_id.as_bytes());
+        }
+        None => {
+            env::storage_remove(CORRELATION_ID_KEY);
+        }
+    }
+}
+
+/// Correlation id for log lines, "none" for swaps created without one
+fn correlation_label() -> String {
+    stored_correlation_id().unwrap_or_else(|| "none".to_string())
+}
+
+// Native principal sent by withdrawals whose callback has not run yet, so a bounced transfer is
+// not mistaken for excess
+const IN_FLIGHT_KEY: &[u8] = b"in_flight";
+
+fn in_flight_amount() -> u128 {
+    env::storage_read(IN_FLIGHT_KEY).map_or(0, |bytes| {
+        u128::from_le_bytes(bytes.try_into().expect("Invalid in-flight amount"))
+    })
+}
+
+fn set_in_flight_amount(amount: u128) {
+    if amount == 0 {
+        env::storage_remove(IN_FLIGHT_KEY);
+    } else {
+        env::storage_write(IN_FLIGHT_KEY, &amount.to_le_bytes());
+    }
+}
 
 /// Copy of Immutables struct from factory
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug)]
@@ -23,9 +116,46 @@ pub struct Immutables {
     pub maker: AccountId,
     pub taker: AccountId, // Resolver address
     pub token: AccountId, // Token contract address ("near" for native NEAR)
-    pub amount: u128,
-    pub safety_deposit: u128,
+    pub amount: U128,
+    pub safety_deposit: U128,
     pub timelocks: Timelocks,
+    /// Accounts allowed to complete the public withdrawal (A3 here, B3 on dst), None keeps it fully public
+    #[serde(default)]
+    pub public_withdrawal_allowlist: Option<Vec<AccountId>>,
+    /// Source and destination chain ids, None for escrows created without an order extension
+    #[serde(default)]
+    pub chain_context: Option<ChainContext>,
+    /// Hash of the secret behind `hashlock` (and Merkle leaves), sha256 unless the order says otherwise
+    #[serde(default)]
+    pub hash_algorithm: HashAlgo,
+}
+
+/// Hash function a hashlock commits with, declared by the order so both legs verify alike
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(JsonSchema))]
+#[serde(crate = "near_sdk::serde", rename_all = "lowercase")]
+pub enum HashAlgo {
+    #[default]
+    Sha256,
+    Keccak256,
+}
+
+impl HashAlgo {
+    fn digest(self, data: &[u8]) -> Vec<u8> {
+        match self {
+            HashAlgo::Sha256 => Sha256::digest(data).to_vec(),
+            HashAlgo::Keccak256 => env::keccak256(data),
+        }
+    }
+}
+
+/// Chains an escrow bridges, copied from the order extension for monitoring
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(JsonSchema))]
+#[serde(crate = "near_sdk::serde")]
+pub struct ChainContext {
+    pub src_chain_id: u64,
+    pub dst_chain_id: u64,
 }
 
 /// Timelock configuration
@@ -41,6 +171,9 @@ pub struct Timelocks {
     pub dst_withdrawal: u32,
     pub dst_public_withdrawal: u32,
     pub dst_cancellation: u32,
+    /// Leniency around phase boundaries in ms, 0 disables it (see the escrow phase checks)
+    #[serde(default)]
+    pub grace_ms: u32,
 }
 
 /// Arguments for creating new escrow instances
@@ -50,6 +183,9 @@ pub struct Timelocks {
 pub struct CreateEscrowArgs {
     pub immutables: Immutables,
     pub factory: AccountId,
+    /// Per-swap id echoed into this escrow's events, for tracing a swap across contracts
+    #[serde(default)]
+    pub correlation_id: Option<String>,
 }
 
 /// Escrow state
@@ -57,11 +193,95 @@ pub struct CreateEscrowArgs {
 #[cfg_attr(not(target_arch = "wasm32"), derive(JsonSchema))]
 #[serde(crate = "near_sdk::serde")]
 pub struct EscrowState {
+    pub is_safety_deposit_funded: bool, // May be attached later by the resolver via deposit_safety
     pub is_withdrawn: bool,
     pub is_cancelled: bool,
     pub revealed_secret: Option<String>,
-    pub withdrawn_at: Option<u64>,
-    pub cancelled_at: Option<u64>,
+    pub withdrawn_at: Option<U64>,
+    pub cancelled_at: Option<U64>,
+    /// Principal released by withdrawals so far, the escrow is withdrawn once it reaches the amount
+    #[serde(default)]
+    pub filled_amount: U128,
+    /// Who the terminal withdrawal or cancellation paid the safety deposit to (view only, stored
+    /// under SAFETY_DEPOSIT_RECIPIENT_KEY)
+    #[serde(default)]
+    #[borsh(skip)]
+    pub safety_deposit_recipient: Option<AccountId>,
+}
+
+/// Actions an account could perform right now, for front-ends deciding which buttons to show
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(JsonSchema))]
+#[serde(crate = "near_sdk::serde")]
+pub struct AuthorizedActions {
+    pub can_withdraw: bool,
+    pub can_public_withdraw: bool,
+    pub can_cancel: bool,
+}
+
+/// Phase, state and next phase opening in one view, for dashboards polling many escrows
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(JsonSchema))]
+#[serde(crate = "near_sdk::serde")]
+pub struct EscrowStatus {
+    pub phase: String,
+    pub state: EscrowState,
+    pub next_action: Option<(String, U64)>,
+}
+
+/// Settlement evidence for the opposite chain's leg, available once the escrow is withdrawn
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(JsonSchema))]
+#[serde(crate = "near_sdk::serde")]
+pub struct CompletionProof {
+    pub order_hash: String,
+    pub revealed_secret: String,
+    pub withdrawn_at: U64,
+    pub recipient: AccountId,
+}
+
+/// How a submitted secret's `value` is encoded
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(JsonSchema))]
+#[serde(crate = "near_sdk::serde")]
+pub enum SecretEncoding {
+    Hex,
+    Base64,
+    Raw, // The UTF-8 bytes of `value` itself
+}
+
+/// Secret preimage submitted to withdraw, decoded to exactly SECRET_LEN bytes before hashing
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(JsonSchema))]
+#[serde(crate = "near_sdk::serde")]
+pub struct Secret {
+    pub encoding: SecretEncoding,
+    pub value: String,
+}
+
+impl Secret {
+    /// Decoded preimage. A wrong encoding fails here instead of burning the withdrawal on a hash mismatch.
+    pub fn decode(&self) -> Vec<u8> {
+        let bytes = match self.encoding {
+            SecretEncoding::Hex => hex::decode(self.value.trim_start_matches("0x"))
+                .unwrap_or_else(|_| env::panic_str("E_INVALID_SECRET: not valid hex")),
+            SecretEncoding::Base64 => {
+                near_sdk::serde_json::from_value::<Base64VecU8>(near_sdk::serde_json::Value::String(
+                    self.value.clone(),
+                ))
+                .unwrap_or_else(|_| env::panic_str("E_INVALID_SECRET: not valid base64"))
+                .0
+            }
+            SecretEncoding::Raw => self.value.as_bytes().to_vec(),
+        };
+        assert_eq!(
+            bytes.len(),
+            SECRET_LEN,
+            "E_INVALID_SECRET: decoded secret must be {} bytes",
+            SECRET_LEN
+        );
+        bytes
+    }
 }
 
 /// Merkle proof for partial fills
@@ -73,6 +293,130 @@ pub struct MerkleProof {
     pub index: u32,
 }
 
+/// Merkle secret indices already consumed, one bit per index in 0..=MAX_MERKLE_PARTS
+#[derive(BorshDeserialize, BorshSerialize, Clone, Debug, Default, PartialEq)]
+pub struct UsedIndexBitmap {
+    words: [u128; USED_INDEX_WORDS],
+}
+
+impl UsedIndexBitmap {
+    fn bit(index: u32) -> (usize, u128) {
+        assert!(
+            index <= MAX_MERKLE_PARTS,
+            "Secret index exceeds maximum of {} parts",
+            MAX_MERKLE_PARTS
+        );
+        ((index / 128) as usize, 1u128 << (index % 128))
+    }
+
+    /// Mark `index` used, false if it already was
+    pub fn mark(&mut self, index: u32) -> bool {
+        let (word, mask) = Self::bit(index);
+        let fresh = self.words[word] & mask == 0;
+        self.words[word] |= mask;
+        fresh
+    }
+
+    /// Free `index` again, for a fill whose transfer failed
+    pub fn unmark(&mut self, index: u32) {
+        let (word, mask) = Self::bit(index);
+        self.words[word] &= !mask;
+    }
+
+    pub fn is_used(&self, index: u32) -> bool {
+        let (word, mask) = Self::bit(index);
+        self.words[word] & mask != 0
+    }
+
+    /// Number of parts filled so far
+    pub fn count(&self) -> u32 {
+        self.words.iter().map(|w| w.count_ones()).sum()
+    }
+}
+
+// Layout version of the escrow state, written by init and migrate. Escrows created before it
+// existed carry the original layout, which `migrate` converts once
+const STATE_VERSION_KEY: &[u8] = b"state_version";
+const STATE_VERSION: u8 = 1;
+
+fn store_state_version() {
+    env::storage_write(STATE_VERSION_KEY, &[STATE_VERSION]);
+}
+
+/// Immutables as laid out before amounts became U128 and the allowlist, chain context, hash
+/// algorithm and grace were added, read once by `migrate`
+#[derive(BorshDeserialize, BorshSerialize)]
+struct ImmutablesV0 {
+    order_hash: String,
+    hashlock: String,
+    maker: AccountId,
+    taker: AccountId,
+    token: AccountId,
+    amount: u128,
+    safety_deposit: u128,
+    timelocks: TimelocksV0,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+struct TimelocksV0 {
+    deployed_at: u64,
+    src_withdrawal: u32,
+    src_public_withdrawal: u32,
+    src_cancellation: u32,
+    src_public_cancellation: u32,
+    dst_withdrawal: u32,
+    dst_public_withdrawal: u32,
+    dst_cancellation: u32,
+}
+
+impl ImmutablesV0 {
+    fn upgrade(self) -> Immutables {
+        let timelocks = self.timelocks;
+        Immutables {
+            order_hash: self.order_hash,
+            hashlock: self.hashlock,
+            maker: self.maker,
+            taker: self.taker,
+            token: self.token,
+            amount: U128(self.amount),
+            safety_deposit: U128(self.safety_deposit),
+            timelocks: Timelocks {
+                deployed_at: timelocks.deployed_at,
+                src_withdrawal: timelocks.src_withdrawal,
+                src_public_withdrawal: timelocks.src_public_withdrawal,
+                src_cancellation: timelocks.src_cancellation,
+                src_public_cancellation: timelocks.src_public_cancellation,
+                dst_withdrawal: timelocks.dst_withdrawal,
+                dst_public_withdrawal: timelocks.dst_public_withdrawal,
+                dst_cancellation: timelocks.dst_cancellation,
+                grace_ms: 0,
+            },
+            public_withdrawal_allowlist: None,
+            chain_context: None,
+            hash_algorithm: HashAlgo::Sha256,
+        }
+    }
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+struct EscrowStateV0 {
+    is_withdrawn: bool,
+    is_cancelled: bool,
+    revealed_secret: Option<String>,
+    withdrawn_at: Option<u64>,
+    cancelled_at: Option<u64>,
+}
+
+/// Escrow state as laid out before the used-index bitmap
+#[derive(BorshDeserialize, BorshSerialize)]
+struct EscrowSrcV0 {
+    immutables: ImmutablesV0,
+    factory: AccountId,
+    state: EscrowStateV0,
+    merkle_root: Option<String>,
+    used_secret_indices: Vector<u32>,
+}
+
 // NEP-141 token interface
 #[ext_contract(ext_nep141)]
 pub trait NEP141Token {
@@ -86,7 +430,7 @@ pub struct EscrowSrc {
     pub factory: AccountId,
     pub state: EscrowState,
     pub merkle_root: Option<String>,
-    pub used_secret_indices: Vector<u32>,
+    pub used_secret_indices: UsedIndexBitmap,
 }
 
 #[near_bindgen]
@@ -103,35 +447,37 @@ impl EscrowSrc {
             factory,
             "Only factory can initialize escrow"
         );
+        // A zero deposit leaves nobody paid to finish or unwind the swap
+        assert!(
+            immutables.safety_deposit.0 > 0,
+            "E_ZERO_SAFETY_DEPOSIT: safety deposit must be non-zero"
+        );
+        assert_hashlock_not_weak(&immutables.hashlock, immutables.hash_algorithm);
 
-        // For source escrows, funds should be attached during creation
-        let expected_amount = if immutables.token.as_str() == "near" {
-            immutables.amount + immutables.safety_deposit
+        // For source escrows, the principal is attached during creation. The safety deposit is
+        // either attached alongside it or supplied separately by the resolver via deposit_safety
+        let principal = if immutables.token.as_str() == "near" {
+            immutables.amount.0
         } else {
-            immutables.safety_deposit
+            0 // NEP-141 principal is pulled with transfer_from below
         };
-
-        assert_eq!(
-            env::attached_deposit().as_yoctonear(),
-            expected_amount,
-            "Incorrect deposit amount"
-        );
+        let attached = env::attached_deposit().as_yoctonear();
+        let is_safety_deposit_funded = attached == principal + immutables.safety_deposit.0;
+        if !is_safety_deposit_funded {
+            assert_eq!(attached, principal, "Incorrect deposit amount");
+        }
 
         // Extract Merkle root if this supports multiple fills
-        let merkle_root = if immutables.hashlock.starts_with("merkle:") {
-            Some(immutables.hashlock[7..].to_string())
-        } else {
-            None
-        };
+        let merkle_root = merkle_root_for_init(&immutables.hashlock);
 
-        if immutables.token.clone().as_str() != "near" && immutables.amount.clone() > 0{
+        if immutables.token.clone().as_str() != "near" && immutables.amount.0 > 0{
             Promise::new(immutables.token.clone()).function_call(
                 "transfer_from".to_string(),
                 near_sdk::serde_json::to_vec(
                     &serde_json::json!({
                         "from": env::predecessor_account_id(),
                         "to": env::current_account_id(),
-                        "value": immutables.amount.clone().to_string(),
+                        "value": immutables.amount.0.to_string(),
                     })
                 ).unwrap(),
                 NearToken::from_yoctonear(0),
@@ -140,30 +486,130 @@ impl EscrowSrc {
         }
 
         let state = EscrowState {
+            is_safety_deposit_funded,
             is_withdrawn: false,
             is_cancelled: false,
             revealed_secret: None,
             withdrawn_at: None,
             cancelled_at: None,
+            filled_amount: U128(0),
+            safety_deposit_recipient: None,
         };
+        store_safety_deposit_recipient(None);
+        store_correlation_id(args.correlation_id);
+        store_state_version();
+        set_in_flight_amount(0);
 
         Self {
             immutables,
             factory,
             state,
             merkle_root,
-            used_secret_indices: Vector::new("used_secrets".as_bytes()),
+            used_secret_indices: UsedIndexBitmap::default(),
+        }
+    }
+
+    /// Initialize from a Borsh-encoded immutables blob (compact alternative to `init`)
+    #[init]
+    #[payable]
+    pub fn init_from_blob(immutables_blob: Base64VecU8, factory: AccountId, correlation_id: Option<String>) -> Self {
+        let immutables =
+            Immutables::try_from_slice(&immutables_blob.0).expect("Invalid immutables blob");
+        Self::init(CreateEscrowArgs {
+            immutables,
+            factory,
+            correlation_id,
+        })
+    }
+
+    /// Upgrade hook for an escrow created before the used-index bitmap, called by the escrow
+    /// account once the template it runs is republished. Used indices, funds and progress carry
+    /// over; the version key makes a second run fail instead of misreading the new layout. A
+    /// Merkle escrow keeps its hashlock without a part count and withdraws as before: any index up
+    /// to MAX_MERKLE_PARTS, releasing the whole remainder.
+    #[private]
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        assert!(env::storage_read(STATE_VERSION_KEY).is_none(), "E_ALREADY_MIGRATED: state is already at the current layout");
+        let mut old: EscrowSrcV0 =
+            env::state_read().unwrap_or_else(|| env::panic_str("E_NO_STATE: nothing to migrate"));
+
+        let mut used_secret_indices = UsedIndexBitmap::default();
+        for index in old.used_secret_indices.iter() {
+            used_secret_indices.mark(index);
+        }
+        old.used_secret_indices.clear();
+
+        // The original factory stamped src escrows in seconds, the phase checks count in ms
+        let mut immutables = old.immutables.upgrade();
+        if immutables.timelocks.deployed_at < 100_000_000_000 {
+            immutables.timelocks.deployed_at *= 1000;
+        }
+        let state = EscrowState {
+            // The original init took the principal and safety deposit together
+            is_safety_deposit_funded: true,
+            is_withdrawn: old.state.is_withdrawn,
+            is_cancelled: old.state.is_cancelled,
+            revealed_secret: old.state.revealed_secret,
+            withdrawn_at: old.state.withdrawn_at.map(U64),
+            cancelled_at: old.state.cancelled_at.map(U64),
+            // Withdrawals always released the whole principal
+            filled_amount: U128(if old.state.is_withdrawn { immutables.amount.0 } else { 0 }),
+            safety_deposit_recipient: None,
+        };
+        store_safety_deposit_recipient(None);
+        set_in_flight_amount(0);
+        store_state_version();
+
+        Self {
+            immutables,
+            factory: old.factory,
+            state,
+            merkle_root: old.merkle_root,
+            used_secret_indices,
         }
     }
 
+    /// Attach the safety deposit when the maker only funded the principal (taker only)
+    #[payable]
+    pub fn deposit_safety(&mut self) {
+        self.assert_taker();
+        self.assert_not_withdrawn();
+        self.assert_not_cancelled();
+        assert!(!self.state.is_safety_deposit_funded, "Safety deposit already funded");
+        assert_eq!(
+            env::attached_deposit().as_yoctonear(),
+            self.immutables.safety_deposit.0,
+            "Incorrect safety deposit amount"
+        );
+
+        self.state.is_safety_deposit_funded = true;
+
+        env::log_str(&format!(
+            "SrcEscrowSafetyDeposited: order_hash={}, correlation_id={}, deposited_by={}",
+            self.immutables.order_hash,
+            correlation_label(),
+            env::predecessor_account_id()
+        ));
+    }
+
     /// Withdraw with secret (resolver/taker only, A2 phase)
-    pub fn withdraw(&mut self, secret: String, merkle_proof: Option<MerkleProof>) -> Promise {
+    /// `expected_immutables`, when given, must equal the stored immutables byte for byte, so a caller that
+    /// knows the swap (the resolver) refuses to release funds from an escrow whose state does not match
+    pub fn withdraw(
+        &mut self,
+        secret: Secret,
+        merkle_proof: Option<MerkleProof>,
+        expected_immutables: Option<Immutables>,
+    ) -> Promise {
         self.assert_taker();
+        self.assert_expected_immutables(expected_immutables.as_ref());
+        self.assert_safety_deposit_funded();
         self.assert_not_withdrawn();
         self.assert_not_cancelled();
 
         // Check timelock - must be after finality, before public cancellation
-        let current_time = env::block_timestamp_ms();
+        let current_time = now_ms();
         let withdrawal_start = self.get_timelock_timestamp(TimelockStage::SrcWithdrawal);
         let public_cancellation_start =
             self.get_timelock_timestamp(TimelockStage::SrcPublicCancellation);
@@ -173,41 +619,65 @@ impl EscrowSrc {
             "Finality lock not expired"
         );
         assert!(
-            current_time < public_cancellation_start,
+            current_time < public_cancellation_start + self.grace_ms(),
             "Public cancellation period started"
         );
 
         // Verify secret
-        self.verify_secret(&secret, merkle_proof.as_ref());
+        let secret_bytes = secret.decode();
+        let fill_index = self.verify_secret(&secret_bytes, merkle_proof.as_ref());
+        let secret = hex::encode(&secret_bytes); // Revealed in canonical hex whatever the input encoding
 
-        // Update state
-        self.state.is_withdrawn = true;
+        // Update state, reserving the Merkle index so a racing withdrawal for it is rejected
+        if let Some(index) = fill_index {
+            self.used_secret_indices.mark(index);
+        }
+        let fill_amount = self.record_fill(fill_index);
         self.state.revealed_secret = Some(secret.clone());
-        self.state.withdrawn_at = Some(current_time);
+        self.state.withdrawn_at = Some(U64(current_time));
+        store_safety_deposit_recipient(self.state.is_withdrawn.then(env::predecessor_account_id));
 
         env::log_str(&format!(
-            "SrcEscrowWithdrawal: order_hash={}, secret={}, withdrawn_by={}",
+            "SrcEscrowWithdrawal: order_hash={}, correlation_id={}, secret={}, withdrawn_by={}",
             self.immutables.order_hash,
+            correlation_label(),
             secret,
             env::predecessor_account_id()
         ));
 
         // Transfer funds to taker (resolver) and return safety deposit
-        self.transfer_funds_to_taker()
-            .then(self.transfer_safety_deposit())
+        // The callback pays the safety deposit only if the principal moved; a failed transfer
+        // rolls the withdrawal back and frees the Merkle index for a retry
+        // Only the fill that completes the escrow earns the safety deposit
+        let safety_deposit_recipient = stored_safety_deposit_recipient();
+
+        self.transfer_funds_to_taker(fill_amount)
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(CALLBACK_GAS)
+                    .on_withdrawal_transfer(fill_index, U128(fill_amount), safety_deposit_recipient),
+            )
     }
 
     /// Public withdraw (anyone with access token, A3 phase)
     pub fn public_withdraw(
         &mut self,
-        secret: String,
+        secret: Secret,
         merkle_proof: Option<MerkleProof>,
     ) -> Promise {
+        // Anyone may call unless an allowlist is set
+        if let Some(allowlist) = &self.immutables.public_withdrawal_allowlist {
+            assert!(
+                allowlist.contains(&env::predecessor_account_id()),
+                "Caller is not allowed to public withdraw"
+            );
+        }
+        self.assert_safety_deposit_funded();
         self.assert_not_withdrawn();
         self.assert_not_cancelled();
 
         // Check timelock - must be in public withdrawal phase
-        let current_time = env::block_timestamp_ms();
+        let current_time = now_ms();
         let public_withdrawal_start =
             self.get_timelock_timestamp(TimelockStage::SrcPublicWithdrawal);
         let cancellation_start = self.get_timelock_timestamp(TimelockStage::SrcCancellation);
@@ -217,28 +687,44 @@ impl EscrowSrc {
             "Public withdrawal not started"
         );
         assert!(
-            current_time < cancellation_start,
+            current_time < cancellation_start + self.grace_ms(),
             "Cancellation period started"
         );
 
         // Verify secret
-        self.verify_secret(&secret, merkle_proof.as_ref());
+        let secret_bytes = secret.decode();
+        let fill_index = self.verify_secret(&secret_bytes, merkle_proof.as_ref());
+        let secret = hex::encode(&secret_bytes); // Revealed in canonical hex whatever the input encoding
 
-        // Update state
-        self.state.is_withdrawn = true;
+        // Update state, reserving the Merkle index so a racing withdrawal for it is rejected
+        if let Some(index) = fill_index {
+            self.used_secret_indices.mark(index);
+        }
+        let fill_amount = self.record_fill(fill_index);
         self.state.revealed_secret = Some(secret.clone());
-        self.state.withdrawn_at = Some(current_time);
+        self.state.withdrawn_at = Some(U64(current_time));
+        store_safety_deposit_recipient(self.state.is_withdrawn.then(env::predecessor_account_id));
 
         env::log_str(&format!(
-            "SrcEscrowPublicWithdrawal: order_hash={}, secret={}, withdrawn_by={}",
+            "SrcEscrowPublicWithdrawal: order_hash={}, correlation_id={}, secret={}, withdrawn_by={}",
             self.immutables.order_hash,
+            correlation_label(),
             secret,
             env::predecessor_account_id()
         ));
 
         // Transfer to taker and safety deposit to caller
-        self.transfer_funds_to_taker()
-            .then(self.transfer_safety_deposit())
+        // The callback pays the safety deposit only if the principal moved; a failed transfer
+        // rolls the withdrawal back and frees the Merkle index for a retry
+        // Only the fill that completes the escrow earns the safety deposit
+        let safety_deposit_recipient = stored_safety_deposit_recipient();
+
+        self.transfer_funds_to_taker(fill_amount)
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(CALLBACK_GAS)
+                    .on_withdrawal_transfer(fill_index, U128(fill_amount), safety_deposit_recipient),
+            )
     }
 
     /// Cancel escrow (maker only during A3, anyone during A4)
@@ -246,12 +732,13 @@ impl EscrowSrc {
         self.assert_not_withdrawn();
         self.assert_not_cancelled();
 
-        let current_time = env::block_timestamp_ms();
+        let current_time = now_ms();
         let cancellation_start = self.get_timelock_timestamp(TimelockStage::SrcCancellation);
         let public_cancellation_start =
             self.get_timelock_timestamp(TimelockStage::SrcPublicCancellation);
 
-        if current_time < public_cancellation_start {
+        // Cancellation starts grace_ms late so it never overlaps a lenient withdrawal
+        if current_time < public_cancellation_start + self.grace_ms() {
             // A3 phase - only maker can cancel
             assert_eq!(
                 env::predecessor_account_id(),
@@ -259,64 +746,131 @@ impl EscrowSrc {
                 "Only maker can cancel during private cancellation"
             );
             assert!(
-                current_time >= cancellation_start,
+                current_time >= cancellation_start + self.grace_ms(),
                 "Private cancellation not started"
             );
-        } else {
-            // A4 phase - anyone can cancel
-            assert!(
-                current_time >= public_cancellation_start,
-                "Public cancellation not started"
-            );
         }
+        // Otherwise A4 phase - anyone can cancel
 
         // Update state
         self.state.is_cancelled = true;
-        self.state.cancelled_at = Some(current_time);
+        self.state.cancelled_at = Some(U64(current_time));
+        let safety_deposit_recipient = self.state.is_safety_deposit_funded.then(env::predecessor_account_id);
+        store_safety_deposit_recipient(safety_deposit_recipient.clone());
 
+        // Partial fills already paid out their share, the maker gets back what is left
+        let remaining = self.remaining_amount();
         env::log_str(&format!(
-            "SrcEscrowCancelled: order_hash={}, cancelled_by={}",
+            "SrcEscrowCancelled: order_hash={}, correlation_id={}, cancelled_by={}, refunded={}, safety_deposit_recipient={}",
             self.immutables.order_hash,
-            env::predecessor_account_id()
+            correlation_label(),
+            env::predecessor_account_id(),
+            remaining,
+            safety_deposit_recipient.as_ref().map_or("none", AccountId::as_str)
         ));
 
-        // Return funds to maker and safety deposit to caller
-        self.transfer_funds_to_maker()
-            .then(self.transfer_safety_deposit())
+        // Return funds to maker and safety deposit, if it was ever funded, to caller
+        if self.state.is_safety_deposit_funded {
+            self.transfer_funds_to_maker(remaining)
+                .then(self.transfer_safety_deposit())
+        } else {
+            self.transfer_funds_to_maker(remaining)
+        }
     }
 
-    /// Emergency fund rescue (maker only, after rescue delay)
-    pub fn rescue_funds(&mut self, token: AccountId, amount: u128) -> Promise {
+    /// Emergency fund rescue (maker only, after rescue delay). NEAR up to `get_excess_balance` was
+    /// never part of the swap and can be rescued at any time while the escrow is open.
+    pub fn rescue_funds(&mut self, token: AccountId, amount: U128) -> Promise {
         assert_eq!(
             env::predecessor_account_id(),
             self.immutables.maker,
             "Only maker can rescue funds"
         );
 
-        let current_time = env::block_timestamp_ms();
+        let current_time = now_ms();
         let rescue_start = self.immutables.timelocks.deployed_at + (30 * 24 * 60 * 60 * 1000);
+        let is_excess = token.as_str() == "near" && amount.0 <= self.get_excess_balance().0;
 
-        assert!(current_time >= rescue_start, "Rescue delay not expired");
+        assert!(is_excess || current_time >= rescue_start, "Rescue delay not expired");
 
-        env::log_str(&format!("FundsRescued: token={}, amount={}", token, amount));
+        env::log_str(&format!(
+            "FundsRescued: token={}, amount={}, correlation_id={}",
+            token,
+            amount.0,
+            correlation_label()
+        ));
 
         if token.as_str() == "near" {
-            Promise::new(self.immutables.maker.clone()).transfer(NearToken::from_yoctonear(amount))
+            Promise::new(self.immutables.maker.clone()).transfer(NearToken::from_yoctonear(amount.0))
         } else {
             ext_nep141::ext(token)
                 .with_static_gas(NEP141_TRANSFER_GAS)
                 .with_attached_deposit(NearToken::from_yoctonear(1))
                 .ft_transfer(
                     self.immutables.maker.clone(),
-                    amount.to_string(),
+                    amount.0.to_string(),
                     Some("Emergency rescue".to_string()),
                 )
         }
     }
 
+    /// Pays the safety deposit once the principal transfer succeeded, if the fill completed the
+    /// escrow. A failed transfer pays nothing and rolls the fill back, freeing its Merkle index for
+    /// a retry, or refunds it to the maker if the escrow was cancelled meanwhile.
+    #[private]
+    pub fn on_withdrawal_transfer(
+        &mut self,
+        fill_index: Option<u32>,
+        fill_amount: U128,
+        safety_deposit_recipient: Option<AccountId>,
+    ) -> PromiseOrValue<()> {
+        if self.immutables.token.as_str() == "near" {
+            set_in_flight_amount(in_flight_amount().saturating_sub(fill_amount.0));
+        }
+        if is_promise_success() {
+            self.log_withdrawn(fill_index, fill_amount.0, safety_deposit_recipient.as_ref());
+            return match safety_deposit_recipient {
+                Some(recipient) => PromiseOrValue::Promise(
+                    Promise::new(recipient).transfer(NearToken::from_yoctonear(self.immutables.safety_deposit.0)),
+                ),
+                None => PromiseOrValue::Value(()),
+            };
+        }
+        if let Some(index) = fill_index {
+            self.used_secret_indices.unmark(index);
+        }
+        self.state.filled_amount = U128(self.state.filled_amount.0 - fill_amount.0);
+        self.state.is_withdrawn = false;
+        if safety_deposit_recipient.is_some() {
+            store_safety_deposit_recipient(None);
+        }
+        if self.state.filled_amount.0 == 0 {
+            self.state.revealed_secret = None;
+            self.state.withdrawn_at = None;
+        }
+        env::log_str(&format!(
+            "WithdrawalTransferFailed: order_hash={}, correlation_id={}, index {:?} freed, {} unfilled, safety deposit kept",
+            self.immutables.order_hash, correlation_label(), fill_index, fill_amount.0
+        ));
+        if self.state.is_cancelled {
+            // The cancellation refunded only what was unfilled at the time
+            return PromiseOrValue::Promise(self.transfer_funds_to_maker(fill_amount.0));
+        }
+        PromiseOrValue::Value(())
+    }
+
     // View methods
+    /// Deployed code version, `<package version>+<git hash>` for upgrade coordination
+    pub fn contract_version(&self) -> String {
+        format!(
+            "{}+{}",
+            env!("CARGO_PKG_VERSION"),
+            option_env!("GIT_COMMIT_HASH").unwrap_or("unknown")
+        )
+    }
+
     pub fn get_current_phase(&self) -> String {
-        let current_time = env::block_timestamp_ms();
+        let current_time = now_ms();
         let withdrawal_start = self.get_timelock_timestamp(TimelockStage::SrcWithdrawal);
         let public_withdrawal_start =
             self.get_timelock_timestamp(TimelockStage::SrcPublicWithdrawal);
@@ -337,16 +891,145 @@ impl EscrowSrc {
         }
     }
 
+    /// Soonest upcoming phase opening as (event, timestamp ms), None once settled or past the last phase
+    pub fn next_action_timestamp(&self) -> Option<(String, U64)> {
+        if self.state.is_withdrawn || self.state.is_cancelled {
+            return None;
+        }
+
+        let current_time = now_ms();
+        [
+            ("withdrawal", TimelockStage::SrcWithdrawal),
+            ("public_withdrawal", TimelockStage::SrcPublicWithdrawal),
+            ("cancellation", TimelockStage::SrcCancellation),
+            ("public_cancellation", TimelockStage::SrcPublicCancellation),
+        ]
+        .into_iter()
+        .map(|(event, stage)| (event, self.get_timelock_timestamp(stage)))
+        .find(|(_, timestamp)| *timestamp > current_time)
+        .map(|(event, timestamp)| (event.to_string(), U64(timestamp)))
+    }
+
+    /// `get_current_phase`, `get_state` and `next_action_timestamp` in a single call
+    pub fn get_status(&self) -> EscrowStatus {
+        EscrowStatus {
+            phase: self.get_current_phase(),
+            state: self.get_state(),
+            next_action: self.next_action_timestamp(),
+        }
+    }
+
+    /// Mirrors the checks in withdraw, public_withdraw and cancel for `account` at the current time
+    pub fn authorized_actions(&self, account: AccountId) -> AuthorizedActions {
+        let current_time = now_ms();
+        let grace = self.grace_ms();
+        let open = !self.state.is_withdrawn && !self.state.is_cancelled;
+        let withdrawal_start = self.get_timelock_timestamp(TimelockStage::SrcWithdrawal);
+        let public_withdrawal_start =
+            self.get_timelock_timestamp(TimelockStage::SrcPublicWithdrawal);
+        let cancellation_start = self.get_timelock_timestamp(TimelockStage::SrcCancellation);
+        let public_cancellation_start =
+            self.get_timelock_timestamp(TimelockStage::SrcPublicCancellation);
+        let withdrawable = open && self.state.is_safety_deposit_funded;
+        let allowed_public = match &self.immutables.public_withdrawal_allowlist {
+            Some(allowlist) => allowlist.contains(&account),
+            None => true,
+        };
+
+        AuthorizedActions {
+            can_withdraw: withdrawable
+                && account == self.immutables.taker
+                && current_time >= withdrawal_start
+                && current_time < public_cancellation_start + grace,
+            can_public_withdraw: withdrawable
+                && allowed_public
+                && current_time >= public_withdrawal_start
+                && current_time < cancellation_start + grace,
+            can_cancel: open
+                && (current_time >= public_cancellation_start + grace
+                    || (account == self.immutables.maker
+                        && current_time >= cancellation_start + grace)),
+        }
+    }
+
+    pub fn get_state(&self) -> EscrowState {
+        EscrowState { safety_deposit_recipient: stored_safety_deposit_recipient(), ..self.state.clone() }
+    }
+
+    /// Immutables the escrow was initialized with, for checking the factory passed them on unchanged
+    pub fn get_immutables(&self) -> Immutables {
+        self.immutables.clone()
+    }
+
+    pub fn get_chain_context(&self) -> Option<ChainContext> {
+        self.immutables.chain_context.clone()
+    }
+
+    pub fn get_completion_proof(&self) -> Option<CompletionProof> {
+        if !self.state.is_withdrawn {
+            return None;
+        }
+        Some(CompletionProof {
+            order_hash: self.immutables.order_hash.clone(),
+            revealed_secret: self.state.revealed_secret.clone()?,
+            withdrawn_at: self.state.withdrawn_at?,
+            recipient: self.immutables.taker.clone(),
+        })
+    }
+
+    /// Secret revealed by the withdrawal, needed to claim the ETH leg; None until withdrawn
+    pub fn get_revealed_secret(&self) -> Option<String> {
+        if !self.state.is_withdrawn {
+            return None;
+        }
+        self.state.revealed_secret.clone()
+    }
+
+    /// Account paid the safety deposit by the terminal withdrawal or cancellation, None while the
+    /// escrow is open or when no safety deposit was paid out
+    pub fn get_safety_deposit_recipient(&self) -> Option<AccountId> {
+        stored_safety_deposit_recipient()
+    }
+
+    /// NEAR held beyond the unreleased and in-flight principal (native only), an unpaid safety
+    /// deposit and the storage stake, e.g. sent straight to the escrow account outside the funding
+    /// flow. 0 once withdrawn or cancelled, while the final payouts may still bounce back.
+    pub fn get_excess_balance(&self) -> U128 {
+        if self.state.is_withdrawn || self.state.is_cancelled {
+            return U128(0);
+        }
+        let principal = if self.immutables.token.as_str() == "near" {
+            self.remaining_amount() + in_flight_amount()
+        } else {
+            0
+        };
+        let safety_deposit = if self.state.is_safety_deposit_funded {
+            self.immutables.safety_deposit.0
+        } else {
+            0
+        };
+        let storage_stake = env::storage_usage() as u128 * env::storage_byte_cost().as_yoctonear();
+        U128(
+            env::account_balance()
+                .as_yoctonear()
+                .saturating_sub(principal + safety_deposit + storage_stake),
+        )
+    }
+
+    /// Partial-fill parts consumed so far, 0 for single-fill escrows
+    pub fn get_filled_parts(&self) -> u32 {
+        self.used_secret_indices.count()
+    }
+
     // Private helper methods
-    fn verify_secret(&mut self, secret: &str, merkle_proof: Option<&MerkleProof>) {
+    /// Checks the secret without touching state, returns the Merkle index a partial fill will consume
+    fn verify_secret(&self, secret: &[u8], merkle_proof: Option<&MerkleProof>) -> Option<u32> {
         if let Some(merkle_root) = &self.merkle_root {
             let proof = merkle_proof.expect("Merkle proof required for partial fills");
 
+            assert_merkle_proof_shape(proof, self.merkle_parts());
             assert!(
-                !self
-                    .used_secret_indices
-                    .iter()
-                    .any(|idx| idx == proof.index),
+                !self.used_secret_indices.is_used(proof.index),
                 "Secret index already used"
             );
 
@@ -356,17 +1039,17 @@ impl EscrowSrc {
                 "Invalid Merkle proof"
             );
 
-            self.used_secret_indices.push(&proof.index);
+            Some(proof.index)
         } else {
             let secret_hash = self.hash_secret(secret);
             let expected_hash = hex::decode(&self.immutables.hashlock).expect("Invalid hashlock");
             assert_eq!(secret_hash, expected_hash, "Invalid secret");
+            None
         }
     }
 
-    fn hash_secret(&self, secret: &str) -> Vec<u8> {
-        let secret_bytes = hex::decode(secret).expect("Invalid secret format");
-        Sha256::digest(&secret_bytes).to_vec()
+    fn hash_secret(&self, secret: &[u8]) -> Vec<u8> {
+        self.immutables.hash_algorithm.digest(secret)
     }
 
     fn verify_merkle_proof(&self, leaf: &[u8], proof: &[String], index: u32, root: &str) -> bool {
@@ -389,9 +1072,21 @@ impl EscrowSrc {
             current_index /= 2;
         }
 
+        // A proof shorter than the tree depth stops at an intermediate node, leaving index bits unconsumed
+        assert_eq!(current_index, 0, "Merkle proof does not reach the root for index {}", index);
+
         hex::encode(&hash) == root
     }
 
+    /// Grace applied to phase boundaries, in the safe direction only: withdrawals with a valid
+    /// secret stay open up to grace_ms past their deadline and cancellations open grace_ms late.
+    /// Start boundaries of withdrawals stay strict so the finality lock is never shortened.
+    /// Trade-off: the effective windows drift from the counterpart chain's timelocks by up to
+    /// grace_ms, so the src/dst timelock gaps must stay wider than the grace.
+    fn grace_ms(&self) -> u64 {
+        self.immutables.timelocks.grace_ms as u64
+    }
+
     fn get_timelock_timestamp(&self, stage: TimelockStage) -> u64 {
         let delay_seconds = match stage {
             TimelockStage::SrcWithdrawal => self.immutables.timelocks.src_withdrawal,
@@ -405,33 +1100,67 @@ impl EscrowSrc {
         self.immutables.timelocks.deployed_at + (delay_seconds as u64 * 1000)
     }
 
-    fn transfer_funds_to_maker(&self) -> Promise {
+    /// Books the principal a withdrawal releases. A partial fill with Merkle index `i` tops the filled
+    /// amount up to `cumulative_fill_target(amount, i, parts)`, so the amount follows from the secret
+    /// and not from the caller; a single fill releases everything. The escrow counts as withdrawn once
+    /// nothing remains, until then it can take further fills or be cancelled for the remainder.
+    fn record_fill(&mut self, fill_index: Option<u32>) -> u128 {
+        let remaining = self.remaining_amount();
+        // A legacy hashlock has no part count to derive the amount from, its fill takes everything
+        let fill_amount = fill_index.zip(self.merkle_parts()).map_or(remaining, |(index, parts)| {
+            cumulative_fill_target(self.immutables.amount.0, index, parts)
+                .saturating_sub(self.state.filled_amount.0)
+        });
+        assert!(
+            fill_amount > 0 && fill_amount <= remaining,
+            "E_INVALID_FILL_AMOUNT: fill of {} with {} remaining",
+            fill_amount,
+            remaining
+        );
+        self.state.filled_amount = U128(self.state.filled_amount.0 + fill_amount);
+        self.state.is_withdrawn = self.state.filled_amount.0 == self.immutables.amount.0;
+        fill_amount
+    }
+
+    /// Number of parts a partial-fill order was split into, None for a single-fill escrow or a
+    /// legacy hashlock that does not record it
+    fn merkle_parts(&self) -> Option<u32> {
+        parse_merkle_hashlock(&self.immutables.hashlock).and_then(|(parts, _)| parts)
+    }
+
+    fn remaining_amount(&self) -> u128 {
+        self.immutables.amount.0 - self.state.filled_amount.0
+    }
+
+    fn transfer_funds_to_maker(&self, amount: u128) -> Promise {
         if self.immutables.token.as_str() == "near" {
             Promise::new(self.immutables.maker.clone())
-                .transfer(NearToken::from_yoctonear(self.immutables.amount))
+                .transfer(NearToken::from_yoctonear(amount))
         } else {
             ext_nep141::ext(self.immutables.token.clone())
                 .with_static_gas(NEP141_TRANSFER_GAS)
                 .with_attached_deposit(NearToken::from_yoctonear(1))
                 .ft_transfer(
                     self.immutables.maker.clone(),
-                    self.immutables.amount.to_string(),
+                    amount.to_string(),
                     Some("Escrow cancellation to maker".to_string()),
                 )
         }
     }
 
-    fn transfer_funds_to_taker(&self) -> Promise {
+    /// Only withdrawals pay the taker, their callback settles the in-flight amount
+    fn transfer_funds_to_taker(&self, amount: u128) -> Promise {
         if self.immutables.token.as_str() == "near" {
+            set_in_flight_amount(in_flight_amount() + amount);
             Promise::new(self.immutables.taker.clone())
-                .transfer(NearToken::from_yoctonear(self.immutables.amount))
+                .transfer(NearToken::from_yoctonear(amount))
         } else {
             ext_nep141::ext(self.immutables.token.clone())
                 .with_static_gas(NEP141_TRANSFER_GAS)
                 .with_attached_deposit(NearToken::from_yoctonear(1))
                 .ft_transfer(
                     self.immutables.taker.clone(),
-                    self.immutables.amount.to_string(),
+                    amount.to_string(),
                     Some("Escrow withdrawal to taker".to_string()),
                 )
         }
@@ -439,7 +1168,40 @@ impl EscrowSrc {
 
     fn transfer_safety_deposit(&self) -> Promise {
         Promise::new(env::predecessor_account_id())
-            .transfer(NearToken::from_yoctonear(self.immutables.safety_deposit))
+            .transfer(NearToken::from_yoctonear(self.immutables.safety_deposit.0))
+    }
+
+    /// NEP-297 `escrow_withdrawn` event carrying the amounts the withdrawal transferred, for off-chain
+    /// reconciliation. Logged once the principal moved. A partial fill releases only its own fill
+    /// amount, and only the completing fill pays the safety deposit.
+    fn log_withdrawn(&self, fill_index: Option<u32>, fill_amount: u128, safety_deposit_recipient: Option<&AccountId>) {
+        let safety_deposit = if safety_deposit_recipient.is_some() { self.immutables.safety_deposit.0 } else { 0 };
+        let event = near_sdk::serde_json::json!({
+            "standard": "1prime-escrow",
+            "version": "1.0.0",
+            "event": "escrow_withdrawn",
+            "data": [{
+                "order_hash": self.immutables.order_hash,
+                "token": self.immutables.token,
+                "recipient": self.immutables.taker,
+                "principal": U128(fill_amount),
+                "safety_deposit": U128(safety_deposit),
+                "safety_deposit_recipient": safety_deposit_recipient,
+                "fill_index": fill_index,
+                "fill_amount": fill_index.map(|_| U128(fill_amount)),
+                "correlation_id": stored_correlation_id(),
+            }],
+        });
+        env::log_str(&format!("EVENT_JSON:{}", event));
+    }
+
+    fn assert_expected_immutables(&self, expected: Option<&Immutables>) {
+        if let Some(expected) = expected {
+            assert!(
+                borsh::to_vec(expected).unwrap() == borsh::to_vec(&self.immutables).unwrap(),
+                "E_IMMUTABLES_MISMATCH: expected immutables differ from the escrow's"
+            );
+        }
     }
 
     // Access control
@@ -458,6 +1220,91 @@ impl EscrowSrc {
     fn assert_not_cancelled(&self) {
         assert!(!self.state.is_cancelled, "Already cancelled");
     }
+
+    fn assert_safety_deposit_funded(&self) {
+        assert!(self.state.is_safety_deposit_funded, "Safety deposit not funded");
+    }
+}
+
+/// Levels of the tree over the `parts + 1` secrets of a partial fill, the length of every proof
+pub fn merkle_proof_depth(parts: u32) -> usize {
+    (parts + 1).next_power_of_two().trailing_zeros() as usize
+}
+
+/// Bounds `proof` by the order's part count. A legacy hashlock without one accepts any index and
+/// depth a MAX_MERKLE_PARTS tree allows, as escrows did before the count was recorded
+fn assert_merkle_proof_shape(proof: &MerkleProof, parts: Option<u32>) {
+    match parts {
+        Some(parts) => {
+            assert!(proof.index <= parts, "Secret index exceeds the order's {} parts", parts);
+            // A shorter proof would stop at an inner node, a longer one climb past the root
+            let depth = merkle_proof_depth(parts);
+            assert_eq!(proof.proof.len(), depth, "Merkle proof must be {} levels deep", depth);
+        }
+        None => {
+            assert!(
+                proof.index <= MAX_MERKLE_PARTS,
+                "Secret index exceeds maximum of {} parts",
+                MAX_MERKLE_PARTS
+            );
+            let max_depth = merkle_proof_depth(MAX_MERKLE_PARTS);
+            assert!(proof.proof.len() <= max_depth, "Merkle proof deeper than {}", max_depth);
+        }
+    }
+}
+
+/// Splits a partial-fill hashlock `merkle:<parts>:<root hex>` into its parts and root,
+/// None for a single-fill hashlock. The parts are None for the legacy `merkle:<root hex>` that
+/// migrated escrows still hold
+fn parse_merkle_hashlock(hashlock: &str) -> Option<(Option<u32>, String)> {
+    let rest = hashlock.strip_prefix("merkle:")?;
+    let Some((parts, root)) = rest.split_once(':') else {
+        return Some((None, rest.to_string()));
+    };
+    let parts: u32 = parts
+        .parse()
+        .unwrap_or_else(|_| env::panic_str("E_INVALID_MERKLE_HASHLOCK: parts is not a number"));
+    assert!(
+        parts > 0 && parts <= MAX_MERKLE_PARTS,
+        "E_INVALID_MERKLE_HASHLOCK: parts must be in 1..={}",
+        MAX_MERKLE_PARTS
+    );
+    Some((Some(parts), root.to_string()))
+}
+
+/// Root of a new escrow's partial-fill hashlock, which must carry its part count
+fn merkle_root_for_init(hashlock: &str) -> Option<String> {
+    parse_merkle_hashlock(hashlock).map(|(parts, root)| {
+        if parts.is_none() {
+            env::panic_str("E_INVALID_MERKLE_HASHLOCK: expected merkle:<parts>:<root>");
+        }
+        root
+    })
+}
+
+/// Cumulative principal released once the secret at `index` is used: `amount * (index + 1) / parts`,
+/// capped at `amount` so the last index (`parts`) completes the order
+pub fn cumulative_fill_target(amount: u128, index: u32, parts: u32) -> u128 {
+    let (steps, parts) = (index as u128 + 1, parts as u128);
+    let target = amount
+        .checked_mul(steps)
+        .map_or_else(
+            || (amount / parts).saturating_mul(steps).saturating_add(amount % parts * steps / parts),
+            |scaled| scaled / parts,
+        );
+    target.min(amount)
+}
+
+/// Rejects a single-fill hashlock committing to one of WEAK_PREIMAGES, a handful of hashes at init
+fn assert_hashlock_not_weak(hashlock: &str, hash_algorithm: HashAlgo) {
+    // "merkle:" roots are not a secret's hash, malformed hex is rejected at withdraw
+    let Ok(hashlock) = hex::decode(hashlock) else {
+        return;
+    };
+    assert!(
+        !WEAK_PREIMAGES.iter().any(|preimage| hash_algorithm.digest(preimage) == hashlock),
+        "E_WEAK_HASHLOCK: hashlock commits to a publicly known preimage"
+    );
 }
 
 /// Timelock stages for source chain
@@ -467,3 +1314,919 @@ pub enum TimelockStage {
     SrcCancellation,
     SrcPublicCancellation,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+    use near_sdk::PromiseResult;
+
+    const DEPLOYED_AT: u64 = 1_700_000_000_000;
+    const SECRET: [u8; 32] = [7u8; 32];
+
+    fn immutables() -> Immutables {
+        Immutables {
+            order_hash: "0xorder".to_string(),
+            hashlock: hex::encode(Sha256::digest(SECRET)),
+            maker: "maker.testnet".parse().unwrap(),
+            taker: "resolver.testnet".parse().unwrap(),
+            token: "near".parse().unwrap(),
+            amount: U128(1_000),
+            safety_deposit: U128(100),
+            timelocks: Timelocks {
+                deployed_at: DEPLOYED_AT,
+                src_withdrawal: 60,
+                src_public_withdrawal: 120,
+                src_cancellation: 180,
+                src_public_cancellation: 240,
+                dst_withdrawal: 30,
+                dst_public_withdrawal: 90,
+                dst_cancellation: 150,
+                grace_ms: 0,
+            },
+            public_withdrawal_allowlist: None,
+            chain_context: None,
+            hash_algorithm: HashAlgo::Sha256,
+        }
+    }
+
+    fn escrow() -> EscrowSrc {
+        let factory: AccountId = "factory.testnet".parse().unwrap();
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(factory.clone())
+            .attached_deposit(NearToken::from_yoctonear(1_100))
+            .build());
+        EscrowSrc::init(CreateEscrowArgs { immutables: immutables(), factory, correlation_id: None })
+    }
+
+    #[test]
+    fn test_contract_version_matches_package_version() {
+        let version = escrow().contract_version();
+        let (package, commit) = version.split_once('+').unwrap();
+        assert_eq!(package, env!("CARGO_PKG_VERSION"));
+        assert!(!commit.is_empty());
+    }
+
+    #[test]
+    fn test_init_from_blob_matches_json_init() {
+        let from_json = escrow();
+        let factory: AccountId = "factory.testnet".parse().unwrap();
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(factory.clone())
+            .attached_deposit(NearToken::from_yoctonear(1_100))
+            .build());
+        let blob = Base64VecU8::from(borsh::to_vec(&immutables()).unwrap());
+        let from_blob = EscrowSrc::init_from_blob(blob, factory, None);
+
+        assert_eq!(borsh::to_vec(&from_blob.immutables).unwrap(), borsh::to_vec(&from_json.immutables).unwrap());
+    }
+
+    #[test]
+    fn test_phase_boundaries() {
+        let escrow = escrow();
+        for (time, phase) in [
+            (DEPLOYED_AT, "A1_FINALITY_LOCK"),
+            (DEPLOYED_AT + 59_999, "A1_FINALITY_LOCK"),
+            (DEPLOYED_AT + 60_000, "A2_RESOLVER_EXCLUSIVE"),
+            (DEPLOYED_AT + 119_999, "A2_RESOLVER_EXCLUSIVE"),
+            (DEPLOYED_AT + 120_000, "A3_PUBLIC_WITHDRAWAL"),
+            (DEPLOYED_AT + 180_000, "A3_PRIVATE_CANCELLATION"),
+            (DEPLOYED_AT + 239_999, "A3_PRIVATE_CANCELLATION"),
+            (DEPLOYED_AT + 240_000, "A4_PUBLIC_CANCELLATION"),
+        ] {
+            set_time_override(Some(time));
+            assert_eq!(escrow.get_current_phase(), phase, "at {}", time);
+        }
+        set_time_override(None);
+    }
+
+    #[test]
+    fn test_next_action_follows_timeline() {
+        let mut escrow = escrow();
+        for (time, next) in [
+            (DEPLOYED_AT, Some(("withdrawal", DEPLOYED_AT + 60_000))),
+            (DEPLOYED_AT + 60_000, Some(("public_withdrawal", DEPLOYED_AT + 120_000))),
+            (DEPLOYED_AT + 150_000, Some(("cancellation", DEPLOYED_AT + 180_000))),
+            (DEPLOYED_AT + 200_000, Some(("public_cancellation", DEPLOYED_AT + 240_000))),
+            (DEPLOYED_AT + 240_000, None),
+        ] {
+            set_time_override(Some(time));
+            let expected = next.map(|(event, timestamp)| (event.to_string(), U64(timestamp)));
+            assert_eq!(escrow.next_action_timestamp(), expected, "at {}", time);
+        }
+
+        // Nothing is left to schedule once the escrow is withdrawn
+        call_at("resolver.testnet", DEPLOYED_AT + 60_000);
+        escrow.withdraw(Secret { encoding: SecretEncoding::Hex, value: hex::encode(SECRET) }, None, None);
+        assert_eq!(escrow.next_action_timestamp(), None);
+        set_time_override(None);
+    }
+
+    fn escrow_with_grace(grace_ms: u32) -> EscrowSrc {
+        let mut escrow = escrow();
+        escrow.immutables.timelocks.grace_ms = grace_ms;
+        escrow
+    }
+
+    fn call_at(account: &str, time: u64) {
+        testing_env!(VMContextBuilder::new().predecessor_account_id(account.parse().unwrap()).build());
+        set_time_override(Some(time));
+    }
+
+    #[test]
+    #[should_panic(expected = "Cancellation period started")]
+    fn test_public_withdrawal_closes_at_cancellation_without_grace() {
+        let mut escrow = escrow_with_grace(0);
+        call_at("random.testnet", DEPLOYED_AT + 180_000);
+        escrow.public_withdraw(Secret { encoding: SecretEncoding::Hex, value: hex::encode(SECRET) }, None);
+    }
+
+    #[test]
+    fn test_public_withdrawal_stays_open_within_grace() {
+        let mut escrow = escrow_with_grace(1_000);
+        call_at("random.testnet", DEPLOYED_AT + 180_999);
+        escrow.public_withdraw(Secret { encoding: SecretEncoding::Hex, value: hex::encode(SECRET) }, None);
+        assert!(escrow.state.is_withdrawn);
+        set_time_override(None);
+    }
+
+    fn escrow_with_allowlist() -> EscrowSrc {
+        let mut escrow = escrow();
+        escrow.immutables.public_withdrawal_allowlist = Some(vec!["backup.testnet".parse().unwrap()]);
+        escrow
+    }
+
+    #[test]
+    fn test_allowlisted_caller_public_withdraws() {
+        let mut escrow = escrow_with_allowlist();
+        call_at("backup.testnet", DEPLOYED_AT + 120_000);
+        assert!(escrow.authorized_actions("backup.testnet".parse().unwrap()).can_public_withdraw);
+        escrow.public_withdraw(Secret { encoding: SecretEncoding::Hex, value: hex::encode(SECRET) }, None);
+        assert!(escrow.state.is_withdrawn);
+        set_time_override(None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Caller is not allowed to public withdraw")]
+    fn test_unlisted_caller_public_withdraw_rejected() {
+        let mut escrow = escrow_with_allowlist();
+        call_at("random.testnet", DEPLOYED_AT + 120_000);
+        assert!(!escrow.authorized_actions("random.testnet".parse().unwrap()).can_public_withdraw);
+        escrow.public_withdraw(Secret { encoding: SecretEncoding::Hex, value: hex::encode(SECRET) }, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Private cancellation not started")]
+    fn test_private_cancellation_opens_late_by_grace() {
+        let mut escrow = escrow_with_grace(1_000);
+        call_at("maker.testnet", DEPLOYED_AT + 180_999);
+        escrow.cancel();
+    }
+
+    #[test]
+    #[should_panic(expected = "Only maker can cancel during private cancellation")]
+    fn test_public_cancellation_opens_late_by_grace() {
+        let mut escrow = escrow_with_grace(1_000);
+        call_at("random.testnet", DEPLOYED_AT + 240_999);
+        escrow.cancel();
+    }
+
+    #[test]
+    fn test_cancellations_open_at_boundary_plus_grace() {
+        for (grace_ms, account, offset) in [
+            (0, "maker.testnet", 180_000),
+            (1_000, "maker.testnet", 181_000),
+            (0, "random.testnet", 240_000),
+            (1_000, "random.testnet", 241_000),
+        ] {
+            let mut escrow = escrow_with_grace(grace_ms);
+            call_at(account, DEPLOYED_AT + offset);
+            escrow.cancel();
+            assert!(escrow.state.is_cancelled, "{} at +{} with grace {}", account, offset, grace_ms);
+        }
+        set_time_override(None);
+    }
+
+    #[test]
+    fn test_status_combines_phase_state_and_next_action() {
+        let escrow = escrow();
+        set_time_override(Some(DEPLOYED_AT + 90_000));
+        let status = escrow.get_status();
+        set_time_override(None);
+
+        assert_eq!(status.phase, "A2_RESOLVER_EXCLUSIVE");
+        assert!(!status.state.is_withdrawn);
+        assert_eq!(
+            status.next_action.map(|(event, at)| (event, at.0)),
+            Some(("public_withdrawal".to_string(), DEPLOYED_AT + 120_000))
+        );
+    }
+
+    fn init_with_hashlock(hashlock: String) -> EscrowSrc {
+        let factory: AccountId = "factory.testnet".parse().unwrap();
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(factory.clone())
+            .attached_deposit(NearToken::from_yoctonear(1_100))
+            .build());
+        EscrowSrc::init(CreateEscrowArgs { immutables: Immutables { hashlock, ..immutables() }, factory, correlation_id: None })
+    }
+
+    #[test]
+    #[should_panic(expected = "E_WEAK_HASHLOCK")]
+    fn test_hashlock_of_empty_preimage_rejected() {
+        init_with_hashlock(hex::encode(Sha256::digest(b"")));
+    }
+
+    #[test]
+    fn test_random_hashlock_accepted() {
+        let hashlock = hex::encode(Sha256::digest(b"9f2c71d4e8a05b36c4e1f7a20d9b83e5"));
+        let escrow = init_with_hashlock(hashlock.clone());
+        assert_eq!(escrow.immutables.hashlock, hashlock);
+    }
+
+    #[test]
+    fn test_authorized_actions_by_role_and_phase() {
+        let escrow = escrow();
+        let taker: AccountId = "resolver.testnet".parse().unwrap();
+        let maker: AccountId = "maker.testnet".parse().unwrap();
+        let random: AccountId = "random.testnet".parse().unwrap();
+        let actions = |withdraw, public_withdraw, cancel| AuthorizedActions {
+            can_withdraw: withdraw,
+            can_public_withdraw: public_withdraw,
+            can_cancel: cancel,
+        };
+
+        for (time, account, expected) in [
+            (DEPLOYED_AT, &taker, actions(false, false, false)),
+            (DEPLOYED_AT + 60_000, &taker, actions(true, false, false)),
+            (DEPLOYED_AT + 60_000, &maker, actions(false, false, false)),
+            (DEPLOYED_AT + 120_000, &random, actions(false, true, false)),
+            (DEPLOYED_AT + 180_000, &taker, actions(true, false, false)),
+            (DEPLOYED_AT + 180_000, &maker, actions(false, false, true)),
+            (DEPLOYED_AT + 180_000, &random, actions(false, false, false)),
+            (DEPLOYED_AT + 240_000, &random, actions(false, false, true)),
+        ] {
+            set_time_override(Some(time));
+            assert_eq!(escrow.authorized_actions(account.clone()), expected, "{} at {}", account, time);
+        }
+        set_time_override(None);
+    }
+
+    #[test]
+    fn test_secret_encodings_hash_alike() {
+        let raw = "0123456789abcdef0123456789abcdef";
+        let decoded: Vec<Vec<u8>> = [
+            (SecretEncoding::Hex, "0x3031323334353637383961626364656630313233343536373839616263646566"),
+            (SecretEncoding::Base64, "MDEyMzQ1Njc4OWFiY2RlZjAxMjM0NTY3ODlhYmNkZWY="),
+            (SecretEncoding::Raw, raw),
+        ]
+        .into_iter()
+        .map(|(encoding, value)| Secret { encoding, value: value.to_string() }.decode())
+        .collect();
+
+        assert!(decoded.iter().all(|bytes| bytes.as_slice() == raw.as_bytes()));
+        assert_eq!(Sha256::digest(&decoded[0]), Sha256::digest(&decoded[1]));
+    }
+
+    #[test]
+    #[should_panic(expected = "E_INVALID_SECRET")]
+    fn test_short_secret_rejected() {
+        Secret { encoding: SecretEncoding::Hex, value: hex::encode(b"secret") }.decode();
+    }
+
+    #[test]
+    #[should_panic(expected = "Merkle proof does not reach the root")]
+    fn test_truncated_merkle_proof_rejected() {
+        let escrow = escrow();
+        let leaves: Vec<Vec<u8>> = (0u8..4).map(|i| Sha256::digest([i; 32]).to_vec()).collect();
+        let node = |left: &[u8], right: &[u8]| Sha256::digest([left, right].concat()).to_vec();
+        let right_subtree = node(&leaves[2], &leaves[3]);
+
+        // Leaf 2 with only its sibling: one level short of the depth-2 root
+        escrow.verify_merkle_proof(&leaves[2], &[hex::encode(&leaves[3])], 2, &hex::encode(right_subtree));
+    }
+
+    /// Runs the withdrawal callback with the principal transfer's `result`, returning the event it logged
+    fn settle_withdrawal(
+        escrow: &mut EscrowSrc,
+        fill_index: Option<u32>,
+        fill_amount: u128,
+        result: PromiseResult,
+    ) -> Option<near_sdk::serde_json::Value> {
+        let recipient = escrow.get_safety_deposit_recipient();
+        testing_env!(
+            VMContextBuilder::new().build(),
+            near_sdk::test_vm_config(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![result],
+        );
+        escrow.on_withdrawal_transfer(fill_index, U128(fill_amount), recipient);
+        near_sdk::test_utils::get_logs()
+            .iter()
+            .find_map(|log| log.strip_prefix("EVENT_JSON:"))
+            .map(|event| near_sdk::serde_json::from_str(event).unwrap())
+    }
+
+    /// Escrow whose maker attached the principal only, leaving the safety deposit to the resolver
+    fn principal_only_escrow() -> EscrowSrc {
+        let factory: AccountId = "factory.testnet".parse().unwrap();
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(factory.clone())
+            .attached_deposit(NearToken::from_yoctonear(1_000))
+            .build());
+        EscrowSrc::init(CreateEscrowArgs { immutables: immutables(), factory, correlation_id: None })
+    }
+
+    fn attached_by(account: &str, yocto: u128) {
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(account.parse().unwrap())
+            .attached_deposit(NearToken::from_yoctonear(yocto))
+            .build());
+    }
+
+    #[test]
+    fn test_split_funding_allows_withdraw_once_safety_deposited() {
+        let mut escrow = principal_only_escrow();
+        assert!(!escrow.state.is_safety_deposit_funded);
+
+        attached_by("resolver.testnet", 100);
+        escrow.deposit_safety();
+        assert!(escrow.state.is_safety_deposit_funded);
+
+        call_at("resolver.testnet", DEPLOYED_AT + 60_000);
+        escrow.withdraw(Secret { encoding: SecretEncoding::Hex, value: hex::encode(SECRET) }, None, None);
+        set_time_override(None);
+        assert!(escrow.state.is_withdrawn);
+    }
+
+    #[test]
+    #[should_panic(expected = "Safety deposit not funded")]
+    fn test_withdraw_before_safety_deposit_rejected() {
+        let mut escrow = principal_only_escrow();
+        call_at("resolver.testnet", DEPLOYED_AT + 60_000);
+        escrow.withdraw(Secret { encoding: SecretEncoding::Hex, value: hex::encode(SECRET) }, None, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Incorrect safety deposit amount")]
+    fn test_partial_safety_deposit_rejected() {
+        let mut escrow = principal_only_escrow();
+        attached_by("resolver.testnet", 99);
+        escrow.deposit_safety();
+    }
+
+    #[test]
+    #[should_panic(expected = "Safety deposit already funded")]
+    fn test_safety_deposit_cannot_be_funded_twice() {
+        // Attached alongside the principal at creation
+        let mut escrow = escrow();
+        attached_by("resolver.testnet", 100);
+        escrow.deposit_safety();
+    }
+
+    #[test]
+    #[should_panic(expected = "Only taker can call this method")]
+    fn test_safety_deposit_only_from_taker() {
+        let mut escrow = principal_only_escrow();
+        attached_by("maker.testnet", 100);
+        escrow.deposit_safety();
+    }
+
+    #[test]
+    #[should_panic(expected = "Incorrect deposit amount")]
+    fn test_init_with_partial_safety_deposit_rejected() {
+        let factory: AccountId = "factory.testnet".parse().unwrap();
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(factory.clone())
+            .attached_deposit(NearToken::from_yoctonear(1_050))
+            .build());
+        EscrowSrc::init(CreateEscrowArgs { immutables: immutables(), factory, correlation_id: None });
+    }
+
+    #[test]
+    fn test_withdrawn_event_carries_amounts() {
+        let mut escrow = escrow();
+        testing_env!(VMContextBuilder::new().predecessor_account_id("resolver.testnet".parse().unwrap()).build());
+        set_time_override(Some(DEPLOYED_AT + 60_000));
+
+        escrow.withdraw(Secret { encoding: SecretEncoding::Hex, value: hex::encode(SECRET) }, None, None);
+        set_time_override(None);
+        // Nothing is reported until the principal transfer succeeded
+        assert!(!near_sdk::test_utils::get_logs().iter().any(|log| log.starts_with("EVENT_JSON:")));
+
+        let event = settle_withdrawal(&mut escrow, None, 1_000, PromiseResult::Successful(vec![])).expect("no event logged");
+        assert_eq!(event["event"], "escrow_withdrawn");
+        let data = &event["data"][0];
+        assert_eq!(data["principal"], "1000");
+        assert_eq!(data["safety_deposit"], "100");
+        assert_eq!(data["recipient"], "resolver.testnet");
+        assert_eq!(data["safety_deposit_recipient"], "resolver.testnet");
+        assert!(data["fill_index"].is_null());
+        assert!(data["fill_amount"].is_null());
+    }
+
+    #[test]
+    fn test_revealed_secret_hidden_until_withdrawal() {
+        let mut escrow = escrow();
+        assert_eq!(escrow.get_revealed_secret(), None);
+
+        call_at("resolver.testnet", DEPLOYED_AT + 60_000);
+        escrow.withdraw(Secret { encoding: SecretEncoding::Hex, value: hex::encode(SECRET) }, None, None);
+        set_time_override(None);
+        assert_eq!(escrow.get_revealed_secret(), Some(hex::encode(SECRET)));
+
+        // A failed transfer rolls the withdrawal back and hides the secret again
+        settle_withdrawal(&mut escrow, None, 1_000, PromiseResult::Failed);
+        assert_eq!(escrow.get_revealed_secret(), None);
+    }
+
+    #[test]
+    fn test_completion_proof_populated_after_withdrawal() {
+        let mut escrow = escrow();
+        assert!(escrow.get_completion_proof().is_none());
+
+        call_at("resolver.testnet", DEPLOYED_AT + 60_000);
+        escrow.withdraw(Secret { encoding: SecretEncoding::Hex, value: hex::encode(SECRET) }, None, None);
+        set_time_override(None);
+
+        let proof = escrow.get_completion_proof().expect("no proof after withdrawal");
+        assert_eq!(proof.order_hash, "0xorder");
+        assert_eq!(proof.revealed_secret, hex::encode(SECRET));
+        assert_eq!(proof.withdrawn_at, U64(DEPLOYED_AT + 60_000));
+        assert_eq!(proof.recipient.as_str(), "resolver.testnet");
+    }
+
+    #[test]
+    fn test_failed_transfer_logs_no_withdrawn_event() {
+        let mut escrow = escrow();
+        testing_env!(VMContextBuilder::new().predecessor_account_id("resolver.testnet".parse().unwrap()).build());
+        set_time_override(Some(DEPLOYED_AT + 60_000));
+
+        escrow.withdraw(Secret { encoding: SecretEncoding::Hex, value: hex::encode(SECRET) }, None, None);
+        set_time_override(None);
+
+        assert!(settle_withdrawal(&mut escrow, None, 1_000, PromiseResult::Failed).is_none());
+    }
+
+    #[test]
+    fn test_correlation_id_in_withdrawal_logs() {
+        let factory: AccountId = "factory.testnet".parse().unwrap();
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(factory.clone())
+            .attached_deposit(NearToken::from_yoctonear(1_100))
+            .build());
+        let mut escrow = EscrowSrc::init(CreateEscrowArgs {
+            immutables: immutables(),
+            factory,
+            correlation_id: Some("swap-7".to_string()),
+        });
+        testing_env!(VMContextBuilder::new().predecessor_account_id("resolver.testnet".parse().unwrap()).build());
+        set_time_override(Some(DEPLOYED_AT + 60_000));
+
+        escrow.withdraw(Secret { encoding: SecretEncoding::Hex, value: hex::encode(SECRET) }, None, None);
+        set_time_override(None);
+        let logs = near_sdk::test_utils::get_logs();
+        assert!(
+            logs.iter().any(|log| log.starts_with("SrcEscrowWithdrawal: order_hash=") && log.contains("correlation_id=swap-7,")),
+            "{:?}",
+            logs
+        );
+        let event = settle_withdrawal(&mut escrow, None, 1_000, PromiseResult::Successful(vec![])).expect("no event logged");
+        assert_eq!(event["data"][0]["correlation_id"], "swap-7");
+    }
+
+    #[test]
+    fn test_safety_deposit_recipient_recorded_on_withdrawal() {
+        let secret = Secret { encoding: SecretEncoding::Hex, value: hex::encode(SECRET) };
+        for (time, caller, public) in [
+            (DEPLOYED_AT + 60_000, "resolver.testnet", false),
+            (DEPLOYED_AT + 120_000, "random.testnet", true),
+        ] {
+            let mut escrow = escrow();
+            assert_eq!(escrow.get_safety_deposit_recipient(), None);
+            testing_env!(VMContextBuilder::new().predecessor_account_id(caller.parse().unwrap()).build());
+            set_time_override(Some(time));
+            if public {
+                escrow.public_withdraw(secret.clone(), None);
+            } else {
+                escrow.withdraw(secret.clone(), None, None);
+            }
+            set_time_override(None);
+
+            assert_eq!(escrow.get_safety_deposit_recipient(), Some(caller.parse().unwrap()));
+            assert_eq!(escrow.get_state().safety_deposit_recipient, Some(caller.parse().unwrap()));
+            let event =
+                settle_withdrawal(&mut escrow, None, 1_000, PromiseResult::Successful(vec![])).expect("no event logged");
+            assert_eq!(event["event"], "escrow_withdrawn");
+            assert_eq!(event["data"][0]["safety_deposit_recipient"], caller);
+        }
+    }
+
+    /// Escrow split into `parts` over a full-depth tree holding SECRET at `index`, with its proof
+    fn partial_fill_escrow(parts: u32, index: u32) -> (EscrowSrc, MerkleProof) {
+        let depth = (parts + 1).next_power_of_two().trailing_zeros();
+        let siblings: Vec<Vec<u8>> = (0..depth).map(|level| Sha256::digest([level as u8 + 9; 32]).to_vec()).collect();
+        let mut node = Sha256::digest(SECRET).to_vec();
+        for (level, sibling) in siblings.iter().enumerate() {
+            node = if (index >> level) % 2 == 0 {
+                Sha256::digest([node.as_slice(), sibling].concat()).to_vec()
+            } else {
+                Sha256::digest([sibling.as_slice(), &node].concat()).to_vec()
+            };
+        }
+        let escrow = init_with_hashlock(format!("merkle:{}:{}", parts, hex::encode(node)));
+        (escrow, MerkleProof { proof: siblings.iter().map(hex::encode).collect(), index })
+    }
+
+    #[test]
+    fn test_merkle_proof_depth_covers_every_secret() {
+        for (parts, depth) in [(1, 1), (2, 2), (3, 2), (4, 3), (7, 3), (MAX_MERKLE_PARTS, 11)] {
+            assert_eq!(merkle_proof_depth(parts), depth, "{} parts", parts);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Merkle proof must be 2 levels deep")]
+    fn test_short_merkle_proof_rejected_at_withdraw() {
+        let (mut escrow, mut proof) = partial_fill_escrow(3, 0);
+        proof.proof.pop();
+        testing_env!(VMContextBuilder::new().predecessor_account_id("resolver.testnet".parse().unwrap()).build());
+        set_time_override(Some(DEPLOYED_AT + 60_000));
+        escrow.withdraw(Secret { encoding: SecretEncoding::Hex, value: hex::encode(SECRET) }, Some(proof), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Merkle proof must be 2 levels deep")]
+    fn test_long_merkle_proof_rejected_at_withdraw() {
+        let (mut escrow, mut proof) = partial_fill_escrow(3, 0);
+        proof.proof.push(hex::encode([0u8; 32]));
+        testing_env!(VMContextBuilder::new().predecessor_account_id("resolver.testnet".parse().unwrap()).build());
+        set_time_override(Some(DEPLOYED_AT + 60_000));
+        escrow.withdraw(Secret { encoding: SecretEncoding::Hex, value: hex::encode(SECRET) }, Some(proof), None);
+    }
+
+    #[test]
+    fn test_failed_partial_withdrawal_frees_index_for_retry() {
+        let (mut escrow, proof) = partial_fill_escrow(1, 0);
+        let secret = Secret { encoding: SecretEncoding::Hex, value: hex::encode(SECRET) };
+        let as_taker = || {
+            testing_env!(VMContextBuilder::new().predecessor_account_id("resolver.testnet".parse().unwrap()).build());
+        };
+        set_time_override(Some(DEPLOYED_AT + 60_000));
+
+        as_taker();
+        escrow.withdraw(secret.clone(), Some(proof.clone()), None);
+        assert!(escrow.used_secret_indices.is_used(0));
+
+        testing_env!(
+            VMContextBuilder::new().build(),
+            near_sdk::test_vm_config(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed],
+        );
+        let payout = escrow.on_withdrawal_transfer(Some(0), U128(1_000), Some("resolver.testnet".parse().unwrap()));
+        assert!(matches!(payout, PromiseOrValue::Value(())), "safety deposit paid despite failed transfer");
+        assert!(!escrow.used_secret_indices.is_used(0));
+        assert!(!escrow.state.is_withdrawn);
+
+        as_taker();
+        escrow.withdraw(secret, Some(proof), None);
+        assert!(escrow.used_secret_indices.is_used(0));
+        assert!(escrow.state.is_withdrawn);
+        set_time_override(None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Secret index already used")]
+    fn test_index_replay_rejected_while_transfer_pending() {
+        let (mut escrow, proof) = partial_fill_escrow(5, 1);
+        let secret = Secret { encoding: SecretEncoding::Hex, value: hex::encode(SECRET) };
+        testing_env!(VMContextBuilder::new().predecessor_account_id("resolver.testnet".parse().unwrap()).build());
+        set_time_override(Some(DEPLOYED_AT + 60_000));
+
+        // The index is taken in withdraw itself, not in the transfer callback
+        escrow.withdraw(secret.clone(), Some(proof.clone()), None);
+        escrow.withdraw(secret, Some(proof), None);
+    }
+
+    #[test]
+    fn test_cancel_after_partial_fill_refunds_only_remainder() {
+        // Index 1 of 5 parts tops the fill up to 2/5 of the 1_000 principal
+        let (mut escrow, proof) = partial_fill_escrow(5, 1);
+        let secret = Secret { encoding: SecretEncoding::Hex, value: hex::encode(SECRET) };
+
+        set_time_override(Some(DEPLOYED_AT + 60_000));
+        testing_env!(VMContextBuilder::new().predecessor_account_id("resolver.testnet".parse().unwrap()).build());
+        escrow.withdraw(secret, Some(proof), None);
+        assert_eq!(escrow.state.filled_amount, U128(400));
+        assert!(!escrow.state.is_withdrawn, "a partial fill leaves the remainder in the escrow");
+
+        set_time_override(Some(DEPLOYED_AT + 180_000));
+        testing_env!(VMContextBuilder::new().predecessor_account_id("maker.testnet".parse().unwrap()).build());
+        escrow.cancel();
+        assert!(escrow.state.is_cancelled);
+        let logs = near_sdk::test_utils::get_logs();
+        assert!(logs[0].ends_with("refunded=600, safety_deposit_recipient=maker.testnet"), "{:?}", logs);
+        set_time_override(None);
+    }
+
+    #[test]
+    fn test_first_index_releases_only_its_part() {
+        let (mut escrow, proof) = partial_fill_escrow(4, 0);
+        let secret = Secret { encoding: SecretEncoding::Hex, value: hex::encode(SECRET) };
+
+        set_time_override(Some(DEPLOYED_AT + 60_000));
+        testing_env!(VMContextBuilder::new().predecessor_account_id("resolver.testnet".parse().unwrap()).build());
+        escrow.withdraw(secret, Some(proof), None);
+        set_time_override(None);
+
+        assert_eq!(escrow.state.filled_amount, U128(250));
+        assert!(!escrow.state.is_withdrawn);
+    }
+
+    #[test]
+    fn test_cumulative_fill_target_caps_last_index() {
+        assert_eq!(cumulative_fill_target(1_000, 0, 3), 333);
+        assert_eq!(cumulative_fill_target(1_000, 2, 3), 1_000);
+        assert_eq!(cumulative_fill_target(1_000, 3, 3), 1_000);
+        assert_eq!(cumulative_fill_target(u128::MAX, 1_024, 1_024), u128::MAX);
+    }
+
+    #[test]
+    #[should_panic(expected = "E_INVALID_MERKLE_HASHLOCK")]
+    fn test_merkle_hashlock_without_parts_rejected() {
+        init_with_hashlock(format!("merkle:{}", "ab".repeat(32)));
+    }
+
+    #[test]
+    fn test_keccak256_order_verifies_keccak_preimage() {
+        let factory: AccountId = "factory.testnet".parse().unwrap();
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(factory.clone())
+            .attached_deposit(NearToken::from_yoctonear(1_100))
+            .build());
+        let mut escrow = EscrowSrc::init(CreateEscrowArgs {
+            immutables: Immutables {
+                hashlock: hex::encode(env::keccak256(&SECRET)),
+                hash_algorithm: HashAlgo::Keccak256,
+                ..immutables()
+            },
+            factory,
+            correlation_id: None,
+        });
+        assert_ne!(escrow.immutables.hashlock, immutables().hashlock);
+
+        testing_env!(VMContextBuilder::new().predecessor_account_id("resolver.testnet".parse().unwrap()).build());
+        set_time_override(Some(DEPLOYED_AT + 60_000));
+        escrow.withdraw(Secret { encoding: SecretEncoding::Hex, value: hex::encode(SECRET) }, None, None);
+        set_time_override(None);
+        assert_eq!(escrow.state.revealed_secret, Some(hex::encode(SECRET)));
+    }
+
+    #[test]
+    fn test_used_index_bitmap_marks_scattered_indices() {
+        let mut used = UsedIndexBitmap::default();
+        let scattered = [0, 1, 127, 128, 255, 513, MAX_MERKLE_PARTS];
+        for index in scattered {
+            assert!(used.mark(index));
+        }
+        assert!(!used.mark(128), "index marked twice");
+
+        for index in scattered {
+            assert!(used.is_used(index));
+        }
+        for index in [2, 126, 129, 256, 512, MAX_MERKLE_PARTS - 1] {
+            assert!(!used.is_used(index));
+        }
+        assert_eq!(used.count(), scattered.len() as u32);
+
+        used.unmark(255);
+        assert!(!used.is_used(255));
+        assert_eq!(used.count(), scattered.len() as u32 - 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Secret index exceeds maximum of 1024 parts")]
+    fn test_used_index_bitmap_capped_at_max_parts() {
+        UsedIndexBitmap::default().mark(MAX_MERKLE_PARTS + 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "E_INVALID_MERKLE_HASHLOCK: parts must be in 1..=1024")]
+    fn test_parts_above_max_rejected_at_init() {
+        init_with_hashlock(format!("merkle:{}:{}", MAX_MERKLE_PARTS + 1, "ab".repeat(32)));
+    }
+
+    #[test]
+    fn test_withdrawal_gas_flat_at_max_parts() {
+        // Every leaf commits to SECRET, so one proof of nodes hashed with themselves serves any index
+        let mut node = Sha256::digest(SECRET).to_vec();
+        let mut proof = Vec::new();
+        for _ in 0..merkle_proof_depth(MAX_MERKLE_PARTS) {
+            proof.push(hex::encode(&node));
+            node = Sha256::digest([node.as_slice(), &node].concat()).to_vec();
+        }
+        let mut escrow = init_with_hashlock(format!("merkle:{}:{}", MAX_MERKLE_PARTS, hex::encode(node)));
+        escrow.immutables.amount = U128(1_024_000);
+        let secret = Secret { encoding: SecretEncoding::Hex, value: hex::encode(SECRET) };
+        let as_taker = || {
+            testing_env!(VMContextBuilder::new().predecessor_account_id("resolver.testnet".parse().unwrap()).build());
+        };
+        set_time_override(Some(DEPLOYED_AT + 60_000));
+
+        as_taker();
+        escrow.withdraw(secret.clone(), Some(MerkleProof { proof: proof.clone(), index: 0 }), None);
+        let first = env::used_gas().as_gas();
+
+        // The highest index the cap allows completes the fill
+        as_taker();
+        escrow.withdraw(secret, Some(MerkleProof { proof, index: MAX_MERKLE_PARTS }), None);
+        let last = env::used_gas().as_gas();
+        set_time_override(None);
+
+        assert!(escrow.state.is_withdrawn);
+        assert_eq!(escrow.get_filled_parts(), 2);
+        assert!(last <= first * 5 / 4, "last withdrawal burnt {} against {} for the first", last, first);
+    }
+
+    fn v0_immutables() -> ImmutablesV0 {
+        let current = immutables();
+        let timelocks = current.timelocks;
+        ImmutablesV0 {
+            order_hash: current.order_hash,
+            hashlock: current.hashlock,
+            maker: current.maker,
+            taker: current.taker,
+            token: current.token,
+            amount: current.amount.0,
+            safety_deposit: current.safety_deposit.0,
+            timelocks: TimelocksV0 {
+                // Stamped in seconds by the original factory
+                deployed_at: DEPLOYED_AT / 1000,
+                src_withdrawal: timelocks.src_withdrawal,
+                src_public_withdrawal: timelocks.src_public_withdrawal,
+                src_cancellation: timelocks.src_cancellation,
+                src_public_cancellation: timelocks.src_public_cancellation,
+                dst_withdrawal: timelocks.dst_withdrawal,
+                dst_public_withdrawal: timelocks.dst_public_withdrawal,
+                dst_cancellation: timelocks.dst_cancellation,
+            },
+        }
+    }
+
+    #[test]
+    fn test_migrate_converts_original_layout() {
+        testing_env!(VMContextBuilder::new().build());
+        let mut used_secret_indices = Vector::new("used_secrets".as_bytes());
+        used_secret_indices.push(&3);
+        used_secret_indices.push(&513);
+        env::state_write(&EscrowSrcV0 {
+            immutables: v0_immutables(),
+            factory: "factory.testnet".parse().unwrap(),
+            state: EscrowStateV0 {
+                is_withdrawn: false,
+                is_cancelled: false,
+                revealed_secret: None,
+                withdrawn_at: None,
+                cancelled_at: None,
+            },
+            merkle_root: None,
+            used_secret_indices,
+        });
+
+        let escrow = EscrowSrc::migrate();
+        assert!(escrow.used_secret_indices.is_used(3) && escrow.used_secret_indices.is_used(513));
+        assert_eq!(escrow.get_filled_parts(), 2);
+        assert_eq!(escrow.immutables.timelocks.deployed_at, DEPLOYED_AT);
+        assert_eq!(escrow.immutables.amount, U128(1_000));
+        assert!(escrow.state.is_safety_deposit_funded);
+        assert_eq!(escrow.state.filled_amount, U128(0));
+        let first_entry_key = [b"used_secrets".as_slice(), &0u64.to_le_bytes()].concat();
+        assert!(env::storage_read(&first_entry_key).is_none(), "old index vector left behind");
+    }
+
+    #[test]
+    #[should_panic(expected = "E_ALREADY_MIGRATED")]
+    fn test_migrate_refuses_current_layout() {
+        escrow();
+        EscrowSrc::migrate();
+    }
+
+    #[test]
+    fn test_migrated_legacy_merkle_hashlock_withdraws_whole_remainder() {
+        testing_env!(VMContextBuilder::new().build());
+        // Single-leaf tree: the root is SECRET's hash and index 0 needs no siblings
+        let root = hex::encode(Sha256::digest(SECRET));
+        env::state_write(&EscrowSrcV0 {
+            immutables: ImmutablesV0 { hashlock: format!("merkle:{}", root), ..v0_immutables() },
+            factory: "factory.testnet".parse().unwrap(),
+            state: EscrowStateV0 {
+                is_withdrawn: false,
+                is_cancelled: false,
+                revealed_secret: None,
+                withdrawn_at: None,
+                cancelled_at: None,
+            },
+            merkle_root: Some(root),
+            used_secret_indices: Vector::new("used_secrets".as_bytes()),
+        });
+        let mut escrow = EscrowSrc::migrate();
+
+        call_at("resolver.testnet", DEPLOYED_AT + 60_000);
+        let secret = Secret { encoding: SecretEncoding::Hex, value: hex::encode(SECRET) };
+        escrow.withdraw(secret, Some(MerkleProof { proof: vec![], index: 0 }), None);
+        set_time_override(None);
+
+        // No part count to split by, the fill releases everything as before the migration
+        assert_eq!(escrow.state.filled_amount, U128(1_000));
+        assert!(escrow.state.is_withdrawn);
+    }
+
+    #[test]
+    #[should_panic(expected = "E_IMMUTABLES_MISMATCH")]
+    fn test_withdraw_rejects_mismatching_expected_immutables() {
+        let mut escrow = escrow();
+        testing_env!(VMContextBuilder::new().predecessor_account_id("resolver.testnet".parse().unwrap()).build());
+        set_time_override(Some(DEPLOYED_AT + 60_000));
+        let mut expected = escrow.immutables.clone();
+        expected.amount = U128(expected.amount.0 + 1);
+
+        escrow.withdraw(Secret { encoding: SecretEncoding::Hex, value: hex::encode(SECRET) }, None, Some(expected));
+    }
+
+    #[test]
+    fn test_excess_near_rescued_before_delay() {
+        let mut escrow = escrow();
+        let storage_stake = env::storage_usage() as u128 * env::storage_byte_cost().as_yoctonear();
+        let with_balance = |balance: u128| {
+            testing_env!(VMContextBuilder::new()
+                .predecessor_account_id("maker.testnet".parse().unwrap())
+                .account_balance(NearToken::from_yoctonear(balance))
+                .storage_usage(env::storage_usage())
+                .build());
+        };
+        set_time_override(Some(DEPLOYED_AT + 60_000));
+
+        // Principal, safety deposit and stake only: nothing to rescue
+        with_balance(1_100 + storage_stake);
+        assert_eq!(escrow.get_excess_balance(), U128(0));
+
+        with_balance(1_100 + storage_stake + 500);
+        assert_eq!(escrow.get_excess_balance(), U128(500));
+        escrow.rescue_funds("near".parse().unwrap(), U128(500));
+        assert!(near_sdk::test_utils::get_logs().iter().any(|log| log == "FundsRescued: token=near, amount=500, correlation_id=none"));
+        set_time_override(None);
+    }
+
+    #[test]
+    fn test_bounced_partial_fill_is_not_excess() {
+        // Index 1 of 5 parts sends 400 of the 1_000 principal
+        let (mut escrow, proof) = partial_fill_escrow(5, 1);
+        set_time_override(Some(DEPLOYED_AT + 60_000));
+        testing_env!(VMContextBuilder::new().predecessor_account_id("resolver.testnet".parse().unwrap()).build());
+        escrow.withdraw(Secret { encoding: SecretEncoding::Hex, value: hex::encode(SECRET) }, Some(proof), None);
+        set_time_override(None);
+
+        // The transfer bounced before its callback ran: all 1_100 are back in the account
+        let storage_stake = env::storage_usage() as u128 * env::storage_byte_cost().as_yoctonear();
+        testing_env!(VMContextBuilder::new()
+            .account_balance(NearToken::from_yoctonear(1_100 + storage_stake))
+            .storage_usage(env::storage_usage())
+            .build());
+        assert_eq!(escrow.get_excess_balance(), U128(0));
+    }
+
+    #[test]
+    fn test_no_excess_once_withdrawn() {
+        let mut escrow = escrow();
+        set_time_override(Some(DEPLOYED_AT + 60_000));
+        testing_env!(VMContextBuilder::new().predecessor_account_id("resolver.testnet".parse().unwrap()).build());
+        escrow.withdraw(Secret { encoding: SecretEncoding::Hex, value: hex::encode(SECRET) }, None, None);
+        set_time_override(None);
+
+        let storage_stake = env::storage_usage() as u128 * env::storage_byte_cost().as_yoctonear();
+        testing_env!(VMContextBuilder::new()
+            .account_balance(NearToken::from_yoctonear(1_100 + storage_stake + 500))
+            .storage_usage(env::storage_usage())
+            .build());
+        assert_eq!(escrow.get_excess_balance(), U128(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "Rescue delay not expired")]
+    fn test_rescue_beyond_excess_waits_for_delay() {
+        let mut escrow = escrow();
+        let storage_stake = env::storage_usage() as u128 * env::storage_byte_cost().as_yoctonear();
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id("maker.testnet".parse().unwrap())
+            .account_balance(NearToken::from_yoctonear(1_100 + storage_stake + 500))
+            .storage_usage(env::storage_usage())
+            .build());
+        set_time_override(Some(DEPLOYED_AT + 60_000));
+
+        escrow.rescue_funds("near".parse().unwrap(), U128(501));
+    }
+}
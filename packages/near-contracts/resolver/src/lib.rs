@@ -1,7 +1,8 @@
 use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
 use near_sdk::serde::{Deserialize, Serialize};
 use near_sdk::json_types::{U128, U64};
-use near_sdk::{env, near_bindgen, AccountId, Gas, NearToken, PanicOnDefault, Promise};
+use near_sdk::store::{LookupMap, LookupSet, Vector};
+use near_sdk::{env, near_bindgen, AccountId, Gas, NearToken, PanicOnDefault, Promise, PromiseOrValue};
 use near_sdk::log;
 
 #[cfg(not(target_arch = "wasm32"))]
@@ -15,6 +16,108 @@ pub struct Resolver {
     pub owner: AccountId,
     pub escrow_factory: AccountId,
     pub dst_chain_resolver: String, // ETH address for the resolver on destination chain
+    pub eth_identity: String, // Resolver's own ETH address (derived funding key), taker on the ETH leg
+    pub filled_amounts: LookupMap<String, u128>, // orderHash -> cumulative making amount deployed
+    pub orders: LookupMap<String, OrderRecord>, // order_key(orderHash, fill index) -> active escrow record of one fill
+    pub fill_counts: LookupMap<String, u32>, // orderHash -> fills deploy_src started, the next fill's index
+    pub history: Vector<SwapHistoryEntry>, // Ring buffer of the last SWAP_HISTORY_CAP settled swaps
+    pub history_next: u32, // Slot the next entry overwrites once the buffer is full
+    pub allowed_tokens: LookupSet<AccountId>, // NEP-141 maker assets accepted when the allowlist is enforced
+    pub enforce_token_allowlist: bool, // Off leaves token vetting to the factory's own allowlist
+    pub evm_escrow_factory: Option<EvmEscrowFactory>, // Lets dst_deployment_request carry the ETH dst escrow address
+    pub price_bounds: Option<PriceBounds>, // Sanity range for an order's implied price, unchecked when None
+    pub supported_chain_ids: Vec<u64>, // Chains orders may name as src or dst
+}
+
+/// Resolver state layout of the deployed contract, only read back by `migrate`
+#[derive(BorshDeserialize, BorshSerialize)]
+struct ResolverV1 {
+    owner: AccountId,
+    escrow_factory: AccountId,
+    dst_chain_resolver: String,
+}
+
+/// EVM EscrowFactory the ETH dst escrows are cloned from, enough to predict their CREATE2 address
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(JsonSchema))]
+#[serde(crate = "near_sdk::serde")]
+pub struct EvmEscrowFactory {
+    pub address: String,                 // 0x-prefixed, lowercase
+    pub dst_proxy_bytecode_hash: String, // keccak256 of the factory's dst proxy bytecode, 0x-prefixed
+}
+
+/// Implied price range an order must fall in, both ends inclusive and scaled by PRICE_SCALE
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(JsonSchema))]
+#[serde(crate = "near_sdk::serde")]
+pub struct PriceBounds {
+    pub min: U128,
+    pub max: U128,
+}
+
+/// Fixed-point scale of a price bound: taking units per PRICE_SCALE making units. 1e20 keeps a
+/// 24 -> 6 decimal pair (1 NEAR for 3 USDC is 300) and its reverse (~3.3e37) within a U128
+pub const PRICE_SCALE: u128 = 100_000_000_000_000_000_000;
+
+/// Settled swaps kept in the audit history, older entries are overwritten
+pub const SWAP_HISTORY_CAP: u32 = 100;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(JsonSchema))]
+#[serde(crate = "near_sdk::serde")]
+pub enum SwapOutcome {
+    Withdrawn,
+    Cancelled,
+    DeploymentFailed,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(JsonSchema))]
+#[serde(crate = "near_sdk::serde")]
+pub struct SwapHistoryEntry {
+    pub order_hash: String,
+    pub outcome: SwapOutcome,
+    pub timestamp: U64,
+}
+
+/// Active swap record, kept until the escrow settles and the owner archives it
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(JsonSchema))]
+#[serde(crate = "near_sdk::serde")]
+pub struct OrderRecord {
+    pub maker: AccountId,
+    pub escrow: AccountId,
+    pub amount: U128,
+    pub created_at: U64,
+    /// Latest withdraw or cancel the resolver forwarded to the escrow, None until it acts
+    #[serde(default)]
+    pub last_action: Option<EscrowActionRecord>,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(JsonSchema))]
+#[serde(crate = "near_sdk::serde")]
+pub enum EscrowAction {
+    Withdraw,
+    Cancel,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(JsonSchema))]
+#[serde(crate = "near_sdk::serde")]
+pub enum EscrowActionStatus {
+    Pending,   // Forwarded, escrow result not seen yet
+    Confirmed, // Escrow call succeeded
+    Failed,    // Escrow call failed, the action may be retried
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(JsonSchema))]
+#[serde(crate = "near_sdk::serde")]
+pub struct EscrowActionRecord {
+    pub action: EscrowAction,
+    pub status: EscrowActionStatus,
+    pub updated_at: U64,
 }
 
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug)]
@@ -23,10 +126,10 @@ pub struct Resolver {
 pub struct Order {
     pub maker: AccountId,
     pub taker: AccountId,
-    pub making_amount: u128,
-    pub taking_amount: u128,
+    pub making_amount: U128,
+    pub taking_amount: U128,
     pub maker_asset: AccountId, // "near" for native NEAR
-    pub taker_asset: String,    // ETH address of token on destination
+    pub taker_asset: String,    // Token on destination, ETH address or NEAR account id (see CrossChainAsset)
     pub salt: String,
     pub extension: OrderExtension,
 }
@@ -38,9 +141,16 @@ pub struct OrderExtension {
     pub hashlock: String,
     pub src_chain_id: u64,
     pub dst_chain_id: u64,
-    pub src_safety_deposit: u128,
-    pub dst_safety_deposit: u128,
+    pub src_safety_deposit: U128,
+    pub dst_safety_deposit: U128,
     pub timelocks: Timelocks,
+    /// Must be Keccak256 when dst_chain_id is an EVM chain, see assert_dst_hash_algorithm
+    #[serde(default)]
+    pub hash_algorithm: HashAlgo,
+    /// Receiver of the dst funds, required when the maker has no address on the destination chain
+    /// (a NEAR maker swapping to an EVM chain), None pays the maker
+    #[serde(default)]
+    pub dst_receiver: Option<String>,
 }
 
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug)]
@@ -55,6 +165,9 @@ pub struct Timelocks {
     pub dst_withdrawal: u32,
     pub dst_public_withdrawal: u32,
     pub dst_cancellation: u32,
+    /// Leniency around phase boundaries in ms, 0 disables it (see the escrow phase checks)
+    #[serde(default)]
+    pub grace_ms: u32,
 }
 
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug)]
@@ -66,9 +179,203 @@ pub struct Immutables {
     pub maker: AccountId,
     pub taker: AccountId,
     pub token: AccountId,
-    pub amount: u128,
-    pub safety_deposit: u128,
+    pub amount: U128,
+    pub safety_deposit: U128,
     pub timelocks: Timelocks,
+    /// Accounts allowed to complete the B3 public withdrawal, None keeps it fully public
+    #[serde(default)]
+    pub public_withdrawal_allowlist: Option<Vec<AccountId>>,
+    /// Source and destination chain ids, None for escrows created without an order extension
+    #[serde(default)]
+    pub chain_context: Option<ChainContext>,
+    /// Hash of the secret behind `hashlock` (and Merkle leaves), sha256 unless the order says otherwise
+    #[serde(default)]
+    pub hash_algorithm: HashAlgo,
+}
+
+/// Hash function a hashlock commits with, declared by the order so both legs verify alike
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(JsonSchema))]
+#[serde(crate = "near_sdk::serde", rename_all = "lowercase")]
+pub enum HashAlgo {
+    #[default]
+    Sha256,
+    Keccak256,
+}
+
+/// Chains an escrow bridges, copied from the order extension for monitoring
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(JsonSchema))]
+#[serde(crate = "near_sdk::serde")]
+pub struct ChainContext {
+    pub src_chain_id: u64,
+    pub dst_chain_id: u64,
+}
+
+/// Gas create_src_escrow gets, enough for the largest escrow init the factory forwards
+const CREATE_SRC_ESCROW_GAS: Gas = Gas::from_tgas(250);
+
+/// Gas for the on_src_deployed callback
+const SRC_DEPLOYED_CALLBACK_GAS: Gas = Gas::from_tgas(5);
+
+/// NEAR has no EIP-155 chain id, use its SLIP-44 coin type to identify it in orders
+pub const NEAR_CHAIN_ID: u64 = 397;
+pub const SEPOLIA_CHAIN_ID: u64 = 11155111;
+
+/// Chains a new resolver bridges, the owner extends the list (e.g. with mainnets) via add_supported_chain
+pub const DEFAULT_SUPPORTED_CHAIN_IDS: [u64; 2] = [NEAR_CHAIN_ID, SEPOLIA_CHAIN_ID];
+
+/// Orders naming a chain outside `supported` are rejected
+pub fn assert_supported_chain(chain_id: u64, supported: &[u64]) {
+    assert!(
+        supported.contains(&chain_id),
+        "E_UNSUPPORTED_CHAIN: chain id {} is not one of {:?}",
+        chain_id,
+        supported
+    );
+}
+
+/// An EVM escrow only verifies keccak256 preimages, a NEAR escrow verifies either. Both legs share
+/// the hashlock, so an order bound for an EVM chain must commit with keccak256
+pub fn assert_dst_hash_algorithm(dst_chain_id: u64, hash_algorithm: HashAlgo) {
+    assert!(
+        dst_chain_id == NEAR_CHAIN_ID || hash_algorithm == HashAlgo::Keccak256,
+        "E_HASH_ALGORITHM_MISMATCH: dst chain {} only verifies Keccak256, order uses {:?}",
+        dst_chain_id,
+        hash_algorithm
+    );
+}
+
+/// Full 256-bit product of two u128s as (high, low) halves, so tuples compare as the product
+pub fn widening_mul(a: u128, b: u128) -> (u128, u128) {
+    const LOW_64: u128 = u64::MAX as u128;
+    let (a_hi, a_lo) = (a >> 64, a & LOW_64);
+    let (b_hi, b_lo) = (b >> 64, b & LOW_64);
+    let lo_lo = a_lo * b_lo;
+    let hi_lo = a_hi * b_lo;
+    let lo_hi = a_lo * b_hi;
+    // At most 3 * (2^64 - 1), no overflow
+    let middle = (lo_lo >> 64) + (hi_lo & LOW_64) + (lo_hi & LOW_64);
+    let high = a_hi * b_hi + (hi_lo >> 64) + (lo_hi >> 64) + (middle >> 64);
+    (high, (middle << 64) | (lo_lo & LOW_64))
+}
+
+/// `a * b / d` rounded down over the full 256-bit product, None when `d` is zero or the quotient
+/// does not fit in a u128
+pub fn mul_div(a: u128, b: u128, d: u128) -> Option<u128> {
+    let (high, low) = widening_mul(a, b);
+    if d == 0 || high >= d {
+        return None;
+    }
+    // Long division of the low half, one bit at a time, the remainder stays below `d`
+    let mut remainder = high;
+    let mut quotient = 0u128;
+    for bit in (0..128).rev() {
+        let carry = remainder >> 127;
+        remainder = (remainder << 1) | ((low >> bit) & 1);
+        quotient <<= 1;
+        if carry == 1 || remainder >= d {
+            remainder = remainder.wrapping_sub(d);
+            quotient |= 1;
+        }
+    }
+    Some(quotient)
+}
+
+/// Both amounts must be non-zero, otherwise a fill releases the maker's funds for nothing on the
+/// destination side, and the implied price must fall within `bounds` when configured
+pub fn check_order_price(making_amount: u128, taking_amount: u128, bounds: Option<&PriceBounds>) -> Result<(), String> {
+    if making_amount == 0 || taking_amount == 0 {
+        return Err(format!(
+            "E_ZERO_PRICE: order making amount {} and taking amount {} must be non-zero",
+            making_amount, taking_amount
+        ));
+    }
+    if let Some(bounds) = bounds {
+        // taking / making against bound / PRICE_SCALE, cross-multiplied so nothing truncates
+        let scaled_taking = widening_mul(taking_amount, PRICE_SCALE);
+        if scaled_taking < widening_mul(bounds.min.0, making_amount)
+            || scaled_taking > widening_mul(bounds.max.0, making_amount)
+        {
+            return Err(format!(
+                "E_PRICE_OUT_OF_BOUNDS: {} for {} is outside [{}, {}] per {} making units",
+                taking_amount, making_amount, bounds.min.0, bounds.max.0, PRICE_SCALE
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Token on the destination chain, an EVM address is not a valid NEAR AccountId
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(JsonSchema))]
+#[serde(crate = "near_sdk::serde")]
+pub enum CrossChainAsset {
+    Near(AccountId),
+    Evm(String), // 0x-prefixed, lowercase
+}
+
+impl CrossChainAsset {
+    /// Parse `asset` as an account id on NEAR or a 20-byte hex address on any other chain
+    pub fn parse(asset: &str, chain_id: u64) -> Result<Self, String> {
+        if chain_id == NEAR_CHAIN_ID {
+            return asset
+                .parse()
+                .map(CrossChainAsset::Near)
+                .map_err(|_| format!("Invalid NEAR account id: {}", asset));
+        }
+
+        parse_evm_address(asset).map(CrossChainAsset::Evm)
+    }
+}
+
+/// Asset a NEAR escrow locks: native NEAR, named by the "near" sentinel, or a NEP-141 contract
+#[derive(Clone, Debug, PartialEq)]
+pub enum TokenKind {
+    Native,
+    Nep141(AccountId),
+}
+
+impl TokenKind {
+    /// Classify `asset`, rejecting accounts that cannot be a token contract: top-level names other
+    /// than the sentinel (e.g. "native") and eth-implicit accounts. 64-hex implicit accounts are
+    /// accepted, native USDC on NEAR is one
+    pub fn parse(asset: &AccountId) -> Result<Self, String> {
+        let id = asset.as_str();
+        if id == "near" {
+            return Ok(TokenKind::Native);
+        }
+        let is_near_implicit = id.len() == 64 && id.bytes().all(|b| b.is_ascii_hexdigit());
+        if parse_evm_address(id).is_ok() || (!id.contains('.') && !is_near_implicit) {
+            return Err(format!(
+                "E_INVALID_MAKER_ASSET: {} is not a NEP-141 contract, use \"near\" for native NEAR",
+                id
+            ));
+        }
+        Ok(TokenKind::Nep141(asset.clone()))
+    }
+}
+
+/// Account the dst escrow pays for an order naming `receiver`, an EVM address becomes its
+/// lowercase eth-implicit form
+pub fn dst_receiver_account(receiver: &str, dst_chain_id: u64) -> Result<AccountId, String> {
+    match CrossChainAsset::parse(receiver, dst_chain_id)? {
+        CrossChainAsset::Near(account) => Ok(account),
+        CrossChainAsset::Evm(address) => address
+            .parse()
+            .map_err(|_| format!("Invalid dst receiver: {}", receiver)),
+    }
+}
+
+/// 0x-prefixed 20-byte hex address, returned lowercased
+pub fn parse_evm_address(address: &str) -> Result<String, String> {
+    let hex_part = address
+        .strip_prefix("0x")
+        .ok_or_else(|| format!("EVM address must be 0x-prefixed: {}", address))?;
+    if hex_part.len() != 40 || !hex_part.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("Invalid EVM address: {}", address));
+    }
+    Ok(format!("0x{}", hex_part.to_ascii_lowercase()))
 }
 
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug)]
@@ -76,23 +383,185 @@ pub struct Immutables {
 #[serde(crate = "near_sdk::serde")]
 pub struct DstImmutablesComplement {
     pub maker: AccountId,
-    pub amount: u128,
-    pub token: AccountId,
-    pub safety_deposit: u128,
-    pub chain_id: String,
+    pub taker: CrossChainAsset, // Resolver identity on the destination chain
+    pub amount: U128,
+    pub token: CrossChainAsset,
+    pub safety_deposit: U128,
+    pub chain_id: U64,
+    /// Must match the src immutables' algorithm, both legs share the hashlock
+    #[serde(default)]
+    pub hash_algorithm: HashAlgo,
+}
+
+/// NEP-297 `dst_deployment_request` for a NEAR -> ETH swap whose src escrow landed: the ETH dst immutables
+/// (timelocks packed with the shared deployed_at), their CREATE2 address when the EVM factory is configured
+/// and the src cancellation time in seconds, everything an ETH relayer needs to deploy the dst escrow.
+/// Ok(None) when the destination is not an EVM chain.
+pub fn dst_deployment_request_event(
+    src_escrow: &AccountId,
+    immutables: &Immutables,
+    complement: &DstImmutablesComplement,
+    deployed_at_ms: u64,
+    evm_factory: Option<&EvmEscrowFactory>,
+    correlation_id: Option<&str>,
+) -> Result<Option<String>, String> {
+    let (CrossChainAsset::Evm(taker), CrossChainAsset::Evm(token)) = (&complement.taker, &complement.token) else {
+        return Ok(None);
+    };
+    let maker = parse_evm_address(complement.maker.as_str())
+        .map_err(|_| {
+            format!(
+                "maker {} has no EVM address to receive the dst funds, set extension.dst_receiver",
+                complement.maker
+            )
+        })?;
+    let deployed_at = u32::try_from(deployed_at_ms / 1000).map_err(|_| "deployed_at overflows u32 seconds")?;
+    let timelocks = evm_timelocks(&immutables.timelocks, deployed_at);
+    let order_hash = normalize_order_hash(&immutables.order_hash);
+    let hashlock = format!("0x{}", hex::encode(bytes32("hashlock", &immutables.hashlock)?));
+
+    // ImmutablesLib.hash: keccak256 over the 8 ABI-encoded words, the CREATE2 salt of the escrow clone
+    let salt = env::keccak256(
+        &[
+            bytes32("order hash", &order_hash)?,
+            bytes32("hashlock", &hashlock)?,
+            address_word(&maker)?,
+            address_word(taker)?,
+            address_word(token)?,
+            uint_word(complement.amount.0),
+            uint_word(complement.safety_deposit.0),
+            timelocks,
+        ]
+        .concat(),
+    );
+    let dst_escrow = match evm_factory {
+        Some(factory) => {
+            let factory_word = address_word(&factory.address)?;
+            let bytecode_hash = bytes32("dst proxy bytecode hash", &factory.dst_proxy_bytecode_hash)?;
+            let preimage: [&[u8]; 4] = [&[0xff], &factory_word[12..], &salt, &bytecode_hash];
+            let create2 = env::keccak256(&preimage.concat());
+            Some(format!("0x{}", hex::encode(&create2[12..])))
+        }
+        None => None,
+    };
+
+    Ok(Some(format!(
+        "EVENT_JSON:{}",
+        near_sdk::serde_json::json!({
+            "standard": "1prime-resolver",
+            "version": "1.0.0",
+            "event": "dst_deployment_request",
+            "data": [{
+                "order_hash": order_hash,
+                "src_escrow": src_escrow,
+                "dst_chain_id": complement.chain_id,
+                "dst_immutables": {
+                    "order_hash": order_hash,
+                    "hashlock": hashlock,
+                    "maker": maker,
+                    "taker": taker,
+                    "token": token,
+                    "amount": complement.amount,
+                    "safety_deposit": complement.safety_deposit,
+                    "timelocks": format!("0x{}", hex::encode(timelocks)),
+                    "deployed_at": deployed_at,
+                },
+                "dst_escrow": dst_escrow,
+                "src_cancellation_timestamp": U64(deployed_at as u64 + immutables.timelocks.src_cancellation as u64),
+                "correlation_id": correlation_id,
+            }],
+        })
+    )))
+}
+
+/// EVM `Timelocks` word: stage offsets in the 32-bit slots 0..=6, deployed_at in seconds in the top slot
+fn evm_timelocks(timelocks: &Timelocks, deployed_at: u32) -> [u8; 32] {
+    let slots = [
+        timelocks.src_withdrawal,
+        timelocks.src_public_withdrawal,
+        timelocks.src_cancellation,
+        timelocks.src_public_cancellation,
+        timelocks.dst_withdrawal,
+        timelocks.dst_public_withdrawal,
+        timelocks.dst_cancellation,
+        deployed_at,
+    ];
+    let mut word = [0u8; 32];
+    for (slot, value) in slots.iter().enumerate() {
+        word[28 - 4 * slot..32 - 4 * slot].copy_from_slice(&value.to_be_bytes());
+    }
+    word
+}
+
+fn bytes32(field: &str, value: &str) -> Result<[u8; 32], String> {
+    let bytes = hex::decode(value.trim_start_matches("0x")).map_err(|_| format!("Invalid {} hex: {}", field, value))?;
+    bytes.try_into().map_err(|_| format!("{} must be 32 bytes", field))
+}
+
+fn address_word(address: &str) -> Result<[u8; 32], String> {
+    let mut word = [0u8; 32];
+    word[12..].copy_from_slice(&hex::decode(&parse_evm_address(address)?[2..]).unwrap());
+    Ok(word)
+}
+
+fn uint_word(value: u128) -> [u8; 32] {
+    let mut word = [0u8; 32];
+    word[16..].copy_from_slice(&value.to_be_bytes());
+    word
+}
+
+/// Canonical order hash string, 0x-prefixed lowercase hex, so lookups match however the caller formats it
+pub fn normalize_order_hash(order_hash: &str) -> String {
+    let hex = order_hash
+        .strip_prefix("0x")
+        .or_else(|| order_hash.strip_prefix("0X"))
+        .unwrap_or(order_hash);
+    format!("0x{}", hex.to_lowercase())
+}
+
+/// Key of one fill's record in `orders`, matching the service's swap id
+fn order_key(order_hash: &str, fill_index: u32) -> String {
+    format!("{}:{}", order_hash, fill_index)
 }
 
 #[near_bindgen]
 impl Resolver {
     #[init]
-    pub fn new(owner: AccountId, escrow_factory: AccountId, dst_chain_resolver: String) -> Self {
+    pub fn new(
+        owner: AccountId,
+        escrow_factory: AccountId,
+        dst_chain_resolver: String,
+        eth_identity: String,
+    ) -> Self {
+        let eth_identity = parse_evm_address(&eth_identity).unwrap_or_else(|e| env::panic_str(&e));
         Self {
             owner,
             escrow_factory,
             dst_chain_resolver,
+            eth_identity,
+            filled_amounts: LookupMap::new(b"f"),
+            orders: LookupMap::new(b"o"),
+            fill_counts: LookupMap::new(b"n"),
+            history: Vector::new(b"h"),
+            history_next: 0,
+            allowed_tokens: LookupSet::new(b"t"),
+            enforce_token_allowlist: false,
+            evm_escrow_factory: None,
+            price_bounds: None,
+            supported_chain_ids: DEFAULT_SUPPORTED_CHAIN_IDS.to_vec(),
         }
     }
 
+    /// Upgrade hook for the deployed state, which only holds the owner, factory and dst resolver.
+    /// The deployed layout has no ETH identity, so it is passed in as for `new`, and everything
+    /// added since starts as `new` leaves it
+    #[private]
+    #[init(ignore_state)]
+    pub fn migrate(eth_identity: String) -> Self {
+        let old: ResolverV1 = env::state_read().unwrap_or_else(|| env::panic_str("E_NO_STATE: nothing to migrate"));
+        Self::new(old.owner, old.escrow_factory, old.dst_chain_resolver, eth_identity)
+    }
+
     /// Deploy source escrow on NEAR for NEAR -> ETH swaps
     /// This replaces the EVM flow of Resolver.deploySrc -> LOP.fillOrderArgs -> postInteraction
     #[payable]
@@ -100,7 +569,8 @@ impl Resolver {
         &mut self,
         order: Order,
         order_signature: String, // For future validation
-        amount: u128,
+        amount: U128,
+        correlation_id: Option<String>, // Echoed into the factory and escrow events for this swap
     ) -> Promise {
         // Only owner can deploy
         assert_eq!(
@@ -111,13 +581,43 @@ impl Resolver {
 
         // Validate amount matches order
         assert!(
-            amount <= order.making_amount,
+            amount.0 <= order.making_amount.0,
             "Amount exceeds order making amount"
         );
+        assert!(
+            order.extension.src_safety_deposit.0 > 0 && order.extension.dst_safety_deposit.0 > 0,
+            "E_ZERO_SAFETY_DEPOSIT: order safety deposits must be non-zero"
+        );
+        check_order_price(order.making_amount.0, order.taking_amount.0, self.price_bounds.as_ref())
+            .unwrap_or_else(|e| env::panic_str(&e));
+
+        assert_supported_chain(order.extension.src_chain_id, &self.supported_chain_ids);
+        assert_supported_chain(order.extension.dst_chain_id, &self.supported_chain_ids);
+        assert_dst_hash_algorithm(order.extension.dst_chain_id, order.extension.hash_algorithm);
+        let token_kind = self.maker_asset_kind(&order.maker_asset);
 
         // Compute order hash
         let order_hash = self.compute_order_hash(&order);
 
+        // A signature over the whole order must not be reused to fill more than making_amount
+        let filled = self.filled_amounts.get(&order_hash).copied().unwrap_or(0);
+        let cumulative_fill = filled + amount.0;
+        assert!(
+            cumulative_fill <= order.making_amount.0,
+            "Cumulative fill {} exceeds order making amount {}",
+            cumulative_fill,
+            order.making_amount.0
+        );
+        self.filled_amounts.insert(order_hash.clone(), cumulative_fill);
+        // Partial fills of one order each get their own record
+        // A record from before per-fill keys already holds fill 0
+        let fill_index = self
+            .fill_counts
+            .get(&order_hash)
+            .copied()
+            .unwrap_or_else(|| u32::from(self.orders.contains_key(&order_hash)));
+        self.fill_counts.insert(order_hash.clone(), fill_index + 1);
+
         // Create immutables for source escrow
         let mut timelocks = order.extension.timelocks.clone();
         timelocks.deployed_at = 0; // Will be set by factory during deployment
@@ -131,33 +631,222 @@ impl Resolver {
             amount,
             safety_deposit: order.extension.src_safety_deposit,
             timelocks,
+            public_withdrawal_allowlist: None,
+            chain_context: Some(ChainContext {
+                src_chain_id: order.extension.src_chain_id,
+                dst_chain_id: order.extension.dst_chain_id,
+            }),
+            hash_algorithm: order.extension.hash_algorithm,
         };
 
-        // Create destination complement info
-        let dst_complement = DstImmutablesComplement {
-            maker: order.maker.clone(), // Can be different if order.receiver is set
-            amount: u128::from((order.taking_amount * amount) / order.making_amount), // Pro-rata
-            token: order.taker_asset.parse().unwrap(),
-            safety_deposit: order.extension.dst_safety_deposit,
-            chain_id: u64::from(order.extension.dst_chain_id).to_string(),
-        };
+        let dst_complement = self.dst_complement(&order, amount);
+        assert!(
+            dst_complement.amount.0 > 0,
+            "E_ZERO_PRICE: a fill of {} rounds to a zero destination amount",
+            amount.0
+        );
 
-        // Calculate required deposit
-        let required_deposit = if order.maker_asset.as_str() == "near" {
-            NearToken::from_yoctonear(u128::from(amount) + u128::from(order.extension.src_safety_deposit))
-        } else {
-            NearToken::from_yoctonear(u128::from(order.extension.src_safety_deposit))
+        // Escrow funds, the storage stake for the new escrow account and the factory's maker index
+        // entry is added once the factory reports it
+        let escrow_funds = match token_kind {
+            TokenKind::Native => u128::from(amount) + u128::from(order.extension.src_safety_deposit),
+            TokenKind::Nep141(_) => u128::from(order.extension.src_safety_deposit),
         };
 
         log!("Gas left: {:?}", Gas::from_gas(env::prepaid_gas().as_gas() - env::used_gas().as_gas()));
 
+        Promise::new(self.escrow_factory.clone())
+            .function_call(
+                "src_escrow_storage_stake".to_string(),
+                b"{}".to_vec(),
+                NearToken::from_yoctonear(0),
+                Gas::from_tgas(5),
+            )
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(
+                        CREATE_SRC_ESCROW_GAS
+                            .saturating_add(SRC_DEPLOYED_CALLBACK_GAS)
+                            .saturating_add(Gas::from_tgas(5)),
+                    )
+                    .on_src_storage_stake(
+                        order_hash,
+                        fill_index,
+                        order.maker,
+                        amount,
+                        immutables,
+                        dst_complement,
+                        correlation_id,
+                        U128(escrow_funds),
+                    ),
+            )
+    }
+
+    /// Create the src escrow with the escrow funds plus the storage stake the factory asks for
+    #[private]
+    pub fn on_src_storage_stake(
+        &mut self,
+        order_hash: String,
+        fill_index: u32,
+        maker: AccountId,
+        amount: U128,
+        immutables: Immutables,
+        dst_complement: DstImmutablesComplement,
+        correlation_id: Option<String>,
+        escrow_funds: U128,
+        #[callback_result] call_result: Result<U128, near_sdk::PromiseError>,
+    ) -> PromiseOrValue<()> {
+        let Ok(storage_stake) = call_result else {
+            self.release_fill(order_hash, amount, correlation_id.as_deref());
+            return PromiseOrValue::Value(());
+        };
+
         // Call factory to create source escrow
-        Promise::new(self.escrow_factory.clone()).function_call(
-            "create_src_escrow".to_string(),
-            serde_json::to_vec(&(order_hash, immutables, dst_complement)).unwrap(),
-            required_deposit,
-            Gas::from_tgas(250),
-        )
+        Promise::new(self.escrow_factory.clone())
+            .function_call(
+                "create_src_escrow".to_string(),
+                serde_json::to_vec(&(&order_hash, &immutables, &dst_complement, &correlation_id)).unwrap(),
+                NearToken::from_yoctonear(escrow_funds.0 + storage_stake.0),
+                CREATE_SRC_ESCROW_GAS,
+            )
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(SRC_DEPLOYED_CALLBACK_GAS)
+                    .on_src_deployed(order_hash, fill_index, maker, amount, immutables, dst_complement, correlation_id),
+            )
+            .into()
+    }
+
+    /// Record the created escrow, or release the reserved fill amount if the factory call failed
+    #[private]
+    pub fn on_src_deployed(
+        &mut self,
+        order_hash: String,
+        fill_index: u32,
+        maker: AccountId,
+        amount: U128,
+        immutables: Immutables,
+        dst_complement: DstImmutablesComplement,
+        correlation_id: Option<String>,
+        #[callback_result] call_result: Result<near_sdk::serde_json::Value, near_sdk::PromiseError>,
+    ) {
+        // The factory reports some failures as an EscrowCreationResult with success = false
+        let escrow = match &call_result {
+            Ok(result) if result["success"] == true => result["escrow_account"]
+                .as_str()
+                .and_then(|escrow| escrow.parse::<AccountId>().ok()),
+            _ => None,
+        };
+
+        match escrow {
+            Some(escrow) => {
+                // The ETH leg is keyed by the same deployed_at the factory stamped on the src escrow
+                let deployed_at = call_result
+                    .as_ref()
+                    .ok()
+                    .and_then(|result| result["deployed_at"].as_str())
+                    .and_then(|deployed_at| deployed_at.parse::<u64>().ok());
+                match deployed_at.ok_or_else(|| "factory reported no deployed_at".to_string()).and_then(|deployed_at| {
+                    dst_deployment_request_event(
+                        &escrow,
+                        &immutables,
+                        &dst_complement,
+                        deployed_at,
+                        self.evm_escrow_factory.as_ref(),
+                        correlation_id.as_deref(),
+                    )
+                }) {
+                    Ok(Some(event)) => env::log_str(&event),
+                    Ok(None) => {}
+                    Err(e) => log!("No dst_deployment_request for {}: {}", order_hash, e),
+                }
+                self.orders.insert(
+                    order_key(&order_hash, fill_index),
+                    OrderRecord {
+                        maker,
+                        escrow,
+                        amount,
+                        created_at: U64(env::block_timestamp_ms()),
+                        last_action: None,
+                    },
+                );
+            }
+            None => self.release_fill(order_hash, amount, correlation_id.as_deref()),
+        }
+    }
+
+    /// Give a failed deployment's reserved amount back to the order
+    fn release_fill(&mut self, order_hash: String, amount: U128, correlation_id: Option<&str>) {
+        let filled = self.filled_amounts.get(&order_hash).copied().unwrap_or(0);
+        self.filled_amounts
+            .insert(order_hash.clone(), filled.saturating_sub(amount.0));
+        log!(
+            "Source escrow deployment failed for {} (correlation_id={}), released {}",
+            order_hash,
+            correlation_id.unwrap_or("none"),
+            amount.0
+        );
+        self.record_history(order_hash, SwapOutcome::DeploymentFailed);
+    }
+
+    /// Remove a settled fill's record and refund the freed storage stake to the owner
+    /// The escrow is queried first, only withdrawn or cancelled fills can be archived
+    pub fn archive_order(&mut self, order_hash: String, fill_index: u32) -> Promise {
+        let order_hash = normalize_order_hash(&order_hash);
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.owner,
+            "Only owner can archive orders"
+        );
+        let record = self.orders.get(&self.order_record_key(&order_hash, fill_index)).expect("Order not found").clone();
+
+        Promise::new(record.escrow)
+            .function_call(
+                "get_state".to_string(),
+                b"{}".to_vec(),
+                NearToken::from_yoctonear(0),
+                Gas::from_tgas(5),
+            )
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(Gas::from_tgas(10))
+                    .on_archive_state_checked(order_hash, fill_index),
+            )
+    }
+
+    #[private]
+    pub fn on_archive_state_checked(
+        &mut self,
+        order_hash: String,
+        fill_index: u32,
+        #[callback_result] call_result: Result<near_sdk::serde_json::Value, near_sdk::PromiseError>,
+    ) {
+        let state = call_result.expect("Failed to read escrow state");
+        assert!(
+            state["is_withdrawn"] == true || state["is_cancelled"] == true,
+            "Order {} is not in a terminal state",
+            order_hash
+        );
+
+        let outcome = if state["is_withdrawn"] == true {
+            SwapOutcome::Withdrawn
+        } else {
+            SwapOutcome::Cancelled
+        };
+
+        let storage_before = env::storage_usage();
+        let key = self.order_record_key(&order_hash, fill_index);
+        self.orders.remove(&key);
+        self.orders.flush();
+        let freed_bytes = storage_before.saturating_sub(env::storage_usage());
+
+        log!("OrderArchived: order_hash={}, fill_index={}, freed_bytes={}", order_hash, fill_index, freed_bytes);
+        self.record_history(order_hash, outcome);
+        if freed_bytes > 0 {
+            Promise::new(self.owner.clone()).transfer(NearToken::from_yoctonear(
+                freed_bytes as u128 * env::storage_byte_cost().as_yoctonear(),
+            ));
+        }
     }
 
     /// Deploy destination escrow on NEAR for ETH -> NEAR swaps
@@ -167,6 +856,7 @@ impl Resolver {
         &mut self,
         dst_immutables: Immutables,
         src_cancellation_timestamp: U64,
+        correlation_id: Option<String>,
     ) -> Promise {
         assert_eq!(
             env::predecessor_account_id(),
@@ -177,39 +867,197 @@ impl Resolver {
         // Forward to factory
         Promise::new(self.escrow_factory.clone()).function_call(
             "create_dst_escrow".to_string(),
-            serde_json::to_vec(&(dst_immutables, src_cancellation_timestamp)).unwrap(),
+            serde_json::to_vec(&near_sdk::serde_json::json!({
+                "dst_immutables": dst_immutables,
+                "src_cancellation_timestamp": src_cancellation_timestamp,
+                "correlation_id": correlation_id,
+            }))
+            .unwrap(),
             env::attached_deposit(),
-            Gas::from_tgas(50),
+            // create_dst_escrow refuses less than the escrow init, its callback and its own overhead
+            Gas::from_tgas(150),
         )
     }
 
-    /// Withdraw from escrow (called by resolver after getting secret)
-    pub fn withdraw(&self, escrow: AccountId, secret: String, immutables: Immutables) -> Promise {
+    /// Withdraw from escrow (called by resolver after getting secret), `fill_index` picks the
+    /// order's fill the escrow belongs to
+    pub fn withdraw(&mut self, escrow: AccountId, secret: String, immutables: Immutables, fill_index: u32) -> Promise {
+        let order_hash = self.record_escrow_action(&escrow, &immutables, fill_index, EscrowAction::Withdraw);
         // Forward to escrow contract
-        Promise::new(escrow).function_call(
-            "withdraw".to_string(),
-            serde_json::to_vec(&(secret, immutables)).unwrap(),
-            NearToken::from_yoctonear(0),
-            Gas::from_tgas(30),
-        )
+        Promise::new(escrow.clone())
+            .function_call(
+                "withdraw".to_string(),
+                // The escrow refuses to pay out if its stored immutables differ from the ones the resolver computed
+                serde_json::to_vec(&near_sdk::serde_json::json!({
+                    "secret": { "encoding": "Hex", "value": secret },
+                    "expected_immutables": immutables,
+                }))
+                .unwrap(),
+                NearToken::from_yoctonear(0),
+                Gas::from_tgas(30),
+            )
+            .then(Self::escrow_state(escrow))
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(Gas::from_tgas(5))
+                    .on_escrow_action(order_hash, fill_index, EscrowAction::Withdraw),
+            )
     }
 
-    /// Cancel escrow
-    pub fn cancel(&self, escrow: AccountId, immutables: Immutables) -> Promise {
+    /// Cancel escrow, `fill_index` as for withdraw
+    pub fn cancel(&mut self, escrow: AccountId, immutables: Immutables, fill_index: u32) -> Promise {
+        let order_hash = self.record_escrow_action(&escrow, &immutables, fill_index, EscrowAction::Cancel);
         // Forward to escrow contract
-        Promise::new(escrow).function_call(
-            "cancel".to_string(),
-            serde_json::to_vec(&immutables).unwrap(),
-            NearToken::from_yoctonear(0),
-            Gas::from_tgas(30),
-        )
+        Promise::new(escrow.clone())
+            .function_call(
+                "cancel".to_string(),
+                serde_json::to_vec(&immutables).unwrap(),
+                NearToken::from_yoctonear(0),
+                Gas::from_tgas(30),
+            )
+            .then(Self::escrow_state(escrow))
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(Gas::from_tgas(5))
+                    .on_escrow_action(order_hash, fill_index, EscrowAction::Cancel),
+            )
+    }
+
+    /// Escrow state read once withdraw or cancel settled, including the escrow's own transfer callback
+    fn escrow_state(escrow: AccountId) -> Promise {
+        Promise::new(escrow).function_call("get_state".to_string(), b"{}".to_vec(), NearToken::from_yoctonear(0), Gas::from_tgas(5))
+    }
+
+    /// Settle the pending action recorded by withdraw or cancel with the escrow's state, as a
+    /// withdrawal whose transfer failed is rolled back by the escrow yet still returns successfully
+    #[private]
+    pub fn on_escrow_action(
+        &mut self,
+        order_hash: String,
+        fill_index: u32,
+        action: EscrowAction,
+        #[callback_result] call_result: Result<near_sdk::serde_json::Value, near_sdk::PromiseError>,
+    ) {
+        let settled = match (action, call_result) {
+            (EscrowAction::Withdraw, Ok(state)) => state["is_withdrawn"] == true,
+            (EscrowAction::Cancel, Ok(state)) => state["is_cancelled"] == true,
+            (_, Err(_)) => false,
+        };
+        let status = if settled {
+            EscrowActionStatus::Confirmed
+        } else {
+            EscrowActionStatus::Failed
+        };
+        let key = self.order_record_key(&order_hash, fill_index);
+        if let Some(record) = self.orders.get_mut(&key) {
+            record.last_action = Some(EscrowActionRecord {
+                action,
+                status,
+                updated_at: U64(env::block_timestamp_ms()),
+            });
+        }
+        log!(
+            "EscrowAction: order_hash={}, fill_index={}, action={:?}, status={:?}",
+            order_hash,
+            fill_index,
+            action,
+            status
+        );
+    }
+
+    /// Key of a fill's record, records written before per-fill keys sit under the bare order hash as fill 0
+    fn order_record_key(&self, order_hash: &str, fill_index: u32) -> String {
+        if fill_index == 0 && self.orders.contains_key(order_hash) {
+            order_hash.to_string()
+        } else {
+            order_key(order_hash, fill_index)
+        }
+    }
+
+    /// Mark `action` pending on the order's record, creating one for escrows deploy_src did not
+    /// record (dst escrows). Owner only, as it writes state.
+    fn record_escrow_action(
+        &mut self,
+        escrow: &AccountId,
+        immutables: &Immutables,
+        fill_index: u32,
+        action: EscrowAction,
+    ) -> String {
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.owner,
+            "Only owner can act on escrows"
+        );
+        let order_hash = normalize_order_hash(&immutables.order_hash);
+        let now = U64(env::block_timestamp_ms());
+        let key = self.order_record_key(&order_hash, fill_index);
+        let record = self.orders.entry(key).or_insert_with(|| OrderRecord {
+            maker: immutables.maker.clone(),
+            escrow: escrow.clone(),
+            amount: immutables.amount,
+            created_at: now,
+            last_action: None,
+        });
+        assert_eq!(&record.escrow, escrow, "Escrow does not match the order's record");
+        record.last_action = Some(EscrowActionRecord { action, status: EscrowActionStatus::Pending, updated_at: now });
+        order_hash
+    }
+
+    /// Classify the maker asset, and check a NEP-141 one against the allowlist when it is enforced
+    fn maker_asset_kind(&self, maker_asset: &AccountId) -> TokenKind {
+        let kind = TokenKind::parse(maker_asset).unwrap_or_else(|e| env::panic_str(&e));
+        if let TokenKind::Nep141(token) = &kind {
+            assert!(
+                self.is_token_allowed(token.clone()),
+                "E_TOKEN_NOT_ALLOWED: {} is not on the resolver's token allowlist",
+                token
+            );
+        }
+        kind
+    }
+
+    /// Destination-side immutables for filling `amount` of `order`
+    fn dst_complement(&self, order: &Order, amount: U128) -> DstImmutablesComplement {
+        DstImmutablesComplement {
+            maker: match &order.extension.dst_receiver {
+                Some(receiver) => dst_receiver_account(receiver, order.extension.dst_chain_id)
+                    .unwrap_or_else(|e| env::panic_str(&e)),
+                None => order.maker.clone(),
+            },
+            taker: if order.extension.dst_chain_id == NEAR_CHAIN_ID {
+                CrossChainAsset::Near(env::current_account_id())
+            } else {
+                CrossChainAsset::Evm(self.eth_identity.clone())
+            },
+            // Pro-rata, fits as the fill never exceeds the making amount
+            amount: U128(
+                mul_div(order.taking_amount.0, amount.0, order.making_amount.0)
+                    .unwrap_or_else(|| env::panic_str("E_ZERO_PRICE: order making amount must be non-zero")),
+            ),
+            token: CrossChainAsset::parse(&order.taker_asset, order.extension.dst_chain_id)
+                .unwrap_or_else(|e| env::panic_str(&e)),
+            safety_deposit: order.extension.dst_safety_deposit,
+            chain_id: U64(order.extension.dst_chain_id),
+            hash_algorithm: order.extension.hash_algorithm,
+        }
+    }
+
+    /// Append to the history ring buffer, overwriting the oldest entry once it holds SWAP_HISTORY_CAP
+    fn record_history(&mut self, order_hash: String, outcome: SwapOutcome) {
+        let entry = SwapHistoryEntry { order_hash, outcome, timestamp: U64(env::block_timestamp_ms()) };
+        if self.history.len() < SWAP_HISTORY_CAP {
+            self.history.push(entry);
+        } else {
+            self.history.set(self.history_next, entry);
+            self.history_next = (self.history_next + 1) % SWAP_HISTORY_CAP;
+        }
     }
 
     /// Compute order hash (simplified - should match cross-chain protocol)
     fn compute_order_hash(&self, order: &Order) -> String {
         use near_sdk::env::sha256;
 
-        let data = format!(
+        let mut data = format!(
             "{}:{}:{}:{}:{}:{}:{}",
             order.maker,
             u128::from(order.making_amount),
@@ -219,9 +1067,143 @@ impl Resolver {
             order.salt,
             order.extension.hashlock
         );
+        // Appended only when set, so orders paying the maker keep their hash
+        if let Some(receiver) = &order.extension.dst_receiver {
+            data.push_str(&format!(":{}", receiver));
+        }
 
         let hash = sha256(data.as_bytes());
-        format!("0x{}", hex::encode(hash))
+        normalize_order_hash(&hex::encode(hash))
+    }
+
+    /// Point the resolver at a new EVM-side resolver address (owner only)
+    pub fn set_dst_chain_resolver(&mut self, addr: String) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.owner,
+            "Only owner can update the destination resolver"
+        );
+        let addr = parse_evm_address(&addr).unwrap_or_else(|e| env::panic_str(&e));
+        let previous = std::mem::replace(&mut self.dst_chain_resolver, addr.clone());
+
+        env::log_str(&format!(
+            "EVENT_JSON:{}",
+            near_sdk::serde_json::json!({
+                "standard": "1prime-resolver",
+                "version": "1.0.0",
+                "event": "dst_resolver_updated",
+                "data": [{ "previous": previous, "dst_chain_resolver": addr }],
+            })
+        ));
+    }
+
+    /// Configure the EVM EscrowFactory dst_deployment_request predicts ETH dst escrow addresses for (owner only)
+    pub fn set_evm_escrow_factory(&mut self, address: String, dst_proxy_bytecode_hash: String) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.owner,
+            "Only owner can update the EVM escrow factory"
+        );
+        let address = parse_evm_address(&address).unwrap_or_else(|e| env::panic_str(&e));
+        let hash = bytes32("dst proxy bytecode hash", &dst_proxy_bytecode_hash).unwrap_or_else(|e| env::panic_str(&e));
+        self.evm_escrow_factory = Some(EvmEscrowFactory {
+            address,
+            dst_proxy_bytecode_hash: format!("0x{}", hex::encode(hash)),
+        });
+    }
+
+    /// Accept a NEP-141 token as maker asset while the allowlist is enforced (owner only)
+    pub fn add_token(&mut self, token: AccountId) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.owner,
+            "Only owner can allow tokens"
+        );
+        self.allowed_tokens.insert(token.clone());
+        log!("Token allowed: {}", token);
+    }
+
+    /// Remove a NEP-141 token from the allowlist (owner only)
+    pub fn remove_token(&mut self, token: AccountId) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.owner,
+            "Only owner can remove tokens"
+        );
+        self.allowed_tokens.remove(&token);
+        log!("Token removed: {}", token);
+    }
+
+    /// Only accept allowlisted NEP-141 maker assets (owner only)
+    pub fn set_enforce_token_allowlist(&mut self, enabled: bool) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.owner,
+            "Only owner can change the token allowlist"
+        );
+        self.enforce_token_allowlist = enabled;
+        log!("Token allowlist enforced: {}", enabled);
+    }
+
+    /// Bound the implied price of orders passed to deploy_src, None disables the check (owner only)
+    pub fn set_price_bounds(&mut self, bounds: Option<PriceBounds>) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.owner,
+            "Only owner can change the price bounds"
+        );
+        if let Some(bounds) = &bounds {
+            assert!(bounds.min.0 <= bounds.max.0, "Price bounds minimum exceeds maximum");
+        }
+        log!("Price bounds: {:?}", bounds);
+        self.price_bounds = bounds;
+    }
+
+    pub fn get_price_bounds(&self) -> Option<PriceBounds> {
+        self.price_bounds.clone()
+    }
+
+    /// Accept orders naming `chain_id` (owner only)
+    pub fn add_supported_chain(&mut self, chain_id: u64) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.owner,
+            "Only owner can change the supported chains"
+        );
+        if !self.supported_chain_ids.contains(&chain_id) {
+            self.supported_chain_ids.push(chain_id);
+        }
+        log!("Chain supported: {}", chain_id);
+    }
+
+    /// Stop accepting orders naming `chain_id`, NEAR itself stays (owner only)
+    pub fn remove_supported_chain(&mut self, chain_id: u64) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.owner,
+            "Only owner can change the supported chains"
+        );
+        assert_ne!(chain_id, NEAR_CHAIN_ID, "NEAR is always supported");
+        self.supported_chain_ids.retain(|supported| *supported != chain_id);
+        log!("Chain removed: {}", chain_id);
+    }
+
+    pub fn get_supported_chain_ids(&self) -> Vec<u64> {
+        self.supported_chain_ids.clone()
+    }
+
+    /// Whether `token` may be a maker asset, native NEAR is always accepted
+    pub fn is_token_allowed(&self, token: AccountId) -> bool {
+        token.as_str() == "near" || !self.enforce_token_allowlist || self.allowed_tokens.contains(&token)
+    }
+
+    /// Deployed code version, `<package version>+<git hash>` for upgrade coordination
+    pub fn contract_version(&self) -> String {
+        format!(
+            "{}+{}",
+            env!("CARGO_PKG_VERSION"),
+            option_env!("GIT_COMMIT_HASH").unwrap_or("unknown")
+        )
     }
 
     /// View methods
@@ -229,6 +1211,11 @@ impl Resolver {
         self.owner.clone()
     }
 
+    /// ETH address the resolver acts as on the destination chain
+    pub fn get_eth_identity(&self) -> String {
+        self.eth_identity.clone()
+    }
+
     pub fn get_factory(&self) -> AccountId {
         self.escrow_factory.clone()
     }
@@ -236,4 +1223,719 @@ impl Resolver {
     pub fn get_dst_resolver(&self) -> String {
         self.dst_chain_resolver.clone()
     }
+
+    pub fn get_order(&self, order_hash: String, fill_index: u32) -> Option<OrderRecord> {
+        let order_hash = normalize_order_hash(&order_hash);
+        self.orders.get(&self.order_record_key(&order_hash, fill_index)).cloned()
+    }
+
+    /// Fills deploy_src has started for `order_hash`, their records sit at indices below it
+    pub fn get_fill_count(&self, order_hash: String) -> u32 {
+        self.fill_counts.get(&normalize_order_hash(&order_hash)).copied().unwrap_or(0)
+    }
+
+    pub fn get_filled_amount(&self, order_hash: String) -> U128 {
+        U128(self.filled_amounts.get(&normalize_order_hash(&order_hash)).copied().unwrap_or(0))
+    }
+
+    /// Up to `limit` most recently settled swaps, newest first
+    pub fn get_recent_history(&self, limit: u32) -> Vec<SwapHistoryEntry> {
+        let len = self.history.len();
+        // Once full, the newest entry sits just before the next slot to overwrite
+        let newest = if len < SWAP_HISTORY_CAP { len } else { self.history_next };
+        (1..=limit.min(len))
+            .filter_map(|back| self.history.get((newest + len - back) % len).cloned())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::{get_logs, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    fn resolver() -> Resolver {
+        Resolver::new(
+            "owner.testnet".parse().unwrap(),
+            "factory.testnet".parse().unwrap(),
+            "0x1111111111111111111111111111111111111111".to_string(),
+            "0x2222222222222222222222222222222222222222".to_string(),
+        )
+    }
+
+    fn called_by(account: &str) {
+        testing_env!(VMContextBuilder::new().predecessor_account_id(account.parse().unwrap()).build());
+    }
+
+    #[test]
+    fn test_contract_version_matches_package_version() {
+        let version = resolver().contract_version();
+        let (package, commit) = version.split_once('+').unwrap();
+        assert_eq!(package, env!("CARGO_PKG_VERSION"));
+        assert!(!commit.is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "Only owner can update the destination resolver")]
+    fn test_non_owner_cannot_set_dst_chain_resolver() {
+        let mut resolver = resolver();
+        called_by("mallory.testnet");
+        resolver.set_dst_chain_resolver("0x3333333333333333333333333333333333333333".to_string());
+    }
+
+    #[test]
+    fn test_owner_sets_dst_chain_resolver() {
+        let mut resolver = resolver();
+        called_by("owner.testnet");
+        resolver.set_dst_chain_resolver("0xABCDEFabcdef3333333333333333333333333333".to_string());
+
+        assert_eq!(resolver.get_dst_resolver(), "0xabcdefabcdef3333333333333333333333333333");
+        assert!(get_logs()[0].contains("\"event\":\"dst_resolver_updated\""));
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid EVM address")]
+    fn test_dst_chain_resolver_must_be_evm_address() {
+        let mut resolver = resolver();
+        called_by("owner.testnet");
+        resolver.set_dst_chain_resolver("0x1234".to_string());
+    }
+
+    #[test]
+    fn test_withdrawal_recorded_after_confirmation() {
+        let mut resolver = resolver();
+        called_by("owner.testnet");
+        let escrow: AccountId = "escrow-0.factory.testnet".parse().unwrap();
+        let immutables = Immutables {
+            order_hash: "0xABCD".to_string(),
+            hashlock: "ab".repeat(32),
+            maker: "maker.testnet".parse().unwrap(),
+            taker: "resolver.testnet".parse().unwrap(),
+            token: "near".parse().unwrap(),
+            amount: U128(1_000),
+            safety_deposit: U128(100),
+            timelocks: Timelocks {
+                deployed_at: 0,
+                src_withdrawal: 60,
+                src_public_withdrawal: 120,
+                src_cancellation: 180,
+                src_public_cancellation: 240,
+                dst_withdrawal: 30,
+                dst_public_withdrawal: 90,
+                dst_cancellation: 150,
+                grace_ms: 0,
+            },
+            public_withdrawal_allowlist: None,
+            chain_context: None,
+            hash_algorithm: HashAlgo::Sha256,
+        };
+
+        let _ = resolver.withdraw(escrow.clone(), "07".repeat(32), immutables, 1);
+        let pending = resolver.get_order("0xabcd".to_string(), 1).unwrap();
+        assert_eq!(pending.escrow, escrow);
+        assert_eq!(pending.last_action.unwrap().status, EscrowActionStatus::Pending);
+        assert!(resolver.get_order("0xabcd".to_string(), 0).is_none());
+
+        resolver.on_escrow_action(
+            "0xabcd".to_string(),
+            1,
+            EscrowAction::Withdraw,
+            Ok(near_sdk::serde_json::json!({ "is_withdrawn": true, "is_cancelled": false })),
+        );
+        let action = resolver.get_order("0xabcd".to_string(), 1).unwrap().last_action.unwrap();
+        assert_eq!(action.action, EscrowAction::Withdraw);
+        assert_eq!(action.status, EscrowActionStatus::Confirmed);
+    }
+
+    #[test]
+    fn test_rolled_back_withdrawal_recorded_failed() {
+        let mut resolver = resolver();
+        called_by("owner.testnet");
+        let escrow: AccountId = "escrow.testnet".parse().unwrap();
+        let order = order(NEAR_CHAIN_ID);
+        let immutables = fill_immutables(&order, "0xabcd");
+        let _ = resolver.withdraw(escrow, "07".repeat(32), immutables, 0);
+
+        // The escrow's transfer callback reopened it, the withdraw call itself still succeeded
+        resolver.on_escrow_action(
+            "0xabcd".to_string(),
+            0,
+            EscrowAction::Withdraw,
+            Ok(near_sdk::serde_json::json!({ "is_withdrawn": false, "is_cancelled": false })),
+        );
+        let action = resolver.get_order("0xabcd".to_string(), 0).unwrap().last_action.unwrap();
+        assert_eq!(action.action, EscrowAction::Withdraw);
+        assert_eq!(action.status, EscrowActionStatus::Failed);
+    }
+
+    /// Immutables deploy_src builds for a 400 fill of `order`
+    fn fill_immutables(order: &Order, order_hash: &str) -> Immutables {
+        Immutables {
+            order_hash: order_hash.to_string(),
+            hashlock: order.extension.hashlock.clone(),
+            maker: order.maker.clone(),
+            taker: "resolver.testnet".parse().unwrap(),
+            token: "near".parse().unwrap(),
+            amount: U128(400),
+            safety_deposit: U128(10),
+            timelocks: order.extension.timelocks.clone(),
+            public_withdrawal_allowlist: None,
+            chain_context: None,
+            hash_algorithm: HashAlgo::Keccak256,
+        }
+    }
+
+    /// deploy_src for one fill, then the factory callback reporting `escrow`
+    fn deploy_fill(resolver: &mut Resolver, escrow: &str) {
+        called_by("owner.testnet");
+        let order = order(SEPOLIA_CHAIN_ID);
+        let _ = resolver.deploy_src(order.clone(), "sig".to_string(), U128(400), None);
+        let order_hash = resolver.compute_order_hash(&order);
+        let fill_index = resolver.get_fill_count(order_hash.clone()) - 1;
+        let immutables = fill_immutables(&order, &order_hash);
+        let complement = resolver.dst_complement(&order, U128(400));
+        called_by("resolver.testnet");
+        resolver.on_src_deployed(
+            order_hash,
+            fill_index,
+            order.maker,
+            U128(400),
+            immutables,
+            complement,
+            None,
+            Ok(near_sdk::serde_json::json!({
+                "escrow_account": escrow,
+                "success": true,
+                "deployed_at": "1700000000000",
+            })),
+        );
+    }
+
+    #[test]
+    fn test_sequential_partial_deploys_fill_up_to_making_amount() {
+        let mut resolver = resolver();
+        called_by("owner.testnet");
+        let order = order(SEPOLIA_CHAIN_ID);
+        let order_hash = resolver.compute_order_hash(&order);
+
+        for (amount, filled) in [(400, 400), (400, 800), (200, 1_000)] {
+            let _ = resolver.deploy_src(order.clone(), "sig".to_string(), U128(amount), None);
+            assert_eq!(resolver.get_filled_amount(order_hash.clone()), U128(filled));
+        }
+        assert_eq!(resolver.get_fill_count(order_hash), 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "Cumulative fill 1200 exceeds order making amount 1000")]
+    fn test_deploy_beyond_making_amount_rejected() {
+        let mut resolver = resolver();
+        called_by("owner.testnet");
+        let order = order(SEPOLIA_CHAIN_ID);
+
+        let _ = resolver.deploy_src(order.clone(), "sig".to_string(), U128(400), None);
+        let _ = resolver.deploy_src(order.clone(), "sig".to_string(), U128(400), None);
+        let _ = resolver.deploy_src(order, "sig".to_string(), U128(400), None);
+    }
+
+    #[test]
+    fn test_unreadable_storage_stake_releases_fill() {
+        let mut resolver = resolver();
+        called_by("owner.testnet");
+        let order = order(SEPOLIA_CHAIN_ID);
+        let _ = resolver.deploy_src(order.clone(), "sig".to_string(), U128(400), None);
+        let order_hash = resolver.compute_order_hash(&order);
+        assert_eq!(resolver.get_filled_amount(order_hash.clone()), U128(400));
+
+        called_by("resolver.testnet");
+        let immutables = fill_immutables(&order, &order_hash);
+        let complement = resolver.dst_complement(&order, U128(400));
+        let _ = resolver.on_src_storage_stake(
+            order_hash.clone(),
+            0,
+            order.maker,
+            U128(400),
+            immutables,
+            complement,
+            None,
+            U128(410),
+            Err(near_sdk::PromiseError::Failed),
+        );
+        assert_eq!(resolver.get_filled_amount(order_hash.clone()), U128(0));
+        assert!(resolver.get_order(order_hash, 0).is_none());
+    }
+
+    #[test]
+    fn test_partial_fills_keep_separate_records() {
+        let mut resolver = resolver();
+        deploy_fill(&mut resolver, "escrow-0.factory.testnet");
+        deploy_fill(&mut resolver, "escrow-1.factory.testnet");
+
+        let order_hash = resolver.compute_order_hash(&order(SEPOLIA_CHAIN_ID));
+        assert_eq!(resolver.get_fill_count(order_hash.clone()), 2);
+        assert_eq!(resolver.get_filled_amount(order_hash.clone()), U128(800));
+        let first = resolver.get_order(order_hash.clone(), 0).unwrap();
+        let second = resolver.get_order(order_hash.clone(), 1).unwrap();
+        assert_eq!(first.escrow.as_str(), "escrow-0.factory.testnet");
+        assert_eq!(second.escrow.as_str(), "escrow-1.factory.testnet");
+        assert!(resolver.get_order(order_hash, 2).is_none());
+    }
+
+    #[test]
+    fn test_order_lookup_ignores_hash_prefix_and_case() {
+        let mut resolver = resolver();
+        deploy_fill(&mut resolver, "escrow-0.factory.testnet");
+
+        let order_hash = resolver.compute_order_hash(&order(SEPOLIA_CHAIN_ID));
+        assert_eq!(order_hash, normalize_order_hash(&order_hash));
+        let bare = order_hash.strip_prefix("0x").unwrap();
+        for variant in [bare.to_string(), bare.to_uppercase(), format!("0X{}", bare.to_uppercase())] {
+            let record = resolver.get_order(variant.clone(), 0).expect("order not found");
+            assert_eq!(record.escrow.as_str(), "escrow-0.factory.testnet", "{}", variant);
+            assert_eq!(resolver.get_filled_amount(variant.clone()), U128(400), "{}", variant);
+        }
+    }
+
+    #[test]
+    fn test_archive_removes_only_settled_fill() {
+        let mut resolver = resolver();
+        deploy_fill(&mut resolver, "escrow-0.factory.testnet");
+        deploy_fill(&mut resolver, "escrow-1.factory.testnet");
+
+        let order_hash = resolver.compute_order_hash(&order(SEPOLIA_CHAIN_ID));
+        called_by("resolver.testnet");
+        resolver.on_archive_state_checked(
+            order_hash.clone(),
+            0,
+            Ok(near_sdk::serde_json::json!({ "is_withdrawn": true, "is_cancelled": false })),
+        );
+        assert!(resolver.get_order(order_hash.clone(), 0).is_none());
+        assert!(resolver.get_order(order_hash, 1).is_some());
+    }
+
+    #[test]
+    #[should_panic(expected = "is not in a terminal state")]
+    fn test_archive_rejects_unsettled_fill() {
+        let mut resolver = resolver();
+        deploy_fill(&mut resolver, "escrow-0.factory.testnet");
+
+        let order_hash = resolver.compute_order_hash(&order(SEPOLIA_CHAIN_ID));
+        called_by("resolver.testnet");
+        resolver.on_archive_state_checked(
+            order_hash,
+            0,
+            Ok(near_sdk::serde_json::json!({ "is_withdrawn": false, "is_cancelled": false })),
+        );
+    }
+
+    #[test]
+    fn test_history_evicts_oldest_beyond_cap() {
+        let mut resolver = resolver();
+        for i in 0..=SWAP_HISTORY_CAP {
+            resolver.record_history(format!("0x{:02x}", i), SwapOutcome::Withdrawn);
+        }
+
+        let history = resolver.get_recent_history(SWAP_HISTORY_CAP + 10);
+        assert_eq!(history.len(), SWAP_HISTORY_CAP as usize);
+        assert_eq!(history[0].order_hash, format!("0x{:02x}", SWAP_HISTORY_CAP));
+        assert_eq!(history.last().unwrap().order_hash, "0x01");
+        assert!(history.iter().all(|entry| entry.order_hash != "0x00"));
+
+        let latest = resolver.get_recent_history(2);
+        assert_eq!(latest.len(), 2);
+        assert_eq!(latest[1].order_hash, format!("0x{:02x}", SWAP_HISTORY_CAP - 1));
+    }
+
+    #[test]
+    fn test_maker_asset_classification() {
+        let asset = |id: &str| TokenKind::parse(&id.parse().unwrap());
+
+        assert_eq!(asset("near"), Ok(TokenKind::Native));
+        assert_eq!(
+            asset("usdc.fakes.testnet"),
+            Ok(TokenKind::Nep141("usdc.fakes.testnet".parse().unwrap()))
+        );
+        // Native USDC on mainnet
+        let usdc = "17208628f84f5d6ad33f0da3bbbeb27ffcb398eac501a31bd6ad2011e36133a1";
+        assert_eq!(asset(usdc), Ok(TokenKind::Nep141(usdc.parse().unwrap())));
+        for malformed in ["native", "wnear", "0x1111111111111111111111111111111111111111"] {
+            assert!(asset(malformed).unwrap_err().starts_with("E_INVALID_MAKER_ASSET"), "{}", malformed);
+        }
+    }
+
+    #[test]
+    fn test_token_allowlist_gates_nep141_maker_assets() {
+        let mut resolver = resolver();
+        called_by("owner.testnet");
+        let usdc: AccountId = "usdc.fakes.testnet".parse().unwrap();
+        assert!(resolver.is_token_allowed(usdc.clone()));
+
+        resolver.set_enforce_token_allowlist(true);
+        assert!(!resolver.is_token_allowed(usdc.clone()));
+        assert!(resolver.is_token_allowed("near".parse().unwrap()));
+
+        resolver.add_token(usdc.clone());
+        assert_eq!(resolver.maker_asset_kind(&usdc), TokenKind::Nep141(usdc));
+    }
+
+    fn order(dst_chain_id: u64) -> Order {
+        near_sdk::serde_json::from_value(near_sdk::serde_json::json!({
+            "maker": "maker.testnet",
+            "taker": "resolver.testnet",
+            "making_amount": "1000",
+            "taking_amount": "3000",
+            "maker_asset": "near",
+            "taker_asset": "0x1c7d4b196cb0c7b01d743fbc6116a902379c7238",
+            "salt": "1",
+            "extension": {
+                "hashlock": "ab".repeat(32),
+                "src_chain_id": NEAR_CHAIN_ID,
+                "dst_chain_id": dst_chain_id,
+                "src_safety_deposit": "10",
+                "dst_safety_deposit": "20",
+                "timelocks": {
+                    "deployed_at": 0,
+                    "src_withdrawal": 60,
+                    "src_public_withdrawal": 600,
+                    "src_cancellation": 1800,
+                    "src_public_cancellation": 3600,
+                    "dst_withdrawal": 60,
+                    "dst_public_withdrawal": 600,
+                    "dst_cancellation": 1500
+                },
+                "hash_algorithm": if dst_chain_id == NEAR_CHAIN_ID { "sha256" } else { "keccak256" }
+            }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_complement_carries_numeric_chain_id() {
+        let resolver = resolver();
+        called_by("owner.testnet");
+        let complement = resolver.dst_complement(&order(SEPOLIA_CHAIN_ID), U128(400));
+
+        assert_eq!(complement.chain_id, U64(SEPOLIA_CHAIN_ID));
+        assert_eq!(near_sdk::serde_json::to_value(&complement).unwrap()["chain_id"], "11155111");
+        assert_eq!(complement.amount, U128(1200));
+    }
+
+    #[test]
+    fn test_valid_eth_taker_asset_becomes_evm_token() {
+        let resolver = resolver();
+        called_by("owner.testnet");
+        let mut order = order(SEPOLIA_CHAIN_ID);
+        order.taker_asset = "0x1C7D4B196Cb0C7B01d743Fbc6116a902379C7238".to_string();
+
+        let complement = resolver.dst_complement(&order, U128(400));
+        assert_eq!(complement.token, CrossChainAsset::Evm("0x1c7d4b196cb0c7b01d743fbc6116a902379c7238".to_string()));
+    }
+
+    #[test]
+    fn test_near_taker_asset_becomes_account() {
+        assert_eq!(
+            CrossChainAsset::parse("usdc.testnet", NEAR_CHAIN_ID),
+            Ok(CrossChainAsset::Near("usdc.testnet".parse().unwrap()))
+        );
+        assert!(CrossChainAsset::parse("usdc.testnet", SEPOLIA_CHAIN_ID).unwrap_err().contains("0x-prefixed"));
+        assert!(CrossChainAsset::parse("0x1c7d4b196cb0c7b01d743fbc6116a902379c723g", SEPOLIA_CHAIN_ID).is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid EVM address: 0x1234")]
+    fn test_malformed_eth_taker_asset_rejected() {
+        let mut resolver = resolver();
+        called_by("owner.testnet");
+        let mut order = order(SEPOLIA_CHAIN_ID);
+        order.taker_asset = "0x1234".to_string();
+
+        resolver.deploy_src(order, "sig".to_string(), U128(400), None);
+    }
+
+    #[test]
+    fn test_eth_identity_stored_and_returned() {
+        let resolver = Resolver::new(
+            "owner.testnet".parse().unwrap(),
+            "factory.testnet".parse().unwrap(),
+            "0x1111111111111111111111111111111111111111".to_string(),
+            "0xABCDEFabcdef2222222222222222222222222222".to_string(),
+        );
+        assert_eq!(resolver.get_eth_identity(), "0xabcdefabcdef2222222222222222222222222222");
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid EVM address")]
+    fn test_malformed_eth_identity_rejected() {
+        Resolver::new(
+            "owner.testnet".parse().unwrap(),
+            "factory.testnet".parse().unwrap(),
+            "0x1111111111111111111111111111111111111111".to_string(),
+            "0x2222".to_string(),
+        );
+    }
+
+    #[test]
+    fn test_eth_identity_is_taker_on_eth_leg_only() {
+        let resolver = resolver();
+        called_by("owner.testnet");
+
+        let eth_leg = resolver.dst_complement(&order(SEPOLIA_CHAIN_ID), U128(400));
+        assert_eq!(eth_leg.taker, CrossChainAsset::Evm(resolver.get_eth_identity()));
+        let mut near_order = order(NEAR_CHAIN_ID);
+        near_order.taker_asset = "near".to_string();
+        let near_leg = resolver.dst_complement(&near_order, U128(400));
+        assert_eq!(near_leg.taker, CrossChainAsset::Near(env::current_account_id()));
+    }
+
+    #[test]
+    #[should_panic(expected = "E_UNSUPPORTED_CHAIN")]
+    fn test_unsupported_chain_rejected() {
+        assert_supported_chain(1, &DEFAULT_SUPPORTED_CHAIN_IDS);
+    }
+
+    #[test]
+    fn test_owner_adds_and_removes_supported_chains() {
+        let mut resolver = resolver();
+        called_by("owner.testnet");
+        let mut mainnet_order = order(SEPOLIA_CHAIN_ID);
+        mainnet_order.extension.dst_chain_id = 1;
+
+        resolver.add_supported_chain(1);
+        assert_eq!(resolver.get_supported_chain_ids(), [NEAR_CHAIN_ID, SEPOLIA_CHAIN_ID, 1]);
+        let _ = resolver.deploy_src(mainnet_order, "sig".to_string(), U128(400), None);
+
+        resolver.remove_supported_chain(SEPOLIA_CHAIN_ID);
+        assert_eq!(resolver.get_supported_chain_ids(), [NEAR_CHAIN_ID, 1]);
+    }
+
+    #[test]
+    #[should_panic(expected = "E_UNSUPPORTED_CHAIN")]
+    fn test_removed_chain_rejected() {
+        let mut resolver = resolver();
+        called_by("owner.testnet");
+        resolver.remove_supported_chain(SEPOLIA_CHAIN_ID);
+
+        resolver.deploy_src(order(SEPOLIA_CHAIN_ID), "sig".to_string(), U128(400), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only owner can change the supported chains")]
+    fn test_non_owner_cannot_add_supported_chain() {
+        let mut resolver = resolver();
+        called_by("mallory.testnet");
+        resolver.add_supported_chain(1);
+    }
+
+    #[test]
+    #[should_panic(expected = "E_HASH_ALGORITHM_MISMATCH")]
+    fn test_sha256_order_to_evm_chain_rejected() {
+        let mut resolver = resolver();
+        called_by("owner.testnet");
+        let mut order = order(SEPOLIA_CHAIN_ID);
+        order.extension.hash_algorithm = HashAlgo::Sha256;
+        resolver.deploy_src(order, "sig".to_string(), U128(400), None);
+    }
+
+    #[test]
+    fn test_near_dst_accepts_either_hash_algorithm() {
+        assert_dst_hash_algorithm(NEAR_CHAIN_ID, HashAlgo::Sha256);
+        assert_dst_hash_algorithm(NEAR_CHAIN_ID, HashAlgo::Keccak256);
+        assert_dst_hash_algorithm(SEPOLIA_CHAIN_ID, HashAlgo::Keccak256);
+    }
+
+    #[test]
+    fn test_dst_deployment_request_event_fields() {
+        let mut resolver = resolver();
+        called_by("owner.testnet");
+        resolver.set_evm_escrow_factory("0x2222222222222222222222222222222222222222".to_string(), "33".repeat(32));
+        // The NEAR maker names the EVM address its dst funds go to
+        let mut order = order(SEPOLIA_CHAIN_ID);
+        order.extension.dst_receiver = Some("0x4444444444444444444444444444444444444444".to_string());
+        let complement = resolver.dst_complement(&order, U128(400));
+        let immutables = Immutables {
+            order_hash: "CD".repeat(32),
+            hashlock: order.extension.hashlock.clone(),
+            maker: order.maker.clone(),
+            taker: "resolver.testnet".parse().unwrap(),
+            token: "near".parse().unwrap(),
+            amount: U128(400),
+            safety_deposit: U128(10),
+            timelocks: order.extension.timelocks.clone(),
+            public_withdrawal_allowlist: None,
+            chain_context: None,
+            hash_algorithm: HashAlgo::Sha256,
+        };
+
+        // EscrowCreationResult as the factory returns it, deployed_at stamped in block ms
+        let factory_result = near_sdk::serde_json::json!({
+            "escrow_account": "escrow-0.factory.testnet",
+            "order_hash": immutables.order_hash,
+            "success": true,
+            "deployed_at": "1700000000000",
+        });
+        let resolver_id: AccountId = "resolver.testnet".parse().unwrap();
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(resolver_id.clone())
+            .predecessor_account_id(resolver_id)
+            .build());
+        resolver.on_src_deployed(
+            immutables.order_hash.clone(),
+            0,
+            order.maker.clone(),
+            U128(400),
+            immutables,
+            complement,
+            None,
+            Ok(factory_result),
+        );
+        let event = get_logs()
+            .into_iter()
+            .find(|log| log.contains("dst_deployment_request"))
+            .expect("no dst_deployment_request emitted");
+        let parsed: near_sdk::serde_json::Value =
+            near_sdk::serde_json::from_str(event.strip_prefix("EVENT_JSON:").unwrap()).unwrap();
+        assert_eq!(parsed["event"], "dst_deployment_request");
+        let data = &parsed["data"][0];
+        assert_eq!(data["src_escrow"], "escrow-0.factory.testnet");
+        assert_eq!(data["src_cancellation_timestamp"], "1700001800");
+
+        let dst = &data["dst_immutables"];
+        assert_eq!(dst["order_hash"], format!("0x{}", "cd".repeat(32)));
+        assert_eq!(dst["hashlock"], format!("0x{}", "ab".repeat(32)));
+        assert_eq!(dst["maker"], "0x4444444444444444444444444444444444444444");
+        assert_eq!(dst["taker"], "0x2222222222222222222222222222222222222222");
+        assert_eq!(dst["token"], "0x1c7d4b196cb0c7b01d743fbc6116a902379c7238");
+        assert_eq!(dst["amount"], "1200");
+        assert_eq!(dst["safety_deposit"], "20");
+        assert_eq!(dst["deployed_at"], 1_700_000_000);
+        // deployed_at (0x6553f100) in the top slot, src_withdrawal (60 = 0x3c) in the lowest
+        let timelocks = dst["timelocks"].as_str().unwrap();
+        assert!(timelocks.starts_with("0x6553f100"), "{}", timelocks);
+        assert!(timelocks.ends_with("0000003c"), "{}", timelocks);
+
+        let dst_escrow = data["dst_escrow"].as_str().unwrap();
+        assert!(dst_escrow.starts_with("0x") && dst_escrow.len() == 42, "{}", dst_escrow);
+    }
+
+    #[test]
+    #[should_panic(expected = "E_ZERO_PRICE")]
+    fn test_zero_taking_amount_rejected() {
+        let mut resolver = resolver();
+        called_by("owner.testnet");
+        let mut order = order(SEPOLIA_CHAIN_ID);
+        order.taking_amount = U128(0);
+        resolver.deploy_src(order, "sig".to_string(), U128(400), None);
+    }
+
+    #[test]
+    fn test_priced_order_accepted() {
+        let mut resolver = resolver();
+        called_by("owner.testnet");
+        let order = order(SEPOLIA_CHAIN_ID);
+        let order_hash = resolver.compute_order_hash(&order);
+        resolver.deploy_src(order, "sig".to_string(), U128(400), None);
+        assert_eq!(resolver.get_filled_amount(order_hash), U128(400));
+    }
+
+    #[test]
+    fn test_order_price_checked_against_bounds() {
+        // order() takes 3000 for 1000, a price of 3 * PRICE_SCALE
+        let bounds = |min: u128, max: u128| PriceBounds { min: U128(min * PRICE_SCALE), max: U128(max * PRICE_SCALE) };
+        assert!(check_order_price(1000, 3000, Some(&bounds(2, 4))).is_ok());
+        assert!(check_order_price(1000, 3000, Some(&bounds(3, 3))).is_ok());
+        let err = check_order_price(1000, 3000, Some(&bounds(4, 10))).unwrap_err();
+        assert!(err.starts_with("E_PRICE_OUT_OF_BOUNDS"), "{}", err);
+        assert!(check_order_price(0, 3000, None).unwrap_err().starts_with("E_ZERO_PRICE"));
+        // Amounts whose scaled product overflows a u128 still compare exactly
+        assert!(check_order_price(10u128.pow(27), 10u128.pow(30), Some(&bounds(1000, 1000))).is_ok());
+        assert!(check_order_price(10u128.pow(27), 10u128.pow(30) + 1, Some(&bounds(1000, 1000))).is_err());
+    }
+
+    #[test]
+    fn test_price_bounds_across_token_decimals() {
+        // 1 NEAR (24 decimals) for 3 USDC (6 decimals) is 300 per PRICE_SCALE yocto
+        let near_to_usdc = PriceBounds { min: U128(250), max: U128(350) };
+        assert!(check_order_price(10u128.pow(24), 3_000_000, Some(&near_to_usdc)).is_ok());
+        assert!(check_order_price(10u128.pow(24), 2_000_000, Some(&near_to_usdc)).is_err());
+        // And the reverse, 3 USDC for 1 NEAR
+        let usdc_to_near = PriceBounds { min: U128(3 * 10u128.pow(37)), max: U128(4 * 10u128.pow(37)) };
+        assert!(check_order_price(3_000_000, 10u128.pow(24), Some(&usdc_to_near)).is_ok());
+        assert!(check_order_price(3_000_000, 2 * 10u128.pow(24), Some(&usdc_to_near)).is_err());
+    }
+
+    #[test]
+    fn test_widening_mul() {
+        assert_eq!(widening_mul(3, 5), (0, 15));
+        assert_eq!(widening_mul(1 << 64, 1 << 64), (1, 0));
+        assert_eq!(widening_mul(u128::MAX, u128::MAX), (u128::MAX - 1, 1));
+    }
+
+    #[test]
+    fn test_mul_div() {
+        assert_eq!(mul_div(3000, 400, 1000), Some(1200));
+        assert_eq!(mul_div(7, 3, 2), Some(10));
+        assert_eq!(mul_div(u128::MAX, u128::MAX, u128::MAX), Some(u128::MAX));
+        assert_eq!(mul_div(10u128.pow(30), 4 * 10u128.pow(26), 10u128.pow(27)), Some(4 * 10u128.pow(29)));
+        assert_eq!(mul_div(u128::MAX, 2, 1), None);
+        assert_eq!(mul_div(1, 1, 0), None);
+    }
+
+    #[test]
+    fn test_large_amounts_do_not_overflow_deploy_src() {
+        let mut resolver = resolver();
+        called_by("owner.testnet");
+        // 1000 NEAR (24 decimals) for 3000 of an 18-decimal token, the product overflows a u128
+        let mut order = order(SEPOLIA_CHAIN_ID);
+        order.making_amount = U128(1000 * 10u128.pow(24));
+        order.taking_amount = U128(3000 * 10u128.pow(18));
+        resolver.set_price_bounds(Some(PriceBounds { min: U128(PRICE_SCALE / 10u128.pow(6)), max: U128(PRICE_SCALE) }));
+
+        let fill = U128(400 * 10u128.pow(24));
+        let _ = resolver.deploy_src(order.clone(), "sig".to_string(), fill, None);
+        assert_eq!(resolver.dst_complement(&order, fill).amount, U128(1200 * 10u128.pow(18)));
+    }
+
+    #[test]
+    fn test_migrate_from_deployed_layout() {
+        let mut context = VMContextBuilder::new();
+        context.current_account_id("resolver.testnet".parse().unwrap());
+        context.predecessor_account_id("resolver.testnet".parse().unwrap());
+        testing_env!(context.build());
+        env::state_write(&ResolverV1 {
+            owner: "owner.testnet".parse().unwrap(),
+            escrow_factory: "factory.testnet".parse().unwrap(),
+            dst_chain_resolver: "0x1111111111111111111111111111111111111111".to_string(),
+        });
+
+        let resolver = Resolver::migrate("0xABCDEFabcdef2222222222222222222222222222".to_string());
+        assert_eq!(resolver.get_owner(), "owner.testnet".parse::<AccountId>().unwrap());
+        assert_eq!(resolver.escrow_factory.as_str(), "factory.testnet");
+        assert_eq!(resolver.get_dst_resolver(), "0x1111111111111111111111111111111111111111");
+        assert_eq!(resolver.get_eth_identity(), "0xabcdefabcdef2222222222222222222222222222");
+        assert!(!resolver.enforce_token_allowlist);
+        assert_eq!(resolver.get_price_bounds(), None);
+        assert_eq!(resolver.get_supported_chain_ids(), DEFAULT_SUPPORTED_CHAIN_IDS);
+        assert_eq!(resolver.get_filled_amount("0xabcd".to_string()), U128(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid EVM address")]
+    fn test_migrate_rejects_malformed_eth_identity() {
+        testing_env!(VMContextBuilder::new().build());
+        env::state_write(&ResolverV1 {
+            owner: "owner.testnet".parse().unwrap(),
+            escrow_factory: "factory.testnet".parse().unwrap(),
+            dst_chain_resolver: "0x1111111111111111111111111111111111111111".to_string(),
+        });
+        Resolver::migrate("0x2222".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "E_PRICE_OUT_OF_BOUNDS")]
+    fn test_order_outside_price_bounds_rejected() {
+        let mut resolver = resolver();
+        called_by("owner.testnet");
+        resolver.set_price_bounds(Some(PriceBounds { min: U128(PRICE_SCALE), max: U128(2 * PRICE_SCALE) }));
+        resolver.deploy_src(order(SEPOLIA_CHAIN_ID), "sig".to_string(), U128(400), None);
+    }
 }
@@ -96,4 +96,110 @@ pub async fn request_signature(
     };
     
     agent_call(args, config).await
-}
\ No newline at end of file
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SignatureResponseError {
+    #[error("Unexpected signature response schema: {0}")]
+    Schema(#[from] serde_json::Error),
+    #[error("Invalid hex in {field}: {source}")]
+    InvalidHex { field: &'static str, source: hex::FromHexError },
+    #[error("Expected a {expected} byte signature, got {actual}")]
+    InvalidLength { expected: usize, actual: usize },
+}
+
+#[derive(Deserialize, Debug)]
+pub struct AffinePoint {
+    pub affine_point: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Scalar {
+    pub scalar: String,
+}
+
+/// Response of `request_signature` with key_type Ecdsa
+#[derive(Deserialize, Debug)]
+pub struct EcdsaSignatureResponse {
+    pub big_r: AffinePoint,
+    pub s: Scalar,
+    pub recovery_id: u64,
+}
+
+impl EcdsaSignatureResponse {
+    pub fn from_value(value: serde_json::Value) -> Result<Self, SignatureResponseError> {
+        Ok(serde_json::from_value(value)?)
+    }
+
+    /// x coordinate of big_r, the compressed point prefix byte is dropped
+    pub fn r_bytes(&self) -> Result<Vec<u8>, SignatureResponseError> {
+        let big_r_hex = self.big_r.affine_point.trim_start_matches("0x");
+        let x_hex = big_r_hex.get(2..).ok_or(SignatureResponseError::InvalidLength { expected: 33, actual: big_r_hex.len() / 2 })?;
+        hex::decode(x_hex).map_err(|source| SignatureResponseError::InvalidHex { field: "big_r", source })
+    }
+
+    pub fn s_bytes(&self) -> Result<Vec<u8>, SignatureResponseError> {
+        hex::decode(self.s.scalar.trim_start_matches("0x"))
+            .map_err(|source| SignatureResponseError::InvalidHex { field: "s", source })
+    }
+}
+
+/// Response of `request_signature` with key_type Eddsa
+#[derive(Deserialize, Debug)]
+pub struct EddsaSignatureResponse {
+    pub signature: Vec<u8>,
+}
+
+impl EddsaSignatureResponse {
+    pub fn from_value(value: serde_json::Value) -> Result<Self, SignatureResponseError> {
+        Ok(serde_json::from_value(value)?)
+    }
+
+    pub fn to_bytes(&self) -> Result<[u8; 64], SignatureResponseError> {
+        self.signature.clone().try_into().map_err(|bytes: Vec<u8>| SignatureResponseError::InvalidLength {
+            expected: 64,
+            actual: bytes.len(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_deserialize_ecdsa_signature_response() {
+        let response = EcdsaSignatureResponse::from_value(json!({
+            "scheme": "Secp256k1",
+            "big_r": { "affine_point": format!("02{}", "ab".repeat(32)) },
+            "s": { "scalar": "cd".repeat(32) },
+            "recovery_id": 1
+        }))
+        .unwrap();
+
+        assert_eq!(response.r_bytes().unwrap(), vec![0xab; 32]);
+        assert_eq!(response.s_bytes().unwrap(), vec![0xcd; 32]);
+        assert_eq!(response.recovery_id, 1);
+    }
+
+    #[test]
+    fn test_deserialize_eddsa_signature_response() {
+        let response = EddsaSignatureResponse::from_value(json!({
+            "scheme": "Ed25519",
+            "signature": vec![7u8; 64]
+        }))
+        .unwrap();
+
+        assert_eq!(response.to_bytes().unwrap(), [7u8; 64]);
+    }
+
+    #[test]
+    fn test_malformed_signature_response_errors() {
+        let missing_big_r = EcdsaSignatureResponse::from_value(json!({ "s": { "scalar": "00" }, "recovery_id": 0 }));
+        assert!(matches!(missing_big_r, Err(SignatureResponseError::Schema(_))));
+
+        let short = EddsaSignatureResponse::from_value(json!({ "signature": [1, 2, 3] })).unwrap();
+        assert!(matches!(short.to_bytes(), Err(SignatureResponseError::InvalidLength { expected: 64, actual: 3 })));
+    }
+}
@@ -1,14 +1,16 @@
-use ethers::{contract::{BaseContract, Contract, ContractFactory}, providers::{Http, Middleware, Provider}, types::{transaction::eip2718::TypedTransaction, Address, TransactionRequest, U256}, utils::keccak256};
+use ethers::{contract::{BaseContract, Contract, ContractFactory}, providers::{Http, Middleware, Provider}, types::{transaction::eip2718::TypedTransaction, Address, TransactionRequest, H256, U256}, utils::keccak256};
 use k256::pkcs8::der::Encode;
 use omni_transaction::{evm::{types::Signature, utils::parse_eth_address, EVMTransaction}, TransactionBuilder, TxBuilder, EVM};
 use sha3::{Digest, Keccak256};
-use crate::{agent::{request_signature, AgentConfig}, routes::eth::get_address::get_funding_eth_address};
+use crate::{agent::{request_signature, AgentConfig, EcdsaSignatureResponse}, routes::eth::get_address::get_funding_eth_address, utils::{max_timelock_offset, validate_timelock_offset}};
+use std::collections::HashMap;
 use std::str::FromStr;
 use std::sync::{Arc, RwLock};
 use lazy_static::lazy_static;
 
 lazy_static! {
     static ref ETH_RESOLVER_CONTRACT_ADDRESS: Arc<RwLock<String>> = Arc::new(RwLock::new(String::new()));
+    static ref SENT_EVM_TXS: RwLock<HashMap<u64, SentEvmTx>> = RwLock::new(HashMap::new());
 }
 
 pub struct TimelocksBuilder {
@@ -48,8 +50,21 @@ pub fn create_timelocks(
     dst_public_withdrawal_offset: u32,
     dst_cancellation_offset: u32,
     deployed_at: u32,
-) -> U256 {
-    TimelocksBuilder::new()
+) -> Result<U256, String> {
+    let max_offset = max_timelock_offset();
+    for (stage, offset) in [
+        ("src_withdrawal", src_withdrawal_offset),
+        ("src_public_withdrawal", src_public_withdrawal_offset),
+        ("src_cancellation", src_cancellation_offset),
+        ("src_public_cancellation", src_public_cancellation_offset),
+        ("dst_withdrawal", dst_withdrawal_offset),
+        ("dst_public_withdrawal", dst_public_withdrawal_offset),
+        ("dst_cancellation", dst_cancellation_offset),
+    ] {
+        validate_timelock_offset(stage, offset, max_offset)?;
+    }
+
+    Ok(TimelocksBuilder::new()
         .set_stage_offset(0, src_withdrawal_offset)
         .set_stage_offset(1, src_public_withdrawal_offset)
         .set_stage_offset(2, src_cancellation_offset)
@@ -58,9 +73,10 @@ pub fn create_timelocks(
         .set_stage_offset(5, dst_public_withdrawal_offset)
         .set_stage_offset(6, dst_cancellation_offset)
         .set_deployed_at(deployed_at)
-        .build()
+        .build())
 }
 
+#[derive(Clone, Debug)]
 pub struct Immutables {
     pub order_hash: [u8; 32],
     pub hashlock: [u8; 32],  // Hash of the secret.
@@ -72,6 +88,28 @@ pub struct Immutables {
     pub timelocks: U256,
 }
 
+impl Immutables {
+    /// Unix timestamp (seconds) from which the taker may cancel the src escrow, deployed_at + stage 2 offset
+    pub fn src_cancellation_start(&self) -> u64 {
+        let deployed_at = (self.timelocks >> 224).low_u64() & 0xffffffff;
+        let src_cancellation_offset = (self.timelocks >> (2 * 32)).low_u64() & 0xffffffff;
+        deployed_at + src_cancellation_offset
+    }
+
+    fn to_token(&self) -> ethers::abi::Token {
+        ethers::abi::Token::Tuple(vec![
+            ethers::abi::Token::FixedBytes(self.order_hash.to_vec()),
+            ethers::abi::Token::FixedBytes(self.hashlock.to_vec()),
+            ethers::abi::Token::Address(self.maker),
+            ethers::abi::Token::Address(self.taker),
+            ethers::abi::Token::Address(self.token),
+            ethers::abi::Token::Uint(self.amount),
+            ethers::abi::Token::Uint(self.safety_deposit),
+            ethers::abi::Token::Uint(self.timelocks),
+        ])
+    }
+}
+
 
 pub struct MakerTraitsBuilder {
     data: U256,
@@ -239,7 +277,72 @@ pub fn get_eth_resolver_contract_address() -> String {
     contract_address.clone()
 }
 
-async fn get_signature(transaction_encoded: Vec<u8>) -> Result<Signature, String>{
+/// Picks up a resolver contract deployed in an earlier run from `ETH_RESOLVER_CONTRACT_ADDRESS`,
+/// unless this process already deployed one. Called on startup.
+pub fn load_eth_resolver_contract_address() -> Result<(), String> {
+    let configured = std::env::var("ETH_RESOLVER_CONTRACT_ADDRESS").ok();
+    if let Some(address) = resolve_eth_resolver_contract_address(&get_eth_resolver_contract_address(), configured)? {
+        update_eth_resolver_contract_address(address);
+    }
+    Ok(())
+}
+
+/// The address deployed this session wins over the configured one, which must be a valid address
+pub fn resolve_eth_resolver_contract_address(deployed: &str, configured: Option<String>) -> Result<Option<String>, String> {
+    if !deployed.is_empty() {
+        return Ok(Some(deployed.to_string()));
+    }
+    match configured.filter(|address| !address.trim().is_empty()) {
+        Some(address) => Address::from_str(address.trim())
+            .map(|parsed| Some(format!("{:?}", parsed)))
+            .map_err(|e| format!("Invalid ETH_RESOLVER_CONTRACT_ADDRESS {}: {}", address, e)),
+        None => Ok(None),
+    }
+}
+
+/// Transaction envelope being signed, determines how the recovery id is encoded into `v`
+pub enum EthTxType {
+    Eip1559,
+    Legacy { chain_id: u64 },
+}
+
+/// Normalize the agent's recovery id (0/1, 27/28 or EIP-155 encoded) into the `v` expected by the tx type
+pub fn normalize_recovery_id(raw_v: u64, tx_type: &EthTxType) -> Result<u64, String> {
+    let parity = match raw_v {
+        0 | 1 => raw_v,
+        27 | 28 => raw_v - 27,
+        v if v >= 35 => (v - 35) % 2,
+        v => return Err(format!("Unexpected recovery id: {}", v)),
+    };
+
+    match tx_type {
+        EthTxType::Eip1559 => Ok(parity),
+        EthTxType::Legacy { chain_id } => Ok(parity + 35 + chain_id * 2),
+    }
+}
+
+/// Recover the signer of `hash` and make sure it is the expected address
+pub fn verify_signer(hash: &[u8], signature: &Signature, expected: Address) -> Result<(), String> {
+    let ethers_signature = ethers::types::Signature {
+        r: U256::from_big_endian(&signature.r),
+        s: U256::from_big_endian(&signature.s),
+        v: signature.v,
+    };
+
+    let recovered = ethers_signature
+        .recover(H256::from_slice(hash))
+        .map_err(|e| format!("Failed to recover signer: {}", e))?;
+
+    if recovered != expected {
+        return Err(format!(
+            "Signature recovers to {:?}, expected {:?}",
+            recovered, expected
+        ));
+    }
+    Ok(())
+}
+
+async fn get_signature(transaction_encoded: Vec<u8>, tx_type: EthTxType) -> Result<Signature, String>{
    let transaction_hash = Keccak256::digest(&transaction_encoded);
 
    let request_signature_result = request_signature(
@@ -253,22 +356,20 @@ async fn get_signature(transaction_encoded: Vec<u8>) -> Result<Signature, String
        return Err(format!("Failed to get signature: {:?}", request_signature_result.err()));
    }
 
-   let signature_data = request_signature_result.unwrap();
-
-   let big_r_hex = signature_data["big_r"]["affine_point"].as_str().expect("Failed to get big_r affine point").trim_start_matches("0x");
-   let s_hex = signature_data["s"]["scalar"].as_str().expect("Failed to get s scalar").trim_start_matches("0x");
-   let v = signature_data["recovery_id"].as_u64().expect("Failed to get recovery ID");
-
-   let big_r_hex_trimmed = &big_r_hex[2..];
-   let r_bytes = hex::decode(big_r_hex_trimmed).expect("Failed to decode big_r hex");
-   let s_bytes = hex::decode(s_hex).expect("Failed to decode s hex");
+   let signature_data = EcdsaSignatureResponse::from_value(request_signature_result.unwrap())
+       .map_err(|e| e.to_string())?;
 
     let signature = Signature {
-        v: v as u64,
-        r: r_bytes.clone(),
-        s: s_bytes.clone(),
+        v: normalize_recovery_id(signature_data.recovery_id, &tx_type)?,
+        r: signature_data.r_bytes().map_err(|e| e.to_string())?,
+        s: signature_data.s_bytes().map_err(|e| e.to_string())?,
     };
 
+    // A wrong v still produces a valid-looking signature for another sender, so check before broadcasting
+    let funding_address = Address::from_str(&get_funding_eth_address())
+        .map_err(|e| format!("Invalid funding address: {}", e))?;
+    verify_signer(&transaction_hash, &signature, funding_address)?;
+
     Ok(signature)
 }
 
@@ -300,6 +401,19 @@ async fn send_transaction(signed_transaction: Vec<u8>) -> Result<String, String>
     }
 }
 
+/// 1inch EscrowFactory and Limit Order Protocol on Sepolia, the resolver contract's defaults
+pub const SEPOLIA_ESCROW_FACTORY_ADDRESS: &str = "0x128ce802AB730FbB360b784CA8C16dD73147649c";
+pub const SEPOLIA_LIMIT_ORDER_PROTOCOL_ADDRESS: &str = "0x111111125421ca6dc452d289314280a0f8842a65";
+
+/// Address from `env_var` when set (e.g. contracts deployed on a local node), `default` otherwise
+pub fn configured_eth_address(env_var: &str, default: &str) -> Result<Address, String> {
+    let address = std::env::var(env_var)
+        .ok()
+        .filter(|address| !address.trim().is_empty())
+        .unwrap_or_else(|| default.to_string());
+    Address::from_str(address.trim()).map_err(|e| format!("Invalid {} {}: {}", env_var, address, e))
+}
+
 pub async fn deploy_eth_resolver_contract() -> Result<Address, String> {
     let provider = Provider::<Http>::try_from(crate::utils::SEPOLIA_RPC_URL.as_str())
         .map_err(|e| format!("Failed to create provider: {:?}", e)).expect("Failed to create provider");
@@ -318,8 +432,8 @@ pub async fn deploy_eth_resolver_contract() -> Result<Address, String> {
 
     // Encode constructor arguments using ethers ABI encoding
     let constructor_args = ethers::abi::encode(&[
-        ethers::abi::Token::Address("0x128ce802AB730FbB360b784CA8C16dD73147649c".parse().unwrap()),
-        ethers::abi::Token::Address("0x111111125421ca6dc452d289314280a0f8842a65".parse().unwrap()),
+        ethers::abi::Token::Address(configured_eth_address("ETH_ESCROW_FACTORY", SEPOLIA_ESCROW_FACTORY_ADDRESS)?),
+        ethers::abi::Token::Address(configured_eth_address("ETH_LIMIT_ORDER_PROTOCOL", SEPOLIA_LIMIT_ORDER_PROTOCOL_ADDRESS)?),
         ethers::abi::Token::Address(from_address_str.parse().unwrap()),
     ]);
 
@@ -351,7 +465,7 @@ pub async fn deploy_eth_resolver_contract() -> Result<Address, String> {
 
     let encoded_tx = evm_tx.build_for_signing();
     // Get signature using your MPC implementation
-    let signature = get_signature(encoded_tx.to_vec()).await?;
+    let signature = get_signature(encoded_tx.to_vec(), EthTxType::Eip1559).await?;
     
     // Create signed transaction bytes
     let signed_tx_bytes = evm_tx.build_with_signature(&signature);
@@ -434,25 +548,18 @@ pub async fn deploy_eth_src_contract(immutables: Immutables, order: Order, r: [u
     let mut contract_call = function_selector.to_vec();
     contract_call.extend(function_args);
 
-    let evm_tx = omni_transaction::TransactionBuilder::new::<EVM>()
-        .nonce(nonce.as_u64())
-        .to(to_address.to_fixed_bytes())
-        .input(contract_call.to_vec())
-        .gas_limit(gas_limit)
-        .max_fee_per_gas(max_gas_fee)
-        .max_priority_fee_per_gas(max_priority_fee_per_gas)
-        .chain_id(11155111)
-        .build();
-    
-    let encoded_tx = evm_tx.build_for_signing();
-    // Get signature using your MPC implementation
-    let signature = get_signature(encoded_tx.to_vec()).await.unwrap();
-    
-    // Create signed transaction bytes
-    let signed_tx_bytes = evm_tx.build_with_signature(&signature);
-    
-    // Send the raw transaction
-    let tx_hash = send_raw_transaction(signed_tx_bytes).await.unwrap();
+    // Signed via MPC and recorded so a stuck deployment can be fee-bumped with replace_transaction
+    let tx_hash = sign_and_send_evm_tx(SentEvmTx {
+        nonce: nonce.as_u64(),
+        to: to_address,
+        value: U256::zero(),
+        input: contract_call,
+        gas_limit,
+        max_fee_per_gas: max_gas_fee,
+        max_priority_fee_per_gas,
+    })
+    .await
+    .unwrap();
     
     println!("Transaction hash: {}", tx_hash);
 }
@@ -510,27 +617,425 @@ pub async fn deploy_eth_dest_contract(dstImmutables: Immutables, srcCancellation
     let mut contract_call = function_selector.to_vec();
     contract_call.extend(function_args);
 
+    // Signed via MPC and recorded so a stuck deployment can be fee-bumped with replace_transaction
+    let tx_hash = sign_and_send_evm_tx(SentEvmTx {
+        nonce: nonce.as_u64(),
+        to: to_address,
+        value: U256::zero(),
+        input: contract_call,
+        gas_limit,
+        max_fee_per_gas: max_gas_fee,
+        max_priority_fee_per_gas,
+    })
+    .await
+    .unwrap();
+    
+    println!("Transaction hash: {}", tx_hash);
+}
+
+/// CREATE2 salt the EVM EscrowFactory uses for an escrow, `ImmutablesLib.hash`:
+/// keccak256 over the 8 ABI-encoded words of the immutables (timelocks must include deployed_at)
+pub fn eth_escrow_salt(immutables: &Immutables) -> [u8; 32] {
+    keccak256(ethers::abi::encode(&[immutables.to_token()]))
+}
+
+/// CREATE2 address of an escrow clone, `proxy_bytecode_hash` is the factory's src or dst proxy hash
+pub fn eth_escrow_address(factory: Address, immutables: &Immutables, proxy_bytecode_hash: [u8; 32]) -> Address {
+    ethers::utils::get_create2_address_from_hash(factory, eth_escrow_salt(immutables), proxy_bytecode_hash)
+}
+
+/// Calldata for Resolver.cancel(escrow, immutables)
+pub fn encode_cancel_call(escrow: Address, immutables: &Immutables) -> Result<Vec<u8>, String> {
+    let contract_artifact_json = include_str!("../../eth_resolver.json");
+    let contract_artifact: serde_json::Value = serde_json::from_str(contract_artifact_json)
+        .map_err(|e| format!("Failed to parse contract artifact: {}", e))?;
+
+    let contract_abi: ethers::abi::Abi = serde_json::from_value(contract_artifact["abi"].clone())
+        .map_err(|e| format!("Failed to parse contract ABI: {}", e))?;
+
+    let cancel_function = contract_abi.function("cancel")
+        .map_err(|e| format!("Failed to find cancel function: {}", e))?;
+
+    cancel_function
+        .encode_input(&[ethers::abi::Token::Address(escrow), immutables.to_token()])
+        .map_err(|e| format!("Failed to encode cancel call: {}", e))
+}
+
+/// Cancel an ETH escrow through the resolver contract, returns the transaction hash.
+/// Only succeeds once the escrow's cancellation timelock has started.
+pub async fn cancel_eth_escrow(escrow: Address, immutables: Immutables) -> Result<String, String> {
+    let provider = Provider::<Http>::try_from(crate::utils::SEPOLIA_RPC_URL.as_str())
+        .map_err(|e| format!("Failed to create provider: {:?}", e))?;
+
+    let from_address = Address::from_str(&get_funding_eth_address())
+        .map_err(|e| format!("Invalid funding address: {}", e))?;
+    let to_address = Address::from_str(&get_eth_resolver_contract_address())
+        .map_err(|e| format!("Invalid resolver address: {}", e))?;
+
+    let max_gas_fee: u128 = 500_000_000;
+    let max_priority_fee_per_gas: u128 = 1_000_000;
+    let gas_limit: u128 = 1_000_000;
+
+    let contract_call = encode_cancel_call(escrow, &immutables)?;
+
+    let nonce = provider
+        .get_transaction_count(from_address, None)
+        .await
+        .map_err(|e| format!("Failed to get nonce: {}", e))?;
+
+    sign_and_send_evm_tx(SentEvmTx {
+        nonce: nonce.as_u64(),
+        to: to_address,
+        value: U256::zero(),
+        input: contract_call,
+        gas_limit,
+        max_fee_per_gas: max_gas_fee,
+        max_priority_fee_per_gas,
+    })
+    .await
+}
+
+/// Settlement state of an EVM escrow. The 1inch escrows expose no state views, so it is rebuilt
+/// from their `EscrowWithdrawal(bytes32 secret)` and `EscrowCancelled()` events.
+#[derive(serde::Serialize, Clone, Debug, Default, PartialEq)]
+pub struct EvmEscrowState {
+    pub withdrawn: bool,
+    pub cancelled: bool,
+    /// 0x-prefixed hex secret from the withdrawal event
+    pub revealed_secret: Option<String>,
+}
+
+pub fn decode_eth_escrow_logs(logs: &[ethers::types::Log]) -> EvmEscrowState {
+    let withdrawal_topic = H256::from(keccak256("EscrowWithdrawal(bytes32)".as_bytes()));
+    let cancelled_topic = H256::from(keccak256("EscrowCancelled()".as_bytes()));
+
+    let mut state = EvmEscrowState::default();
+    for log in logs {
+        match log.topics.first() {
+            Some(topic) if *topic == withdrawal_topic && log.data.len() >= 32 => {
+                state.withdrawn = true;
+                state.revealed_secret = Some(format!("0x{}", hex::encode(&log.data[..32])));
+            }
+            Some(topic) if *topic == cancelled_topic => state.cancelled = true,
+            _ => {}
+        }
+    }
+    state
+}
+
+/// Reads whether an ETH escrow was withdrawn or cancelled, with the secret a withdrawal revealed.
+/// `deploy_block` is the block the escrow was created in, its logs are only searched from there
+/// as providers reject (or crawl through) log queries over the whole chain
+pub async fn read_eth_escrow_state(escrow: Address, deploy_block: u64) -> Result<EvmEscrowState, String> {
+    let provider = Provider::<Http>::try_from(crate::utils::SEPOLIA_RPC_URL.as_str())
+        .map_err(|e| format!("Failed to create provider: {:?}", e))?;
+
+    let filter = ethers::types::Filter::new().address(escrow).from_block(deploy_block);
+    let logs = provider
+        .get_logs(&filter)
+        .await
+        .map_err(|e| format!("Failed to fetch logs of escrow {:?}: {}", escrow, e))?;
+    Ok(decode_eth_escrow_logs(&logs))
+}
+
+/// Block a mined transaction landed in, e.g. the deploy of an escrow for `read_eth_escrow_state`
+pub async fn eth_transaction_block(tx_hash: H256) -> Result<u64, String> {
+    let provider = Provider::<Http>::try_from(crate::utils::SEPOLIA_RPC_URL.as_str())
+        .map_err(|e| format!("Failed to create provider: {:?}", e))?;
+
+    let receipt = provider
+        .get_transaction_receipt(tx_hash)
+        .await
+        .map_err(|e| format!("Failed to fetch receipt of {:?}: {}", tx_hash, e))?
+        .ok_or_else(|| format!("Transaction {:?} is not mined", tx_hash))?;
+    receipt
+        .block_number
+        .map(|block| block.as_u64())
+        .ok_or_else(|| format!("Receipt of {:?} has no block number", tx_hash))
+}
+
+/// WETH9 on Sepolia, unwrapped for makers whose order sets the unwrap_weth trait
+pub const SEPOLIA_WETH_ADDRESS: &str = "0xfFf9976782d46CC05630D1f6eBAb18b2324d6B14";
+
+/// MakerTraits bit 247, set by `MakerTraitsBuilder::unwrap_weth`
+pub fn maker_wants_native_eth(maker_traits: U256) -> bool {
+    maker_traits.bit(247)
+}
+
+/// A single transaction sent from the funding address
+#[derive(Clone, Debug, PartialEq)]
+pub struct EthCall {
+    pub to: Address,
+    pub data: Vec<u8>,
+    pub value: U256,
+}
+
+/// Calldata for Resolver.withdraw(escrow, secret, immutables)
+pub fn encode_withdraw_call(escrow: Address, secret: [u8; 32], immutables: &Immutables) -> Result<Vec<u8>, String> {
+    let contract_artifact_json = include_str!("../../eth_resolver.json");
+    let contract_artifact: serde_json::Value = serde_json::from_str(contract_artifact_json)
+        .map_err(|e| format!("Failed to parse contract artifact: {}", e))?;
+
+    let contract_abi: ethers::abi::Abi = serde_json::from_value(contract_artifact["abi"].clone())
+        .map_err(|e| format!("Failed to parse contract ABI: {}", e))?;
+
+    let withdraw_function = contract_abi.function("withdraw")
+        .map_err(|e| format!("Failed to find withdraw function: {}", e))?;
+
+    withdraw_function
+        .encode_input(&[
+            ethers::abi::Token::Address(escrow),
+            ethers::abi::Token::FixedBytes(secret.to_vec()),
+            immutables.to_token(),
+        ])
+        .map_err(|e| format!("Failed to encode withdraw call: {}", e))
+}
+
+/// Calldata for WETH9.withdraw(wad)
+pub fn encode_weth_unwrap_call(amount: U256) -> Vec<u8> {
+    let mut data = keccak256("withdraw(uint256)".as_bytes())[0..4].to_vec();
+    data.extend(ethers::abi::encode(&[ethers::abi::Token::Uint(amount)]));
+    data
+}
+
+/// Transactions settling an ETH dst escrow. When the maker asked for native ETH the escrow's
+/// immutables name the funding address as recipient, so the WETH it receives is unwrapped and
+/// forwarded to `recipient` as plain ETH.
+pub fn build_eth_withdrawal_calls(
+    escrow: Address,
+    secret: [u8; 32],
+    immutables: &Immutables,
+    maker_traits: U256,
+    weth: Address,
+    recipient: Address,
+) -> Result<Vec<EthCall>, String> {
+    let resolver = Address::from_str(&get_eth_resolver_contract_address())
+        .map_err(|e| format!("Invalid resolver address: {}", e))?;
+
+    let mut calls = vec![EthCall {
+        to: resolver,
+        data: encode_withdraw_call(escrow, secret, immutables)?,
+        value: U256::zero(),
+    }];
+
+    if maker_wants_native_eth(maker_traits) {
+        if immutables.token != weth {
+            return Err(format!("unwrap_weth is set but the escrow token {:?} is not WETH", immutables.token));
+        }
+        calls.push(EthCall { to: weth, data: encode_weth_unwrap_call(immutables.amount), value: U256::zero() });
+        calls.push(EthCall { to: recipient, data: Vec::new(), value: immutables.amount });
+    }
+
+    Ok(calls)
+}
+
+/// Withdraw an ETH escrow, unwrapping WETH for the recipient if the maker traits ask for it.
+/// Returns the hashes of the submitted transactions in order.
+pub async fn withdraw_eth_escrow(
+    escrow: Address,
+    secret: [u8; 32],
+    immutables: Immutables,
+    maker_traits: U256,
+    recipient: Address,
+) -> Result<Vec<String>, String> {
+    let weth = Address::from_str(SEPOLIA_WETH_ADDRESS).map_err(|e| format!("Invalid WETH address: {}", e))?;
+    let calls = build_eth_withdrawal_calls(escrow, secret, &immutables, maker_traits, weth, recipient)?;
+
+    let provider = Provider::<Http>::try_from(crate::utils::SEPOLIA_RPC_URL.as_str())
+        .map_err(|e| format!("Failed to create provider: {:?}", e))?;
+
+    let from_address = Address::from_str(&get_funding_eth_address())
+        .map_err(|e| format!("Invalid funding address: {}", e))?;
+
+    // Counting pending transactions too, the calls go out back to back without waiting for receipts
+    let first_nonce = provider
+        .get_transaction_count(from_address, Some(ethers::types::BlockNumber::Pending.into()))
+        .await
+        .map_err(|e| format!("Failed to get nonce: {}", e))?;
+
+    let mut tx_hashes = Vec::new();
+    for tx in eth_call_txs(calls, first_nonce.as_u64()) {
+        tx_hashes.push(sign_and_send_evm_tx(tx).await?);
+    }
+    Ok(tx_hashes)
+}
+
+/// One transaction per call on consecutive nonces from `first_nonce`, so later calls queue
+/// behind earlier ones instead of replacing them
+pub fn eth_call_txs(calls: Vec<EthCall>, first_nonce: u64) -> Vec<SentEvmTx> {
+    let max_gas_fee: u128 = 500_000_000;
+    let max_priority_fee_per_gas: u128 = 1_000_000;
+    let gas_limit: u128 = 1_000_000;
+
+    calls
+        .into_iter()
+        .zip(first_nonce..)
+        .map(|(call, nonce)| SentEvmTx {
+            nonce,
+            to: call.to,
+            value: call.value,
+            input: call.data,
+            gas_limit,
+            max_fee_per_gas: max_gas_fee,
+            max_priority_fee_per_gas,
+        })
+        .collect()
+}
+
+/// Minimum fee bump accepted by geth-style mempools for a same-nonce replacement
+pub const MIN_REPLACEMENT_FEE_MULTIPLIER: f64 = 1.1;
+
+/// An EIP-1559 transaction sent from the funding account, kept so it can be rebuilt with higher fees
+#[derive(Clone, Debug, PartialEq)]
+pub struct SentEvmTx {
+    pub nonce: u64,
+    pub to: Address,
+    pub value: U256,
+    pub input: Vec<u8>,
+    pub gas_limit: u128,
+    pub max_fee_per_gas: u128,
+    pub max_priority_fee_per_gas: u128,
+}
+
+/// Signs `tx` via MPC, broadcasts it and records it by nonce, returns the transaction hash
+async fn sign_and_send_evm_tx(tx: SentEvmTx) -> Result<String, String> {
     let evm_tx = omni_transaction::TransactionBuilder::new::<EVM>()
-        .nonce(nonce.as_u64())
-        .to(to_address.to_fixed_bytes())
-        .input(contract_call.to_vec())
-        .gas_limit(gas_limit)
-        .max_fee_per_gas(max_gas_fee)
-        .max_priority_fee_per_gas(max_priority_fee_per_gas)
+        .nonce(tx.nonce)
+        .to(tx.to.to_fixed_bytes())
+        .value(tx.value.as_u128())
+        .input(tx.input.clone())
+        .gas_limit(tx.gas_limit)
+        .max_fee_per_gas(tx.max_fee_per_gas)
+        .max_priority_fee_per_gas(tx.max_priority_fee_per_gas)
         .chain_id(11155111)
         .build();
-    
+
     let encoded_tx = evm_tx.build_for_signing();
-    // Get signature using your MPC implementation
-    let signature = get_signature(encoded_tx.to_vec()).await.unwrap();
-    
-    // Create signed transaction bytes
+    let signature = get_signature(encoded_tx.to_vec(), EthTxType::Eip1559).await?;
     let signed_tx_bytes = evm_tx.build_with_signature(&signature);
-    
-    // Send the raw transaction
-    let tx_hash = send_raw_transaction(signed_tx_bytes).await.unwrap();
-    
-    println!("Transaction hash: {}", tx_hash);
+
+    let tx_hash = send_raw_transaction(signed_tx_bytes).await?;
+    SENT_EVM_TXS.write().unwrap().insert(tx.nonce, tx);
+    Ok(tx_hash)
+}
+
+/// Same transaction and nonce with both fee caps multiplied, rounded up
+pub fn bump_fees(tx: &SentEvmTx, fee_multiplier: f64) -> Result<SentEvmTx, String> {
+    if fee_multiplier.is_nan() || fee_multiplier < MIN_REPLACEMENT_FEE_MULTIPLIER {
+        return Err(format!(
+            "Fee multiplier {} is below the {} required to replace a pending transaction",
+            fee_multiplier, MIN_REPLACEMENT_FEE_MULTIPLIER
+        ));
+    }
+    let bump = |fee: u128| (fee as f64 * fee_multiplier).ceil() as u128;
+    Ok(SentEvmTx {
+        max_fee_per_gas: bump(tx.max_fee_per_gas),
+        max_priority_fee_per_gas: bump(tx.max_priority_fee_per_gas),
+        ..tx.clone()
+    })
+}
+
+/// Rebroadcast the transaction previously sent with `original_nonce` at higher fees, replacing
+/// it in the mempool if it is stuck. Returns the replacement's transaction hash.
+pub async fn replace_transaction(original_nonce: u64, new_fee_multiplier: f64) -> Result<String, String> {
+    let original = SENT_EVM_TXS
+        .read()
+        .unwrap()
+        .get(&original_nonce)
+        .cloned()
+        .ok_or(format!("No transaction was sent with nonce {}", original_nonce))?;
+
+    let replacement = bump_fees(&original, new_fee_multiplier)?;
+    println!(
+        "Replacing tx with nonce {}: max fee {} -> {}, priority fee {} -> {}",
+        original_nonce,
+        original.max_fee_per_gas,
+        replacement.max_fee_per_gas,
+        original.max_priority_fee_per_gas,
+        replacement.max_priority_fee_per_gas
+    );
+    sign_and_send_evm_tx(replacement).await
+}
+
+/// Gas used by a plain value transfer
+const TRANSFER_GAS: u128 = 21_000;
+/// Blocks sampled by eth_feeHistory when pricing a nonce gap filler
+const NONCE_GAP_FEE_HISTORY_BLOCKS: u64 = 10;
+/// The filler's max fee covers this many times the next base fee, so it survives a few full blocks
+const NONCE_GAP_BASE_FEE_MULTIPLIER: u128 = 2;
+/// Floor for the filler's priority fee when recent blocks tipped next to nothing
+const NONCE_GAP_MIN_PRIORITY_FEE_PER_GAS: u128 = 1_000_000_000; // 1 gwei
+
+/// (max fee, priority fee) for a nonce gap filler: the highest median tip of the sampled blocks on top
+/// of the next base fee with headroom. When `pending` still sits at that nonce the fees are raised
+/// at least to what its replacement needs.
+pub fn nonce_gap_fees(next_base_fee: u128, recent_tips: &[u128], pending: Option<&SentEvmTx>) -> (u128, u128) {
+    let mut priority_fee = recent_tips
+        .iter()
+        .copied()
+        .max()
+        .unwrap_or(0)
+        .max(NONCE_GAP_MIN_PRIORITY_FEE_PER_GAS);
+    let mut max_fee = next_base_fee * NONCE_GAP_BASE_FEE_MULTIPLIER + priority_fee;
+    if let Some(Ok(replacement)) = pending.map(|tx| bump_fees(tx, MIN_REPLACEMENT_FEE_MULTIPLIER)) {
+        priority_fee = priority_fee.max(replacement.max_priority_fee_per_gas);
+        max_fee = max_fee.max(replacement.max_fee_per_gas);
+    }
+    (max_fee, priority_fee)
+}
+
+/// Zero-value transfer from the funding account to itself at `target_nonce`, priced by `nonce_gap_fees`.
+/// It is signed with the funding key like every `SentEvmTx`, so `funding` ends up both sender and recipient.
+pub fn nonce_gap_filler(funding: Address, target_nonce: u64, (max_fee_per_gas, max_priority_fee_per_gas): (u128, u128)) -> SentEvmTx {
+    SentEvmTx {
+        nonce: target_nonce,
+        to: funding,
+        value: U256::zero(),
+        input: vec![],
+        gas_limit: TRANSFER_GAS,
+        max_fee_per_gas,
+        max_priority_fee_per_gas,
+    }
+}
+
+/// Fill the nonce of a transaction dropped from the mempool with a self-transfer, unblocking the
+/// funding account's later transactions. Returns the filler's transaction hash.
+pub async fn clear_nonce_gap(target_nonce: u64) -> Result<String, String> {
+    let provider = Provider::<Http>::try_from(crate::utils::SEPOLIA_RPC_URL.as_str())
+        .map_err(|e| format!("Failed to create provider: {:?}", e))?;
+    let funding = Address::from_str(&get_funding_eth_address())
+        .map_err(|e| format!("Invalid funding address: {}", e))?;
+
+    let confirmed = provider
+        .get_transaction_count(funding, None)
+        .await
+        .map_err(|e| format!("Failed to get nonce: {}", e))?
+        .as_u64();
+    if target_nonce < confirmed {
+        return Err(format!("Nonce {} is already confirmed (next is {})", target_nonce, confirmed));
+    }
+
+    let history = provider
+        .fee_history(NONCE_GAP_FEE_HISTORY_BLOCKS, ethers::types::BlockNumber::Latest, &[50.0])
+        .await
+        .map_err(|e| format!("Failed to get fee history: {}", e))?;
+    // base_fee_per_gas ends with the base fee of the block after the sampled ones
+    let next_base_fee = history
+        .base_fee_per_gas
+        .last()
+        .ok_or("Fee history returned no base fee")?
+        .as_u128();
+    let recent_tips: Vec<u128> = history
+        .reward
+        .iter()
+        .filter_map(|rewards| rewards.first())
+        .map(|tip| tip.as_u128())
+        .collect();
+    let pending = SENT_EVM_TXS.read().unwrap().get(&target_nonce).cloned();
+    let fees = nonce_gap_fees(next_base_fee, &recent_tips, pending.as_ref());
+
+    println!("Filling nonce gap at {} for {:?} (max fee {}, priority fee {})", target_nonce, funding, fees.0, fees.1);
+    sign_and_send_evm_tx(nonce_gap_filler(funding, target_nonce, fees)).await
 }
 
 // Helper functions
@@ -608,10 +1113,214 @@ pub async fn deploy_with_constructor_args(
 
     let typed_tx: TypedTransaction = deployment_tx.into();
     let encoded_tx = typed_tx.rlp();
-    let signature = get_signature(encoded_tx.to_vec()).await?;
+    let signature = get_signature(encoded_tx.to_vec(), EthTxType::Legacy { chain_id: 11155111 }).await?;
     let signed_tx_bytes = create_signed_transaction(&typed_tx, &signature)?;
     let tx_hash = send_raw_transaction(signed_tx_bytes).await?;
     let contract_address = calculate_contract_address(&from_address, &nonce);
     
     Ok(contract_address)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::signers::{LocalWallet, Signer};
+
+    #[test]
+    fn test_eth_escrow_salt_matches_immutables_lib_hash() {
+        let immutables = Immutables {
+            order_hash: [1u8; 32],
+            hashlock: [2u8; 32],
+            maker: Address::repeat_byte(0x11),
+            taker: Address::repeat_byte(0x22),
+            token: Address::repeat_byte(0x33),
+            amount: U256::from(1000),
+            safety_deposit: U256::from(10),
+            timelocks: TimelocksBuilder::new()
+                .set_stage_offset(0, 3600)
+                .set_deployed_at(1700000000)
+                .build(),
+        };
+
+        // keccak256(abi.encode(immutables)) as computed by ImmutablesLib.hash
+        assert_eq!(
+            hex::encode(eth_escrow_salt(&immutables)),
+            "6a7db3e7f082ff85f11091c0c167a4b2f619039a57b743769af5bec38e0dfe25"
+        );
+    }
+
+    #[test]
+    fn test_withdrawal_unwraps_weth_only_when_flag_set() {
+        update_eth_resolver_contract_address(format!("{:?}", Address::repeat_byte(0xaa)));
+        let weth = Address::from_str(SEPOLIA_WETH_ADDRESS).unwrap();
+        let recipient = Address::repeat_byte(0x44);
+        let immutables = Immutables {
+            order_hash: [1u8; 32],
+            hashlock: [2u8; 32],
+            maker: Address::repeat_byte(0x11),
+            taker: Address::repeat_byte(0x22),
+            token: weth,
+            amount: U256::from(1000),
+            safety_deposit: U256::from(10),
+            timelocks: U256::zero(),
+        };
+        let escrow = Address::repeat_byte(0xee);
+
+        let plain = build_eth_withdrawal_calls(escrow, [3u8; 32], &immutables, MakerTraitsBuilder::new().build(), weth, recipient).unwrap();
+        assert_eq!(plain.len(), 1);
+        assert!(plain.iter().all(|call| call.to != weth));
+
+        let traits = MakerTraitsBuilder::new().unwrap_weth().build();
+        let unwrapped = build_eth_withdrawal_calls(escrow, [3u8; 32], &immutables, traits, weth, recipient).unwrap();
+        assert_eq!(unwrapped.len(), 3);
+        assert_eq!(unwrapped[1].to, weth);
+        assert_eq!(unwrapped[1].data[..4], [0x2e, 0x1a, 0x7d, 0x4d]); // withdraw(uint256)
+        assert_eq!(unwrapped[2], EthCall { to: recipient, data: Vec::new(), value: U256::from(1000) });
+    }
+
+    #[test]
+    fn test_signature_recovering_wrong_address_is_rejected() {
+        let signer = LocalWallet::new(&mut rand::thread_rng());
+        let other = LocalWallet::new(&mut rand::thread_rng());
+        let hash = keccak256(b"transaction");
+
+        let ethers_signature = signer.sign_hash(H256::from(hash)).unwrap();
+        let mut r = [0u8; 32];
+        let mut s = [0u8; 32];
+        ethers_signature.r.to_big_endian(&mut r);
+        ethers_signature.s.to_big_endian(&mut s);
+        let signature = Signature {
+            v: normalize_recovery_id(ethers_signature.v, &EthTxType::Eip1559).unwrap(),
+            r: r.to_vec(),
+            s: s.to_vec(),
+        };
+
+        assert!(verify_signer(&hash, &signature, signer.address()).is_ok());
+        assert!(verify_signer(&hash, &signature, other.address()).is_err());
+    }
+
+    #[test]
+    fn test_replacement_reuses_nonce_and_raises_fees() {
+        let original = SentEvmTx {
+            nonce: 42,
+            to: Address::repeat_byte(0x11),
+            value: U256::zero(),
+            input: vec![0xde, 0xad],
+            gas_limit: 1_000_000,
+            max_fee_per_gas: 500_000_000,
+            max_priority_fee_per_gas: 1_000_000,
+        };
+
+        let replacement = bump_fees(&original, 1.5).unwrap();
+        assert_eq!(replacement.nonce, original.nonce);
+        assert_eq!(replacement.input, original.input);
+        assert_eq!(replacement.max_fee_per_gas, 750_000_000);
+        assert_eq!(replacement.max_priority_fee_per_gas, 1_500_000);
+
+        // Mempools drop replacements that do not bump fees by at least 10%
+        assert!(bump_fees(&original, 1.05).is_err());
+    }
+
+    #[test]
+    fn test_configured_resolver_address_used_without_deployment() {
+        let configured = Some("0x1111111111111111111111111111111111111111".to_string());
+        assert_eq!(
+            resolve_eth_resolver_contract_address("", configured.clone()).unwrap(),
+            Some("0x1111111111111111111111111111111111111111".to_string())
+        );
+        // A deployment in this session takes precedence
+        assert_eq!(
+            resolve_eth_resolver_contract_address("0x2222222222222222222222222222222222222222", configured).unwrap(),
+            Some("0x2222222222222222222222222222222222222222".to_string())
+        );
+        assert_eq!(resolve_eth_resolver_contract_address("", None).unwrap(), None);
+        assert!(resolve_eth_resolver_contract_address("", Some("0x1234".to_string())).is_err());
+    }
+
+    #[test]
+    fn test_withdrawal_calls_get_consecutive_nonces() {
+        let call = |byte: u8| EthCall { to: Address::repeat_byte(byte), data: vec![byte], value: U256::zero() };
+
+        let txs = eth_call_txs(vec![call(1), call(2), call(3)], 41);
+        assert_eq!(txs.iter().map(|tx| tx.nonce).collect::<Vec<_>>(), [41, 42, 43]);
+        assert_eq!(txs[2].to, Address::repeat_byte(3));
+        assert_eq!(txs[2].input, vec![3]);
+    }
+
+    #[test]
+    fn test_unset_eth_address_falls_back_to_sepolia_default() {
+        assert_eq!(
+            configured_eth_address("ONEPRIME_TEST_UNSET_FACTORY", SEPOLIA_ESCROW_FACTORY_ADDRESS).unwrap(),
+            Address::from_str(SEPOLIA_ESCROW_FACTORY_ADDRESS).unwrap()
+        );
+        assert!(configured_eth_address("ONEPRIME_TEST_UNSET_FACTORY", "0x1234").is_err());
+    }
+
+    #[test]
+    fn test_nonce_gap_filler_is_self_transfer_at_target_nonce() {
+        let funding = Address::repeat_byte(0x22);
+
+        let filler = nonce_gap_filler(funding, 17, (30_000_000_000, 2_000_000_000));
+        assert_eq!(filler.nonce, 17);
+        assert_eq!(filler.to, funding);
+        assert_eq!(filler.value, U256::zero());
+        assert!(filler.input.is_empty());
+        assert_eq!(filler.gas_limit, TRANSFER_GAS);
+        assert_eq!((filler.max_fee_per_gas, filler.max_priority_fee_per_gas), (30_000_000_000, 2_000_000_000));
+    }
+
+    #[test]
+    fn test_nonce_gap_fees_follow_the_network() {
+        // Twice the next base fee plus the highest median tip
+        assert_eq!(
+            nonce_gap_fees(10_000_000_000, &[1_500_000_000, 3_000_000_000, 2_000_000_000], None),
+            (23_000_000_000, 3_000_000_000)
+        );
+        // Near-zero tips still pay the floor
+        assert_eq!(
+            nonce_gap_fees(1_000_000, &[1], None),
+            (2_000_000 + NONCE_GAP_MIN_PRIORITY_FEE_PER_GAS, NONCE_GAP_MIN_PRIORITY_FEE_PER_GAS)
+        );
+    }
+
+    #[test]
+    fn test_nonce_gap_fees_outbid_pending_transaction() {
+        let pending = SentEvmTx {
+            nonce: 17,
+            to: Address::repeat_byte(0x11),
+            value: U256::zero(),
+            input: vec![],
+            gas_limit: 1_000_000,
+            max_fee_per_gas: 50_000_000_000,
+            max_priority_fee_per_gas: 5_000_000_000,
+        };
+
+        // A quiet network would price the filler below the stuck transaction, the replacement bump wins
+        let (max_fee, priority_fee) = nonce_gap_fees(1_000_000_000, &[1_000_000_000], Some(&pending));
+        let replacement = bump_fees(&pending, MIN_REPLACEMENT_FEE_MULTIPLIER).unwrap();
+        assert_eq!((max_fee, priority_fee), (replacement.max_fee_per_gas, replacement.max_priority_fee_per_gas));
+        assert!(max_fee > 50_000_000_000 && priority_fee > 5_000_000_000);
+    }
+
+    #[test]
+    fn test_decode_withdrawn_escrow_logs() {
+        let secret = [0xabu8; 32];
+        let log = |signature: &str, data: Vec<u8>| ethers::types::Log {
+            address: Address::repeat_byte(0x44),
+            topics: vec![H256::from(keccak256(signature.as_bytes()))],
+            data: data.into(),
+            ..Default::default()
+        };
+
+        assert_eq!(decode_eth_escrow_logs(&[]), EvmEscrowState::default());
+
+        let state = decode_eth_escrow_logs(&[log("EscrowWithdrawal(bytes32)", secret.to_vec())]);
+        assert!(state.withdrawn);
+        assert!(!state.cancelled);
+        assert_eq!(state.revealed_secret, Some(format!("0x{}", "ab".repeat(32))));
+
+        let state = decode_eth_escrow_logs(&[log("EscrowCancelled()", vec![])]);
+        assert!(state.cancelled);
+        assert_eq!(state.revealed_secret, None);
+    }
+}
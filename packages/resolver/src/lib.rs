@@ -0,0 +1,9 @@
+//! Resolver service modules, split out of the binary so the integration tests under tests/
+//! can drive the orchestration against a local sandbox
+pub mod routes;
+pub mod agent;
+pub mod utils;
+pub mod near;
+pub mod eth;
+pub mod orchestration;
+pub mod secret;
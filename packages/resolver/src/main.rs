@@ -1,28 +1,21 @@
-mod routes;
-mod agent;
-mod utils;
-mod near;
-mod eth;
-
-use progenitor::generate_api;
-use routes::agentAccount::{get_agent_account};
-
 use axum::Router;
 
-use crate::{agent::agent_account_id, eth::utils::{deploy_eth_resolver_contract, deploy_eth_src_contract}, near::utils::{construct_sample_order, create_near_funding_account, delete_near_account, deploy_near_resolver_contract, deploy_near_src_contract, setup_near_account_from_agent}, routes::{eth::get_address::setup_funding_eth_address, near::get_address::{setup_funding_near_address, setup_holding_near_address}}};
+use oneprime_resolver::{orchestration, routes};
+use oneprime_resolver::{agent::agent_account_id, eth::utils::{deploy_eth_resolver_contract, deploy_eth_src_contract, load_eth_resolver_contract_address}, near::utils::{construct_sample_order, create_near_funding_account, delete_near_account, deploy_near_resolver_contract, deploy_near_src_contract, setup_near_account_from_agent}, routes::{eth::get_address::setup_funding_eth_address, near::get_address::{setup_funding_near_address, setup_holding_near_address}}, utils::new_correlation_id};
 
 pub async fn sample_deploy_near_src_contract() {
     let order = construct_sample_order().await;
-    deploy_near_src_contract(order, "1234567890".to_string(), 10).await;
+    deploy_near_src_contract(order, "1234567890".to_string(), 10, new_correlation_id()).await;
 }
 
 #[tokio::main]
 async fn main() {
 
     println!("Running Setup...");
-    //setup_funding_eth_address().await;
-    setup_funding_near_address().await;
-    setup_holding_near_address().await;
+    setup_funding_eth_address().await.expect("Failed to set up funding ETH address");
+    load_eth_resolver_contract_address().expect("Failed to load ETH resolver contract address");
+    setup_funding_near_address().await.expect("Failed to set up funding NEAR address");
+    setup_holding_near_address().await.expect("Failed to set up holding NEAR address");
     
     delete_near_account().await;
     create_near_funding_account().await;
@@ -45,21 +38,73 @@ async fn main() {
         }
     });
 
+    orchestration::reconcile::spawn_reconciliation(orchestration::reconcile::RECONCILE_INTERVAL);
+    orchestration::sweep::spawn_sweep(orchestration::sweep::SWEEP_INTERVAL);
+
     println!("Running on Port 3001...");
+    let app = build_router(dev_routes_enabled());
+
+    let listener = tokio::net::TcpListener::bind("0.0.0.0:3001").await.unwrap();
+    axum::serve(listener, app).await.unwrap();
+}
+
+/// Mock transfer, sample deploy and direct fill routes are only mounted when ENABLE_DEV_ROUTES=true
+fn dev_routes_enabled() -> bool {
+    std::env::var("ENABLE_DEV_ROUTES").map(|v| v == "true" || v == "1").unwrap_or(false)
+}
+
+fn build_router(dev_routes: bool) -> Router {
     let app = Router::new()
         .route("/api/eth/get_address", axum::routing::get(routes::eth::get_address::get_funding_eth_address_await))
         .route("/api/near/get_address", axum::routing::get(routes::near::get_address::get_funding_near_address))
         .route("/api/eth/get_balance", axum::routing::get(routes::eth::get_balance::get_balance))
         .route("/api/near/get_balance", axum::routing::get(routes::near::get_balance::get_near_balance))
+        .route("/api/balances", axum::routing::get(routes::balances::get_balances))
+        .route("/api/eth/deploy_eth_resolver", axum::routing::get(routes::eth::deploy_resolver::deploy_resolver))
+        .route("/api/eth/resolver_address", axum::routing::get(routes::eth::resolver_address::get_resolver_address))
+        .route("/api/eth/timelocks", axum::routing::get(routes::eth::timelocks::get_timelocks))
+        .route("/api/eth/maker_traits", axum::routing::post(routes::eth::traits::get_maker_traits))
+        .route("/api/eth/taker_traits", axum::routing::post(routes::eth::traits::get_taker_traits))
+        .route("/api/swap/plan", axum::routing::post(routes::swap::plan::plan_swap))
+        .route("/api/order/hash", axum::routing::post(routes::order::hash::get_order_hash))
+        .route("/api/maker/{account}/swaps", axum::routing::get(routes::maker::swaps::get_maker_swaps));
+
+    if !dev_routes {
+        return app;
+    }
+
+    app
         .route("/api/eth/mock_transfer", axum::routing::get(routes::eth::mock_transfer_funds::mock_transfer_funds))
         .route("/api/near/mock_transfer", axum::routing::get(routes::near::mock_transfer_funds::mock_transfer_funds))
         .route("/api/near/get_mock_transfer_address", axum::routing::get(routes::near::mock_transfer_funds_with_gas_sponsorship::get_additional_mock_address))
         .route("/api/near/mock_transfer_usdc_with_gas_sponsorship", axum::routing::get(routes::near::mock_transfer_funds_with_gas_sponsorship::mock_transfer_usdc))
-        .route("/api/eth/deploy_eth_resolver", axum::routing::get(routes::eth::deploy_resolver::deploy_resolver))
-        .route("/api/eth/deploy_near_src_contract", axum::routing::get(sample_deploy_near_src_contract));
-
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3001").await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+        .route("/api/eth/deploy_near_src_contract", axum::routing::get(sample_deploy_near_src_contract))
+        .route("/api/near/deploy_templates", axum::routing::get(routes::near::deploy_templates::deploy_templates))
+        // Unauthenticated and funded from the resolver's own account, see routes::fill
+        .route("/api/fill", axum::routing::post(routes::fill::fill_order))
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_mock_routes_not_mounted_without_dev_flag() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, build_router(false)).await.unwrap();
+        });
 
+        let response = reqwest::get(format!("http://{}/api/near/mock_transfer", addr)).await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+
+        let response = reqwest::Client::new()
+            .post(format!("http://{}/api/fill", addr))
+            .json(&serde_json::json!({}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,218 @@
+use std::{env, str::FromStr, sync::LazyLock};
+use near_api::{AccountId, NetworkConfig, RPCEndpoint};
+use serde_json::json;
+
+pub static NEAR_NETWORK: LazyLock<NearNetwork> = LazyLock::new(NearNetwork::from_env);
+
+/// NEAR network every RPC call, view and MPC signature request targets, selected via NEAR_NETWORK
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NearNetwork {
+    #[default]
+    Testnet,
+    Mainnet,
+    /// Local near-workspaces sandbox at NEAR_SANDBOX_RPC_URL, used by the integration tests
+    Sandbox,
+}
+
+pub const DEFAULT_SANDBOX_RPC_URL: &str = "http://127.0.0.1:3030";
+
+impl NearNetwork {
+    pub fn from_env() -> Self {
+        env::var("NEAR_NETWORK")
+            .ok()
+            .and_then(|v| Self::from_str(&v).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn network_config(&self) -> NetworkConfig {
+        match self {
+            Self::Testnet => NetworkConfig::testnet(),
+            Self::Mainnet => NetworkConfig::mainnet(),
+            Self::Sandbox => NetworkConfig::from_rpc_url("sandbox", self.rpc_url().parse().expect("Invalid NEAR_SANDBOX_RPC_URL")),
+        }
+    }
+
+    pub fn rpc_url(&self) -> String {
+        match self {
+            Self::Testnet => RPCEndpoint::testnet().url.to_string(),
+            Self::Mainnet => RPCEndpoint::mainnet().url.to_string(),
+            Self::Sandbox => env::var("NEAR_SANDBOX_RPC_URL").unwrap_or_else(|_| DEFAULT_SANDBOX_RPC_URL.to_string()),
+        }
+    }
+
+    /// Chain signatures MPC contract deriving the resolver's keys. A sandbox has none, the
+    /// integration tests sign through a mock agent API and set the derived addresses directly
+    pub fn mpc_signer_account_id(&self) -> AccountId {
+        match self {
+            Self::Testnet => AccountId::from_str("v1.signer-prod.testnet").unwrap(),
+            Self::Mainnet => AccountId::from_str("v1.signer").unwrap(),
+            Self::Sandbox => AccountId::from_str("signer.test.near").unwrap(),
+        }
+    }
+}
+
+impl FromStr for NearNetwork {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "testnet" => Ok(Self::Testnet),
+            "mainnet" => Ok(Self::Mainnet),
+            "sandbox" => Ok(Self::Sandbox),
+            other => Err(format!("Unknown NEAR network: {}", other)),
+        }
+    }
+}
+
+pub static MPC_DOMAINS: LazyLock<MpcDomains> = LazyLock::new(MpcDomains::from_env);
+
+/// MPC signer domains the resolver derives keys in, each domain is bound to one curve.
+/// On the chain signatures contract domain 0 is secp256k1 and domain 1 is ed25519.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MpcDomains {
+    /// secp256k1 keys, behind the ETH funding address
+    pub secp256k1: u32,
+    /// ed25519 keys, behind the NEAR implicit funding and holding accounts
+    pub ed25519: u32,
+}
+
+impl Default for MpcDomains {
+    fn default() -> Self {
+        Self { secp256k1: 0, ed25519: 1 }
+    }
+}
+
+impl MpcDomains {
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let domain = |name: &str, default: u32| env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default);
+        Self {
+            secp256k1: domain("MPC_SECP256K1_DOMAIN_ID", defaults.secp256k1),
+            ed25519: domain("MPC_ED25519_DOMAIN_ID", defaults.ed25519),
+        }
+    }
+}
+
+/// Arguments for the MPC signer's `derived_public_key` view
+pub fn derived_public_key_args(path: &str, predecessor: &str, domain_id: u32) -> serde_json::Value {
+    json!({
+        "path": path,
+        "predecessor": predecessor,
+        "domain_id": domain_id,
+    })
+}
+
+pub const DEFAULT_GLOBAL_RESOLVER_CONTRACT: &str = "1prime-global-resolver-contract.testnet";
+pub const DEFAULT_ESCROW_FACTORY: &str = "1prime-global-factory-contract.testnet";
+pub const DEFAULT_DST_CHAIN_RESOLVER: &str = "test";
+pub const DEFAULT_ESCROW_SRC_TEMPLATE: &str = "1prime-escrow-src-template.testnet";
+pub const DEFAULT_ESCROW_DST_TEMPLATE: &str = "1prime-escrow-dst-template.testnet";
+
+/// Accounts the NEAR resolver deployment points at, defaults to the shared testnet deployment
+#[derive(Clone, Debug)]
+pub struct NearDeploymentConfig {
+    pub global_resolver_contract: String,
+    /// Pinned base58 sha256 of the global resolver code, the deployment aborts if the published code differs
+    pub global_resolver_code_hash: Option<String>,
+    pub escrow_factory: String,
+    pub dst_chain_resolver: String,
+    /// Named accounts the escrow templates are published from, each must hold the funding key
+    pub escrow_src_template: String,
+    pub escrow_dst_template: String,
+}
+
+impl Default for NearDeploymentConfig {
+    fn default() -> Self {
+        Self {
+            global_resolver_contract: DEFAULT_GLOBAL_RESOLVER_CONTRACT.to_string(),
+            global_resolver_code_hash: None,
+            escrow_factory: DEFAULT_ESCROW_FACTORY.to_string(),
+            dst_chain_resolver: DEFAULT_DST_CHAIN_RESOLVER.to_string(),
+            escrow_src_template: DEFAULT_ESCROW_SRC_TEMPLATE.to_string(),
+            escrow_dst_template: DEFAULT_ESCROW_DST_TEMPLATE.to_string(),
+        }
+    }
+}
+
+impl NearDeploymentConfig {
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            global_resolver_contract: env::var("NEAR_GLOBAL_RESOLVER_CONTRACT")
+                .unwrap_or(defaults.global_resolver_contract),
+            global_resolver_code_hash: env::var("NEAR_GLOBAL_RESOLVER_CODE_HASH").ok(),
+            escrow_factory: env::var("NEAR_ESCROW_FACTORY").unwrap_or(defaults.escrow_factory),
+            dst_chain_resolver: env::var("DST_CHAIN_RESOLVER").unwrap_or(defaults.dst_chain_resolver),
+            escrow_src_template: env::var("NEAR_ESCROW_SRC_TEMPLATE").unwrap_or(defaults.escrow_src_template),
+            escrow_dst_template: env::var("NEAR_ESCROW_DST_TEMPLATE").unwrap_or(defaults.escrow_dst_template),
+        }
+    }
+
+    /// Arguments for the resolver contract's `new`
+    pub fn resolver_init_args(&self, owner: &str, eth_identity: &str) -> serde_json::Value {
+        json!({
+            "owner": owner,
+            "escrow_factory": self.escrow_factory,
+            "dst_chain_resolver": self.dst_chain_resolver,
+            "eth_identity": eth_identity,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_derivation_args_carry_configured_domain() {
+        let domains = MpcDomains { secp256k1: 7, ed25519: 8 };
+
+        let eth = derived_public_key_args("oneprime-funding-eth", "agent.testnet", domains.secp256k1);
+        let near = derived_public_key_args("oneprime-funding-eth", "agent.testnet", domains.ed25519);
+
+        assert_eq!(eth["domain_id"], 7);
+        assert_eq!(near["domain_id"], 8);
+        assert_eq!(near["path"], "oneprime-funding-eth");
+        assert_eq!(MpcDomains::default(), MpcDomains { secp256k1: 0, ed25519: 1 });
+    }
+
+    #[test]
+    fn test_custom_addresses_in_init_args() {
+        let config = NearDeploymentConfig {
+            global_resolver_contract: "my-resolver-code.testnet".to_string(),
+            global_resolver_code_hash: None,
+            escrow_factory: "my-factory.testnet".to_string(),
+            dst_chain_resolver: "0x1111111111111111111111111111111111111111".to_string(),
+            ..NearDeploymentConfig::default()
+        };
+
+        let args = serde_json::to_string(
+            &config.resolver_init_args("owner.testnet", "0x2222222222222222222222222222222222222222"),
+        )
+        .unwrap();
+        assert!(args.contains("\"escrow_factory\":\"my-factory.testnet\""));
+        assert!(args.contains("\"dst_chain_resolver\":\"0x1111111111111111111111111111111111111111\""));
+        assert!(args.contains("\"owner\":\"owner.testnet\""));
+        assert!(args.contains("\"eth_identity\":\"0x2222222222222222222222222222222222222222\""));
+    }
+
+    #[test]
+    fn test_mainnet_selects_mainnet_rpc_and_signer() {
+        let network = NearNetwork::from_str("mainnet").unwrap();
+        assert_eq!(network.rpc_url(), RPCEndpoint::mainnet().url.to_string());
+        assert!(network.rpc_url().contains("mainnet"));
+        assert_eq!(network.mpc_signer_account_id().as_str(), "v1.signer");
+
+        assert_eq!(NearNetwork::default().mpc_signer_account_id().as_str(), "v1.signer-prod.testnet");
+        assert!(NearNetwork::from_str("betanet").is_err());
+    }
+
+    #[test]
+    fn test_sandbox_targets_local_rpc() {
+        let network = NearNetwork::from_str("Sandbox").unwrap();
+        assert_eq!(network, NearNetwork::Sandbox);
+        if env::var("NEAR_SANDBOX_RPC_URL").is_err() {
+            assert_eq!(network.rpc_url(), DEFAULT_SANDBOX_RPC_URL);
+        }
+    }
+}
@@ -1 +1,3 @@
-pub mod utils;
\ No newline at end of file
+pub mod config;
+pub mod templates;
+pub mod utils;
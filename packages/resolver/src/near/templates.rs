@@ -0,0 +1,217 @@
+use std::{env, str::FromStr};
+use async_trait::async_trait;
+use near_api::{AccountId, Contract, Data};
+use omni_transaction::near::types::{
+    Action, DeployGlobalContractAction, FunctionCallAction, GlobalContractDeployMode, TransferAction, U128, U64,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::{
+    near::{
+        config::{NearDeploymentConfig, NEAR_NETWORK},
+        utils::{send_actions_with_funding_key, send_funding_actions},
+    },
+    utils::json_bytes,
+};
+
+pub const DEFAULT_ESCROW_SRC_WASM: &str = "../near-contracts/target/near/escrow_src/escrow_src.wasm";
+pub const DEFAULT_ESCROW_DST_WASM: &str = "../near-contracts/target/near/escrow_dst/escrow_dst.wasm";
+
+/// Global contract code is charged at 10x the regular storage price of 10^19 yoctoNEAR per byte
+const GLOBAL_CONTRACT_BYTE_COST: u128 = 10 * 10u128.pow(19);
+/// Extra balance left on a template account for its own state and key
+const TEMPLATE_ACCOUNT_BUFFER: u128 = 10u128.pow(24); // 1 NEAR
+
+/// Subset of the factory's FactoryStats used to confirm the registration
+#[derive(Deserialize, Debug)]
+pub struct FactoryTemplates {
+    pub escrow_src_template: String,
+    pub escrow_dst_template: String,
+}
+
+/// Chain side of template setup, abstracted so the registration flow can be tested without a chain
+#[async_trait]
+pub trait TemplateDeployer: Send + Sync {
+    /// Funds `account` and publishes `code` from it as a global contract referenced by that account id
+    async fn publish_global_contract(&self, account: &str, code: Vec<u8>) -> Result<(), String>;
+    async fn call_factory(&self, factory: &str, method: &str, args: Value) -> Result<(), String>;
+    async fn get_factory_templates(&self, factory: &str) -> Result<FactoryTemplates, String>;
+}
+
+pub struct FundingTemplateDeployer;
+
+#[async_trait]
+impl TemplateDeployer for FundingTemplateDeployer {
+    async fn publish_global_contract(&self, account: &str, code: Vec<u8>) -> Result<(), String> {
+        // The template account pays for the code it publishes, the funding account tops it up first
+        let deposit = code.len() as u128 * GLOBAL_CONTRACT_BYTE_COST + TEMPLATE_ACCOUNT_BUFFER;
+        send_funding_actions(account, vec![Action::Transfer(TransferAction { deposit: U128(deposit) })]).await?;
+
+        let deploy = Action::DeployGlobalContract(DeployGlobalContractAction {
+            code,
+            deploy_mode: GlobalContractDeployMode::AccountId,
+        });
+        send_actions_with_funding_key(account, account, vec![deploy]).await.map(|_| ())
+    }
+
+    async fn call_factory(&self, factory: &str, method: &str, args: Value) -> Result<(), String> {
+        let action = Action::FunctionCall(Box::new(FunctionCallAction {
+            method_name: method.to_string(),
+            args: json_bytes(args),
+            gas: U64(30000000000000), // 30 TGas
+            deposit: U128(0),
+        }));
+        send_funding_actions(factory, vec![action]).await.map(|_| ())
+    }
+
+    async fn get_factory_templates(&self, factory: &str) -> Result<FactoryTemplates, String> {
+        let factory_id = AccountId::from_str(factory).map_err(|e| format!("Invalid factory account {}: {}", factory, e))?;
+        let stats: Data<FactoryTemplates> = Contract(factory_id)
+            .call_function("get_stats", json!({}))
+            .map_err(|e| format!("Failed to build get_stats call: {}", e))?
+            .read_only()
+            .fetch_from(&NEAR_NETWORK.network_config())
+            .await
+            .map_err(|e| format!("Failed to fetch factory stats: {}", e))?;
+        Ok(stats.data)
+    }
+}
+
+/// Publishes the escrow-src and escrow-dst wasms built by build.sh as global contracts from the
+/// configured template accounts and points the factory at them. The funding account must own the
+/// factory and its key must be a full access key on both template accounts.
+pub async fn deploy_and_register_templates() -> Result<FactoryTemplates, String> {
+    let src_code = read_wasm("ESCROW_SRC_WASM", DEFAULT_ESCROW_SRC_WASM)?;
+    let dst_code = read_wasm("ESCROW_DST_WASM", DEFAULT_ESCROW_DST_WASM)?;
+    let config = NearDeploymentConfig::from_env();
+    deploy_and_register_templates_with(
+        &FundingTemplateDeployer,
+        &config.escrow_factory,
+        (&config.escrow_src_template, &config.escrow_dst_template),
+        src_code,
+        dst_code,
+    )
+    .await
+}
+
+/// `templates` are the (src, dst) template account ids
+pub async fn deploy_and_register_templates_with(
+    deployer: &dyn TemplateDeployer,
+    factory: &str,
+    templates: (&str, &str),
+    src_code: Vec<u8>,
+    dst_code: Vec<u8>,
+) -> Result<FactoryTemplates, String> {
+    let (src_template, dst_template) = (templates.0.to_string(), templates.1.to_string());
+    check_named_account(&src_template)?;
+    check_named_account(&dst_template)?;
+
+    deployer.publish_global_contract(&src_template, src_code).await?;
+    deployer.publish_global_contract(&dst_template, dst_code).await?;
+
+    deployer.call_factory(factory, "set_escrow_src_template", json!({ "template": src_template })).await?;
+    deployer.call_factory(factory, "set_escrow_dst_template", json!({ "template": dst_template })).await?;
+
+    let registered = deployer.get_factory_templates(factory).await?;
+    if registered.escrow_src_template != src_template || registered.escrow_dst_template != dst_template {
+        return Err(format!(
+            "Factory {} reports templates {} / {}, expected {} / {}",
+            factory, registered.escrow_src_template, registered.escrow_dst_template, src_template, dst_template
+        ));
+    }
+    println!("Factory {} now uses templates {} and {}", factory, src_template, dst_template);
+    Ok(registered)
+}
+
+/// Implicit accounts can hold a global contract but are no valid factory template ids to configure
+fn check_named_account(account: &str) -> Result<(), String> {
+    AccountId::from_str(account).map_err(|e| format!("Invalid template account {}: {}", account, e))?;
+    let near_implicit = account.len() == 64 && account.bytes().all(|b| b.is_ascii_hexdigit());
+    let eth_implicit = account.len() == 42 && account.starts_with("0x");
+    if near_implicit || eth_implicit {
+        return Err(format!("Template account {} must be a named account", account));
+    }
+    Ok(())
+}
+
+fn read_wasm(env_var: &str, default_path: &str) -> Result<Vec<u8>, String> {
+    let path = env::var(env_var).unwrap_or_else(|_| default_path.to_string());
+    std::fs::read(&path).map_err(|e| format!("Failed to read {} ({}): {}, run build.sh first", path, env_var, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct MockDeployer {
+        published: Mutex<Vec<String>>,
+        factory_calls: Mutex<Vec<(String, Value)>>,
+    }
+
+    #[async_trait]
+    impl TemplateDeployer for MockDeployer {
+        async fn publish_global_contract(&self, account: &str, _code: Vec<u8>) -> Result<(), String> {
+            self.published.lock().unwrap().push(account.to_string());
+            Ok(())
+        }
+
+        async fn call_factory(&self, _factory: &str, method: &str, args: Value) -> Result<(), String> {
+            self.factory_calls.lock().unwrap().push((method.to_string(), args));
+            Ok(())
+        }
+
+        async fn get_factory_templates(&self, _factory: &str) -> Result<FactoryTemplates, String> {
+            // Reflect whatever the setters were called with
+            let calls = self.factory_calls.lock().unwrap();
+            let template = |method: &str| {
+                calls.iter().find(|(m, _)| m == method).map(|(_, args)| args["template"].as_str().unwrap().to_string())
+            };
+            Ok(FactoryTemplates {
+                escrow_src_template: template("set_escrow_src_template").unwrap_or_default(),
+                escrow_dst_template: template("set_escrow_dst_template").unwrap_or_default(),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_setters_called_with_deployed_templates() {
+        let deployer = MockDeployer::default();
+
+        let registered = deploy_and_register_templates_with(
+            &deployer,
+            "factory.testnet",
+            ("src-template.testnet", "dst-template.testnet"),
+            vec![1],
+            vec![2],
+        )
+        .await
+        .unwrap();
+
+        let published = deployer.published.lock().unwrap().clone();
+        assert_eq!(published, vec!["src-template.testnet", "dst-template.testnet"]);
+        assert_eq!(
+            *deployer.factory_calls.lock().unwrap(),
+            vec![
+                ("set_escrow_src_template".to_string(), json!({ "template": published[0] })),
+                ("set_escrow_dst_template".to_string(), json!({ "template": published[1] })),
+            ]
+        );
+        assert_eq!(registered.escrow_src_template, published[0]);
+        assert_eq!(registered.escrow_dst_template, published[1]);
+    }
+
+    #[tokio::test]
+    async fn test_implicit_template_account_rejected() {
+        let deployer = MockDeployer::default();
+        let implicit = "a".repeat(64);
+
+        let err = deploy_and_register_templates_with(&deployer, "factory.testnet", (&implicit, "dst-template.testnet"), vec![1], vec![2])
+            .await
+            .unwrap_err();
+        assert!(err.contains("must be a named account"));
+        assert!(deployer.published.lock().unwrap().is_empty());
+    }
+}
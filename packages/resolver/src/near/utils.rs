@@ -1,21 +1,26 @@
 use std::{env, str::FromStr, sync::LazyLock};
 use borsh::BorshDeserialize;
 use k256::{elliptic_curve::rand_core::le, sha2::Sha256};
-use near_api::{Account, AccountId, Chain, NearToken};
+use async_trait::async_trait;
+use ::base64::Engine as _;
+use near_api::{Account, AccountId, Chain, Contract, Data, NearToken};
 use near_crypto::ED25519PublicKey;
 use omni_transaction::{near::{types::{Action, BlockHash, CreateAccountAction, DeleteAccountAction, ED25519Signature, FunctionCallAction, GlobalContractIdentifier, NonDelegateAction, Signature, TransferAction, UseGlobalContractAction, U128, U64}, utils::PublicKeyStrExt}, TransactionBuilder, TxBuilder, NEAR};
 use serde_json::json;
 use sha3::Digest;
 use near_primitives::action::base64;
 
-use crate::{agent::{agent_account_id, AgentConfig}, routes::near::get_address::{get_funding_near_address, get_funding_near_public_key, get_holding_near_address, get_holding_near_public_key}, utils::json_bytes};
+use crate::{agent::{agent_account_id, AgentConfig, EddsaSignatureResponse}, near::config::NearDeploymentConfig, secret::{generate_secret, keccak_hashlock_from_secret}, routes::eth::get_address::get_funding_eth_address, routes::near::get_address::{get_funding_near_address, get_funding_near_public_key, get_holding_near_address, get_holding_near_public_key}, utils::{json_bytes, max_timelock_offset, validate_timelock_offset}};
 use serde::{Deserialize, Serialize};
+use crate::near::config::NEAR_NETWORK;
 
 #[derive(Serialize, Deserialize)]
 pub struct Order {
     pub maker: AccountId,
     pub taker: AccountId,
+    #[serde(with = "crate::utils::u128_string")]
     pub making_amount: u128,
+    #[serde(with = "crate::utils::u128_string")]
     pub taking_amount: u128,
     pub maker_asset: AccountId, // "near" for native NEAR
     pub taker_asset: String,    // ETH address of token on destination
@@ -28,14 +33,22 @@ pub struct OrderExtension {
     pub hashlock: String,
     pub src_chain_id: u64,
     pub dst_chain_id: u64,
+    #[serde(with = "crate::utils::u128_string")]
     pub src_safety_deposit: u128,
+    #[serde(with = "crate::utils::u128_string")]
     pub dst_safety_deposit: u128,
     pub timelocks: Timelocks,
+    /// Hash of the secret behind `hashlock`, carried into the immutables of both legs
+    #[serde(default)]
+    pub hash_algorithm: HashAlgo,
+    /// Receiver of the dst funds when the maker has no address on the destination chain
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dst_receiver: Option<String>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Timelocks {
-    pub deployed_at: u64, // Deployment timestamp (MUST match factory)
+    pub deployed_at: u64, // Deployment timestamp in ms (MUST match factory)
     pub src_withdrawal: u32,
     pub src_public_withdrawal: u32,
     pub src_cancellation: u32,
@@ -43,6 +56,22 @@ pub struct Timelocks {
     pub dst_withdrawal: u32,
     pub dst_public_withdrawal: u32,
     pub dst_cancellation: u32,
+    /// Leniency around phase boundaries in ms, 0 disables it (see the escrow phase checks)
+    #[serde(default)]
+    pub grace_ms: u32,
+}
+
+impl Timelocks {
+    /// Reject offsets above `max_offset` seconds, catching ms/s unit mistakes before the order is built
+    pub fn validate(&self, max_offset: u32) -> Result<(), String> {
+        validate_timelock_offset("src_withdrawal", self.src_withdrawal, max_offset)?;
+        validate_timelock_offset("src_public_withdrawal", self.src_public_withdrawal, max_offset)?;
+        validate_timelock_offset("src_cancellation", self.src_cancellation, max_offset)?;
+        validate_timelock_offset("src_public_cancellation", self.src_public_cancellation, max_offset)?;
+        validate_timelock_offset("dst_withdrawal", self.dst_withdrawal, max_offset)?;
+        validate_timelock_offset("dst_public_withdrawal", self.dst_public_withdrawal, max_offset)?;
+        validate_timelock_offset("dst_cancellation", self.dst_cancellation, max_offset)
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -52,9 +81,34 @@ pub struct Immutables {
     pub maker: AccountId,
     pub taker: AccountId,
     pub token: AccountId,
+    #[serde(with = "crate::utils::u128_string")]
     pub amount: u128,
+    #[serde(with = "crate::utils::u128_string")]
     pub safety_deposit: u128,
     pub timelocks: Timelocks,
+    /// Accounts allowed to complete the B3 public withdrawal, None keeps it fully public
+    #[serde(default)]
+    pub public_withdrawal_allowlist: Option<Vec<AccountId>>,
+    /// Source and destination chain ids, None for escrows created without an order extension
+    #[serde(default)]
+    pub chain_context: Option<ChainContext>,
+    #[serde(default)]
+    pub hash_algorithm: HashAlgo,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ChainContext {
+    pub src_chain_id: u64,
+    pub dst_chain_id: u64,
+}
+
+/// Mirrors the escrows' HashAlgo, the hash function a hashlock commits with
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum HashAlgo {
+    #[default]
+    Sha256,
+    Keccak256,
 }
 
 pub async fn construct_sample_order() -> Order {
@@ -64,16 +118,17 @@ pub async fn construct_sample_order() -> Order {
         making_amount: 10, // 1 NEAR
         taking_amount: 10, // 1 ETH
         maker_asset: AccountId::from_str("3e2210e1184b45b64c8a434c0a7e7b23cc04ea7eb7a6c3c32520d03d4afcb8af").unwrap(),
-        taker_asset: AccountId::from_str("3e2210e1184b45b64c8a434c0a7e7b23cc04ea7eb7a6c3c32520d03d4afcb8af").unwrap().to_string(), // Example ETH address
+        taker_asset: "0x1c7d4b196cb0c7b01d743fbc6116a902379c7238".to_string(), // USDC on Sepolia
         salt: "example-salt".to_string(),
         extension: OrderExtension {
-            hashlock: "example-hashlock".to_string(),
+            // The dst chain is an EVM chain, which only verifies keccak256
+            hashlock: keccak_hashlock_from_secret(&generate_secret()).unwrap(),
             src_chain_id: 11155111, // Sepolia Testnet Chain ID
             dst_chain_id: 1, // Mainnet Chain ID
             src_safety_deposit: NearToken::from_yoctonear(10000).as_yoctonear(), // 0.01 NEAR
             dst_safety_deposit: 10000000000000000, // 0.01 ETH
             timelocks: Timelocks {
-                deployed_at: 1700000000, // Example timestamp
+                deployed_at: 1700000000000, // Example timestamp (ms)
                 src_withdrawal: 3600,
                 src_public_withdrawal: 7200,
                 src_cancellation: 10800,
@@ -81,7 +136,10 @@ pub async fn construct_sample_order() -> Order {
                 dst_withdrawal: 3600,
                 dst_public_withdrawal: 7200,
                 dst_cancellation: 10800,
+                grace_ms: 0,
             },
+            hash_algorithm: HashAlgo::Keccak256,
+            dst_receiver: None,
         },
     };
     order
@@ -109,11 +167,13 @@ pub async fn get_signature(encoded_tx: Vec<u8>, path: &str) -> Option<Signature>
     let signature_data = request_signature_result.unwrap();
     println!("Signature data: {:?}", signature_data);
 
-    let signature_bytes = signature_data["signature"].as_array().expect("Failed to get signature array");
-    let signature_u8_vec: Vec<u8> = signature_bytes.iter()
-        .map(|v| v.as_u64().expect("Failed to convert to u64") as u8)
-        .collect();
-    let signature_array: [u8; 64] = signature_u8_vec.try_into().expect("Signature must be exactly 64 bytes");
+    let signature_array = match EddsaSignatureResponse::from_value(signature_data).and_then(|r| r.to_bytes()) {
+        Ok(signature_array) => signature_array,
+        Err(e) => {
+            eprintln!("Failed to parse signature: {}", e);
+            return None;
+        }
+    };
 
     Some(Signature::ED25519(ED25519Signature::try_from_slice(&signature_array).unwrap()))
 }
@@ -127,47 +187,64 @@ pub async fn request_sign_holding(encoded_tx: Vec<u8>) -> Option<Signature> {
 }
 
 pub async fn send_transaction(signed_tx: Vec<u8>, signer_id: String){
+    if let Err(e) = try_send_transaction(signed_tx, signer_id).await {
+        eprintln!("{}", e);
+    }
+}
+
+/// Like `send_transaction` but surfaces RPC and execution failures to the caller
+pub async fn try_send_transaction(signed_tx: Vec<u8>, signer_id: String) -> Result<String, String> {
+    try_send_transaction_until(signed_tx, signer_id, "INCLUDED_FINAL").await
+}
+
+/// `try_send_transaction` waiting for `wait_until`, "FINAL" when the caller needs the return value of
+/// the whole receipt chain
+pub async fn try_send_transaction_until(signed_tx: Vec<u8>, signer_id: String, wait_until: &str) -> Result<String, String> {
     let base64_tx = base64(&signed_tx);
     println!("{}", base64_tx);
 
 
-    let near_testnet_url = near_api::RPCEndpoint::testnet().url.to_string();
+    let near_rpc_url = NEAR_NETWORK.rpc_url();
     let request_body = serde_json::json!({
         "jsonrpc": "2.0",
         "id": signer_id,
         "method": "send_tx",
         "params": {
             "signed_tx_base64": base64_tx,
-            "wait_until": "INCLUDED_FINAL"
+            "wait_until": wait_until
         }
     });
 
     let client = reqwest::Client::new();
     let response = client
-        .post(&near_testnet_url)
+        .post(&near_rpc_url)
         .header("Content-Type", "application/json")
         .json(&request_body)
         .send()
-        .await;
-
-    match response {
-        Ok(resp) => {
-            let status = resp.status();
-            let body = resp.text().await.unwrap_or_else(|_| "Failed to read response body".to_string());
-            println!("Response status: {}", status);
-            println!("Response body: {}", body);
-        }
-        Err(e) => {
-            eprintln!("Failed to send transaction: {:?}", e);
-        }
+        .await
+        .map_err(|e| format!("Failed to send transaction: {:?}", e))?;
+
+    let status = response.status();
+    let body = response.text().await.unwrap_or_else(|_| "Failed to read response body".to_string());
+    println!("Response status: {}", status);
+    println!("Response body: {}", body);
+
+    let parsed: serde_json::Value = serde_json::from_str(&body)
+        .map_err(|e| format!("Invalid RPC response: {}", e))?;
+    if let Some(error) = parsed.get("error") {
+        return Err(format!("Transaction rejected: {}", error));
+    }
+    if let Some(failure) = parsed["result"]["status"].get("Failure") {
+        return Err(format!("Transaction failed: {}", failure));
     }
+    Ok(body)
 }
 
 pub async fn create_near_funding_account() {
 
     let signer_id = get_funding_near_address().await;
 
-    let block_hash = Chain::block_hash().fetch_from_testnet().await.unwrap();
+    let block_hash = Chain::block_hash().fetch_from(&NEAR_NETWORK.network_config()).await.unwrap();
     
     let signer_account_id = AccountId::from_str(&signer_id.clone()).expect("Invalid NEAR account ID");
     let signer_public_key = get_funding_near_public_key().await;
@@ -187,7 +264,7 @@ pub async fn create_near_funding_account() {
 pub async fn delete_near_account() {
     let signer_id = get_funding_near_address().await;
 
-    let block_hash = Chain::block_hash().fetch_from_testnet().await.unwrap();
+    let block_hash = Chain::block_hash().fetch_from(&NEAR_NETWORK.network_config()).await.unwrap();
     
     let signer_account_id = AccountId::from_str(&signer_id.clone()).expect("Invalid NEAR account ID");
     let signer_public_key = get_funding_near_public_key().await;
@@ -201,7 +278,7 @@ pub async fn delete_near_account() {
             .access_key(
                 near_crypto::PublicKey::ED25519(ED25519PublicKey(signer_public_key_bytes))
             )
-            .fetch_from_testnet()
+            .fetch_from(&NEAR_NETWORK.network_config())
             .await;
     
     if (nonce_data.is_err()) {
@@ -236,7 +313,7 @@ pub async fn delete_near_account() {
 
 pub async fn setup_near_account_from_agent() {
 
-    let block_hash = Chain::block_hash().fetch_from_testnet().await.unwrap();
+    let block_hash = Chain::block_hash().fetch_from(&NEAR_NETWORK.network_config()).await.unwrap();
 
     let signer_account_id = AccountId::from_str(get_holding_near_address().await.to_string().as_str()).unwrap();
     let signer_public_key = get_holding_near_public_key().await;
@@ -250,7 +327,7 @@ pub async fn setup_near_account_from_agent() {
             .access_key(
                 near_crypto::PublicKey::ED25519(ED25519PublicKey(signer_public_key_bytes))
             )
-            .fetch_from_testnet()
+            .fetch_from(&NEAR_NETWORK.network_config())
             .await.unwrap();
 
     let mut nonce = U64(nonce_data.data.nonce);
@@ -277,12 +354,125 @@ pub async fn setup_near_account_from_agent() {
 }
 
 /// deploy resolver contract if it doesn't exist
+/// Reads the owner of an initialized resolver contract, abstracted so setup can be tested without a chain
+#[async_trait]
+pub trait ResolverOwnerReader: Send + Sync {
+    /// None when no initialized resolver contract lives at `account`
+    async fn get_owner(&self, account: &str) -> Option<String>;
+}
+
+pub struct NearResolverOwnerReader;
+
+#[async_trait]
+impl ResolverOwnerReader for NearResolverOwnerReader {
+    async fn get_owner(&self, account: &str) -> Option<String> {
+        let account_id = AccountId::from_str(account).ok()?;
+        let owner: Data<String> = Contract(account_id)
+            .call_function("get_owner", json!({}))
+            .ok()?
+            .read_only()
+            .fetch_from(&NEAR_NETWORK.network_config())
+            .await
+            .ok()?;
+        Some(owner.data)
+    }
+}
+
+/// Reads the code hash of a global contract published under an account id
+#[async_trait]
+pub trait GlobalCodeHashReader: Send + Sync {
+    /// Base58 sha256 of the code, as NEAR reports code hashes
+    async fn get_global_code_hash(&self, account: &str) -> Result<String, String>;
+}
+
+pub struct NearGlobalCodeHashReader;
+
+#[async_trait]
+impl GlobalCodeHashReader for NearGlobalCodeHashReader {
+    async fn get_global_code_hash(&self, account: &str) -> Result<String, String> {
+        let request_body = json!({
+            "jsonrpc": "2.0",
+            "id": "dontcare",
+            "method": "query",
+            "params": {
+                "request_type": "view_global_contract_code_by_account_id",
+                "finality": "final",
+                "account_id": account
+            }
+        });
+        let response: serde_json::Value = reqwest::Client::new()
+            .post(NEAR_NETWORK.rpc_url())
+            .json(&request_body)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to query global contract {}: {}", account, e))?
+            .json()
+            .await
+            .map_err(|e| format!("Invalid RPC response for global contract {}: {}", account, e))?;
+        if let Some(error) = response.get("error") {
+            return Err(format!("Global contract {} not found: {}", account, error));
+        }
+        let code_base64 = response["result"]["code_base64"]
+            .as_str()
+            .ok_or(format!("No code published under global contract {}", account))?;
+        let code = ::base64::engine::general_purpose::STANDARD
+            .decode(code_base64)
+            .map_err(|e| format!("Invalid code for global contract {}: {:?}", account, e))?;
+        Ok(bs58::encode(Sha256::digest(code)).into_string())
+    }
+}
+
+/// Checks the global resolver code against the pinned hash, skipped with a warning when none is configured
+pub async fn verify_global_resolver_code(
+    reader: &dyn GlobalCodeHashReader,
+    config: &NearDeploymentConfig,
+) -> Result<(), String> {
+    let Some(expected) = &config.global_resolver_code_hash else {
+        println!("NEAR_GLOBAL_RESOLVER_CODE_HASH not set, deploying {} unverified", config.global_resolver_contract);
+        return Ok(());
+    };
+    let actual = reader.get_global_code_hash(&config.global_resolver_contract).await?;
+    if &actual != expected {
+        return Err(format!(
+            "Global resolver contract {} has code hash {}, expected {}",
+            config.global_resolver_contract, actual, expected
+        ));
+    }
+    Ok(())
+}
+
 pub async fn deploy_near_resolver_contract() {
+    if let Err(e) = deploy_near_resolver_contract_with(&NearResolverOwnerReader, &NearGlobalCodeHashReader).await {
+        eprintln!("Failed to deploy resolver contract: {}", e);
+    }
+}
+
+/// Deploys and initializes the resolver contract, skipping both if it is already initialized
+pub async fn deploy_near_resolver_contract_with(
+    reader: &dyn ResolverOwnerReader,
+    code_hash_reader: &dyn GlobalCodeHashReader,
+) -> Result<(), String> {
+    let config = NearDeploymentConfig::from_env();
+
     /// Deploy Resolver Contract
     /// The contract that needs to have the resolver code deployed
     let signer_id = get_funding_near_address().await;
 
-    let block_hash = Chain::block_hash().fetch_from_testnet().await.unwrap();
+    if let Some(owner) = reader.get_owner(&signer_id).await {
+        println!("Resolver contract already initialized on {} (owner {}), skipping deployment", signer_id, owner);
+        return Ok(());
+    }
+
+    // The contract records the resolver's ETH address as its taker identity on the ETH leg
+    let eth_identity = get_funding_eth_address();
+    if eth_identity.is_empty() {
+        return Err("Funding ETH address is not set up, run setup_funding_eth_address first".to_string());
+    }
+
+    verify_global_resolver_code(code_hash_reader, &config).await?;
+
+    let block_hash = Chain::block_hash().fetch_from(&NEAR_NETWORK.network_config()).await
+        .map_err(|e| format!("Failed to fetch block hash: {}", e))?;
     
     let signer_account_id = AccountId::from_str(&signer_id.clone()).expect("Invalid NEAR account ID");
     let signer_public_key = get_funding_near_public_key().await;
@@ -296,8 +486,9 @@ pub async fn deploy_near_resolver_contract() {
             .access_key(
                 near_crypto::PublicKey::ED25519(ED25519PublicKey(signer_public_key_bytes))
             )
-            .fetch_from_testnet()
-            .await.unwrap();
+            .fetch_from(&NEAR_NETWORK.network_config())
+            .await
+            .map_err(|e| format!("Failed to fetch access key nonce: {}", e))?;
 
     let mut nonce = U64(nonce_data.data.nonce);
 
@@ -305,7 +496,7 @@ pub async fn deploy_near_resolver_contract() {
     let global_contract_deploy_action = Action::UseGlobalContract(Box::new(
         UseGlobalContractAction {
             contract_identifier: GlobalContractIdentifier::AccountId(
-                AccountId::from_str("1prime-global-resolver-contract.testnet").unwrap()
+                AccountId::from_str(&config.global_resolver_contract).expect("Invalid global resolver contract account")
             ),
         }
     ));
@@ -313,13 +504,7 @@ pub async fn deploy_near_resolver_contract() {
     let contract_init_action = Action::FunctionCall(Box::new(
         FunctionCallAction {
             method_name: "new".to_string(),
-            args: json_bytes(json!(
-                {
-                    "owner": signer_id.clone(),
-                    "escrow_factory": "1prime-global-factory-contract.testnet",
-                    "dst_chain_resolver": "test"
-                }
-            )),
+            args: json_bytes(config.resolver_init_args(&signer_id, &eth_identity)),
             gas: U64(300000000000000), // 30 TGas
             deposit: U128(0)
         }
@@ -337,16 +522,21 @@ pub async fn deploy_near_resolver_contract() {
         .build();
 
     let encoded_tx = near_tx.build_for_signing();
-    let signature = request_sign_funding(encoded_tx).await.expect("Failed to get signature");
+    let signature = request_sign_funding(encoded_tx).await.ok_or("Failed to get signature")?;
     let signed_tx = near_tx.build_with_signature(signature);
-    send_transaction(signed_tx, signer_id).await;
+    try_send_transaction(signed_tx, signer_id).await.map(|_| ())
 }
 
-pub async fn deploy_near_src_contract(order: Order, order_signature: String, amount: u128) {
+pub async fn deploy_near_src_contract(order: Order, order_signature: String, amount: u128, correlation_id: String) {
+    if let Err(e) = order.extension.timelocks.validate(max_timelock_offset()) {
+        eprintln!("Refusing to deploy src escrow: {}", e);
+        return;
+    }
+
         /// The contract that needs to have the resolver code deployed
     let signer_id = get_funding_near_address().await;
 
-    let block_hash = Chain::block_hash().fetch_from_testnet().await.unwrap();
+    let block_hash = Chain::block_hash().fetch_from(&NEAR_NETWORK.network_config()).await.unwrap();
     
     let signer_account_id = AccountId::from_str(&signer_id.clone()).expect("Invalid NEAR account ID");
     let signer_public_key = get_funding_near_public_key().await;
@@ -359,7 +549,7 @@ pub async fn deploy_near_src_contract(order: Order, order_signature: String, amo
             .access_key(
                 near_crypto::PublicKey::ED25519(ED25519PublicKey(signer_public_key_bytes))
             )
-            .fetch_from_testnet()
+            .fetch_from(&NEAR_NETWORK.network_config())
             .await.unwrap();
 
     let mut nonce = U64(nonce_data.data.nonce);
@@ -371,7 +561,8 @@ pub async fn deploy_near_src_contract(order: Order, order_signature: String, amo
                 {
                     "order": order,
                     "order_signature": order_signature,
-                    "amount": amount,
+                    "amount": amount.to_string(),
+                    "correlation_id": correlation_id,
                 }
             )),
             gas: U64(300000000000000), // 30 TGas
@@ -396,14 +587,40 @@ pub async fn deploy_near_src_contract(order: Order, order_signature: String, amo
     send_transaction(signed_tx, signer_id).await;
 }
 
+/// Decodes the `SuccessValue` of a `send_tx` response body into the transaction's return value
+pub fn decode_success_value<T: serde::de::DeserializeOwned>(body: &str) -> Result<T, String> {
+    let parsed: serde_json::Value = serde_json::from_str(body)
+        .map_err(|e| format!("Invalid RPC response: {}", e))?;
+    let value = parsed["result"]["status"]["SuccessValue"]
+        .as_str()
+        .ok_or_else(|| format!("Transaction has no return value yet: {}", parsed["result"]["status"]))?;
+    let bytes = ::base64::engine::general_purpose::STANDARD
+        .decode(value)
+        .map_err(|e| format!("Invalid SuccessValue: {}", e))?;
+    serde_json::from_slice(&bytes).map_err(|e| format!("Unexpected return value: {}", e))
+}
+
+/// What the factory's create_dst_escrow returns through the resolver's deploy_dst
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct EscrowCreationResult {
+    pub escrow_account: String,
+    pub order_hash: String,
+    pub success: bool,
+    /// The dst escrow's actual timelock reference time in ms, the factory's block time unless pinned
+    #[serde(with = "crate::utils::u64_string")]
+    pub deployed_at: u64,
+}
+
 pub async fn deploy_near_dst_contract(
     dst_immutables: Immutables,
-    src_cancellation_timestamp: u64
-) {
+    src_cancellation_timestamp: u64,
+    correlation_id: &str,
+) -> Result<EscrowCreationResult, String> {
             /// The contract that needs to have the resolver code deployed
     let signer_id = get_funding_near_address().await;
 
-    let block_hash = Chain::block_hash().fetch_from_testnet().await.unwrap();
+    let block_hash = Chain::block_hash().fetch_from(&NEAR_NETWORK.network_config()).await
+        .map_err(|e| format!("Failed to fetch block hash: {}", e))?;
     
     let signer_account_id = AccountId::from_str(&signer_id.clone()).expect("Invalid NEAR account ID");
     let signer_public_key = get_funding_near_public_key().await;
@@ -416,22 +633,28 @@ pub async fn deploy_near_dst_contract(
             .access_key(
                 near_crypto::PublicKey::ED25519(ED25519PublicKey(signer_public_key_bytes))
             )
-            .fetch_from_testnet()
-            .await.unwrap();
+            .fetch_from(&NEAR_NETWORK.network_config())
+            .await
+            .map_err(|e| format!("Failed to fetch access key nonce: {}", e))?;
 
     let mut nonce = U64(nonce_data.data.nonce);
 
+    // The factory requires the native amount plus the safety deposit, or only the safety deposit
+    // for a NEP-141 escrow funded later through deposit_funds, and the maker index stake
+    let deposit = dst_creation_deposit(&dst_immutables);
+
     let deploy_src_contract_action = Action::FunctionCall(Box::new(
         FunctionCallAction {
             method_name: "deploy_dst".to_string(),
             args: json_bytes(json!(
                 {
                     "dst_immutables": dst_immutables,
-                    "src_cancellation_timestamp": src_cancellation_timestamp,
+                    "src_cancellation_timestamp": src_cancellation_timestamp.to_string(),
+                    "correlation_id": correlation_id,
                 }
             )),
             gas: U64(300000000000000), // 30 TGas
-            deposit: U128(0)
+            deposit: U128(deposit)
         }
     ));
 
@@ -447,7 +670,189 @@ pub async fn deploy_near_dst_contract(
         .build();
 
     let encoded_tx = near_tx.build_for_signing();
-    let signature = request_sign_funding(encoded_tx).await.expect("Failed to get signature");
+    let signature = request_sign_funding(encoded_tx).await.ok_or("Failed to get signature")?;
     let signed_tx = near_tx.build_with_signature(signature);
-    send_transaction(signed_tx, signer_id).await;
+    let body = try_send_transaction_until(signed_tx, signer_id, "FINAL").await?;
+    let result: EscrowCreationResult = decode_success_value(&body)?;
+    if !result.success {
+        return Err(format!("Factory failed to create dst escrow {}", result.escrow_account));
+    }
+    Ok(result)
+}
+
+/// Storage stake `create_src_escrow` expects on top of the escrow funds, as the factory reports it
+pub async fn get_src_escrow_storage_stake(factory: &str) -> Result<u128, String> {
+    let factory_id = AccountId::from_str(factory).map_err(|e| format!("Invalid factory account {:?}: {}", factory, e))?;
+    let stake: Data<String> = Contract(factory_id)
+        .call_function("src_escrow_storage_stake", json!({}))
+        .map_err(|e| format!("Failed to encode src_escrow_storage_stake call: {}", e))?
+        .read_only()
+        .fetch_from(&NEAR_NETWORK.network_config())
+        .await
+        .map_err(|e| format!("Failed to read src_escrow_storage_stake from {}: {}", factory, e))?;
+    stake.data.parse().map_err(|e| format!("Invalid src escrow storage stake {:?}: {}", stake.data, e))
+}
+
+/// Mirrors EscrowFactory::maker_index_storage_stake: 200 bytes at 10^19 yoctoNEAR per byte
+pub const MAKER_INDEX_STORAGE_STAKE: u128 = 200 * 10u128.pow(19);
+
+/// Deposit `create_dst_escrow` expects attached for `dst_immutables`
+pub fn dst_creation_deposit(dst_immutables: &Immutables) -> u128 {
+    let funds = if dst_immutables.token.as_str() == "near" {
+        dst_immutables.amount + dst_immutables.safety_deposit
+    } else {
+        dst_immutables.safety_deposit
+    };
+    funds + MAKER_INDEX_STORAGE_STAKE
+}
+
+/// Calls `cancel` on a NEAR escrow from the funding account, which receives the safety deposit
+pub async fn cancel_near_escrow(escrow_account: &str) -> Result<String, String> {
+    let cancel_action = Action::FunctionCall(Box::new(
+        FunctionCallAction {
+            method_name: "cancel".to_string(),
+            args: json_bytes(json!({})),
+            gas: U64(100000000000000), // 100 TGas, covers the fund and safety deposit transfers
+            deposit: U128(0)
+        }
+    ));
+    send_funding_actions(escrow_account, vec![cancel_action]).await
+}
+
+/// Signs `actions` against `receiver_id` with the funding account via MPC and sends them
+pub async fn send_funding_actions(receiver_id: &str, actions: Vec<Action>) -> Result<String, String> {
+    send_actions_with_funding_key(&get_funding_near_address().await, receiver_id, actions).await
+}
+
+/// Sends `actions` from `signer_id`, an account holding the funding key as a full access key
+pub async fn send_actions_with_funding_key(signer_id: &str, receiver_id: &str, actions: Vec<Action>) -> Result<String, String> {
+    let signer_id = signer_id.to_string();
+
+    let block_hash = Chain::block_hash().fetch_from(&NEAR_NETWORK.network_config()).await
+        .map_err(|e| format!("Failed to fetch block hash: {}", e))?;
+
+    let signer_account_id = AccountId::from_str(&signer_id.clone()).map_err(|e| format!("Invalid NEAR account ID: {}", e))?;
+    let signer_public_key = get_funding_near_public_key().await;
+    let signer_public_key_bytes: [u8; 32] = signer_public_key.to_public_key_as_bytes()
+        .expect("Failed to get public key bytes")
+        .try_into()
+        .expect("Public key must be exactly 32 bytes");
+
+    let nonce_data = Account(signer_account_id.clone())
+            .access_key(
+                near_crypto::PublicKey::ED25519(ED25519PublicKey(signer_public_key_bytes))
+            )
+            .fetch_from(&NEAR_NETWORK.network_config())
+            .await
+            .map_err(|e| format!("Failed to fetch access key nonce: {}", e))?;
+
+    let near_tx = omni_transaction::TransactionBuilder::new::<NEAR>()
+        .signer_id(signer_id.clone())
+        .receiver_id(receiver_id.to_string())
+        .nonce(nonce_data.data.nonce + 1)
+        .actions(actions)
+        .block_hash(BlockHash(block_hash.0))
+        .signer_public_key(signer_public_key.to_public_key().unwrap())
+        .build();
+
+    let encoded_tx = near_tx.build_for_signing();
+    let signature = request_sign_funding(encoded_tx).await.ok_or("Failed to get signature")?;
+    let signed_tx = near_tx.build_with_signature(signature);
+    try_send_transaction(signed_tx, signer_id).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routes::near::get_address::update_funding_near_address;
+
+    struct ExistingResolver;
+
+    #[async_trait]
+    impl ResolverOwnerReader for ExistingResolver {
+        async fn get_owner(&self, account: &str) -> Option<String> {
+            Some(account.to_string())
+        }
+    }
+
+    #[test]
+    fn test_dst_creation_result_decoded_from_final_outcome() {
+        let returned = json!({
+            "escrow_account": "escrow-3-0xabcdef.factory.testnet",
+            "order_hash": "0xabcdef",
+            "success": true,
+            "deployed_at": "1700000123456",
+        });
+        let body = json!({
+            "result": {
+                "status": { "SuccessValue": ::base64::engine::general_purpose::STANDARD.encode(returned.to_string()) }
+            }
+        });
+
+        let result: EscrowCreationResult = decode_success_value(&body.to_string()).unwrap();
+        assert_eq!(result.deployed_at, 1_700_000_123_456);
+        assert!(result.success);
+
+        let pending = json!({ "result": { "status": "Started" } });
+        assert!(decode_success_value::<EscrowCreationResult>(&pending.to_string()).is_err());
+    }
+
+    #[test]
+    fn test_dst_creation_deposit_covers_native_amount() {
+        let immutables = |token: &str| -> Immutables {
+            serde_json::from_value(json!({
+                "order_hash": "0xabcdef",
+                "hashlock": "ab".repeat(32),
+                "maker": "maker.testnet",
+                "taker": "resolver.testnet",
+                "token": token,
+                "amount": "1000",
+                "safety_deposit": "100",
+                "timelocks": {
+                    "deployed_at": 0,
+                    "src_withdrawal": 60,
+                    "src_public_withdrawal": 120,
+                    "src_cancellation": 180,
+                    "src_public_cancellation": 240,
+                    "dst_withdrawal": 30,
+                    "dst_public_withdrawal": 90,
+                    "dst_cancellation": 150,
+                },
+            }))
+            .unwrap()
+        };
+
+        assert_eq!(dst_creation_deposit(&immutables("near")), 1_100 + MAKER_INDEX_STORAGE_STAKE);
+        assert_eq!(dst_creation_deposit(&immutables("usdc.testnet")), 100 + MAKER_INDEX_STORAGE_STAKE);
+    }
+
+    #[tokio::test]
+    async fn test_deploy_resolver_skips_when_already_initialized() {
+        update_funding_near_address("resolver.1prime.testnet".to_string());
+        assert!(deploy_near_resolver_contract_with(&ExistingResolver, &PublishedCode("unused")).await.is_ok());
+    }
+
+    struct PublishedCode(&'static str);
+
+    #[async_trait]
+    impl GlobalCodeHashReader for PublishedCode {
+        async fn get_global_code_hash(&self, _account: &str) -> Result<String, String> {
+            Ok(self.0.to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_global_resolver_code_hash_must_match_pin() {
+        let config = NearDeploymentConfig {
+            global_resolver_code_hash: Some("8Zb1ZkqmLq3dkbV5vRoxUHHsHpRxBHpHHvbq7Q2xJcS1".to_string()),
+            ..NearDeploymentConfig::default()
+        };
+
+        assert!(verify_global_resolver_code(&PublishedCode("8Zb1ZkqmLq3dkbV5vRoxUHHsHpRxBHpHHvbq7Q2xJcS1"), &config).await.is_ok());
+
+        let err = verify_global_resolver_code(&PublishedCode("4hLFcQv1YDyRYRW5pxBj4dpJzsDZhFjL6JMxLPa9pUpS"), &config)
+            .await
+            .unwrap_err();
+        assert!(err.contains("expected 8Zb1ZkqmLq3dkbV5vRoxUHHsHpRxBHpHHvbq7Q2xJcS1"));
+    }
 }
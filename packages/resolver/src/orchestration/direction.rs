@@ -0,0 +1,141 @@
+use serde::{Deserialize, Serialize};
+
+/// EIP-155 chain id the contracts use for NEAR, which has none of its own
+pub const NEAR_CHAIN_ID: u64 = 397;
+pub const SEPOLIA_CHAIN_ID: u64 = 11155111;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Chain {
+    Ethereum,
+    Near,
+}
+
+impl Chain {
+    pub fn chain_id(&self) -> u64 {
+        match self {
+            Chain::Ethereum => SEPOLIA_CHAIN_ID,
+            Chain::Near => NEAR_CHAIN_ID,
+        }
+    }
+
+    pub fn from_chain_id(chain_id: u64) -> Result<Self, String> {
+        match chain_id {
+            SEPOLIA_CHAIN_ID => Ok(Chain::Ethereum),
+            NEAR_CHAIN_ID => Ok(Chain::Near),
+            other => Err(format!("Unsupported chain id {}", other)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AssetKind {
+    Erc20,
+    NativeNear,
+    Nep141,
+}
+
+/// Order hash algorithm of the source chain, the one both escrows are keyed by
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OrderHashAlgorithm {
+    /// `near_order_hash`, the NEAR resolver contract's `compute_order_hash`
+    NearSha256,
+    /// `evm_order_hash`, the Limit Order Protocol's EIP-712 hash
+    Eip712,
+}
+
+/// First orchestration step of a swap, the src escrow the resolver deploys
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OrchestrationEntrypoint {
+    /// `near::utils::deploy_near_src_contract`, then the ETH dst escrow
+    DeployNearSrc,
+    /// `eth::utils::deploy_eth_src_contract`, then `orchestration::eth_cancel::deploy_dst_or_cancel_src`
+    DeployEthSrc,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SwapDirection {
+    EthToNear,
+    NearToEth,
+}
+
+impl SwapDirection {
+    pub fn from_chain_ids(src_chain_id: u64, dst_chain_id: u64) -> Result<Self, String> {
+        match (Chain::from_chain_id(src_chain_id)?, Chain::from_chain_id(dst_chain_id)?) {
+            (Chain::Ethereum, Chain::Near) => Ok(SwapDirection::EthToNear),
+            (Chain::Near, Chain::Ethereum) => Ok(SwapDirection::NearToEth),
+            (src, dst) => Err(format!("No swap route from {:?} to {:?}", src, dst)),
+        }
+    }
+
+    pub fn source(&self) -> Chain {
+        match self {
+            SwapDirection::EthToNear => Chain::Ethereum,
+            SwapDirection::NearToEth => Chain::Near,
+        }
+    }
+
+    pub fn destination(&self) -> Chain {
+        match self {
+            SwapDirection::EthToNear => Chain::Near,
+            SwapDirection::NearToEth => Chain::Ethereum,
+        }
+    }
+
+    /// Asset kinds the maker may offer on the source chain
+    pub fn source_assets(&self) -> &'static [AssetKind] {
+        assets_on(self.source())
+    }
+
+    /// Asset kinds the maker may receive on the destination chain
+    pub fn destination_assets(&self) -> &'static [AssetKind] {
+        assets_on(self.destination())
+    }
+
+    pub fn order_hash_algorithm(&self) -> OrderHashAlgorithm {
+        match self.source() {
+            Chain::Near => OrderHashAlgorithm::NearSha256,
+            Chain::Ethereum => OrderHashAlgorithm::Eip712,
+        }
+    }
+
+    pub fn entrypoint(&self) -> OrchestrationEntrypoint {
+        match self.source() {
+            Chain::Near => OrchestrationEntrypoint::DeployNearSrc,
+            Chain::Ethereum => OrchestrationEntrypoint::DeployEthSrc,
+        }
+    }
+}
+
+fn assets_on(chain: Chain) -> &'static [AssetKind] {
+    match chain {
+        Chain::Ethereum => &[AssetKind::Erc20],
+        Chain::Near => &[AssetKind::NativeNear, AssetKind::Nep141],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_direction_source_and_destination() {
+        let near_to_eth = SwapDirection::from_chain_ids(NEAR_CHAIN_ID, SEPOLIA_CHAIN_ID).unwrap();
+        assert_eq!(near_to_eth, SwapDirection::NearToEth);
+        assert_eq!(near_to_eth.source(), Chain::Near);
+        assert_eq!(near_to_eth.destination(), Chain::Ethereum);
+        assert_eq!(near_to_eth.destination_assets(), &[AssetKind::Erc20]);
+        assert_eq!(near_to_eth.order_hash_algorithm(), OrderHashAlgorithm::NearSha256);
+        assert_eq!(near_to_eth.entrypoint(), OrchestrationEntrypoint::DeployNearSrc);
+
+        let eth_to_near = SwapDirection::from_chain_ids(SEPOLIA_CHAIN_ID, NEAR_CHAIN_ID).unwrap();
+        assert_eq!(eth_to_near, SwapDirection::EthToNear);
+        assert_eq!(eth_to_near.source(), Chain::Ethereum);
+        assert_eq!(eth_to_near.destination(), Chain::Near);
+        assert_eq!(eth_to_near.source_assets(), &[AssetKind::Erc20]);
+        assert_eq!(eth_to_near.order_hash_algorithm(), OrderHashAlgorithm::Eip712);
+        assert_eq!(eth_to_near.entrypoint(), OrchestrationEntrypoint::DeployEthSrc);
+
+        assert!(SwapDirection::from_chain_ids(NEAR_CHAIN_ID, NEAR_CHAIN_ID).is_err());
+    }
+}
@@ -0,0 +1,63 @@
+use std::str::FromStr;
+use async_trait::async_trait;
+use near_api::{AccountId, Contract, Data};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use crate::near::config::NEAR_NETWORK;
+
+/// Mirror of the escrow contracts' EscrowState as returned by `get_state`
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct EscrowState {
+    #[serde(default)]
+    pub is_funded: bool, // Only present on destination escrows
+    pub is_withdrawn: bool,
+    pub is_cancelled: bool,
+    pub revealed_secret: Option<String>,
+    #[serde(default, with = "crate::utils::option_u64_string")]
+    pub withdrawn_at: Option<u64>,
+    #[serde(default, with = "crate::utils::option_u64_string")]
+    pub cancelled_at: Option<u64>,
+}
+
+/// Source of truth for escrow state, abstracted so the orchestration can be tested without a chain
+#[async_trait]
+pub trait EscrowStateReader: Send + Sync {
+    async fn get_escrow_state(&self, escrow_account: &str) -> Result<EscrowState, String>;
+    /// Phase name as reported by the escrow's `get_current_phase`, e.g. "B1_FINALITY_LOCK"
+    async fn get_current_phase(&self, escrow_account: &str) -> Result<String, String>;
+}
+
+pub struct NearEscrowStateReader;
+
+#[async_trait]
+impl EscrowStateReader for NearEscrowStateReader {
+    async fn get_escrow_state(&self, escrow_account: &str) -> Result<EscrowState, String> {
+        let escrow_id = AccountId::from_str(escrow_account)
+            .map_err(|e| format!("Invalid escrow account {}: {}", escrow_account, e))?;
+
+        let state: Data<EscrowState> = Contract(escrow_id)
+            .call_function("get_state", json!({}))
+            .map_err(|e| format!("Failed to build get_state call: {}", e))?
+            .read_only()
+            .fetch_from(&NEAR_NETWORK.network_config())
+            .await
+            .map_err(|e| format!("Failed to fetch escrow state: {}", e))?;
+
+        Ok(state.data)
+    }
+
+    async fn get_current_phase(&self, escrow_account: &str) -> Result<String, String> {
+        let escrow_id = AccountId::from_str(escrow_account)
+            .map_err(|e| format!("Invalid escrow account {}: {}", escrow_account, e))?;
+
+        let phase: Data<String> = Contract(escrow_id)
+            .call_function("get_current_phase", json!({}))
+            .map_err(|e| format!("Failed to build get_current_phase call: {}", e))?
+            .read_only()
+            .fetch_from(&NEAR_NETWORK.network_config())
+            .await
+            .map_err(|e| format!("Failed to fetch escrow phase: {}", e))?;
+
+        Ok(phase.data)
+    }
+}
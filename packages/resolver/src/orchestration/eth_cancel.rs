@@ -0,0 +1,180 @@
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use async_trait::async_trait;
+use ethers::types::Address;
+use tokio::task::JoinHandle;
+
+use crate::{
+    eth::utils::{cancel_eth_escrow, Immutables as EthImmutables},
+    near::utils::{deploy_near_dst_contract, Immutables as NearImmutables},
+    orchestration::{
+        store::{SwapStatus, SwapStore, SWAP_STORE},
+        timelock_sync::{dst_deadlines, DstDeadlines},
+    },
+    utils::new_correlation_id,
+};
+
+/// Submits the ETH cancel, abstracted so the failure path can be tested without a chain
+#[async_trait]
+pub trait EthEscrowCanceller: Send + Sync {
+    async fn cancel(&self, escrow: Address, immutables: EthImmutables) -> Result<String, String>;
+}
+
+pub struct ResolverEthCanceller;
+
+#[async_trait]
+impl EthEscrowCanceller for ResolverEthCanceller {
+    async fn cancel(&self, escrow: Address, immutables: EthImmutables) -> Result<String, String> {
+        cancel_eth_escrow(escrow, immutables).await
+    }
+}
+
+/// A cancel scheduled for the ETH src escrow, `cancel_at` is a unix timestamp in seconds
+pub struct ScheduledCancel {
+    pub cancel_at: u64,
+    pub handle: JoinHandle<()>,
+}
+
+/// ETH -> NEAR leg: deploy the NEAR dst escrow, and if that fails while the ETH src escrow
+/// is already live, schedule its cancellation so the resolver's funds are not stranded.
+/// On success the dst deadlines are computed from the deployed_at the factory reports.
+pub async fn deploy_dst_or_cancel_src(
+    swap_id: &str,
+    dst_immutables: NearImmutables,
+    src_cancellation_timestamp: u64,
+    eth_escrow: Address,
+    eth_immutables: EthImmutables,
+) -> Result<DstDeadlines, String> {
+    let timelocks = dst_immutables.timelocks.clone();
+    // The NEAR dst leg shares the id the swap was tracked with
+    let correlation_id = SWAP_STORE
+        .get(swap_id)
+        .map(|record| record.correlation_id)
+        .filter(|correlation_id| !correlation_id.is_empty())
+        .unwrap_or_else(new_correlation_id);
+    match deploy_near_dst_contract(dst_immutables, src_cancellation_timestamp, &correlation_id).await {
+        Ok(result) => {
+            let deadlines = dst_deadlines(&timelocks, result.deployed_at);
+            println!(
+                "NEAR dst escrow {} for {} (correlation_id={}) deployed at {}, cancellable from {}",
+                result.escrow_account, swap_id, correlation_id, result.deployed_at, deadlines.cancellation
+            );
+            Ok(deadlines)
+        }
+        Err(e) => {
+            let scheduled = schedule_eth_cancel(
+                Arc::new(ResolverEthCanceller),
+                &SWAP_STORE,
+                swap_id,
+                eth_escrow,
+                eth_immutables,
+                unix_now(),
+            );
+            eprintln!(
+                "NEAR dst escrow creation failed for {} (correlation_id={}): {}. ETH cancel scheduled at {}",
+                swap_id, correlation_id, e, scheduled.cancel_at
+            );
+            Err(e)
+        }
+    }
+}
+
+pub fn schedule_eth_cancel(
+    canceller: Arc<dyn EthEscrowCanceller>,
+    store: &'static SwapStore,
+    swap_id: &str,
+    eth_escrow: Address,
+    eth_immutables: EthImmutables,
+    now: u64,
+) -> ScheduledCancel {
+    let cancel_at = eth_immutables.src_cancellation_start();
+    let delay = Duration::from_secs(cancel_at.saturating_sub(now));
+    let swap_id = swap_id.to_string();
+
+    store.set_status(
+        &swap_id,
+        SwapStatus::NeedsReview { reason: format!("Dst creation failed, ETH cancel pending at {}", cancel_at) },
+    );
+
+    let handle = tokio::spawn(async move {
+        tokio::time::sleep(delay).await;
+        let status = match canceller.cancel(eth_escrow, eth_immutables).await {
+            Ok(tx_hash) => {
+                println!("ETH escrow {:?} cancelled in {}", eth_escrow, tx_hash);
+                SwapStatus::Cancelled
+            }
+            Err(e) => SwapStatus::NeedsReview { reason: format!("ETH cancel failed: {}", e) },
+        };
+        store.set_status(&swap_id, status);
+    });
+
+    ScheduledCancel { cancel_at, handle }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use ethers::types::U256;
+    use lazy_static::lazy_static;
+    use crate::{eth::utils::create_timelocks, orchestration::store::SwapRecord};
+
+    lazy_static! {
+        static ref TEST_STORE: SwapStore = SwapStore::new();
+    }
+
+    #[derive(Default)]
+    struct MockCanceller {
+        calls: Mutex<Vec<Address>>,
+    }
+
+    #[async_trait]
+    impl EthEscrowCanceller for MockCanceller {
+        async fn cancel(&self, escrow: Address, _immutables: EthImmutables) -> Result<String, String> {
+            self.calls.lock().unwrap().push(escrow);
+            Ok("0xcancel".to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dst_failure_schedules_eth_cancel_at_src_cancellation() {
+        let deployed_at = 1_700_000_000;
+        let immutables = EthImmutables {
+            order_hash: [1u8; 32],
+            hashlock: [2u8; 32],
+            maker: Address::zero(),
+            taker: Address::zero(),
+            token: Address::zero(),
+            amount: U256::from(100),
+            safety_deposit: U256::from(1),
+            timelocks: create_timelocks(60, 120, 10800, 14400, 60, 120, 7200, deployed_at).unwrap(),
+        };
+        TEST_STORE.insert(SwapRecord {
+            order_hash: "0xdst-failed".to_string(),
+            fill_index: 0,
+            escrow_account: None,
+            status: SwapStatus::Deployed,
+            correlation_id: String::new(),
+        });
+
+        let canceller = Arc::new(MockCanceller::default());
+        let escrow = Address::repeat_byte(0xee);
+        let scheduled = schedule_eth_cancel(
+            canceller.clone(),
+            &TEST_STORE,
+            "0xdst-failed:0",
+            escrow,
+            immutables,
+            deployed_at as u64 + 10800, // Cancellation window just opened
+        );
+
+        assert_eq!(scheduled.cancel_at, deployed_at as u64 + 10800);
+        scheduled.handle.await.unwrap();
+        assert_eq!(*canceller.calls.lock().unwrap(), vec![escrow]);
+        assert_eq!(TEST_STORE.get("0xdst-failed:0").unwrap().status, SwapStatus::Cancelled);
+    }
+}
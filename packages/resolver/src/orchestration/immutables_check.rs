@@ -0,0 +1,167 @@
+use std::{fmt, str::FromStr};
+use async_trait::async_trait;
+use near_api::{AccountId, Contract, Data};
+use serde::Serialize;
+use serde_json::json;
+
+use crate::near::{config::NEAR_NETWORK, utils::Immutables};
+
+/// Why an escrow's on-chain immutables could not be confirmed
+#[derive(Debug, Clone, PartialEq)]
+pub enum Mismatch {
+    /// The escrow's `get_immutables` could not be read
+    Unavailable(String),
+    /// `field` holds `actual` on chain where `expected` was computed
+    Field { field: &'static str, expected: String, actual: String },
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Mismatch::Unavailable(e) => write!(f, "Escrow immutables unavailable: {}", e),
+            Mismatch::Field { field, expected, actual } => {
+                write!(f, "Escrow immutables differ in {}: expected {}, got {}", field, expected, actual)
+            }
+        }
+    }
+}
+
+/// Escrow `get_immutables` view, abstracted so the check can be tested without a chain
+#[async_trait]
+pub trait EscrowImmutablesReader: Send + Sync {
+    async fn get_immutables(&self, escrow_account: &str) -> Result<Immutables, String>;
+}
+
+pub struct NearEscrowImmutablesReader;
+
+#[async_trait]
+impl EscrowImmutablesReader for NearEscrowImmutablesReader {
+    async fn get_immutables(&self, escrow_account: &str) -> Result<Immutables, String> {
+        let escrow_id = AccountId::from_str(escrow_account)
+            .map_err(|e| format!("Invalid escrow account {}: {}", escrow_account, e))?;
+
+        let immutables: Data<Immutables> = Contract(escrow_id)
+            .call_function("get_immutables", json!({}))
+            .map_err(|e| format!("Failed to build get_immutables call: {}", e))?
+            .read_only()
+            .fetch_from(&NEAR_NETWORK.network_config())
+            .await
+            .map_err(|e| format!("Failed to fetch escrow immutables: {}", e))?;
+
+        Ok(immutables.data)
+    }
+}
+
+/// Confirms a deployed escrow holds exactly the immutables the resolver computed, run before the
+/// secret is revealed so a factory-side change cannot go unnoticed
+pub async fn verify_escrow_immutables(escrow_account: &str, expected: &Immutables) -> Result<(), Mismatch> {
+    verify_escrow_immutables_with(&NearEscrowImmutablesReader, escrow_account, expected).await
+}
+
+pub async fn verify_escrow_immutables_with(
+    reader: &dyn EscrowImmutablesReader,
+    escrow_account: &str,
+    expected: &Immutables,
+) -> Result<(), Mismatch> {
+    let actual = reader.get_immutables(escrow_account).await.map_err(Mismatch::Unavailable)?;
+    compare_immutables(expected, &actual)
+}
+
+/// Field-by-field comparison reporting the first field that differs. The factory stamps
+/// `deployed_at` itself, so it is only compared when the expected value is set.
+pub fn compare_immutables(expected: &Immutables, actual: &Immutables) -> Result<(), Mismatch> {
+    check("order_hash", &normalize_hex(&expected.order_hash), &normalize_hex(&actual.order_hash))?;
+    check("hashlock", &expected.hashlock, &actual.hashlock)?;
+    check("maker", &expected.maker, &actual.maker)?;
+    check("taker", &expected.taker, &actual.taker)?;
+    check("token", &expected.token, &actual.token)?;
+    check("amount", &expected.amount, &actual.amount)?;
+    check("safety_deposit", &expected.safety_deposit, &actual.safety_deposit)?;
+
+    let (e, a) = (&expected.timelocks, &actual.timelocks);
+    if e.deployed_at != 0 {
+        check("timelocks.deployed_at", &e.deployed_at, &a.deployed_at)?;
+    }
+    check("timelocks.src_withdrawal", &e.src_withdrawal, &a.src_withdrawal)?;
+    check("timelocks.src_public_withdrawal", &e.src_public_withdrawal, &a.src_public_withdrawal)?;
+    check("timelocks.src_cancellation", &e.src_cancellation, &a.src_cancellation)?;
+    check("timelocks.src_public_cancellation", &e.src_public_cancellation, &a.src_public_cancellation)?;
+    check("timelocks.dst_withdrawal", &e.dst_withdrawal, &a.dst_withdrawal)?;
+    check("timelocks.dst_public_withdrawal", &e.dst_public_withdrawal, &a.dst_public_withdrawal)?;
+    check("timelocks.dst_cancellation", &e.dst_cancellation, &a.dst_cancellation)?;
+    check("timelocks.grace_ms", &e.grace_ms, &a.grace_ms)?;
+
+    check("public_withdrawal_allowlist", &expected.public_withdrawal_allowlist, &actual.public_withdrawal_allowlist)?;
+    check("chain_context", &expected.chain_context, &actual.chain_context)?;
+    check("hash_algorithm", &expected.hash_algorithm, &actual.hash_algorithm)
+}
+
+fn check<T: Serialize + PartialEq>(field: &'static str, expected: &T, actual: &T) -> Result<(), Mismatch> {
+    if expected == actual {
+        return Ok(());
+    }
+    let show = |value: &T| serde_json::to_string(value).unwrap_or_default();
+    Err(Mismatch::Field { field, expected: show(expected), actual: show(actual) })
+}
+
+/// The factory stores order hashes 0x-prefixed and lowercase
+fn normalize_hex(value: &str) -> String {
+    let hex = value.strip_prefix("0x").or_else(|| value.strip_prefix("0X")).unwrap_or(value);
+    format!("0x{}", hex.to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn immutables(amount: &str) -> Immutables {
+        serde_json::from_value(json!({
+            "order_hash": "0xABCDEF",
+            "hashlock": "ab".repeat(32),
+            "maker": "maker.testnet",
+            "taker": "resolver.testnet",
+            "token": "near",
+            "amount": amount,
+            "safety_deposit": "100",
+            "timelocks": {
+                "deployed_at": 0,
+                "src_withdrawal": 60,
+                "src_public_withdrawal": 120,
+                "src_cancellation": 180,
+                "src_public_cancellation": 240,
+                "dst_withdrawal": 30,
+                "dst_public_withdrawal": 90,
+                "dst_cancellation": 150,
+            },
+        }))
+        .unwrap()
+    }
+
+    /// Returns what the factory would have deployed, with `amount` as stored on chain
+    struct DeployedEscrow(&'static str);
+
+    #[async_trait]
+    impl EscrowImmutablesReader for DeployedEscrow {
+        async fn get_immutables(&self, _escrow_account: &str) -> Result<Immutables, String> {
+            let mut deployed = immutables(self.0);
+            deployed.order_hash = "0xabcdef".to_string();
+            deployed.timelocks.deployed_at = 1_700_000_000_000;
+            Ok(deployed)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_matching_immutables_verified() {
+        let result = verify_escrow_immutables_with(&DeployedEscrow("1000"), "escrow.testnet", &immutables("1000")).await;
+        assert_eq!(result, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn test_tampered_amount_reported() {
+        let result = verify_escrow_immutables_with(&DeployedEscrow("999"), "escrow.testnet", &immutables("1000")).await;
+        assert_eq!(
+            result,
+            Err(Mismatch::Field { field: "amount", expected: "1000".to_string(), actual: "999".to_string() })
+        );
+    }
+}
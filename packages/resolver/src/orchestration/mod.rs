@@ -0,0 +1,11 @@
+pub mod direction;
+pub mod escrow_state;
+pub mod immutables_check;
+pub mod eth_cancel;
+pub mod order_hash;
+pub mod reconcile;
+pub mod reveal;
+pub mod store;
+pub mod sweep;
+pub mod timelock_sync;
+pub mod withdrawal;
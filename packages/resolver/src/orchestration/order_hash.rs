@@ -0,0 +1,54 @@
+use ethers::{abi::Token, types::{Address, U256}, utils::keccak256};
+use k256::sha2::{Digest, Sha256};
+
+use crate::{eth::utils::Order as EthOrder, near::utils::Order as NearOrder};
+
+const ORDER_TYPE: &str = "Order(uint256 salt,address maker,address receiver,address makerAsset,address takerAsset,uint256 makingAmount,uint256 takingAmount,uint256 makerTraits)";
+const LOP_DOMAIN_NAME: &str = "1inch Limit Order Protocol";
+const LOP_DOMAIN_VERSION: &str = "4";
+
+/// Order hash as computed by the NEAR resolver contract's `compute_order_hash`
+pub fn near_order_hash(order: &NearOrder) -> String {
+    let mut data = format!(
+        "{}:{}:{}:{}:{}:{}:{}",
+        order.maker,
+        order.making_amount,
+        order.taking_amount,
+        order.maker_asset,
+        order.taker_asset,
+        order.salt,
+        order.extension.hashlock
+    );
+    if let Some(receiver) = &order.extension.dst_receiver {
+        data.push_str(&format!(":{}", receiver));
+    }
+    format!("0x{}", hex::encode(Sha256::digest(data.as_bytes())))
+}
+
+/// EIP-712 order hash used by the EVM Limit Order Protocol deployed at `lop` on `chain_id`
+pub fn evm_order_hash(order: &EthOrder, chain_id: u64, lop: Address) -> [u8; 32] {
+    let domain_separator = keccak256(ethers::abi::encode(&[
+        Token::FixedBytes(keccak256("EIP712Domain(string name,string version,uint256 chainId,address verifyingContract)").to_vec()),
+        Token::FixedBytes(keccak256(LOP_DOMAIN_NAME).to_vec()),
+        Token::FixedBytes(keccak256(LOP_DOMAIN_VERSION).to_vec()),
+        Token::Uint(U256::from(chain_id)),
+        Token::Address(lop),
+    ]));
+
+    let struct_hash = keccak256(ethers::abi::encode(&[
+        Token::FixedBytes(keccak256(ORDER_TYPE).to_vec()),
+        Token::Uint(order.salt),
+        Token::Uint(order.maker),
+        Token::Uint(order.receiver),
+        Token::Uint(order.maker_asset),
+        Token::Uint(order.taker_asset),
+        Token::Uint(order.making_amount),
+        Token::Uint(order.taking_amount),
+        Token::Uint(order.maker_traits),
+    ]));
+
+    let mut digest_input = vec![0x19, 0x01];
+    digest_input.extend_from_slice(&domain_separator);
+    digest_input.extend_from_slice(&struct_hash);
+    keccak256(digest_input)
+}
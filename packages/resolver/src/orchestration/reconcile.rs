@@ -0,0 +1,113 @@
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+use crate::orchestration::{
+    escrow_state::{EscrowStateReader, NearEscrowStateReader},
+    store::{SwapStatus, SwapStore, SWAP_STORE},
+};
+
+pub const RECONCILE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// A tracked swap whose store status disagreed with its escrow, the store now holds `actual`
+#[derive(Debug, PartialEq)]
+pub struct Discrepancy {
+    pub order_hash: String,
+    pub recorded: SwapStatus,
+    pub actual: SwapStatus,
+}
+
+/// Periodically corrects the store from chain, e.g. after a B3 public withdrawal or an
+/// out-of-band cancel the resolver did not drive itself
+pub fn spawn_reconciliation(interval: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            reconcile_once(&NearEscrowStateReader, &SWAP_STORE).await;
+        }
+    })
+}
+
+pub async fn reconcile_once(reader: &dyn EscrowStateReader, store: &SwapStore) -> Vec<Discrepancy> {
+    let mut discrepancies = Vec::new();
+
+    for record in store.active() {
+        let Some(escrow_account) = &record.escrow_account else {
+            continue; // Not deployed yet, nothing on chain to compare
+        };
+
+        let state = match reader.get_escrow_state(escrow_account).await {
+            Ok(state) => state,
+            Err(e) => {
+                eprintln!("Reconciliation skipped {}: {}", record.order_hash, e);
+                continue;
+            }
+        };
+
+        let actual = if state.is_withdrawn {
+            SwapStatus::Completed
+        } else if state.is_cancelled {
+            SwapStatus::Cancelled
+        } else {
+            continue;
+        };
+
+        eprintln!(
+            "Swap {} drifted from chain: store {:?}, escrow {} reports {:?}",
+            record.order_hash, record.status, escrow_account, actual
+        );
+        store.set_status(&record.swap_id(), actual.clone());
+        discrepancies.push(Discrepancy { order_hash: record.order_hash, recorded: record.status, actual });
+    }
+
+    discrepancies
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use crate::orchestration::{escrow_state::EscrowState, store::{swap_id, SwapRecord}};
+
+    struct MockReader(EscrowState);
+
+    #[async_trait]
+    impl EscrowStateReader for MockReader {
+        async fn get_escrow_state(&self, _escrow_account: &str) -> Result<EscrowState, String> {
+            Ok(self.0.clone())
+        }
+
+        async fn get_current_phase(&self, _escrow_account: &str) -> Result<String, String> {
+            Ok("B3_PUBLIC_WITHDRAWAL".to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_deployed_swap_withdrawn_on_chain_is_completed() {
+        let store = SwapStore::new();
+        store.insert(SwapRecord {
+            order_hash: "0xabc".to_string(),
+            fill_index: 0,
+            escrow_account: Some("escrow-1.factory.testnet".to_string()),
+            status: SwapStatus::Deployed,
+            correlation_id: String::new(),
+        });
+        let reader = MockReader(EscrowState {
+            is_withdrawn: true,
+            revealed_secret: Some("secret".to_string()),
+            ..Default::default()
+        });
+
+        let discrepancies = reconcile_once(&reader, &store).await;
+
+        assert_eq!(store.get(&swap_id("0xabc", 0)).unwrap().status, SwapStatus::Completed);
+        assert_eq!(
+            discrepancies,
+            vec![Discrepancy {
+                order_hash: "0xabc".to_string(),
+                recorded: SwapStatus::Deployed,
+                actual: SwapStatus::Completed,
+            }]
+        );
+    }
+}
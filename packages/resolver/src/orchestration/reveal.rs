@@ -0,0 +1,123 @@
+use std::env;
+use std::time::Duration;
+
+use crate::{
+    orchestration::{
+        escrow_state::{EscrowStateReader, NearEscrowStateReader},
+        store::{SwapStatus, SWAP_STORE},
+    },
+    secret::validate_secret,
+};
+
+/// Dst phases in which the withdrawal the secret unlocks can still happen. In B1 the resolver
+/// could cancel after learning the secret, from B4 on the dst funds can go back to the resolver.
+const DST_REVEAL_PHASES: [&str; 2] = ["B2_RESOLVER_EXCLUSIVE", "B3_PUBLIC_WITHDRAWAL"];
+const CONFIRMATION_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Number of consecutive reads that must agree the dst escrow is funded and past finality,
+/// overridable via DST_FUNDING_CONFIRMATIONS
+pub fn dst_funding_confirmations() -> u32 {
+    env::var("DST_FUNDING_CONFIRMATIONS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(1)
+        .max(1)
+}
+
+/// Accepts the maker's secret for an ETH -> NEAR swap only once the NEAR dst escrow is funded
+/// and in a withdrawal phase. Revealing outside of it lets the dst be cancelled once the secret is out.
+pub async fn accept_secret_reveal(swap_id: &str, dst_escrow: &str, secret_hex: &str) -> Result<(), String> {
+    let secret = hex::decode(secret_hex).map_err(|e| format!("Invalid secret hex: {}", e))?;
+    validate_secret(&secret)?;
+
+    confirm_dst_ready_for_reveal(
+        &NearEscrowStateReader,
+        dst_escrow,
+        dst_funding_confirmations(),
+        CONFIRMATION_INTERVAL,
+    )
+    .await?;
+
+    SWAP_STORE.set_status(swap_id, SwapStatus::Withdrawing);
+    Ok(())
+}
+
+pub async fn confirm_dst_ready_for_reveal(
+    reader: &dyn EscrowStateReader,
+    dst_escrow: &str,
+    confirmations: u32,
+    interval: Duration,
+) -> Result<(), String> {
+    for confirmation in 0..confirmations {
+        if confirmation > 0 {
+            tokio::time::sleep(interval).await;
+        }
+
+        let state = reader.get_escrow_state(dst_escrow).await?;
+        if !state.is_funded {
+            return Err(format!("Dst escrow {} is not funded yet", dst_escrow));
+        }
+        if state.is_withdrawn || state.is_cancelled {
+            return Err(format!("Dst escrow {} is already settled", dst_escrow));
+        }
+
+        let phase = reader.get_current_phase(dst_escrow).await?;
+        if !DST_REVEAL_PHASES.contains(&phase.as_str()) {
+            return Err(format!("Dst escrow {} is in {}, not in a withdrawal phase", dst_escrow, phase));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use crate::orchestration::escrow_state::EscrowState;
+
+    struct MockReader {
+        state: EscrowState,
+        phase: &'static str,
+    }
+
+    #[async_trait]
+    impl EscrowStateReader for MockReader {
+        async fn get_escrow_state(&self, _escrow_account: &str) -> Result<EscrowState, String> {
+            Ok(self.state.clone())
+        }
+
+        async fn get_current_phase(&self, _escrow_account: &str) -> Result<String, String> {
+            Ok(self.phase.to_string())
+        }
+    }
+
+    fn funded() -> EscrowState {
+        EscrowState { is_funded: true, ..Default::default() }
+    }
+
+    #[tokio::test]
+    async fn test_reveal_during_finality_lock_is_rejected() {
+        let reader = MockReader { state: funded(), phase: "B1_FINALITY_LOCK" };
+        let result = confirm_dst_ready_for_reveal(&reader, "escrow.testnet", 1, Duration::ZERO).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_reveal_after_finality_on_funded_escrow_is_accepted() {
+        let reader = MockReader { state: funded(), phase: "B2_RESOLVER_EXCLUSIVE" };
+        assert!(confirm_dst_ready_for_reveal(&reader, "escrow.testnet", 2, Duration::ZERO).await.is_ok());
+
+        let unfunded = MockReader { state: EscrowState::default(), phase: "B2_RESOLVER_EXCLUSIVE" };
+        assert!(confirm_dst_ready_for_reveal(&unfunded, "escrow.testnet", 1, Duration::ZERO).await.is_err());
+
+        let public = MockReader { state: funded(), phase: "B3_PUBLIC_WITHDRAWAL" };
+        assert!(confirm_dst_ready_for_reveal(&public, "escrow.testnet", 1, Duration::ZERO).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_reveal_during_cancellation_is_rejected() {
+        let reader = MockReader { state: funded(), phase: "B4_CANCELLATION" };
+        let err = confirm_dst_ready_for_reveal(&reader, "escrow.testnet", 1, Duration::ZERO).await.unwrap_err();
+        assert!(err.contains("B4_CANCELLATION"));
+    }
+}
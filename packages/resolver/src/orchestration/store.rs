@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+
+lazy_static! {
+    pub static ref SWAP_STORE: SwapStore = SwapStore::new();
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum SwapStatus {
+    Created,
+    Deployed,
+    Withdrawing,
+    Completed,
+    Cancelled,
+    /// The chain disagrees with what the resolver expected, needs manual review
+    NeedsReview { reason: String },
+}
+
+impl SwapStatus {
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, SwapStatus::Completed | SwapStatus::Cancelled)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SwapRecord {
+    pub order_hash: String,
+    /// Position among the fills of `order_hash`, 0 for an order filled in one go
+    #[serde(default)]
+    pub fill_index: u32,
+    pub escrow_account: Option<String>,
+    pub status: SwapStatus,
+    /// Passed to the NEAR contracts for every leg of this swap, see `utils::new_correlation_id`
+    #[serde(default)]
+    pub correlation_id: String,
+}
+
+impl SwapRecord {
+    pub fn swap_id(&self) -> String {
+        swap_id(&self.order_hash, self.fill_index)
+    }
+}
+
+/// Key a swap is tracked under, each partial fill of an order is its own swap
+pub fn swap_id(order_hash: &str, fill_index: u32) -> String {
+    format!("{}:{}", order_hash, fill_index)
+}
+
+/// In-memory store of the swaps the resolver is tracking, keyed by swap id
+pub struct SwapStore {
+    swaps: RwLock<HashMap<String, SwapRecord>>,
+}
+
+impl SwapStore {
+    pub fn new() -> Self {
+        Self { swaps: RwLock::new(HashMap::new()) }
+    }
+
+    pub fn insert(&self, record: SwapRecord) {
+        let mut swaps = self.swaps.write().unwrap();
+        swaps.insert(record.swap_id(), record);
+    }
+
+    pub fn get(&self, swap_id: &str) -> Option<SwapRecord> {
+        let swaps = self.swaps.read().unwrap();
+        swaps.get(swap_id).cloned()
+    }
+
+    /// Tracks `record` as the next fill of its order, assigning its fill index under the same lock
+    /// so concurrent fills of one order never share a swap id
+    pub fn insert_fill(&self, mut record: SwapRecord) -> SwapRecord {
+        let mut swaps = self.swaps.write().unwrap();
+        record.fill_index = swaps.values().filter(|other| other.order_hash == record.order_hash).count() as u32;
+        swaps.insert(record.swap_id(), record.clone());
+        record
+    }
+
+    /// Every fill of `order_hash` tracked so far, in fill order
+    pub fn fills_of(&self, order_hash: &str) -> Vec<SwapRecord> {
+        let swaps = self.swaps.read().unwrap();
+        let mut fills: Vec<SwapRecord> =
+            swaps.values().filter(|record| record.order_hash == order_hash).cloned().collect();
+        fills.sort_by_key(|record| record.fill_index);
+        fills
+    }
+
+    /// Snapshot of swaps not yet completed or cancelled
+    pub fn active(&self) -> Vec<SwapRecord> {
+        let swaps = self.swaps.read().unwrap();
+        swaps.values().filter(|record| !record.status.is_terminal()).cloned().collect()
+    }
+
+    /// Returns false if the swap is not tracked
+    pub fn set_status(&self, swap_id: &str, status: SwapStatus) -> bool {
+        let mut swaps = self.swaps.write().unwrap();
+        match swaps.get_mut(swap_id) {
+            Some(record) => {
+                record.status = status;
+                true
+            }
+            None => false,
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use std::time::Duration;
+use async_trait::async_trait;
+use tokio::task::JoinHandle;
+
+use crate::{
+    near::utils::cancel_near_escrow,
+    orchestration::{
+        escrow_state::{EscrowStateReader, NearEscrowStateReader},
+        store::{SwapStatus, SwapStore, SWAP_STORE},
+    },
+};
+
+pub const SWEEP_INTERVAL: Duration = Duration::from_secs(300);
+
+/// Phase in which anyone may cancel a src escrow, as reported by `get_current_phase`
+const PUBLIC_CANCELLATION_PHASE: &str = "A4_PUBLIC_CANCELLATION";
+
+/// Submits the NEAR cancel, abstracted so the sweep can be tested without a chain
+#[async_trait]
+pub trait NearEscrowCanceller: Send + Sync {
+    async fn cancel(&self, escrow_account: &str) -> Result<String, String>;
+}
+
+pub struct ResolverNearCanceller;
+
+#[async_trait]
+impl NearEscrowCanceller for ResolverNearCanceller {
+    async fn cancel(&self, escrow_account: &str) -> Result<String, String> {
+        cancel_near_escrow(escrow_account).await
+    }
+}
+
+/// Periodically cancels expired escrows so the safety deposit, which only goes to whoever
+/// calls `cancel`, returns to the resolver instead of sitting idle
+pub fn spawn_sweep(interval: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            sweep_once(&NearEscrowStateReader, &ResolverNearCanceller, &SWAP_STORE).await;
+        }
+    })
+}
+
+/// Cancels every tracked escrow in public cancellation that was neither withdrawn nor cancelled,
+/// returns the order hashes that were cancelled
+pub async fn sweep_once(
+    reader: &dyn EscrowStateReader,
+    canceller: &dyn NearEscrowCanceller,
+    store: &SwapStore,
+) -> Vec<String> {
+    let mut cancelled = Vec::new();
+
+    for record in store.active() {
+        let Some(escrow_account) = &record.escrow_account else {
+            continue;
+        };
+
+        match reader.get_current_phase(escrow_account).await {
+            Ok(phase) if phase == PUBLIC_CANCELLATION_PHASE => {}
+            Ok(_) => continue,
+            Err(e) => {
+                eprintln!("Sweep skipped {}: {}", record.order_hash, e);
+                continue;
+            }
+        }
+
+        // The phase is time based only, a settled escrow still reports A4
+        match reader.get_escrow_state(escrow_account).await {
+            Ok(state) if state.is_withdrawn || state.is_cancelled => continue,
+            Ok(_) => {}
+            Err(e) => {
+                eprintln!("Sweep skipped {}: {}", record.order_hash, e);
+                continue;
+            }
+        }
+
+        let status = match canceller.cancel(escrow_account).await {
+            Ok(_) => {
+                println!("Swept expired escrow {} for {}", escrow_account, record.order_hash);
+                cancelled.push(record.order_hash.clone());
+                SwapStatus::Cancelled
+            }
+            Err(e) => SwapStatus::NeedsReview { reason: format!("Sweep cancel of {} failed: {}", escrow_account, e) },
+        };
+        store.set_status(&record.swap_id(), status);
+    }
+
+    cancelled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use crate::orchestration::{escrow_state::EscrowState, store::{swap_id, SwapRecord}};
+
+    struct MockReader(&'static str);
+
+    #[async_trait]
+    impl EscrowStateReader for MockReader {
+        async fn get_escrow_state(&self, _escrow_account: &str) -> Result<EscrowState, String> {
+            Ok(EscrowState::default())
+        }
+
+        async fn get_current_phase(&self, _escrow_account: &str) -> Result<String, String> {
+            Ok(self.0.to_string())
+        }
+    }
+
+    #[derive(Default)]
+    struct MockCanceller {
+        calls: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl NearEscrowCanceller for MockCanceller {
+        async fn cancel(&self, escrow_account: &str) -> Result<String, String> {
+            self.calls.lock().unwrap().push(escrow_account.to_string());
+            Ok("cancel-tx".to_string())
+        }
+    }
+
+    fn tracked_store() -> SwapStore {
+        let store = SwapStore::new();
+        store.insert(SwapRecord {
+            order_hash: "0xexpired".to_string(),
+            fill_index: 0,
+            escrow_account: Some("escrow-1.factory.testnet".to_string()),
+            status: SwapStatus::Deployed,
+            correlation_id: String::new(),
+        });
+        store
+    }
+
+    #[tokio::test]
+    async fn test_escrow_in_public_cancellation_is_swept() {
+        let store = tracked_store();
+        let canceller = MockCanceller::default();
+
+        let cancelled = sweep_once(&MockReader("A4_PUBLIC_CANCELLATION"), &canceller, &store).await;
+
+        assert_eq!(cancelled, vec!["0xexpired".to_string()]);
+        assert_eq!(*canceller.calls.lock().unwrap(), vec!["escrow-1.factory.testnet".to_string()]);
+        assert_eq!(store.get(&swap_id("0xexpired", 0)).unwrap().status, SwapStatus::Cancelled);
+
+        // Before A4 the sweep leaves the escrow alone
+        let store = tracked_store();
+        let canceller = MockCanceller::default();
+        assert!(sweep_once(&MockReader("A3_PRIVATE_CANCELLATION"), &canceller, &store).await.is_empty());
+        assert!(canceller.calls.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,228 @@
+use std::env;
+
+use crate::{near::utils::Timelocks, orchestration::direction::{Chain, SwapDirection}};
+
+/// Mirrors the factory's default min_cancellation_gap_ms
+pub const MIN_CANCELLATION_GAP_MS: u64 = 5 * 60 * 1000;
+
+/// ~12 blocks at 12s, overridable via ETH_FINALITY_SECS
+pub const DEFAULT_ETH_FINALITY_SECS: u32 = 12 * 12;
+/// Doomslug finality is a couple of blocks, overridable via NEAR_FINALITY_SECS
+pub const DEFAULT_NEAR_FINALITY_SECS: u32 = 3;
+
+/// Each finality wait is multiplied by this before it becomes a lock window
+const FINALITY_SAFETY_FACTOR: u32 = 2;
+/// Fixed slack on top of every finality wait, covers RPC lag and the resolver's own polling
+const FINALITY_SAFETY_MARGIN_SECS: u32 = 60;
+/// Length of the resolver-exclusive and public windows on both legs
+const EXCLUSIVE_WINDOW_SECS: u32 = 10 * 60;
+const PUBLIC_WINDOW_SECS: u32 = 10 * 60;
+
+/// Seconds until a block on each chain can be treated as final
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChainFinality {
+    pub eth_secs: u32,
+    pub near_secs: u32,
+}
+
+impl ChainFinality {
+    pub fn from_env() -> Self {
+        let secs = |key: &str, default: u32| env::var(key).ok().and_then(|v| v.parse().ok()).unwrap_or(default);
+        Self {
+            eth_secs: secs("ETH_FINALITY_SECS", DEFAULT_ETH_FINALITY_SECS),
+            near_secs: secs("NEAR_FINALITY_SECS", DEFAULT_NEAR_FINALITY_SECS),
+        }
+    }
+
+    /// Finality wait padded with the safety factor and margin
+    fn lock_secs(&self, chain: Chain) -> u32 {
+        let secs = match chain {
+            Chain::Ethereum => self.eth_secs,
+            Chain::Near => self.near_secs,
+        };
+        secs * FINALITY_SAFETY_FACTOR + FINALITY_SAFETY_MARGIN_SECS
+    }
+}
+
+impl Default for ChainFinality {
+    fn default() -> Self {
+        Self { eth_secs: DEFAULT_ETH_FINALITY_SECS, near_secs: DEFAULT_NEAR_FINALITY_SECS }
+    }
+}
+
+/// Timelock offsets sized from the configured finality of both chains, for operators who would
+/// otherwise pick them by hand. deployed_at is left at 0 for the factory to fill in.
+pub fn recommend_timelocks(direction: SwapDirection) -> Timelocks {
+    recommend_timelocks_with(direction, &ChainFinality::from_env())
+}
+
+/// All offsets are relative to the src deployed_at, which `align_dst_timelocks` also gives the
+/// dst escrow. The dst escrow only lands once the src one is final, so its windows start after
+/// both finality waits, and src cancellation leaves the factory's minimum gap after dst
+/// cancellation plus another round of finality for the resolver to notice and act.
+pub fn recommend_timelocks_with(direction: SwapDirection, finality: &ChainFinality) -> Timelocks {
+    let src_lock = finality.lock_secs(direction.source());
+    let dst_lock = finality.lock_secs(direction.destination());
+    let gap_secs = MIN_CANCELLATION_GAP_MS.div_ceil(1000) as u32;
+
+    let dst_withdrawal = src_lock + dst_lock;
+    let dst_public_withdrawal = dst_withdrawal + EXCLUSIVE_WINDOW_SECS;
+    let dst_cancellation = dst_public_withdrawal + PUBLIC_WINDOW_SECS;
+
+    // The resolver withdraws on src with the secret revealed on dst, so src windows trail the dst ones
+    let src_withdrawal = dst_withdrawal + dst_lock;
+    let src_public_withdrawal = src_withdrawal + EXCLUSIVE_WINDOW_SECS;
+    let src_cancellation = (dst_cancellation + gap_secs + src_lock + dst_lock).max(src_public_withdrawal + PUBLIC_WINDOW_SECS);
+    let src_public_cancellation = src_cancellation + EXCLUSIVE_WINDOW_SECS;
+
+    Timelocks {
+        deployed_at: 0,
+        src_withdrawal,
+        src_public_withdrawal,
+        src_cancellation,
+        src_public_cancellation,
+        dst_withdrawal,
+        dst_public_withdrawal,
+        dst_cancellation,
+        grace_ms: 0,
+    }
+}
+
+/// Absolute src cancellation start in ms, the `src_cancellation_timestamp` the dst factory checks against
+pub fn src_cancellation_timestamp(timelocks: &Timelocks, src_deployed_at_ms: u64) -> u64 {
+    src_deployed_at_ms + timelocks.src_cancellation as u64 * 1000
+}
+
+/// Absolute dst phase starts in ms, derived from the deployed_at the factory actually used
+#[derive(Debug, Clone, PartialEq)]
+pub struct DstDeadlines {
+    pub withdrawal: u64,
+    pub public_withdrawal: u64,
+    pub cancellation: u64,
+}
+
+pub fn dst_deadlines(timelocks: &Timelocks, deployed_at_ms: u64) -> DstDeadlines {
+    let at = |offset: u32| deployed_at_ms + offset as u64 * 1000;
+    DstDeadlines {
+        withdrawal: at(timelocks.dst_withdrawal),
+        public_withdrawal: at(timelocks.dst_public_withdrawal),
+        cancellation: at(timelocks.dst_cancellation),
+    }
+}
+
+/// Dst timelocks sharing the src escrow's deployed_at, so a slow dst deployment cannot push the
+/// dst windows past the src ones. `src_deployed_at_ms` is the value recorded by the src escrow,
+/// not the time the order was built.
+pub fn align_dst_timelocks(timelocks: &Timelocks, src_deployed_at_ms: u64, now_ms: u64) -> Result<Timelocks, String> {
+    if src_deployed_at_ms == 0 || src_deployed_at_ms > now_ms {
+        return Err(format!("Src deployed_at {} is not a past block time", src_deployed_at_ms));
+    }
+
+    let mut aligned = timelocks.clone();
+    aligned.deployed_at = src_deployed_at_ms;
+
+    let dst_cancellation_start = src_deployed_at_ms + aligned.dst_cancellation as u64 * 1000;
+    if dst_cancellation_start + MIN_CANCELLATION_GAP_MS > src_cancellation_timestamp(&aligned, src_deployed_at_ms) {
+        return Err(format!(
+            "Dst cancellation offset {}s must precede src cancellation offset {}s by at least {}ms",
+            aligned.dst_cancellation, aligned.src_cancellation, MIN_CANCELLATION_GAP_MS
+        ));
+    }
+    if dst_cancellation_start <= now_ms {
+        return Err(format!(
+            "Dst cancellation would already have started ({} <= {}), too late to deploy the dst escrow",
+            dst_cancellation_start, now_ms
+        ));
+    }
+
+    Ok(aligned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::DEFAULT_MAX_TIMELOCK_OFFSET;
+
+    fn timelocks() -> Timelocks {
+        Timelocks {
+            deployed_at: 0,
+            src_withdrawal: 60,
+            src_public_withdrawal: 600,
+            src_cancellation: 1800,
+            src_public_cancellation: 3600,
+            dst_withdrawal: 60,
+            dst_public_withdrawal: 600,
+            dst_cancellation: 1500,
+            grace_ms: 0,
+        }
+    }
+
+    #[test]
+    fn test_dst_cancellation_precedes_src_across_deploy_gaps() {
+        let src_deployed_at = 1_700_000_000_000;
+        let src_cancellation = src_cancellation_timestamp(&timelocks(), src_deployed_at);
+
+        for gap_ms in [0, 5_000, 90_000, 600_000] {
+            let aligned = align_dst_timelocks(&timelocks(), src_deployed_at, src_deployed_at + gap_ms).unwrap();
+            assert_eq!(aligned.deployed_at, src_deployed_at);
+            let dst_cancellation = aligned.deployed_at + aligned.dst_cancellation as u64 * 1000;
+            assert!(dst_cancellation < src_cancellation, "gap {}ms", gap_ms);
+        }
+
+        // Past the dst cancellation start there is nothing left to deploy
+        assert!(align_dst_timelocks(&timelocks(), src_deployed_at, src_deployed_at + 1_500_000).is_err());
+    }
+
+    #[test]
+    fn test_dst_deadlines_follow_factory_deployed_at() {
+        // The caller left deployed_at at 0, the factory used its block time
+        let deadlines = dst_deadlines(&timelocks(), 1_700_000_042_000);
+        assert_eq!(deadlines.withdrawal, 1_700_000_102_000);
+        assert_eq!(deadlines.public_withdrawal, 1_700_000_642_000);
+        assert_eq!(deadlines.cancellation, 1_700_001_542_000);
+    }
+
+    #[test]
+    fn test_cancellation_gap_below_minimum_is_rejected() {
+        let src_deployed_at = 1_700_000_000_000;
+
+        // 1500s vs 1800s leaves exactly the 5 minute minimum
+        assert!(align_dst_timelocks(&timelocks(), src_deployed_at, src_deployed_at).is_ok());
+
+        let mut tight = timelocks();
+        tight.dst_cancellation = 1700;
+        let err = align_dst_timelocks(&tight, src_deployed_at, src_deployed_at).unwrap_err();
+        assert!(err.contains("at least"));
+    }
+
+    #[test]
+    fn test_recommended_timelocks_pass_factory_validation() {
+        let src_deployed_at = 1_700_000_000_000;
+
+        for direction in [SwapDirection::EthToNear, SwapDirection::NearToEth] {
+            let t = recommend_timelocks_with(direction, &ChainFinality::default());
+
+            assert!(t.validate(DEFAULT_MAX_TIMELOCK_OFFSET).is_ok(), "{:?}", direction);
+            assert!(t.src_withdrawal < t.src_public_withdrawal, "{:?}", direction);
+            assert!(t.src_public_withdrawal < t.src_cancellation, "{:?}", direction);
+            assert!(t.src_cancellation < t.src_public_cancellation, "{:?}", direction);
+            assert!(t.dst_withdrawal < t.dst_public_withdrawal, "{:?}", direction);
+            assert!(t.dst_public_withdrawal < t.dst_cancellation, "{:?}", direction);
+
+            // Holds even when the dst escrow lands right before its withdrawal window opens
+            let late = src_deployed_at + t.dst_withdrawal as u64 * 1000;
+            assert!(align_dst_timelocks(&t, src_deployed_at, late).is_ok(), "{:?}", direction);
+        }
+    }
+
+    #[test]
+    fn test_recommended_timelocks_scale_with_finality() {
+        let fast = recommend_timelocks_with(SwapDirection::EthToNear, &ChainFinality::default());
+        let slow = recommend_timelocks_with(
+            SwapDirection::EthToNear,
+            &ChainFinality { eth_secs: DEFAULT_ETH_FINALITY_SECS * 4, ..ChainFinality::default() },
+        );
+        assert!(slow.dst_withdrawal > fast.dst_withdrawal);
+        assert!(slow.src_cancellation > fast.src_cancellation);
+    }
+}
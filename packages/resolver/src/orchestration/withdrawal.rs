@@ -0,0 +1,92 @@
+use crate::orchestration::{escrow_state::{EscrowStateReader, NearEscrowStateReader}, store::{SwapStatus, SwapStore, SWAP_STORE}};
+
+/// Confirms a withdrawal against the escrow's on-chain state before marking the swap complete.
+/// An accepted transaction is not enough, the escrow must report `is_withdrawn` with the revealed secret.
+pub async fn confirm_withdrawal(escrow_account: &str, swap_id: &str) -> SwapStatus {
+    confirm_withdrawal_with(&NearEscrowStateReader, &SWAP_STORE, escrow_account, swap_id).await
+}
+
+pub async fn confirm_withdrawal_with(
+    reader: &dyn EscrowStateReader,
+    store: &SwapStore,
+    escrow_account: &str,
+    swap_id: &str,
+) -> SwapStatus {
+    let status = match reader.get_escrow_state(escrow_account).await {
+        Ok(state) if state.is_withdrawn && state.revealed_secret.is_some() => SwapStatus::Completed,
+        Ok(state) => SwapStatus::NeedsReview {
+            reason: format!(
+                "Withdrawal tx accepted but escrow {} reports is_withdrawn={}, revealed_secret={}",
+                escrow_account,
+                state.is_withdrawn,
+                state.revealed_secret.is_some()
+            ),
+        },
+        Err(e) => SwapStatus::NeedsReview {
+            reason: format!("Failed to read escrow {} state: {}", escrow_account, e),
+        },
+    };
+
+    if !store.set_status(swap_id, status.clone()) {
+        eprintln!("Swap {} is not tracked, status not recorded", swap_id);
+    }
+    status
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use crate::orchestration::{escrow_state::EscrowState, store::SwapRecord};
+
+    struct MockReader(EscrowState);
+
+    #[async_trait]
+    impl EscrowStateReader for MockReader {
+        async fn get_escrow_state(&self, _escrow_account: &str) -> Result<EscrowState, String> {
+            Ok(self.0.clone())
+        }
+
+        async fn get_current_phase(&self, _escrow_account: &str) -> Result<String, String> {
+            Ok("B2_RESOLVER_EXCLUSIVE".to_string())
+        }
+    }
+
+    fn tracked_store() -> SwapStore {
+        let store = SwapStore::new();
+        store.insert(SwapRecord {
+            order_hash: "0xabc".to_string(),
+            fill_index: 0,
+            escrow_account: Some("escrow-1.factory.testnet".to_string()),
+            status: SwapStatus::Withdrawing,
+            correlation_id: String::new(),
+        });
+        store
+    }
+
+    #[tokio::test]
+    async fn test_withdrawal_not_reflected_on_chain_is_not_completed() {
+        let store = tracked_store();
+        let reader = MockReader(EscrowState::default());
+
+        let status = confirm_withdrawal_with(&reader, &store, "escrow-1.factory.testnet", "0xabc:0").await;
+
+        assert!(matches!(status, SwapStatus::NeedsReview { .. }));
+        assert_ne!(store.get("0xabc:0").unwrap().status, SwapStatus::Completed);
+    }
+
+    #[tokio::test]
+    async fn test_withdrawal_confirmed_on_chain_is_completed() {
+        let store = tracked_store();
+        let reader = MockReader(EscrowState {
+            is_withdrawn: true,
+            revealed_secret: Some("deadbeef".to_string()),
+            ..Default::default()
+        });
+
+        let status = confirm_withdrawal_with(&reader, &store, "escrow-1.factory.testnet", "0xabc:0").await;
+
+        assert_eq!(status, SwapStatus::Completed);
+        assert_eq!(store.get("0xabc:0").unwrap().status, SwapStatus::Completed);
+    }
+}
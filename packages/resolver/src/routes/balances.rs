@@ -0,0 +1,132 @@
+use async_trait::async_trait;
+use axum::Json;
+use serde::Serialize;
+
+use crate::routes::{
+    eth::{get_address::get_funding_eth_address, get_balance::get_balance},
+    near::{get_address::get_funding_near_address, get_balance::try_get_near_balance},
+};
+
+#[derive(Serialize, Debug)]
+pub struct EthBalance {
+    pub address: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub balance_wei: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct NearBalance {
+    pub address: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub balance_yocto: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Balances {
+    pub eth: EthBalance,
+    pub near: NearBalance,
+}
+
+/// Per-chain balance lookups of the funding accounts, abstracted so the handler can be tested without RPCs
+#[async_trait]
+pub trait BalanceReader: Send + Sync {
+    async fn eth_address(&self) -> String;
+    async fn eth_balance(&self) -> Result<String, String>;
+    async fn near_address(&self) -> String;
+    async fn near_balance(&self) -> Result<String, String>;
+}
+
+pub struct FundingBalanceReader;
+
+#[async_trait]
+impl BalanceReader for FundingBalanceReader {
+    async fn eth_address(&self) -> String {
+        get_funding_eth_address()
+    }
+
+    async fn eth_balance(&self) -> Result<String, String> {
+        get_balance().await
+    }
+
+    async fn near_address(&self) -> String {
+        get_funding_near_address().await
+    }
+
+    async fn near_balance(&self) -> Result<String, String> {
+        try_get_near_balance().await
+    }
+}
+
+/// <summary>
+/// balances of the resolver's ETH and NEAR funding accounts, a failing chain is reported without failing the other
+/// </summary>
+pub async fn get_balances() -> Json<Balances> {
+    Json(get_balances_with(&FundingBalanceReader).await)
+}
+
+pub async fn get_balances_with(reader: &dyn BalanceReader) -> Balances {
+    let (eth_address, eth_balance, near_address, near_balance) = tokio::join!(
+        reader.eth_address(),
+        reader.eth_balance(),
+        reader.near_address(),
+        reader.near_balance(),
+    );
+
+    let (balance_wei, eth_error) = split(eth_balance);
+    let (balance_yocto, near_error) = split(near_balance);
+    Balances {
+        eth: EthBalance { address: eth_address, balance_wei, error: eth_error },
+        near: NearBalance { address: near_address, balance_yocto, error: near_error },
+    }
+}
+
+fn split(result: Result<String, String>) -> (Option<String>, Option<String>) {
+    match result {
+        Ok(balance) => (Some(balance), None),
+        Err(e) => (None, Some(e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct EthDown;
+
+    #[async_trait]
+    impl BalanceReader for EthDown {
+        async fn eth_address(&self) -> String {
+            "0x1111111111111111111111111111111111111111".to_string()
+        }
+
+        async fn eth_balance(&self) -> Result<String, String> {
+            Err("Getting balance has failed".to_string())
+        }
+
+        async fn near_address(&self) -> String {
+            "resolver.testnet".to_string()
+        }
+
+        async fn near_balance(&self) -> Result<String, String> {
+            Ok("5000000000000000000000000".to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_one_chain_failing_keeps_the_other() {
+        let balances = get_balances_with(&EthDown).await;
+
+        assert_eq!(balances.near.balance_yocto.as_deref(), Some("5000000000000000000000000"));
+        assert!(balances.near.error.is_none());
+        assert!(balances.eth.balance_wei.is_none());
+        assert_eq!(balances.eth.error.as_deref(), Some("Getting balance has failed"));
+
+        let json = serde_json::to_value(&balances).unwrap();
+        assert_eq!(json["eth"]["address"], "0x1111111111111111111111111111111111111111");
+        assert!(json["eth"].get("balance_wei").is_none());
+    }
+}
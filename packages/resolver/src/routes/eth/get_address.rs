@@ -1,10 +1,10 @@
 use near_api::{AccountId, Contract, Data};
-use serde_json::json;
 use sha3::{Digest, Keccak256};
 
-use crate::{agent::{request_signature, AgentConfig}, utils::{get_tee_account, get_testnet_mpc_signer_account_id}};
+use crate::{agent::{request_signature, AgentConfig}, utils::{get_tee_account, get_mpc_signer_account_id}};
 use std::sync::{Arc, RwLock};
 use lazy_static::lazy_static;
+use crate::near::config::{derived_public_key_args, MPC_DOMAINS, NEAR_NETWORK};
 
 lazy_static! {
     static ref FUNDING_ETH_ADDRESS: Arc<RwLock<String>> = Arc::new(RwLock::new(String::new()));
@@ -25,29 +25,53 @@ pub async fn get_funding_eth_address_await() -> String {
     funding_eth_address.clone()
 }
 
-pub async fn setup_funding_eth_address() {
+pub async fn setup_funding_eth_address() -> Result<(), String> {
     
-    let derived_address_data : Data<String> =Contract(get_testnet_mpc_signer_account_id().await)
-    .call_function("derived_public_key", json!(
-        {
-            "path": "oneprime-funding-eth",
-            "predecessor": std::env::var("NEXT_PUBLIC_contractId").unwrap()
-        }
+    let derived_address_data : Data<String> =Contract(get_mpc_signer_account_id().await)
+    .call_function("derived_public_key", derived_public_key_args(
+        "oneprime-funding-eth",
+        &std::env::var("NEXT_PUBLIC_contractId").unwrap(),
+        MPC_DOMAINS.secp256k1,
     ))
     .unwrap()
     .read_only()
-        .fetch_from_testnet()
+        .fetch_from(&NEAR_NETWORK.network_config())
     .await
     .expect("Failed to fetch etherum address");
 
-    let data = derived_address_data.data;
-    let base58_key = data.trim_start_matches("secp256k1:");
-    let pubkey_bytes = bs58::decode(base58_key.trim()).into_vec().expect("Failed to decode base58 public key");
+    update_funding_eth_address(eth_address_from_derived_key(&derived_address_data.data)?);
+    Ok(())
+}
+
+/// ETH address for an MPC-derived key, which must be an uncompressed secp256k1 key (domain_id 0)
+pub fn eth_address_from_derived_key(data: &str) -> Result<String, String> {
+    let base58_key = data.trim().strip_prefix("secp256k1:").ok_or_else(|| format!(
+        "Expected a secp256k1 key for an ETH address, got {:?}; check the derivation uses domain_id 0",
+        data
+    ))?;
+    let pubkey_bytes = bs58::decode(base58_key)
+        .into_vec()
+        .map_err(|e| format!("Failed to decode base58 public key: {}", e))?;
+    if pubkey_bytes.len() != 64 {
+        return Err(format!("Expected a 64 byte uncompressed secp256k1 key, got {} bytes", pubkey_bytes.len()));
+    }
     let hash = Keccak256::digest(&pubkey_bytes);
-    let eth_address = &hash[12..];
-    update_funding_eth_address(
-        format!("0x{}",hex::encode(eth_address))
-    );
+    Ok(format!("0x{}", hex::encode(&hash[12..])))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ed25519_key_rejected_for_eth_address() {
+        let err = eth_address_from_derived_key("ed25519:11111111111111111111111111111111").unwrap_err();
+        assert!(err.contains("secp256k1"));
+        assert!(err.contains("domain_id 0"));
+
+        let key = format!("secp256k1:{}", bs58::encode([1u8; 64]).into_string());
+        assert!(eth_address_from_derived_key(&key).unwrap().starts_with("0x"));
+    }
 }
 
 /* <summary>
@@ -60,7 +84,7 @@ pub async fn get_funding_eth_address() -> String{
 
     println!("TEE Sig: {:?}", get_tee_acc().await);
 
-    let derived_address_data : Data<String> =Contract(get_testnet_mpc_signer_account_id().await)
+    let derived_address_data : Data<String> =Contract(get_mpc_signer_account_id().await)
     .call_function("derived_public_key", json!(
         {
             "path": "eth-1",
@@ -69,7 +93,7 @@ pub async fn get_funding_eth_address() -> String{
     ))
     .unwrap()
     .read_only()
-    .fetch_from_testnet()
+    .fetch_from(&NEAR_NETWORK.network_config())
     .await
     .expect("Failed to fetch etherum address");
 
@@ -12,7 +12,7 @@ pub async fn get_balance() -> Result<String, String>{
         .map_err(|e| format!("Provider error: {}", e))?;
     
     let addr_str = get_funding_eth_address();
-    let addr = Address::from_str(&addr_str).unwrap();
+    let addr = Address::from_str(&addr_str).map_err(|e| format!("Invalid funding address {:?}: {}", addr_str, e))?;
     let balance_result = provider.get_balance(addr, None).await;
     if balance_result.is_err() {
         return Err("Getting balance has failed".into())
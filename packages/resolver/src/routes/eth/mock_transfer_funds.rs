@@ -6,7 +6,7 @@ use sha3::{Digest, Keccak256};
 use std::env;
 use std::str::FromStr;
 
-use crate::{agent::{request_signature, AgentConfig}, routes::eth::get_address::get_funding_eth_address};
+use crate::{agent::{request_signature, AgentConfig, EcdsaSignatureResponse}, routes::eth::get_address::get_funding_eth_address};
 
 pub async fn mock_transfer_funds() -> String{
     println!("Mock transfer funds called");
@@ -56,20 +56,20 @@ pub async fn mock_transfer_funds() -> String{
        return format!("Failed to get signature: {:?}", request_signature_result.err());
    }
 
-   let signature_data = request_signature_result.unwrap();
+   let signature_data = match EcdsaSignatureResponse::from_value(request_signature_result.unwrap()) {
+       Ok(signature_data) => signature_data,
+       Err(e) => return format!("Failed to parse signature: {}", e),
+   };
 
-   let big_r_hex = signature_data["big_r"]["affine_point"].as_str().expect("Failed to get big_r affine point").trim_start_matches("0x");
-   let s_hex = signature_data["s"]["scalar"].as_str().expect("Failed to get s scalar").trim_start_matches("0x");
-   let v = signature_data["recovery_id"].as_u64().expect("Failed to get recovery ID");
-
-   let big_r_hex_trimmed = &big_r_hex[2..];
-   let r_bytes = hex::decode(big_r_hex_trimmed).expect("Failed to decode big_r hex");
-   let s_bytes = hex::decode(s_hex).expect("Failed to decode s hex");
+   let (r_bytes, s_bytes) = match (signature_data.r_bytes(), signature_data.s_bytes()) {
+       (Ok(r), Ok(s)) => (r, s),
+       (Err(e), _) | (_, Err(e)) => return format!("Failed to parse signature: {}", e),
+   };
 
     let signature = Signature {
-        v: v as u64,
-        r: r_bytes.clone(),
-        s: s_bytes.clone(),
+        v: signature_data.recovery_id,
+        r: r_bytes,
+        s: s_bytes,
     };
 
     let signed_transaction = evm_tx.build_with_signature(&signature);
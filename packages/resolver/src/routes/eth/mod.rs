@@ -1,4 +1,7 @@
 pub mod get_address;
 pub mod get_balance;
 pub mod mock_transfer_funds;
-pub mod deploy_resolver;
\ No newline at end of file
+pub mod deploy_resolver;
+pub mod resolver_address;
+pub mod timelocks;
+pub mod traits;
@@ -0,0 +1,13 @@
+use crate::eth::utils::get_eth_resolver_contract_address;
+
+/// <summary>
+/// address of the ETH resolver contract, deployed this session or configured via ETH_RESOLVER_CONTRACT_ADDRESS
+/// </summary>
+
+pub async fn get_resolver_address() -> Result<String, String> {
+    let address = get_eth_resolver_contract_address();
+    if address.is_empty() {
+        return Err("ETH resolver contract not deployed, set ETH_RESOLVER_CONTRACT_ADDRESS or deploy it".to_string());
+    }
+    Ok(address)
+}
@@ -0,0 +1,67 @@
+use axum::extract::Query;
+use serde::Deserialize;
+
+use crate::eth::utils::create_timelocks;
+
+#[derive(Deserialize)]
+pub struct TimelocksQuery {
+    pub src_withdrawal: u32,
+    pub src_public_withdrawal: u32,
+    pub src_cancellation: u32,
+    pub src_public_cancellation: u32,
+    pub dst_withdrawal: u32,
+    pub dst_public_withdrawal: u32,
+    pub dst_cancellation: u32,
+    pub deployed_at: u32,
+}
+
+/// <summary>
+/// pack the timelock offsets into the EVM U256 layout, returned as 0x-prefixed hex
+/// </summary>
+
+pub async fn get_timelocks(Query(query): Query<TimelocksQuery>) -> Result<String, String> {
+    let timelocks = create_timelocks(
+        query.src_withdrawal,
+        query.src_public_withdrawal,
+        query.src_cancellation,
+        query.src_public_cancellation,
+        query.dst_withdrawal,
+        query.dst_public_withdrawal,
+        query.dst_cancellation,
+        query.deployed_at,
+    )?;
+    Ok(format!("{:#x}", timelocks))
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eth::utils::TimelocksBuilder;
+
+    #[tokio::test]
+    async fn test_get_timelocks_matches_builder() {
+        let query = TimelocksQuery {
+            src_withdrawal: 60,
+            src_public_withdrawal: 120,
+            src_cancellation: 10800,
+            src_public_cancellation: 14400,
+            dst_withdrawal: 60,
+            dst_public_withdrawal: 120,
+            dst_cancellation: 7200,
+            deployed_at: 1700000000,
+        };
+
+        let expected = TimelocksBuilder::new()
+            .set_stage_offset(0, 60)
+            .set_stage_offset(1, 120)
+            .set_stage_offset(2, 10800)
+            .set_stage_offset(3, 14400)
+            .set_stage_offset(4, 60)
+            .set_stage_offset(5, 120)
+            .set_stage_offset(6, 7200)
+            .set_deployed_at(1700000000)
+            .build();
+
+        let packed = get_timelocks(Query(query)).await.unwrap();
+        assert_eq!(packed, format!("{:#x}", expected));
+    }
+}
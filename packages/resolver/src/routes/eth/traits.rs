@@ -0,0 +1,163 @@
+use std::str::FromStr;
+
+use axum::Json;
+use ethers::types::{Address, U256};
+use serde::Deserialize;
+
+use crate::eth::utils::{MakerTraitsBuilder, TakerTraitsBuilder};
+
+/// Widest value the uint40 expiration, nonce/epoch and series slots hold
+const UINT40_MAX: u64 = (1 << 40) - 1;
+/// Widest value the 24-bit args interaction/extension length slots hold
+const UINT24_MAX: u32 = (1 << 24) - 1;
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct MakerTraitsRequest {
+    /// Only this taker may fill, only the low 80 bits of the address are packed
+    pub allowed_sender: Option<String>,
+    pub expiration: u64,
+    pub nonce_or_epoch: u64,
+    pub series: u64,
+    pub no_partial_fills: bool,
+    pub allow_multiple_fills: bool,
+    pub pre_interaction_call: bool,
+    pub post_interaction_call: bool,
+    pub need_check_epoch_manager: bool,
+    pub has_extension: bool,
+    pub use_permit2: bool,
+    pub unwrap_weth: bool,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct TakerTraitsRequest {
+    /// Decimal threshold amount, at most 185 bits
+    pub threshold: Option<String>,
+    pub args_interaction_length: u32,
+    pub args_extension_length: u32,
+    pub args_has_target: bool,
+    pub use_permit2: bool,
+    pub skip_maker_permit: bool,
+    pub unwrap_weth: bool,
+    pub is_making_amount: bool,
+}
+
+/// <summary>
+/// pack the Limit Order Protocol MakerTraits for an order, returned as 0x-prefixed hex
+/// </summary>
+pub async fn get_maker_traits(Json(request): Json<MakerTraitsRequest>) -> Result<String, String> {
+    for (field, value) in [
+        ("expiration", request.expiration),
+        ("nonce_or_epoch", request.nonce_or_epoch),
+        ("series", request.series),
+    ] {
+        if value > UINT40_MAX {
+            return Err(format!("{} {} does not fit in 40 bits", field, value));
+        }
+    }
+
+    let mut builder = MakerTraitsBuilder::new()
+        .set_expiration(request.expiration)
+        .set_nonce_or_epoch(request.nonce_or_epoch)
+        .set_series(request.series);
+    if let Some(sender) = &request.allowed_sender {
+        let sender = Address::from_str(sender).map_err(|e| format!("Invalid allowed_sender {}: {}", sender, e))?;
+        builder = builder.set_allowed_sender(sender);
+    }
+    for (enabled, flag) in [
+        (request.no_partial_fills, MakerTraitsBuilder::no_partial_fills as fn(MakerTraitsBuilder) -> MakerTraitsBuilder),
+        (request.allow_multiple_fills, MakerTraitsBuilder::allow_multiple_fills),
+        (request.pre_interaction_call, MakerTraitsBuilder::pre_interaction_call),
+        (request.post_interaction_call, MakerTraitsBuilder::post_interaction_call),
+        (request.need_check_epoch_manager, MakerTraitsBuilder::need_check_epoch_manager),
+        (request.has_extension, MakerTraitsBuilder::has_extension),
+        (request.use_permit2, MakerTraitsBuilder::use_permit2),
+        (request.unwrap_weth, MakerTraitsBuilder::unwrap_weth),
+    ] {
+        if enabled {
+            builder = flag(builder);
+        }
+    }
+    Ok(format!("{:#x}", builder.build()))
+}
+
+/// <summary>
+/// pack the Limit Order Protocol TakerTraits for a fill, returned as 0x-prefixed hex
+/// </summary>
+pub async fn get_taker_traits(Json(request): Json<TakerTraitsRequest>) -> Result<String, String> {
+    for (field, value) in [
+        ("args_interaction_length", request.args_interaction_length),
+        ("args_extension_length", request.args_extension_length),
+    ] {
+        if value > UINT24_MAX {
+            return Err(format!("{} {} does not fit in 24 bits", field, value));
+        }
+    }
+
+    let mut builder = TakerTraitsBuilder::new()
+        .set_args_interaction_length(request.args_interaction_length)
+        .set_args_extension_length(request.args_extension_length);
+    if let Some(threshold) = &request.threshold {
+        let threshold = U256::from_dec_str(threshold).map_err(|e| format!("Invalid threshold {}: {}", threshold, e))?;
+        if threshold.bits() > 185 {
+            return Err(format!("threshold {} does not fit in 185 bits", threshold));
+        }
+        builder = builder.set_threshold(threshold);
+    }
+    for (enabled, flag) in [
+        (request.args_has_target, TakerTraitsBuilder::args_has_target as fn(TakerTraitsBuilder) -> TakerTraitsBuilder),
+        (request.use_permit2, TakerTraitsBuilder::use_permit2),
+        (request.skip_maker_permit, TakerTraitsBuilder::skip_maker_permit),
+        (request.unwrap_weth, TakerTraitsBuilder::unwrap_weth),
+        (request.is_making_amount, TakerTraitsBuilder::is_making_amount),
+    ] {
+        if enabled {
+            builder = flag(builder);
+        }
+    }
+    Ok(format!("{:#x}", builder.build()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_maker_traits_flags_and_slots() {
+        let packed = get_maker_traits(Json(MakerTraitsRequest {
+            no_partial_fills: true,
+            allow_multiple_fills: true,
+            expiration: 1_700_000_000,
+            nonce_or_epoch: 5,
+            ..Default::default()
+        }))
+        .await
+        .unwrap();
+
+        // Bits 255 and 254, nonce 5 at bit 120, expiration 0x6553f100 at bit 80
+        assert_eq!(packed, "0xc000000000000000000000000000000005006553f10000000000000000000000");
+
+        let only_no_partial_fills =
+            get_maker_traits(Json(MakerTraitsRequest { no_partial_fills: true, ..Default::default() })).await.unwrap();
+        assert_eq!(only_no_partial_fills, format!("0x8{}", "0".repeat(63)));
+
+        let too_late = MakerTraitsRequest { expiration: 1 << 40, ..Default::default() };
+        assert!(get_maker_traits(Json(too_late)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_taker_traits_flags_and_threshold() {
+        let packed = get_taker_traits(Json(TakerTraitsRequest {
+            is_making_amount: true,
+            args_has_target: true,
+            threshold: Some("1000".to_string()),
+            ..Default::default()
+        }))
+        .await
+        .unwrap();
+
+        // Bit 255 (making amount), bit 251 (target), threshold 0x3e8 in the low bits
+        assert_eq!(packed, format!("0x88{}3e8", "0".repeat(59)));
+    }
+}
@@ -0,0 +1,297 @@
+use std::str::FromStr;
+
+use async_trait::async_trait;
+use axum::Json;
+use near_api::{Account, AccountId, Tokens};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    near::{
+        config::{NearDeploymentConfig, NEAR_NETWORK},
+        utils::{deploy_near_src_contract, get_src_escrow_storage_stake, Order},
+    },
+    orchestration::{
+        order_hash::near_order_hash,
+        store::{SwapRecord, SwapStatus, SwapStore, SWAP_STORE},
+    },
+    routes::{
+        eth::get_balance::get_balance,
+        near::get_address::get_funding_near_address,
+        swap::plan::{near_to_eth_dst_amount, src_required_deposit},
+    },
+    utils::new_correlation_id,
+};
+
+#[derive(Deserialize)]
+pub struct FillRequest {
+    pub order: Order,
+    pub signature: OrderSignature,
+    #[serde(with = "crate::utils::u128_string")]
+    pub amount: u128,
+}
+
+/// Maker's ed25519 signature over the 32-byte order hash
+#[derive(Deserialize, Clone, Debug)]
+pub struct OrderSignature {
+    /// Access key of the maker that signed, e.g. "ed25519:..."
+    pub public_key: String,
+    pub signature: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct FillResponse {
+    /// `order_hash:fill_index`, each partial fill of an order is tracked as its own swap
+    pub swap_id: String,
+}
+
+/// Chain access a direct fill needs, abstracted so the handler can be tested without RPCs or deployments
+#[async_trait]
+pub trait FillExecutor: Send + Sync {
+    /// Ok if `public_key` is an access key of `maker`
+    async fn check_maker_key(&self, maker: &AccountId, public_key: &near_crypto::PublicKey) -> Result<(), String>;
+    async fn near_balance(&self) -> Result<u128, String>;
+    async fn eth_balance(&self) -> Result<u128, String>;
+    /// The factory's src_escrow_storage_stake, deploy_src attaches it on top of the escrow funds
+    async fn src_escrow_storage_stake(&self) -> Result<u128, String>;
+    /// Starts the src escrow deployment, the dst escrow follows once it lands
+    async fn dispatch(&self, order: Order, order_signature: String, amount: u128, correlation_id: String);
+}
+
+pub struct NearFillExecutor;
+
+#[async_trait]
+impl FillExecutor for NearFillExecutor {
+    async fn check_maker_key(&self, maker: &AccountId, public_key: &near_crypto::PublicKey) -> Result<(), String> {
+        Account(maker.clone())
+            .access_key(public_key.clone())
+            .fetch_from(&NEAR_NETWORK.network_config())
+            .await
+            .map(|_| ())
+            .map_err(|e| format!("{} is not an access key of {}: {}", public_key, maker, e))
+    }
+
+    async fn near_balance(&self) -> Result<u128, String> {
+        let funding = get_funding_near_address().await;
+        let funding = AccountId::from_str(&funding)
+            .map_err(|e| format!("Invalid funding account {:?}: {}", funding, e))?;
+        let balance = Tokens::account(funding)
+            .near_balance()
+            .fetch_from(&NEAR_NETWORK.network_config())
+            .await
+            .map_err(|e| format!("Failed to get NEAR balance: {}", e))?;
+        Ok(balance.total.as_yoctonear())
+    }
+
+    async fn eth_balance(&self) -> Result<u128, String> {
+        let balance = get_balance().await?;
+        balance.parse().map_err(|e| format!("Invalid ETH balance {}: {}", balance, e))
+    }
+
+    async fn src_escrow_storage_stake(&self) -> Result<u128, String> {
+        get_src_escrow_storage_stake(&NearDeploymentConfig::from_env().escrow_factory).await
+    }
+
+    async fn dispatch(&self, order: Order, order_signature: String, amount: u128, correlation_id: String) {
+        tokio::spawn(deploy_near_src_contract(order, order_signature, amount, correlation_id));
+    }
+}
+
+/// <summary>
+/// fills a maker-signed NEAR -> ETH order directly, no order book involved: checks the signature and the
+/// resolver's capital, starts the escrow deployments and tracks the swap under the returned swap_id.
+/// deploy_src attaches the src principal from the resolver's own funding account, so this is only
+/// mounted with the dev routes until makers fund the principal themselves
+/// </summary>
+pub async fn fill_order(Json(request): Json<FillRequest>) -> Result<Json<FillResponse>, String> {
+    fill_order_with(&NearFillExecutor, &SWAP_STORE, request).await.map(Json)
+}
+
+pub async fn fill_order_with(
+    executor: &dyn FillExecutor,
+    store: &SwapStore,
+    request: FillRequest,
+) -> Result<FillResponse, String> {
+    let FillRequest { order, signature, amount } = request;
+    let dst_amount = near_to_eth_dst_amount(&order, amount)?;
+    let order_hash = near_order_hash(&order);
+
+    let public_key = verify_order_signature(&order_hash, &signature)?;
+    executor.check_maker_key(&order.maker, &public_key).await?;
+
+    // Native balances only, the ERC-20 leg is checked when the ETH dst escrow is deployed
+    let near_required = src_required_deposit(&order, amount, executor.src_escrow_storage_stake().await?);
+    let near_balance = executor.near_balance().await?;
+    if near_balance < near_required {
+        return Err(format!("Insufficient NEAR capital: need {} yoctoNEAR, have {}", near_required, near_balance));
+    }
+    let eth_required = order.extension.dst_safety_deposit;
+    let eth_balance = executor.eth_balance().await?;
+    if eth_balance < eth_required {
+        return Err(format!("Insufficient ETH capital: need {} wei, have {}", eth_required, eth_balance));
+    }
+
+    // Further fills of the same order get the next fill index, the resolver contract caps their
+    // cumulative amount at making_amount
+    let record = store.insert_fill(SwapRecord {
+        order_hash: order_hash.clone(),
+        fill_index: 0,
+        escrow_account: None,
+        status: SwapStatus::Created,
+        correlation_id: new_correlation_id(),
+    });
+    println!(
+        "Filling order {} for {} (fill {}, dst amount {}, correlation_id={})",
+        order_hash, amount, record.fill_index, dst_amount, record.correlation_id
+    );
+    executor.dispatch(order, signature.signature, amount, record.correlation_id.clone()).await;
+
+    Ok(FillResponse { swap_id: record.swap_id() })
+}
+
+/// Checks `signature` signs the order hash, returns the key it was made with
+pub fn verify_order_signature(order_hash: &str, signature: &OrderSignature) -> Result<near_crypto::PublicKey, String> {
+    let public_key = near_crypto::PublicKey::from_str(&signature.public_key)
+        .map_err(|e| format!("Invalid public key {}: {}", signature.public_key, e))?;
+    let parsed = near_crypto::Signature::from_str(&signature.signature)
+        .map_err(|e| format!("Invalid signature {}: {}", signature.signature, e))?;
+    let message = hex::decode(order_hash.trim_start_matches("0x"))
+        .map_err(|e| format!("Invalid order hash {}: {}", order_hash, e))?;
+
+    if !parsed.verify(&message, &public_key) {
+        return Err(format!("Signature does not sign order {} with {}", order_hash, public_key));
+    }
+    Ok(public_key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use near_crypto::{KeyType, SecretKey};
+    use serde_json::json;
+
+    #[derive(Default)]
+    struct FundedExecutor {
+        dispatched: Mutex<Vec<u128>>,
+        correlation_ids: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl FillExecutor for FundedExecutor {
+        async fn check_maker_key(&self, _maker: &AccountId, _public_key: &near_crypto::PublicKey) -> Result<(), String> {
+            Ok(())
+        }
+
+        async fn near_balance(&self) -> Result<u128, String> {
+            Ok(10u128.pow(26))
+        }
+
+        async fn eth_balance(&self) -> Result<u128, String> {
+            Ok(10u128.pow(18))
+        }
+
+        async fn src_escrow_storage_stake(&self) -> Result<u128, String> {
+            Ok(2_200 * 10u128.pow(19))
+        }
+
+        async fn dispatch(&self, _order: Order, _order_signature: String, amount: u128, correlation_id: String) {
+            self.dispatched.lock().unwrap().push(amount);
+            self.correlation_ids.lock().unwrap().push(correlation_id);
+        }
+    }
+
+    fn order() -> Order {
+        serde_json::from_value(json!({
+            "maker": "maker.testnet",
+            "taker": "resolver.testnet",
+            "making_amount": "1000",
+            "taking_amount": "3000",
+            "maker_asset": "near",
+            "taker_asset": "0x1c7d4b196cb0c7b01d743fbc6116a902379c7238",
+            "salt": "1",
+            "extension": {
+                "hashlock": "ab".repeat(32),
+                "src_chain_id": 397,
+                "dst_chain_id": 11155111,
+                "src_safety_deposit": "10",
+                "dst_safety_deposit": "20",
+                "timelocks": {
+                    "deployed_at": 0,
+                    "src_withdrawal": 60,
+                    "src_public_withdrawal": 600,
+                    "src_cancellation": 1800,
+                    "src_public_cancellation": 3600,
+                    "dst_withdrawal": 60,
+                    "dst_public_withdrawal": 600,
+                    "dst_cancellation": 1500
+                }
+            }
+        }))
+        .unwrap()
+    }
+
+    fn signed_by(key: &SecretKey, order_hash: &str) -> OrderSignature {
+        OrderSignature {
+            public_key: key.public_key().to_string(),
+            signature: key.sign(&hex::decode(order_hash.trim_start_matches("0x")).unwrap()).to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_signed_order_is_dispatched() {
+        let (executor, store) = (FundedExecutor::default(), SwapStore::new());
+        let order_hash = near_order_hash(&order());
+        let signature = signed_by(&SecretKey::from_random(KeyType::ED25519), &order_hash);
+
+        let response = fill_order_with(&executor, &store, FillRequest { order: order(), signature, amount: 400 })
+            .await
+            .unwrap();
+
+        assert_eq!(response.swap_id, format!("{}:0", order_hash));
+        assert_eq!(*executor.dispatched.lock().unwrap(), vec![400]);
+        let record = store.get(&response.swap_id).unwrap();
+        assert_eq!(record.status, SwapStatus::Created);
+        // The id the contracts echo is the one the swap is tracked with
+        assert_eq!(*executor.correlation_ids.lock().unwrap(), vec![record.correlation_id]);
+    }
+
+    #[tokio::test]
+    async fn test_partial_fills_of_one_order_are_tracked_separately() {
+        let (executor, store) = (FundedExecutor::default(), SwapStore::new());
+        let order_hash = near_order_hash(&order());
+        let key = SecretKey::from_random(KeyType::ED25519);
+
+        let first = fill_order_with(&executor, &store, FillRequest { order: order(), signature: signed_by(&key, &order_hash), amount: 400 })
+            .await
+            .unwrap();
+        store.set_status(&first.swap_id, SwapStatus::Deployed);
+        let second = fill_order_with(&executor, &store, FillRequest { order: order(), signature: signed_by(&key, &order_hash), amount: 600 })
+            .await
+            .unwrap();
+
+        assert_ne!(first.swap_id, second.swap_id);
+        assert_eq!(*executor.dispatched.lock().unwrap(), vec![400, 600]);
+        assert_ne!(store.get(&first.swap_id).unwrap().correlation_id, store.get(&second.swap_id).unwrap().correlation_id);
+        // The second fill must not overwrite the first one's record
+        assert_eq!(store.get(&first.swap_id).unwrap().status, SwapStatus::Deployed);
+        assert_eq!(store.get(&second.swap_id).unwrap().fill_index, 1);
+        assert_eq!(store.fills_of(&order_hash).len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_signature_is_rejected() {
+        let (executor, store) = (FundedExecutor::default(), SwapStore::new());
+        let key = SecretKey::from_random(KeyType::ED25519);
+        let mut other = order();
+        other.salt = "2".to_string();
+        let signature = signed_by(&key, &near_order_hash(&other));
+
+        let err = fill_order_with(&executor, &store, FillRequest { order: order(), signature, amount: 400 })
+            .await
+            .unwrap_err();
+
+        assert!(err.starts_with("Signature does not sign order"), "{}", err);
+        assert!(executor.dispatched.lock().unwrap().is_empty());
+        assert!(store.fills_of(&near_order_hash(&order())).is_empty());
+    }
+}
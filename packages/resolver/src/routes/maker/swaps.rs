@@ -0,0 +1,169 @@
+use std::str::FromStr;
+use async_trait::async_trait;
+use axum::{extract::Path, Json};
+use futures::future::join_all;
+use near_api::{AccountId, Contract, Data};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    near::config::{NearDeploymentConfig, NEAR_NETWORK},
+    orchestration::escrow_state::EscrowState,
+};
+
+/// What the dashboard needs from one escrow, read from its `get_status` view
+#[derive(Clone, Debug)]
+pub struct EscrowSummary {
+    pub phase: String,
+    pub state: EscrowState,
+    /// Upcoming phase and the timestamp (ms) it opens at, from `next_action_timestamp`
+    pub next_action: Option<(String, u64)>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct MakerSwap {
+    pub escrow: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub phase: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_remaining_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Factory maker index and escrow views, abstracted so the handler can be tested without a chain
+#[async_trait]
+pub trait MakerSwapReader: Send + Sync {
+    async fn get_maker_escrows(&self, maker: &str) -> Result<Vec<String>, String>;
+    async fn get_escrow_summary(&self, escrow: &str) -> Result<EscrowSummary, String>;
+}
+
+pub struct NearMakerSwapReader;
+
+async fn view<T: DeserializeOwned + Send + Sync>(account: &str, method: &str, args: serde_json::Value) -> Result<T, String> {
+    let account_id = AccountId::from_str(account).map_err(|e| format!("Invalid account {}: {}", account, e))?;
+    let data: Data<T> = Contract(account_id)
+        .call_function(method, args)
+        .map_err(|e| format!("Failed to build {} call: {}", method, e))?
+        .read_only()
+        .fetch_from(&NEAR_NETWORK.network_config())
+        .await
+        .map_err(|e| format!("Failed to call {} on {}: {}", method, account, e))?;
+    Ok(data.data)
+}
+
+/// Page size for `get_escrows_by_maker`, the factory caps it at the same value
+const MAKER_ESCROWS_PAGE: u64 = 100;
+
+/// Wire format of the escrows' `get_status` view, the timestamp is a U64 string
+#[derive(Deserialize)]
+struct EscrowStatus {
+    phase: String,
+    state: EscrowState,
+    next_action: Option<(String, String)>,
+}
+
+#[async_trait]
+impl MakerSwapReader for NearMakerSwapReader {
+    async fn get_maker_escrows(&self, maker: &str) -> Result<Vec<String>, String> {
+        let factory = NearDeploymentConfig::from_env().escrow_factory;
+        let mut escrows = Vec::new();
+        loop {
+            let page: Vec<String> = view(
+                &factory,
+                "get_escrows_by_maker",
+                json!({
+                    "maker": maker,
+                    "from_index": escrows.len().to_string(),
+                    "limit": MAKER_ESCROWS_PAGE.to_string(),
+                }),
+            )
+            .await?;
+            let last_page = (page.len() as u64) < MAKER_ESCROWS_PAGE;
+            escrows.extend(page);
+            if last_page {
+                return Ok(escrows);
+            }
+        }
+    }
+
+    async fn get_escrow_summary(&self, escrow: &str) -> Result<EscrowSummary, String> {
+        let status: EscrowStatus = view(escrow, "get_status", json!({})).await?;
+        let next_action = status
+            .next_action
+            .map(|(event, at)| at.parse().map(|at| (event, at)).map_err(|e| format!("Invalid next action timestamp {}: {}", at, e)))
+            .transpose()?;
+        Ok(EscrowSummary { phase: status.phase, state: status.state, next_action })
+    }
+}
+
+/// <summary>
+/// phase and time to the next phase of every active (neither withdrawn nor cancelled) escrow of a maker
+/// </summary>
+pub async fn get_maker_swaps(Path(account): Path<String>) -> Result<Json<Vec<MakerSwap>>, String> {
+    let now_ms = chrono::Utc::now().timestamp_millis() as u64;
+    get_maker_swaps_with(&NearMakerSwapReader, &account, now_ms).await.map(Json)
+}
+
+/// All escrows are queried in one concurrent batch, one view each, an escrow that fails to read is reported with its error
+pub async fn get_maker_swaps_with(reader: &dyn MakerSwapReader, maker: &str, now_ms: u64) -> Result<Vec<MakerSwap>, String> {
+    let escrows = reader.get_maker_escrows(maker).await?;
+    let summaries = join_all(escrows.iter().map(|escrow| reader.get_escrow_summary(escrow))).await;
+
+    Ok(escrows
+        .into_iter()
+        .zip(summaries)
+        .filter_map(|(escrow, summary)| match summary {
+            Ok(summary) if summary.state.is_withdrawn || summary.state.is_cancelled => None,
+            Ok(summary) => Some(MakerSwap {
+                escrow,
+                phase: Some(summary.phase),
+                time_remaining_ms: summary.next_action.map(|(_, at)| at.saturating_sub(now_ms)),
+                error: None,
+            }),
+            Err(e) => Some(MakerSwap { escrow, phase: None, time_remaining_ms: None, error: Some(e) }),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TwoEscrowMaker;
+
+    #[async_trait]
+    impl MakerSwapReader for TwoEscrowMaker {
+        async fn get_maker_escrows(&self, maker: &str) -> Result<Vec<String>, String> {
+            assert_eq!(maker, "maker.testnet");
+            Ok(vec!["escrow-0.factory.testnet".to_string(), "escrow-1.factory.testnet".to_string()])
+        }
+
+        async fn get_escrow_summary(&self, escrow: &str) -> Result<EscrowSummary, String> {
+            Ok(match escrow {
+                "escrow-0.factory.testnet" => EscrowSummary {
+                    phase: "A1_FINALITY_LOCK".to_string(),
+                    state: EscrowState::default(),
+                    next_action: Some(("withdrawal".to_string(), 1_700_000_060_000)),
+                },
+                _ => EscrowSummary {
+                    phase: "B3_PUBLIC_WITHDRAWAL".to_string(),
+                    state: EscrowState { is_funded: true, ..Default::default() },
+                    next_action: Some(("cancellation".to_string(), 1_700_000_030_000)),
+                },
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_maker_swaps_report_each_phase() {
+        let swaps = get_maker_swaps_with(&TwoEscrowMaker, "maker.testnet", 1_700_000_000_000).await.unwrap();
+
+        assert_eq!(swaps.len(), 2);
+        assert_eq!(swaps[0].escrow, "escrow-0.factory.testnet");
+        assert_eq!(swaps[0].phase.as_deref(), Some("A1_FINALITY_LOCK"));
+        assert_eq!(swaps[0].time_remaining_ms, Some(60_000));
+        assert_eq!(swaps[1].phase.as_deref(), Some("B3_PUBLIC_WITHDRAWAL"));
+        assert_eq!(swaps[1].time_remaining_ms, Some(30_000));
+    }
+}
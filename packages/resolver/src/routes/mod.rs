@@ -1,3 +1,8 @@
 pub mod agentAccount;
+pub mod balances;
 pub mod eth;
-pub mod near;
\ No newline at end of file
+pub mod fill;
+pub mod maker;
+pub mod near;
+pub mod order;
+pub mod swap;
@@ -0,0 +1,9 @@
+use crate::near::templates::deploy_and_register_templates;
+
+/// <summary>
+/// publish the escrow templates and register them with the factory
+/// </summary>
+pub async fn deploy_templates() -> Result<String, String> {
+    let templates = deploy_and_register_templates().await?;
+    Ok(format!("src: {}, dst: {}", templates.escrow_src_template, templates.escrow_dst_template))
+}
@@ -5,11 +5,12 @@ use k256::sha2::Sha256;
 use lazy_static::lazy_static;
 use near_api::{Account, Contract, Data};
 use omni_transaction::near::types::{ED25519Signature, Signature};
-use serde_json::json;
 use sha3::Digest;
-use crate::utils::get_testnet_mpc_signer_account_id;
+use crate::utils::get_mpc_signer_account_id;
 use near_crypto::{PublicKey, ED25519PublicKey};
 use near_primitives::{action::base64, types::AccountId};
+use crate::near::config::{derived_public_key_args, MPC_DOMAINS, NEAR_NETWORK};
+use async_trait::async_trait;
 
 
 lazy_static! {
@@ -60,66 +61,118 @@ pub async fn get_holding_near_public_key() -> String {
     holding_near_public_key.clone()
 }
 
-pub async fn setup_holding_near_address() {
-    let derived_address_data : Data<String> =Contract(get_testnet_mpc_signer_account_id().await)
-    .call_function("derived_public_key", json!(
-        {
-            "path": "oneprime-holding-eth",
-            "predecessor": std::env::var("NEXT_PUBLIC_contractId").unwrap(),
-            "domain_id": 1
-        }
-    ))
-    .unwrap()
-    .read_only()
-        .fetch_from_testnet()
-    .await
-    .expect("Failed to fetch near address");
-
-    // Parse the ED25519 public key from the data
-    let public_key_str = derived_address_data.data;
-    update_holding_near_public_key(public_key_str.clone());
-
-    println!("Public Key Data: {:?}", public_key_str);
-    let public_key = PublicKey::from_str(&public_key_str)
-        .expect("Failed to parse public key");
-
-    // Convert to implicit NEAR address
-    let implicit_address = hex::encode(public_key.key_data());
-    println!("Implicit Address: {:?}", implicit_address);
-    let near_address = format!("{}", implicit_address);
-
-    // delete the account
+#[derive(Debug, thiserror::Error)]
+pub enum DerivationError {
+    #[error("Failed to fetch the derived public key for path {path}: {reason}")]
+    View { path: String, reason: String },
+    #[error("Invalid derived key for path {path}: {reason}")]
+    InvalidKey { path: String, reason: String },
+}
+
+/// Source of MPC-derived ed25519 public keys, abstracted so derivation can be tested without the signer
+#[async_trait]
+pub trait DerivedKeyReader: Send + Sync {
+    async fn derived_public_key(&self, path: &str) -> Result<String, String>;
+}
+
+/// Reads `derived_public_key` from the MPC signer for the agent contract, in the ed25519 domain
+pub struct MpcDerivedKeyReader;
+
+#[async_trait]
+impl DerivedKeyReader for MpcDerivedKeyReader {
+    async fn derived_public_key(&self, path: &str) -> Result<String, String> {
+        let predecessor = std::env::var("NEXT_PUBLIC_contractId").map_err(|e| format!("NEXT_PUBLIC_contractId: {}", e))?;
+        let derived: Data<String> = Contract(get_mpc_signer_account_id().await)
+            .call_function("derived_public_key", derived_public_key_args(path, &predecessor, MPC_DOMAINS.ed25519))
+            .map_err(|e| e.to_string())?
+            .read_only()
+            .fetch_from(&NEAR_NETWORK.network_config())
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(derived.data)
+    }
+}
+
+/// Implicit NEAR account and its ed25519 public key for the MPC key derived at `path`
+pub async fn derive_near_implicit_account(path: &str) -> Result<(String, String), DerivationError> {
+    derive_near_implicit_account_with(&MpcDerivedKeyReader, path).await
+}
+
+pub async fn derive_near_implicit_account_with(
+    reader: &dyn DerivedKeyReader,
+    path: &str,
+) -> Result<(String, String), DerivationError> {
+    let public_key = reader
+        .derived_public_key(path)
+        .await
+        .map_err(|reason| DerivationError::View { path: path.to_string(), reason })?;
+    let account = implicit_address_from_derived_key(&public_key)
+        .map_err(|reason| DerivationError::InvalidKey { path: path.to_string(), reason })?;
+    Ok((account, public_key))
+}
+
+pub async fn setup_holding_near_address() -> Result<(), String> {
+    let (near_address, public_key_str) = derive_near_implicit_account("oneprime-holding-eth").await.map_err(|e| e.to_string())?;
+    println!("Implicit Address: {:?}", near_address);
+
+    update_holding_near_public_key(public_key_str);
     update_holding_near_address(near_address);
+    Ok(())
 }
 
-pub async fn setup_funding_near_address() {
-    let derived_address_data : Data<String> =Contract(get_testnet_mpc_signer_account_id().await)
-    .call_function("derived_public_key", json!(
-        {
-            "path": "oneprime-funding-eth",
-            "predecessor": std::env::var("NEXT_PUBLIC_contractId").unwrap(),
-            "domain_id": 1
-        }
-    ))
-    .unwrap()
-    .read_only()
-        .fetch_from_testnet()
-    .await
-    .expect("Failed to fetch near address");
-
-    // Parse the ED25519 public key from the data
-    let public_key_str = derived_address_data.data;
-    update_funding_near_public_key(public_key_str.clone());
-
-    println!("Public Key Data: {:?}", public_key_str);
-    let public_key = PublicKey::from_str(&public_key_str)
-        .expect("Failed to parse public key");
-
-    // Convert to implicit NEAR address
-    let implicit_address = hex::encode(public_key.key_data());
-    println!("Implicit Address: {:?}", implicit_address);
-    let near_address = format!("{}", implicit_address);
-
-    // delete the account
+pub async fn setup_funding_near_address() -> Result<(), String> {
+    let (near_address, public_key_str) = derive_near_implicit_account("oneprime-funding-eth").await.map_err(|e| e.to_string())?;
+    println!("Implicit Address: {:?}", near_address);
+
+    update_funding_near_public_key(public_key_str);
     update_funding_near_address(near_address);
-}
\ No newline at end of file
+    Ok(())
+}
+
+/// Implicit account id for an MPC-derived NEAR key, which must be ed25519 (domain_id 1)
+pub fn implicit_address_from_derived_key(public_key_str: &str) -> Result<String, String> {
+    if !public_key_str.starts_with("ed25519:") {
+        return Err(format!(
+            "Expected an ed25519 key for a NEAR address, got {:?}; check the derivation uses domain_id 1",
+            public_key_str
+        ));
+    }
+    let public_key = PublicKey::from_str(public_key_str)
+        .map_err(|e| format!("Failed to parse ed25519 public key: {}", e))?;
+    Ok(hex::encode(public_key.key_data()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secp256k1_key_rejected_for_near_address() {
+        let err = implicit_address_from_derived_key("secp256k1:3tFz1k3Ud6Q9z3vWr9RkB1").unwrap_err();
+        assert!(err.contains("domain_id 1"));
+
+        let address = implicit_address_from_derived_key("ed25519:11111111111111111111111111111111").unwrap();
+        assert_eq!(address, "00".repeat(32));
+    }
+
+    /// Derives like the MPC signer would, a distinct deterministic key per path
+    struct SeededKeys;
+
+    #[async_trait]
+    impl DerivedKeyReader for SeededKeys {
+        async fn derived_public_key(&self, path: &str) -> Result<String, String> {
+            Ok(near_crypto::SecretKey::from_seed(near_crypto::KeyType::ED25519, path).public_key().to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_distinct_paths_derive_distinct_accounts() {
+        let (funding, funding_key) = derive_near_implicit_account_with(&SeededKeys, "oneprime-funding-eth").await.unwrap();
+        let (holding, holding_key) = derive_near_implicit_account_with(&SeededKeys, "oneprime-holding-eth").await.unwrap();
+
+        assert_ne!(funding, holding);
+        assert_ne!(funding_key, holding_key);
+        assert_eq!(funding, implicit_address_from_derived_key(&funding_key).unwrap());
+        assert_eq!(funding.len(), 64);
+    }
+}
@@ -3,23 +3,30 @@ use std::str::FromStr;
 use crate::{agent::{agent, agent_account_id, AgentConfig}, routes::near::get_address::get_funding_near_address};
 use tokio;
 use near_api::{AccountId, Tokens};
+use crate::near::config::NEAR_NETWORK;
 
 pub async fn get_near_balance() -> String{
-
-    let fund_holder_account_str = get_funding_near_address().await;
-    let fund_holder_account = AccountId::from_str(&fund_holder_account_str).unwrap();
-    let get_balance_result = Tokens::account(fund_holder_account.clone()).near_balance().fetch_from_testnet().await;
-    match get_balance_result {
-        Ok(balance) => {
-            println!("Balance response: {:?}", balance);
-            balance.total.to_string()
-        }
+    match try_get_near_balance().await {
+        Ok(balance) => balance,
         Err(e) => {
-            eprintln!("Failed to get balance: {}", e);
+            eprintln!("{}", e);
             "0".to_string() // Return 0 if there's an error
         }
     }
+}
 
+/// Total balance of the funding account in yoctoNEAR, surfacing RPC failures to the caller
+pub async fn try_get_near_balance() -> Result<String, String> {
+    let fund_holder_account_str = get_funding_near_address().await;
+    let fund_holder_account = AccountId::from_str(&fund_holder_account_str)
+        .map_err(|e| format!("Invalid funding account {:?}: {}", fund_holder_account_str, e))?;
+    let balance = Tokens::account(fund_holder_account)
+        .near_balance()
+        .fetch_from(&NEAR_NETWORK.network_config())
+        .await
+        .map_err(|e| format!("Failed to get balance: {}", e))?;
+    println!("Balance response: {:?}", balance);
+    Ok(balance.total.to_string())
 }
 
 /*pub async fn get_near_balance() -> Result<String, String> {
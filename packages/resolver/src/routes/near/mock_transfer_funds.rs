@@ -9,12 +9,13 @@ use omni_transaction::{near::{types::{Action, BlockHash, TransferAction, U128, U
 use omni_transaction::near::types::{Signature, ED25519Signature};
 use sha3::Digest;
 
-use crate::routes::near::get_address::{get_funding_near_address, get_funding_near_public_key};
+use crate::{agent::EddsaSignatureResponse, routes::near::get_address::{get_funding_near_address, get_funding_near_public_key}};
+use crate::near::config::NEAR_NETWORK;
 
 pub async fn mock_transfer_funds() {
 
 
-    let block_hash = Chain::block_hash().fetch_from_testnet().await.unwrap();
+    let block_hash = Chain::block_hash().fetch_from(&NEAR_NETWORK.network_config()).await.unwrap();
     
     let signer_id = get_funding_near_address().await;
     let signer_account_id = AccountId::from_str(&signer_id.clone()).expect("Invalid NEAR account ID");
@@ -28,7 +29,7 @@ pub async fn mock_transfer_funds() {
             .access_key(
                 near_crypto::PublicKey::ED25519(ED25519PublicKey(signer_public_key_bytes))
             )
-            .fetch_from_testnet()
+            .fetch_from(&NEAR_NETWORK.network_config())
             .await.unwrap();
 
     let mut nonce = U64(nonce_data.data.nonce);
@@ -67,11 +68,13 @@ pub async fn mock_transfer_funds() {
     let signature_data = request_signature_result.unwrap();
     println!("Signature data: {:?}", signature_data);
 
-    let signature_bytes = signature_data["signature"].as_array().expect("Failed to get signature array");
-    let signature_u8_vec: Vec<u8> = signature_bytes.iter()
-        .map(|v| v.as_u64().expect("Failed to convert to u64") as u8)
-        .collect();
-    let signature_array: [u8; 64] = signature_u8_vec.try_into().expect("Signature must be exactly 64 bytes");
+    let signature_array = match EddsaSignatureResponse::from_value(signature_data).and_then(|r| r.to_bytes()) {
+        Ok(signature_array) => signature_array,
+        Err(e) => {
+            eprintln!("Failed to parse signature: {}", e);
+            return;
+        }
+    };
 
     let signature = Signature::ED25519(ED25519Signature::try_from_slice(&signature_array).unwrap());
     let signed_tx = near_tx.build_with_signature(signature);
@@ -79,7 +82,7 @@ pub async fn mock_transfer_funds() {
     println!("{}", base64_tx);
 
 
-    let near_testnet_url = near_api::RPCEndpoint::testnet().url.to_string();
+    let near_rpc_url = NEAR_NETWORK.rpc_url();
     let request_body = serde_json::json!({
         "jsonrpc": "2.0",
         "id": signer_id,
@@ -92,7 +95,7 @@ pub async fn mock_transfer_funds() {
 
     let client = reqwest::Client::new();
     let response = client
-        .post(&near_testnet_url)
+        .post(&near_rpc_url)
         .header("Content-Type", "application/json")
         .json(&request_body)
         .send()
@@ -1,16 +1,17 @@
 use std::str::FromStr;
 
 use k256::sha2::Sha256;
-use near_api::{Account, AccountId, Chain, Contract, Data, NearToken};
+use near_api::{Account, AccountId, Chain, NearToken};
 use serde::Serialize;
 use sha3::Digest;
-use crate::{routes::near::get_address::{get_funding_near_address, get_funding_near_public_key}, utils::get_testnet_mpc_signer_account_id};
+use crate::{agent::EddsaSignatureResponse, routes::near::get_address::{derive_near_implicit_account, get_funding_near_address, get_funding_near_public_key}};
 use serde_json::json;
 use near_primitives::{action::{base64, delegate::{self, NonDelegateAction}, FunctionCallAction}, block, hash::CryptoHash, signable_message::{SignableMessage, SignableMessageType}};
 use omni_transaction::{near::{types::{Action, BlockHash, DelegateAction, ED25519PublicKey, TransferAction, U128, U64}, utils::PublicKeyStrExt}, TxBuilder, NEAR};
 use omni_transaction::near::types::{Signature, ED25519Signature, SignedDelegateAction};
 use borsh::{BorshSerialize, BorshDeserialize};
 use near_primitives::hash;
+use crate::near::config::NEAR_NETWORK;
 
 #[derive(BorshSerialize, BorshDeserialize)]
 struct FtTransferCallArgs {
@@ -21,34 +22,7 @@ struct FtTransferCallArgs {
 /// first value being near address
 /// second value being public key
 pub async fn get_additional_mock_details() -> (String, String){
-    let derived_address_data : Data<String> =Contract(get_testnet_mpc_signer_account_id().await)
-    .call_function("derived_public_key", json!(
-        {
-            "path": "oneprime-funding-eth-mock",
-            "predecessor": std::env::var("NEXT_PUBLIC_contractId").unwrap(),
-            "domain_id": 1
-        }
-    ))
-    .unwrap()
-    .read_only()
-        .fetch_from_testnet()
-    .await
-    .expect("Failed to fetch near address");
-
-    // Parse the ED25519 public key from the data
-    let public_key_str = derived_address_data.data;
-
-    println!("Public Key Data: {:?}", public_key_str);
-    let public_key = near_crypto::PublicKey::from_str(&public_key_str)
-        .expect("Failed to parse public key");
-    println!("Public Key: {:?}", public_key);
-
-    // Convert to implicit NEAR address
-    let implicit_address = hex::encode(public_key.key_data());
-    println!("Implicit Address: {:?}", implicit_address);
-    let near_address = format!("{}", implicit_address);
-
-    (near_address, public_key_str)
+    derive_near_implicit_account("oneprime-funding-eth-mock").await.expect("Failed to derive mock NEAR address")
 }
 
 pub async fn get_additional_mock_address() -> String {
@@ -75,8 +49,8 @@ pub fn json_bytes<T>(structure: T) -> Vec<u8> where T: Serialize {
 
 async fn generate_near_mock_usdc_transfer_delegate_action() -> Option<SignedDelegateAction> {
 
-    let block_hash = Chain::block_hash().fetch_from_testnet().await.unwrap();
-    let maximum_block_height = Chain::block_number().fetch_from_testnet().await.unwrap() + 1000;
+    let block_hash = Chain::block_hash().fetch_from(&NEAR_NETWORK.network_config()).await.unwrap();
+    let maximum_block_height = Chain::block_number().fetch_from(&NEAR_NETWORK.network_config()).await.unwrap() + 1000;
 
     let (signer_id, signer_public_key) = get_additional_mock_details().await;
     let signer_account_id = AccountId::from_str(&signer_id.clone()).expect("Invalid NEAR account ID");
@@ -89,7 +63,7 @@ async fn generate_near_mock_usdc_transfer_delegate_action() -> Option<SignedDele
         .access_key(
             near_crypto::PublicKey::ED25519(near_crypto::ED25519PublicKey(signer_public_key_bytes))
         )
-        .fetch_from_testnet()
+        .fetch_from(&NEAR_NETWORK.network_config())
         .await.unwrap();
     let mut nonce = U64(nonce_data.data.nonce);
 
@@ -139,11 +113,13 @@ async fn generate_near_mock_usdc_transfer_delegate_action() -> Option<SignedDele
     let signature_data = request_signature_result.unwrap();
     println!("Signature data: {:?}", signature_data);
 
-    let signature_bytes = signature_data["signature"].as_array().expect("Failed to get signature array");
-    let signature_u8_vec: Vec<u8> = signature_bytes.iter()
-        .map(|v| v.as_u64().expect("Failed to convert to u64") as u8)
-        .collect();
-    let signature_array: [u8; 64] = signature_u8_vec.try_into().expect("Signature must be exactly 64 bytes");
+    let signature_array = match EddsaSignatureResponse::from_value(signature_data).and_then(|r| r.to_bytes()) {
+        Ok(signature_array) => signature_array,
+        Err(e) => {
+            eprintln!("Failed to parse signature: {}", e);
+            return None;
+        }
+    };
 
     let signature = Signature::ED25519(ED25519Signature::try_from_slice(&signature_array).unwrap());
 
@@ -156,7 +132,7 @@ async fn generate_near_mock_usdc_transfer_delegate_action() -> Option<SignedDele
 
 pub async fn mock_transfer_usdc() {
 
-    let block_hash = Chain::block_hash().fetch_from_testnet().await.unwrap();
+    let block_hash = Chain::block_hash().fetch_from(&NEAR_NETWORK.network_config()).await.unwrap();
     
     let signer_id = get_funding_near_address().await;
     let signer_account_id = AccountId::from_str(&signer_id.clone()).expect("Invalid NEAR account ID");
@@ -170,7 +146,7 @@ pub async fn mock_transfer_usdc() {
             .access_key(
                 near_crypto::PublicKey::ED25519(near_crypto::ED25519PublicKey(signer_public_key_bytes))
             )
-            .fetch_from_testnet()
+            .fetch_from(&NEAR_NETWORK.network_config())
             .await.unwrap();
 
     let mut nonce = U64(nonce_data.data.nonce);
@@ -214,11 +190,13 @@ pub async fn mock_transfer_usdc() {
     let signature_data = request_signature_result.unwrap();
     println!("Signature data: {:?}", signature_data);
 
-    let signature_bytes = signature_data["signature"].as_array().expect("Failed to get signature array");
-    let signature_u8_vec: Vec<u8> = signature_bytes.iter()
-        .map(|v| v.as_u64().expect("Failed to convert to u64") as u8)
-        .collect();
-    let signature_array: [u8; 64] = signature_u8_vec.try_into().expect("Signature must be exactly 64 bytes");
+    let signature_array = match EddsaSignatureResponse::from_value(signature_data).and_then(|r| r.to_bytes()) {
+        Ok(signature_array) => signature_array,
+        Err(e) => {
+            eprintln!("Failed to parse signature: {}", e);
+            return;
+        }
+    };
 
     let signature = Signature::ED25519(ED25519Signature::try_from_slice(&signature_array).unwrap());
     let signed_tx = near_tx.build_with_signature(signature);
@@ -226,7 +204,7 @@ pub async fn mock_transfer_usdc() {
     println!("{}", base64_tx);
 
 
-    let near_testnet_url = near_api::RPCEndpoint::testnet().url.to_string();
+    let near_rpc_url = NEAR_NETWORK.rpc_url();
     let request_body = serde_json::json!({
         "jsonrpc": "2.0",
         "id": signer_id,
@@ -239,7 +217,7 @@ pub async fn mock_transfer_usdc() {
 
     let client = reqwest::Client::new();
     let response = client
-        .post(&near_testnet_url)
+        .post(&near_rpc_url)
         .header("Content-Type", "application/json")
         .json(&request_body)
         .send()
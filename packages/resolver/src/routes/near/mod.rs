@@ -1,4 +1,5 @@
+pub mod deploy_templates;
 pub mod get_address;
 pub mod get_balance;
 pub mod mock_transfer_funds;
-pub mod mock_transfer_funds_with_gas_sponsorship;
\ No newline at end of file
+pub mod mock_transfer_funds_with_gas_sponsorship;
@@ -0,0 +1,58 @@
+use axum::Json;
+use serde::Serialize;
+
+use crate::{near::utils::Order, orchestration::order_hash::near_order_hash};
+
+#[derive(Serialize, Debug)]
+pub struct OrderHashResponse {
+    pub order_hash: String,
+}
+
+/// <summary>
+/// canonical order hash, computed exactly as the NEAR resolver contract's `compute_order_hash`
+/// </summary>
+pub async fn get_order_hash(Json(order): Json<Order>) -> Json<OrderHashResponse> {
+    Json(OrderHashResponse { order_hash: near_order_hash(&order) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_order_hash_matches_contract() {
+        let order: Order = serde_json::from_value(json!({
+            "maker": "maker.testnet",
+            "taker": "resolver.testnet",
+            "making_amount": "100",
+            "taking_amount": "200",
+            "maker_asset": "near",
+            "taker_asset": "0x1c7d4b196cb0c7b01d743fbc6116a902379c7238",
+            "salt": "1",
+            "extension": {
+                "hashlock": "0x00",
+                "src_chain_id": 397,
+                "dst_chain_id": 11155111,
+                "src_safety_deposit": "1",
+                "dst_safety_deposit": "1",
+                "timelocks": {
+                    "deployed_at": 0,
+                    "src_withdrawal": 60,
+                    "src_public_withdrawal": 120,
+                    "src_cancellation": 180,
+                    "src_public_cancellation": 240,
+                    "dst_withdrawal": 60,
+                    "dst_public_withdrawal": 120,
+                    "dst_cancellation": 180
+                }
+            }
+        }))
+        .unwrap();
+
+        let Json(response) = get_order_hash(Json(order)).await;
+
+        // sha256("maker.testnet:100:200:near:0x1c7d...7238:1:0x00"), as Resolver::compute_order_hash yields
+        assert_eq!(response.order_hash, "0x2439dad5eb8f3a6bbdc3f82365a0c6c452109d603fb501ea950ccef0ad97dcd9");
+    }
+}
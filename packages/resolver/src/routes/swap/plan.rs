@@ -0,0 +1,290 @@
+use std::str::FromStr;
+use axum::Json;
+use ethers::types::{Address, U256};
+use serde::{Deserialize, Serialize};
+use sha3::Digest;
+
+use crate::{
+    eth::utils::{create_timelocks, eth_escrow_address, Immutables as EthImmutables},
+    near::{config::NearDeploymentConfig, utils::{get_src_escrow_storage_stake, ChainContext, Immutables as NearImmutables, Order}},
+    orchestration::{direction::SwapDirection, order_hash::near_order_hash},
+};
+
+#[derive(Deserialize)]
+pub struct PlanRequest {
+    /// `order.taker` must be the NEAR resolver contract, deploy_src makes itself the escrow's taker
+    pub order: Order,
+    #[serde(with = "crate::utils::u128_string")]
+    pub amount: u128,
+    /// Reference deployment time in ms, the NEAR escrow address depends on it. The factory stamps
+    /// its block time in ms, so the address only matches for the block the escrow lands in
+    pub deployed_at: u64,
+    /// Maker's ETH address receiving the dst funds
+    pub eth_receiver: String,
+    pub eth_escrow_factory: String,
+    /// keccak256 of the EVM factory's dst proxy bytecode
+    pub eth_dst_proxy_bytecode_hash: String,
+}
+
+/// Absolute phase start times in ms
+#[derive(Serialize, Debug, PartialEq)]
+pub struct TimelockSchedule {
+    pub src_withdrawal: u64,
+    pub src_public_withdrawal: u64,
+    pub src_cancellation: u64,
+    pub src_public_cancellation: u64,
+    pub dst_withdrawal: u64,
+    pub dst_public_withdrawal: u64,
+    pub dst_cancellation: u64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct DeploymentPlan {
+    pub direction: SwapDirection,
+    pub order_hash: String,
+    pub near_src_escrow: String,
+    pub eth_dst_escrow: String,
+    #[serde(with = "crate::utils::u128_string")]
+    pub src_required_deposit: u128,
+    #[serde(with = "crate::utils::u128_string")]
+    pub dst_amount: u128,
+    #[serde(with = "crate::utils::u128_string")]
+    pub dst_safety_deposit: u128,
+    pub timelocks: TimelockSchedule,
+}
+
+/// <summary>
+/// dry run of a NEAR -> ETH fill: escrow addresses, deposits and timelocks, nothing is sent on-chain
+/// </summary>
+pub async fn plan_swap(Json(request): Json<PlanRequest>) -> Result<Json<DeploymentPlan>, String> {
+    let near_escrow_factory = NearDeploymentConfig::from_env().escrow_factory;
+    let src_storage_stake = get_src_escrow_storage_stake(&near_escrow_factory).await?;
+    build_deployment_plan(&request, &near_escrow_factory, src_storage_stake).map(Json)
+}
+
+/// `src_storage_stake` is the factory's src_escrow_storage_stake
+pub fn build_deployment_plan(
+    request: &PlanRequest,
+    near_escrow_factory: &str,
+    src_storage_stake: u128,
+) -> Result<DeploymentPlan, String> {
+    let order = &request.order;
+    let dst_amount = near_to_eth_dst_amount(order, request.amount)?;
+    let src_required_deposit = src_required_deposit(order, request.amount, src_storage_stake);
+
+    let order_hash = near_order_hash(order);
+    let mut timelocks = order.extension.timelocks.clone();
+    timelocks.deployed_at = request.deployed_at;
+
+    let near_immutables = NearImmutables {
+        order_hash: order_hash.clone(),
+        hashlock: order.extension.hashlock.clone(),
+        maker: order.maker.clone(),
+        taker: order.taker.clone(),
+        token: order.maker_asset.clone(),
+        amount: request.amount,
+        safety_deposit: order.extension.src_safety_deposit,
+        timelocks: timelocks.clone(),
+        public_withdrawal_allowlist: None,
+        chain_context: Some(ChainContext {
+            src_chain_id: order.extension.src_chain_id,
+            dst_chain_id: order.extension.dst_chain_id,
+        }),
+        hash_algorithm: order.extension.hash_algorithm,
+    };
+    let near_src_escrow = near_escrow_address(&near_immutables, near_escrow_factory)?;
+
+    let eth_immutables = EthImmutables {
+        order_hash: decode_bytes32("order hash", &order_hash)?,
+        hashlock: decode_bytes32("hashlock", &order.extension.hashlock)?,
+        maker: parse_address("eth_receiver", &request.eth_receiver)?,
+        taker: Address::zero(), // Unknown until the ETH resolver deploys, not part of the planned salt
+        token: parse_address("taker_asset", &order.taker_asset)?,
+        amount: U256::from(dst_amount),
+        safety_deposit: U256::from(order.extension.dst_safety_deposit),
+        timelocks: create_timelocks(
+            timelocks.src_withdrawal,
+            timelocks.src_public_withdrawal,
+            timelocks.src_cancellation,
+            timelocks.src_public_cancellation,
+            timelocks.dst_withdrawal,
+            timelocks.dst_public_withdrawal,
+            timelocks.dst_cancellation,
+            (request.deployed_at / 1000) as u32,
+        )?,
+    };
+    let eth_dst_escrow = eth_escrow_address(
+        parse_address("eth_escrow_factory", &request.eth_escrow_factory)?,
+        &eth_immutables,
+        decode_bytes32("eth_dst_proxy_bytecode_hash", &request.eth_dst_proxy_bytecode_hash)?,
+    );
+
+    let at = |offset: u32| request.deployed_at + offset as u64 * 1000;
+    Ok(DeploymentPlan {
+        direction: SwapDirection::NearToEth,
+        order_hash,
+        near_src_escrow,
+        eth_dst_escrow: format!("{:?}", eth_dst_escrow),
+        src_required_deposit,
+        dst_amount,
+        dst_safety_deposit: order.extension.dst_safety_deposit,
+        timelocks: TimelockSchedule {
+            src_withdrawal: at(timelocks.src_withdrawal),
+            src_public_withdrawal: at(timelocks.src_public_withdrawal),
+            src_cancellation: at(timelocks.src_cancellation),
+            src_public_cancellation: at(timelocks.src_public_cancellation),
+            dst_withdrawal: at(timelocks.dst_withdrawal),
+            dst_public_withdrawal: at(timelocks.dst_public_withdrawal),
+            dst_cancellation: at(timelocks.dst_cancellation),
+        },
+    })
+}
+
+/// Checks `amount` of `order` is a NEAR -> ETH fill the resolver can execute, returns the pro-rata dst amount
+pub fn near_to_eth_dst_amount(order: &Order, amount: u128) -> Result<u128, String> {
+    let direction = SwapDirection::from_chain_ids(order.extension.src_chain_id, order.extension.dst_chain_id)?;
+    if direction != SwapDirection::NearToEth {
+        return Err(format!("Only NEAR -> ETH fills are supported, order is {:?}", direction));
+    }
+    if amount == 0 || amount > order.making_amount {
+        return Err(format!(
+            "Amount {} must be between 1 and the order making amount {}",
+            amount, order.making_amount
+        ));
+    }
+
+    Ok(order
+        .taking_amount
+        .checked_mul(amount)
+        .ok_or("Pro-rata destination amount overflows")?
+        / order.making_amount)
+}
+
+/// Same deposit rule as Resolver::deploy_src, including the new account's storage stake
+pub fn src_required_deposit(order: &Order, amount: u128, storage_stake: u128) -> u128 {
+    if order.maker_asset.as_str() == "near" {
+        amount + order.extension.src_safety_deposit + storage_stake
+    } else {
+        order.extension.src_safety_deposit + storage_stake
+    }
+}
+
+/// Mirrors EscrowFactory::compute_escrow_address, sha256 over the immutables' JSON with
+/// `deployed_at` in ms
+fn near_escrow_address(immutables: &NearImmutables, factory: &str) -> Result<String, String> {
+    let serialized = serde_json::to_vec(immutables).map_err(|e| format!("Failed to serialize immutables: {}", e))?;
+    let hash = k256::sha2::Sha256::digest(&serialized);
+    Ok(format!("escrow-{}.{}", &hex::encode(hash)[..16], factory))
+}
+
+fn parse_address(field: &str, value: &str) -> Result<Address, String> {
+    Address::from_str(value).map_err(|e| format!("Invalid {} address {}: {}", field, value, e))
+}
+
+fn decode_bytes32(field: &str, value: &str) -> Result<[u8; 32], String> {
+    let bytes = hex::decode(value.trim_start_matches("0x")).map_err(|e| format!("Invalid {} hex: {}", field, e))?;
+    bytes.try_into().map_err(|_| format!("{} must be 32 bytes", field))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_api::AccountId;
+    use crate::near::utils::{HashAlgo, OrderExtension, Timelocks};
+
+    fn request() -> PlanRequest {
+        PlanRequest {
+            order: Order {
+                maker: AccountId::from_str("maker.testnet").unwrap(),
+                taker: AccountId::from_str("resolver.testnet").unwrap(),
+                making_amount: 1000,
+                taking_amount: 3000,
+                maker_asset: AccountId::from_str("near").unwrap(),
+                taker_asset: "0x1c7d4b196cb0c7b01d743fbc6116a902379c7238".to_string(),
+                salt: "1".to_string(),
+                extension: OrderExtension {
+                    hashlock: "ab".repeat(32),
+                    src_chain_id: 397,
+                    dst_chain_id: 11155111,
+                    src_safety_deposit: 10,
+                    dst_safety_deposit: 20,
+                    timelocks: Timelocks {
+                        deployed_at: 0,
+                        src_withdrawal: 60,
+                        src_public_withdrawal: 600,
+                        src_cancellation: 1800,
+                        src_public_cancellation: 3600,
+                        dst_withdrawal: 60,
+                        dst_public_withdrawal: 600,
+                        dst_cancellation: 1500,
+                        grace_ms: 0,
+                    },
+                    hash_algorithm: HashAlgo::Sha256,
+                    dst_receiver: None,
+                },
+            },
+            amount: 400,
+            deployed_at: 1_700_000_000_000,
+            eth_receiver: format!("{:?}", Address::repeat_byte(0x11)),
+            eth_escrow_factory: format!("{:?}", Address::repeat_byte(0x22)),
+            eth_dst_proxy_bytecode_hash: "33".repeat(32),
+        }
+    }
+
+    /// A factory's src_escrow_storage_stake at 10^19 yoctoNEAR per byte
+    const SRC_ESCROW_STORAGE_STAKE: u128 = 2_200 * 10u128.pow(19);
+
+    #[test]
+    fn test_plan_for_sample_order() {
+        let factory = NearDeploymentConfig::default().escrow_factory;
+        let plan = build_deployment_plan(&request(), &factory, SRC_ESCROW_STORAGE_STAKE).unwrap();
+
+        assert_eq!(plan.direction, SwapDirection::NearToEth);
+        assert_eq!(plan.order_hash, near_order_hash(&request().order));
+        assert!(plan.near_src_escrow.starts_with("escrow-"));
+        assert!(plan.near_src_escrow.ends_with(&format!(".{}", factory)));
+        assert!(plan.eth_dst_escrow.starts_with("0x"));
+        assert_eq!(plan.src_required_deposit, 410 + SRC_ESCROW_STORAGE_STAKE); // Native NEAR: amount + safety deposit + storage
+        assert_eq!(plan.dst_amount, 1200);
+        assert_eq!(plan.dst_safety_deposit, 20);
+        assert_eq!(plan.timelocks.src_cancellation, 1_700_001_800_000);
+        assert_eq!(plan.timelocks.dst_cancellation, 1_700_001_500_000);
+
+        let mut over = request();
+        over.amount = 1001;
+        assert!(build_deployment_plan(&over, "factory.testnet", SRC_ESCROW_STORAGE_STAKE).is_err());
+    }
+
+    #[test]
+    fn test_near_escrow_address_matches_factory() {
+        // Same immutables and hash as the factory's test_immutables_hash_bytes_are_pinned
+        let immutables = NearImmutables {
+            order_hash: "0xorder".to_string(),
+            hashlock: "ab".repeat(32),
+            maker: AccountId::from_str("maker.testnet").unwrap(),
+            taker: AccountId::from_str("resolver.testnet").unwrap(),
+            token: AccountId::from_str("near").unwrap(),
+            amount: 1000,
+            safety_deposit: 100,
+            timelocks: Timelocks {
+                deployed_at: 1_700_000_000_000,
+                src_withdrawal: 60,
+                src_public_withdrawal: 120,
+                src_cancellation: 180,
+                src_public_cancellation: 240,
+                dst_withdrawal: 30,
+                dst_public_withdrawal: 90,
+                dst_cancellation: 150,
+                grace_ms: 0,
+            },
+            public_withdrawal_allowlist: None,
+            chain_context: Some(ChainContext { src_chain_id: 397, dst_chain_id: 11155111 }),
+            hash_algorithm: HashAlgo::Sha256,
+        };
+
+        assert_eq!(
+            near_escrow_address(&immutables, "factory.testnet").unwrap(),
+            "escrow-216d1d0c4f6ea80f.factory.testnet"
+        );
+    }
+}
@@ -0,0 +1,63 @@
+use k256::sha2::Sha256;
+use rand::{rngs::OsRng, RngCore};
+use sha3::Digest;
+
+pub const SECRET_LENGTH: usize = 32;
+
+/// Generate a fresh swap secret from the OS CSPRNG
+pub fn generate_secret() -> [u8; SECRET_LENGTH] {
+    let mut secret = [0u8; SECRET_LENGTH];
+    OsRng.fill_bytes(&mut secret);
+    secret
+}
+
+/// Short secrets can be brute-forced from the hashlock, so only full 32-byte preimages are accepted
+pub fn validate_secret(secret: &[u8]) -> Result<[u8; SECRET_LENGTH], String> {
+    secret.try_into().map_err(|_| {
+        format!(
+            "Secret must be exactly {} bytes, got {}",
+            SECRET_LENGTH,
+            secret.len()
+        )
+    })
+}
+
+/// Hex encoded sha256 hashlock matching the NEAR escrows' `hash_secret`
+pub fn hashlock_from_secret(secret: &[u8]) -> Result<String, String> {
+    let secret = validate_secret(secret)?;
+    Ok(hex::encode(Sha256::digest(secret)))
+}
+
+/// Hex encoded keccak256 hashlock, the only kind an EVM escrow verifies
+pub fn keccak_hashlock_from_secret(secret: &[u8]) -> Result<String, String> {
+    let secret = validate_secret(secret)?;
+    Ok(hex::encode(sha3::Keccak256::digest(secret)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_short_secret_is_rejected() {
+        assert!(validate_secret(&[1u8; 16]).is_err());
+        assert!(hashlock_from_secret(b"password").is_err());
+    }
+
+    #[test]
+    fn test_generate_secret_is_distinct() {
+        let first = generate_secret();
+        let second = generate_secret();
+        assert_eq!(first.len(), SECRET_LENGTH);
+        assert_ne!(first, second);
+        assert!(hashlock_from_secret(&first).is_ok());
+    }
+
+    #[test]
+    fn test_keccak_hashlock_of_zero_secret() {
+        assert_eq!(
+            keccak_hashlock_from_secret(&[0u8; SECRET_LENGTH]).unwrap(),
+            "290decd9548b62a8d60345a988386fc84ba6bc95484008f6362f93160ef3e563"
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use std::{env, str::FromStr, sync::LazyLock};
+use std::{env, sync::LazyLock};
 use k256::elliptic_curve::rand_core::le;
 use near_api::{Account, AccountId, Chain};
 use near_crypto::ED25519PublicKey;
@@ -14,8 +14,74 @@ generate_api!("openapi.yaml");
 pub static SEPOLIA_RPC_URL: LazyLock<String> = LazyLock::new(|| env::var("ALCHEMY_ETH_SEPOLIA_RPC_URL").unwrap());
 pub static NEAR_RESOLVER_WASM: &[u8] = include_bytes!("../near_resolver.wasm");
 
-pub async fn get_testnet_mpc_signer_account_id() -> AccountId {
-    AccountId::from_str("v1.signer-prod.testnet").unwrap()
+pub const DEFAULT_MAX_TIMELOCK_OFFSET: u32 = 30 * 24 * 60 * 60; // 30 days in seconds
+
+/// Upper bound for a single timelock offset in seconds, overridable via MAX_TIMELOCK_OFFSET.
+/// Offsets beyond a few days usually mean milliseconds were passed where seconds are expected.
+pub fn max_timelock_offset() -> u32 {
+    env::var("MAX_TIMELOCK_OFFSET")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_MAX_TIMELOCK_OFFSET)
+}
+
+pub fn validate_timelock_offset(stage: &str, offset: u32, max_offset: u32) -> Result<(), String> {
+    if offset > max_offset {
+        return Err(format!(
+            "Timelock offset for {} is {}s, exceeds maximum of {}s (was it given in ms?)",
+            stage, offset, max_offset
+        ));
+    }
+    Ok(())
+}
+
+/// Serde adapter encoding u128 amounts as decimal strings, matching the contracts' U128 JSON format.
+/// Plain JSON numbers lose precision above 2^53 in most clients.
+pub mod u128_string {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &u128, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&value.to_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u128, D::Error> {
+        String::deserialize(deserializer)?.parse().map_err(D::Error::custom)
+    }
+}
+
+/// Serde adapter encoding u64 timestamps as decimal strings, matching the contracts' U64 JSON format
+pub mod u64_string {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &u64, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&value.to_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+        String::deserialize(deserializer)?.parse().map_err(D::Error::custom)
+    }
+}
+
+/// `u64_string` for optional timestamps, None stays null
+pub mod option_u64_string {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &Option<u64>, serializer: S) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(value) => serializer.serialize_str(&value.to_string()),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|value| value.parse().map_err(D::Error::custom))
+            .transpose()
+    }
+}
+
+pub async fn get_mpc_signer_account_id() -> AccountId {
+    crate::near::config::NEAR_NETWORK.mpc_signer_account_id()
 }
 
 pub async fn get_tee_account() -> String {
@@ -23,6 +89,12 @@ pub async fn get_tee_account() -> String {
     agent_account_id_result["accountId"].to_string().trim_matches('"').to_string()
 }
 
+/// Fresh id for one swap, passed to the NEAR contracts so the factory, escrow and resolver
+/// events of that swap can be joined with the service's own logs
+pub fn new_correlation_id() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
 pub fn json_bytes<T>(structure: T) -> Vec<u8> where T: Serialize {
     let mut bytes: Vec<u8> = Vec::new();
     serde_json::to_writer(&mut bytes, &structure).unwrap();
@@ -35,4 +107,54 @@ fn get_client() -> Client{
 
 pub async fn read_order() {
     println!("{:?}", get_client().get_active_orders().await);
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_timelock_offset() {
+        assert!(validate_timelock_offset("src_withdrawal", 3600, DEFAULT_MAX_TIMELOCK_OFFSET).is_ok());
+        // 1 hour given in milliseconds
+        assert!(validate_timelock_offset("src_withdrawal", 3_600_000, DEFAULT_MAX_TIMELOCK_OFFSET).is_err());
+    }
+
+    #[derive(Serialize, serde::Deserialize)]
+    struct Amount {
+        #[serde(with = "u128_string")]
+        value: u128,
+    }
+
+    #[test]
+    fn test_u128_string_round_trip() {
+        let amount = Amount { value: u128::MAX };
+        let encoded = serde_json::to_string(&amount).unwrap();
+        assert_eq!(encoded, format!("{{\"value\":\"{}\"}}", u128::MAX));
+
+        let decoded: Amount = serde_json::from_str(&encoded).unwrap();
+        assert_eq!(decoded.value, u128::MAX);
+    }
+
+    #[derive(Serialize, serde::Deserialize)]
+    struct Timestamps {
+        #[serde(with = "u64_string")]
+        at: u64,
+        #[serde(default, with = "option_u64_string")]
+        maybe_at: Option<u64>,
+    }
+
+    #[test]
+    fn test_u64_string_round_trip() {
+        let timestamps = Timestamps { at: u64::MAX, maybe_at: Some(1_700_000_000_000) };
+        let encoded = serde_json::to_string(&timestamps).unwrap();
+        assert_eq!(encoded, format!("{{\"at\":\"{}\",\"maybe_at\":\"1700000000000\"}}", u64::MAX));
+
+        let decoded: Timestamps = serde_json::from_str(&encoded).unwrap();
+        assert_eq!(decoded.at, u64::MAX);
+        assert_eq!(decoded.maybe_at, Some(1_700_000_000_000));
+
+        let decoded: Timestamps = serde_json::from_str(r#"{"at":"0","maybe_at":null}"#).unwrap();
+        assert_eq!(decoded.maybe_at, None);
+    }
+}
@@ -0,0 +1,193 @@
+//! The 1inch side of the ETH src leg on anvil: Limit Order Protocol, escrow factory and a
+//! mintable maker token, deployed from the forge output of cross-chain-resolver-example:
+//!
+//!     forge build contracts/src \
+//!         contracts/lib/cross-chain-swap/lib/limit-order-protocol/contracts/LimitOrderProtocol.sol \
+//!         contracts/lib/cross-chain-swap/lib/solidity-utils/contracts/mocks/TokenMock.sol \
+//!         contracts/lib/cross-chain-swap/contracts/mocks/ERC20True.sol
+
+use std::sync::Arc;
+
+use ethers::abi::{Abi, Token};
+use ethers::contract::{Contract, ContractFactory};
+use ethers::middleware::SignerMiddleware;
+use ethers::providers::{Http, Middleware, Provider};
+use ethers::signers::{LocalWallet, Signer};
+use ethers::types::{transaction::eip2718::TypedTransaction, Address, Bytes, TransactionRequest, H256, U256};
+use ethers::utils::{keccak256, AnvilInstance};
+use serde_json::Value;
+
+use oneprime_resolver::eth::utils::{Immutables, MakerTraitsBuilder, Order};
+use oneprime_resolver::orchestration::direction::NEAR_CHAIN_ID;
+use oneprime_resolver::orchestration::order_hash::evm_order_hash;
+
+use super::ETH_CHAIN_ID;
+
+const ARTIFACTS_DIR: &str = "../evm-contracts/1inch-contracts/cross-chain-resolver-example/dist/contracts";
+
+type Client = SignerMiddleware<Provider<Http>, LocalWallet>;
+
+fn artifact(env_var: &str, file: &str) -> (Abi, Bytes) {
+    let path = std::env::var(env_var).unwrap_or_else(|_| format!("{}/{}.sol/{}.json", ARTIFACTS_DIR, file, file));
+    let json: Value = std::fs::read_to_string(&path)
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_else(|| panic!("Failed to read {} ({}), run forge build first", path, env_var));
+    let abi = serde_json::from_value(json["abi"].clone()).unwrap_or_else(|e| panic!("Invalid ABI in {}: {}", path, e));
+    let bytecode = json["bytecode"]["object"]
+        .as_str()
+        .and_then(|bytecode| bytecode.parse().ok())
+        .unwrap_or_else(|| panic!("Missing bytecode in {}", path));
+    (abi, bytecode)
+}
+
+async fn deploy<T: ethers::abi::Tokenize>(client: &Arc<Client>, env_var: &str, file: &str, args: T) -> anyhow::Result<Contract<Client>> {
+    let (abi, bytecode) = artifact(env_var, file);
+    Ok(ContractFactory::new(abi, bytecode, client.clone()).deploy(args)?.legacy().send().await?)
+}
+
+fn client(anvil: &AnvilInstance, key_index: usize) -> anyhow::Result<Arc<Client>> {
+    let wallet = LocalWallet::from(anvil.keys()[key_index].clone()).with_chain_id(ETH_CHAIN_ID);
+    Ok(Arc::new(SignerMiddleware::new(Provider::<Http>::try_from(anvil.endpoint())?, wallet)))
+}
+
+/// Extension carrying the escrow factory's postInteraction: the resolver whitelist and the
+/// `ExtraDataArgs` the src escrow immutables and dst complement are built from
+pub fn src_extension(factory: Address, resolver: Address, hashlock: [u8; 32], deposits: (u128, u128), timelocks: U256) -> Vec<u8> {
+    let mut post_interaction = factory.as_bytes().to_vec();
+    post_interaction.extend(0u32.to_be_bytes()); // allowed time
+    post_interaction.extend(&resolver.as_bytes()[10..]); // low 80 bits of the whitelisted resolver
+    post_interaction.extend(0u16.to_be_bytes()); // time delta
+    post_interaction.push(1 << 3); // one whitelisted resolver, no resolver fee
+    post_interaction.extend(ethers::abi::encode(&[
+        Token::FixedBytes(hashlock.to_vec()),
+        Token::Uint(U256::from(NEAR_CHAIN_ID)),
+        Token::Address(Address::zero()),
+        Token::Uint((U256::from(deposits.0) << 128) | U256::from(deposits.1)),
+        Token::Uint(timelocks),
+    ]));
+
+    // Offsets word: PostInteractionData is the last of the 8 fields and the only one present
+    let mut extension = vec![0u8; 32];
+    extension[..4].copy_from_slice(&(post_interaction.len() as u32).to_be_bytes());
+    extension.extend(post_interaction);
+    extension
+}
+
+/// A maker's signed order, ready for Resolver.deploySrc
+pub struct SignedOrder {
+    pub order: Order,
+    pub order_hash: [u8; 32],
+    pub maker_traits: U256,
+    pub extension: Vec<u8>,
+    pub r: [u8; 32],
+    pub vs: [u8; 32],
+}
+
+pub struct EvmContracts {
+    pub lop: Address,
+    pub factory: Address,
+    /// Maker asset on the src chain
+    pub token: Contract<Client>,
+    /// Taker asset, its transfers always succeed so the resolver needs no balance
+    pub taker_asset: Address,
+    pub maker: Arc<Client>,
+    factory_abi: Abi,
+}
+
+impl EvmContracts {
+    /// Deploys everything from anvil's first key and funds the maker (anvil's second key) with
+    /// `maker_balance` tokens approved to the LOP
+    pub async fn deploy(anvil: &AnvilInstance, maker_balance: U256) -> anyhow::Result<Self> {
+        let deployer = client(anvil, 0)?;
+        let maker = client(anvil, 1)?;
+
+        let token = deploy(&deployer, "TOKEN_MOCK_ARTIFACT", "TokenMock", ("Maker Token".to_string(), "MKR".to_string())).await?;
+        let taker_token = deploy(&deployer, "ERC20_TRUE_ARTIFACT", "ERC20True", ()).await?;
+        // WETH is only touched by native-ETH orders, any address will do
+        let lop = deploy(&deployer, "LIMIT_ORDER_PROTOCOL_ARTIFACT", "LimitOrderProtocol", token.address()).await?;
+        let factory = deploy(
+            &deployer,
+            "ESCROW_FACTORY_ARTIFACT",
+            "TestEscrowFactory",
+            (lop.address(), taker_token.address(), taker_token.address(), deployer.address(), 86_400u32, 86_400u32),
+        )
+        .await?;
+
+        token.method::<_, ()>("mint", (maker.address(), maker_balance))?.legacy().send().await?.await?;
+        token
+            .connect(maker.clone())
+            .method::<_, bool>("approve", (lop.address(), U256::MAX))?
+            .legacy()
+            .send()
+            .await?
+            .await?;
+
+        Ok(Self {
+            lop: lop.address(),
+            factory: factory.address(),
+            token,
+            taker_asset: taker_token.address(),
+            maker,
+            factory_abi: factory.abi().clone(),
+        })
+    }
+
+    /// Order selling `making_amount` maker tokens for `taking_amount`, filled in one go by
+    /// `resolver` through the escrow factory, signed by the maker
+    pub fn sign_order(
+        &self,
+        resolver: Address,
+        making_amount: U256,
+        taking_amount: U256,
+        hashlock: [u8; 32],
+        deposits: (u128, u128),
+        timelocks: U256,
+    ) -> anyhow::Result<SignedOrder> {
+        let extension = src_extension(self.factory, resolver, hashlock, deposits, timelocks);
+        let maker_traits = MakerTraitsBuilder::new().has_extension().post_interaction_call().build();
+        let order = Order {
+            // The LOP checks the low 160 bits of the salt against the extension hash
+            salt: U256::from_big_endian(&keccak256(&extension)) & ((U256::one() << 160) - 1),
+            maker: U256::from_big_endian(self.maker.address().as_bytes()),
+            receiver: U256::zero(),
+            maker_asset: U256::from_big_endian(self.token.address().as_bytes()),
+            taker_asset: U256::from_big_endian(self.taker_asset.as_bytes()),
+            making_amount,
+            taking_amount,
+            maker_traits,
+        };
+        let order_hash = evm_order_hash(&order, ETH_CHAIN_ID, self.lop);
+
+        // Compact (r, vs) signature as the LOP expects it
+        let signature = self.maker.signer().sign_hash(H256::from(order_hash))?;
+        let mut r = [0u8; 32];
+        signature.r.to_big_endian(&mut r);
+        let mut vs = [0u8; 32];
+        (signature.s | (U256::from(signature.v - 27) << 255)).to_big_endian(&mut vs);
+
+        Ok(SignedOrder { order, order_hash, maker_traits, extension, r, vs })
+    }
+
+    /// EscrowFactory.addressOfEscrowSrc, `immutables.timelocks` must include deployed_at
+    pub async fn src_escrow_address(&self, provider: &Provider<Http>, immutables: &Immutables) -> anyhow::Result<Address> {
+        let address = |address: Address| Token::Uint(U256::from_big_endian(address.as_bytes()));
+        let data = self.factory_abi.function("addressOfEscrowSrc")?.encode_input(&[Token::Tuple(vec![
+            Token::FixedBytes(immutables.order_hash.to_vec()),
+            Token::FixedBytes(immutables.hashlock.to_vec()),
+            address(immutables.maker),
+            address(immutables.taker),
+            address(immutables.token),
+            Token::Uint(immutables.amount),
+            Token::Uint(immutables.safety_deposit),
+            Token::Uint(immutables.timelocks),
+        ])])?;
+        let call: TypedTransaction = TransactionRequest::new().to(self.factory).data(data).into();
+        let output = provider.call(&call, None).await?;
+        Ok(Address::from_slice(&output[12..32]))
+    }
+
+    pub async fn token_balance(&self, owner: Address) -> anyhow::Result<U256> {
+        Ok(self.token.method::<_, U256>("balanceOf", owner)?.call().await?)
+    }
+}
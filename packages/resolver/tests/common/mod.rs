@@ -0,0 +1,261 @@
+//! Local stand-ins for everything the resolver service talks to: a near-workspaces sandbox with
+//! the escrow factory and the resolver contract, an anvil node with the 1inch contracts for the
+//! ETH leg (see evm.rs), and a mock shade agent API answering `request_signature` with fixed
+//! local keys instead of the MPC network.
+//!
+//! The service reads its endpoints from the environment once, so each test binary starts a
+//! single `Harness` before calling into the service.
+
+pub mod evm;
+
+use std::sync::Arc;
+
+use axum::{extract::{Path, State}, routing::post, Json, Router};
+use ethers::providers::{Http, Middleware, Provider};
+use ethers::types::{Address, U256};
+use ethers::utils::{Anvil, AnvilInstance};
+use k256::ecdsa::SigningKey;
+use k256::elliptic_curve::sec1::ToEncodedPoint;
+use near_crypto::{KeyType, SecretKey, Signature};
+use near_workspaces::network::Sandbox;
+use near_workspaces::types::NearToken;
+use near_workspaces::{Account, AccountId, Contract, Worker};
+use serde_json::{json, Value};
+
+use evm::EvmContracts;
+use oneprime_resolver::near::utils::{deploy_near_resolver_contract_with, NearGlobalCodeHashReader, NearResolverOwnerReader};
+use oneprime_resolver::routes::eth::get_address::{eth_address_from_derived_key, update_funding_eth_address};
+use oneprime_resolver::routes::near::get_address::{
+    implicit_address_from_derived_key, update_funding_near_address, update_funding_near_public_key,
+};
+
+/// Sepolia's chain id, the service signs every EVM transaction for it
+const ETH_CHAIN_ID: u64 = 11155111;
+const AGENT_ACCOUNT: &str = "agent.test.near";
+
+pub fn wasm(env_var: &str, default_path: &str) -> Vec<u8> {
+    let path = std::env::var(env_var).unwrap_or_else(|_| default_path.to_string());
+    std::fs::read(&path).unwrap_or_else(|e| panic!("Failed to read {} ({}): {}, run build.sh first", path, env_var, e))
+}
+
+/// Signs like the MPC network would for the funding path: ed25519 over the sha256 transaction
+/// hash for NEAR, recoverable secp256k1 over the keccak hash for ETH
+pub struct MockSigner {
+    pub near_key: SecretKey,
+    pub eth_key: SigningKey,
+}
+
+impl MockSigner {
+    pub fn new() -> Self {
+        Self {
+            near_key: SecretKey::from_seed(KeyType::ED25519, "oneprime-funding-eth"),
+            eth_key: SigningKey::from_bytes(&[0x42; 32].into()).unwrap(),
+        }
+    }
+
+    /// Implicit account of the ed25519 key, the resolver's NEAR funding account
+    pub fn near_address(&self) -> String {
+        implicit_address_from_derived_key(&self.near_key.public_key().to_string()).unwrap()
+    }
+
+    pub fn eth_address(&self) -> String {
+        let point = self.eth_key.verifying_key().to_encoded_point(false);
+        let key = format!("secp256k1:{}", bs58::encode(&point.as_bytes()[1..]).into_string());
+        eth_address_from_derived_key(&key).unwrap()
+    }
+
+    fn sign(&self, payload_hex: &str, key_type: &str) -> Value {
+        let payload = hex::decode(payload_hex).expect("payload must be a hex hash");
+        match key_type {
+            "Eddsa" => match self.near_key.sign(&payload) {
+                Signature::ED25519(signature) => json!({ "signature": signature.to_bytes().to_vec() }),
+                other => panic!("Unexpected signature {:?}", other),
+            },
+            _ => {
+                let (signature, recovery_id) = self.eth_key.sign_prehash_recoverable(&payload).unwrap();
+                json!({
+                    "big_r": { "affine_point": format!("02{}", hex::encode(signature.r().to_bytes())) },
+                    "s": { "scalar": hex::encode(signature.s().to_bytes()) },
+                    "recovery_id": recovery_id.to_byte(),
+                })
+            }
+        }
+    }
+}
+
+async fn agent_api(State(signer): State<Arc<MockSigner>>, Path(method): Path<String>, Json(body): Json<Value>) -> Json<Value> {
+    match method.as_str() {
+        "getAccountId" => Json(json!({ "accountId": AGENT_ACCOUNT })),
+        "call" if body["methodName"] == "request_signature" => Json(signer.sign(
+            body["args"]["payload"].as_str().unwrap_or_default(),
+            body["args"]["key_type"].as_str().unwrap_or("Ecdsa"),
+        )),
+        _ => panic!("Mock agent API does not serve {} {}", method, body),
+    }
+}
+
+/// Serves the agent API on a free local port, returns the port
+async fn spawn_agent_api(signer: Arc<MockSigner>) -> anyhow::Result<u16> {
+    let app = Router::new().route("/api/agent/{method}", post(agent_api)).with_state(signer);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let port = listener.local_addr()?.port();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    Ok(port)
+}
+
+pub struct Harness {
+    pub worker: Worker<Sandbox>,
+    pub anvil: AnvilInstance,
+    pub root: Account,
+    pub factory: Contract,
+    pub maker: Account,
+    pub evm: EvmContracts,
+    /// Resolver contract account, the implicit account of the mock signer's ed25519 key
+    pub funding_account: AccountId,
+}
+
+impl Harness {
+    /// Starts both chains and the agent API, points the service at them and deploys the
+    /// resolver contract on NEAR the way the service does on startup
+    pub async fn start() -> anyhow::Result<Self> {
+        let signer = Arc::new(MockSigner::new());
+        let agent_port = spawn_agent_api(signer.clone()).await?;
+
+        let worker = near_workspaces::sandbox().await?;
+        // The service signs with fixed tip and fee caps, a zero base fee keeps them includable
+        let anvil = Anvil::new()
+            .chain_id(ETH_CHAIN_ID)
+            .args(["--block-base-fee-per-gas", "0"])
+            .spawn();
+
+        let evm = EvmContracts::deploy(&anvil, U256::exp10(24)).await?;
+
+        let root = worker.root_account()?;
+        let src_template = root
+            .create_subaccount("escrow-src-template")
+            .initial_balance(NearToken::from_near(50))
+            .transact()
+            .await?
+            .into_result()?;
+        src_template
+            .deploy_global_contract_by_account_id(&wasm("ESCROW_SRC_WASM", "../near-contracts/target/near/escrow_src/escrow_src.wasm"))
+            .await?
+            .into_result()?;
+        let dst_template = root
+            .create_subaccount("escrow-dst-template")
+            .initial_balance(NearToken::from_near(50))
+            .transact()
+            .await?
+            .into_result()?;
+        dst_template
+            .deploy_global_contract_by_account_id(&wasm("ESCROW_DST_WASM", "../near-contracts/target/near/escrow_dst/escrow_dst.wasm"))
+            .await?
+            .into_result()?;
+
+        let resolver_code = root
+            .create_subaccount("resolver-code")
+            .initial_balance(NearToken::from_near(50))
+            .transact()
+            .await?
+            .into_result()?;
+        resolver_code
+            .deploy_global_contract_by_account_id(&wasm(
+                "NEAR_RESOLVER_WASM",
+                "../near-contracts/target/near/near_resolver/near_resolver.wasm",
+            ))
+            .await?
+            .into_result()?;
+
+        let factory = root
+            .create_subaccount("factory")
+            .initial_balance(NearToken::from_near(50))
+            .transact()
+            .await?
+            .into_result()?
+            .deploy(&wasm("ESCROW_FACTORY_WASM", "../near-contracts/target/near/escrow_factory/escrow_factory.wasm"))
+            .await?
+            .into_result()?;
+        factory
+            .call("new")
+            .args_json(json!({
+                "owner": root.id(),
+                "rescue_delay": 86_400,
+                "escrow_src_template": src_template.id(),
+                "escrow_dst_template": dst_template.id(),
+            }))
+            .transact()
+            .await?
+            .into_result()?;
+
+        let maker = root.create_subaccount("maker").initial_balance(NearToken::from_near(1)).transact().await?.into_result()?;
+
+        // SAFETY: set once per test binary, before the service reads any of them
+        unsafe {
+            std::env::set_var("API_PORT", agent_port.to_string());
+            std::env::set_var("NEXT_PUBLIC_contractId", AGENT_ACCOUNT);
+            std::env::set_var("NEAR_NETWORK", "sandbox");
+            std::env::set_var("NEAR_SANDBOX_RPC_URL", worker.rpc_addr());
+            std::env::set_var("ALCHEMY_ETH_SEPOLIA_RPC_URL", anvil.endpoint());
+            std::env::set_var("ETH_ESCROW_FACTORY", format!("{:?}", evm.factory));
+            std::env::set_var("ETH_LIMIT_ORDER_PROTOCOL", format!("{:?}", evm.lop));
+            std::env::set_var("NEAR_ESCROW_FACTORY", factory.id().as_str());
+            std::env::set_var("NEAR_GLOBAL_RESOLVER_CONTRACT", resolver_code.id().as_str());
+        }
+
+        // What setup_funding_*_address would derive from the MPC contract
+        update_funding_near_public_key(signer.near_key.public_key().to_string());
+        update_funding_near_address(signer.near_address());
+        update_funding_eth_address(signer.eth_address());
+
+        let funding_account: AccountId = signer.near_address().parse()?;
+        root.transfer_near(&funding_account, NearToken::from_near(20)).await?.into_result()?;
+
+        let eth_provider = Provider::<Http>::try_from(anvil.endpoint())?;
+        let eth_funding: Address = signer.eth_address().parse()?;
+        eth_provider
+            .request::<_, ()>("anvil_setBalance", (eth_funding, U256::exp10(20)))
+            .await?;
+
+        deploy_near_resolver_contract_with(&NearResolverOwnerReader, &NearGlobalCodeHashReader)
+            .await
+            .map_err(anyhow::Error::msg)?;
+
+        Ok(Self { worker, anvil, root, factory, maker, evm, funding_account })
+    }
+
+    pub fn eth_provider(&self) -> Provider<Http> {
+        Provider::<Http>::try_from(self.anvil.endpoint()).expect("Invalid anvil endpoint")
+    }
+
+    /// Timestamp of the latest anvil block in ms
+    pub async fn eth_now_ms(&self) -> anyhow::Result<u64> {
+        let block = self.eth_provider().get_block(ethers::types::BlockNumber::Latest).await?.expect("anvil has a latest block");
+        Ok(block.timestamp.as_u64() * 1000)
+    }
+
+    /// Timestamp of the latest sandbox block in ms
+    pub async fn near_now_ms(&self) -> anyhow::Result<u64> {
+        Ok(self.worker.view_block().await?.timestamp() / 1_000_000)
+    }
+
+    /// Waits for the sandbox clock to reach `timestamp_ms`, the two local clocks drift apart
+    pub async fn wait_for_near_time(&self, timestamp_ms: u64) -> anyhow::Result<u64> {
+        loop {
+            let now_ms = self.near_now_ms().await?;
+            if now_ms >= timestamp_ms {
+                return Ok(now_ms);
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        }
+    }
+
+    /// Moves anvil's clock forward and mines a block at the new time
+    pub async fn eth_fast_forward(&self, secs: u64) -> anyhow::Result<()> {
+        let provider = self.eth_provider();
+        provider.request::<_, Value>("evm_increaseTime", [secs]).await?;
+        provider.request::<_, Value>("evm_mine", ()).await?;
+        Ok(())
+    }
+}
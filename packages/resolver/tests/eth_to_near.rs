@@ -0,0 +1,209 @@
+//! ETH -> NEAR swap driven through the resolver service against local chains, see common/mod.rs.
+//!
+//! The maker's order is filled through the resolver contract on anvil, which makes the 1inch
+//! escrow factory deploy the src escrow. The NEAR leg then runs as the service runs it: dst
+//! escrow creation, secret reveal, withdrawal and confirmation, after which the resolver
+//! withdraws the src escrow with the revealed secret.
+//!
+//! Needs the wasm output of near-contracts/build.sh (override with ESCROW_FACTORY_WASM,
+//! ESCROW_SRC_WASM, ESCROW_DST_WASM and NEAR_RESOLVER_WASM), the forge output listed in
+//! common/evm.rs, anvil on the PATH and a near-sandbox binary (NEAR_SANDBOX_BIN_PATH skips the
+//! download near-workspaces would otherwise do):
+//!
+//!     (cd ../near-contracts && ./build.sh) && cargo test --test eth_to_near -- --ignored
+//!
+//! .github/workflows/resolver-integration.yml runs it in CI.
+
+mod common;
+
+use ethers::types::{Address, U256};
+use ethers::utils::keccak256;
+use near_workspaces::types::NearToken;
+use near_workspaces::AccountId;
+use omni_transaction::near::types::{Action, FunctionCallAction, U128, U64};
+use serde_json::json;
+
+use common::Harness;
+use oneprime_resolver::eth::utils::{
+    create_timelocks, deploy_eth_resolver_contract, deploy_eth_src_contract, withdraw_eth_escrow, Immutables as EthImmutables,
+    TakerTraitsBuilder,
+};
+use oneprime_resolver::near::utils::{send_funding_actions, HashAlgo, Immutables, Timelocks};
+use oneprime_resolver::orchestration::{
+    eth_cancel::deploy_dst_or_cancel_src,
+    reveal::accept_secret_reveal,
+    store::{swap_id, SwapRecord, SwapStatus, SWAP_STORE},
+    timelock_sync::{align_dst_timelocks, src_cancellation_timestamp},
+    withdrawal::confirm_withdrawal,
+};
+use oneprime_resolver::utils::{json_bytes, new_correlation_id};
+
+const DST_WITHDRAWAL_SECS: u32 = 10;
+
+/// `send_funding_actions` returns once the transaction is included, give its receipts time to run
+async fn settle() {
+    tokio::time::sleep(std::time::Duration::from_secs(3)).await;
+}
+
+fn function_call(method_name: &str, args: serde_json::Value) -> Action {
+    Action::FunctionCall(Box::new(FunctionCallAction {
+        method_name: method_name.to_string(),
+        args: json_bytes(args),
+        gas: U64(300_000_000_000_000),
+        deposit: U128(0),
+    }))
+}
+
+#[tokio::test]
+#[ignore = "needs build.sh and forge output, a NEAR sandbox and anvil"]
+async fn test_eth_to_near_swap_completes() -> anyhow::Result<()> {
+    let harness = Harness::start().await?;
+    let eth_resolver = deploy_eth_resolver_contract().await.map_err(anyhow::Error::msg)?;
+
+    // The EVM src escrow checks keccak256(secret), so both legs lock on the keccak hashlock
+    let secret = [7u8; 32];
+    let hashlock = keccak256(secret);
+    let making_amount = U256::exp10(20);
+    let amount = NearToken::from_millinear(500).as_yoctonear();
+    let safety_deposit = NearToken::from_millinear(100).as_yoctonear();
+    let timelocks = Timelocks {
+        deployed_at: 0,
+        src_withdrawal: DST_WITHDRAWAL_SECS,
+        src_public_withdrawal: 1_000,
+        src_cancellation: 10_000,
+        src_public_cancellation: 20_000,
+        dst_withdrawal: DST_WITHDRAWAL_SECS,
+        dst_public_withdrawal: 1_000,
+        dst_cancellation: 2_000,
+        grace_ms: 0,
+    };
+    let eth_timelocks = |deployed_at: u32| {
+        create_timelocks(
+            timelocks.src_withdrawal,
+            timelocks.src_public_withdrawal,
+            timelocks.src_cancellation,
+            timelocks.src_public_cancellation,
+            timelocks.dst_withdrawal,
+            timelocks.dst_public_withdrawal,
+            timelocks.dst_cancellation,
+            deployed_at,
+        )
+        .map_err(anyhow::Error::msg)
+    };
+
+    // ETH leg: the resolver contract fills the maker's order, the factory deploys the src escrow
+    let signed = harness
+        .evm
+        .sign_order(eth_resolver, making_amount, U256::from(amount), hashlock, (0, safety_deposit), eth_timelocks(0)?)?;
+    let mut src_immutables = EthImmutables {
+        order_hash: signed.order_hash,
+        hashlock,
+        maker: harness.evm.maker.address(),
+        taker: eth_resolver,
+        token: harness.evm.token.address(),
+        amount: making_amount,
+        safety_deposit: U256::zero(),
+        timelocks: eth_timelocks(0)?,
+    };
+    let taker_traits = TakerTraitsBuilder::new()
+        .is_making_amount()
+        .set_threshold(U256::from(amount))
+        .set_args_extension_length(signed.extension.len() as u32)
+        .build();
+    deploy_eth_src_contract(
+        src_immutables.clone(),
+        signed.order,
+        signed.r,
+        signed.vs,
+        making_amount,
+        taker_traits,
+        signed.extension,
+    )
+    .await;
+
+    // deploySrc stamps the fill block's time into the src timelocks
+    let src_deployed_at_ms = harness.eth_now_ms().await?;
+    src_immutables.timelocks = eth_timelocks((src_deployed_at_ms / 1000) as u32)?;
+    let src_escrow = harness.evm.src_escrow_address(&harness.eth_provider(), &src_immutables).await?;
+    assert_eq!(harness.evm.token_balance(src_escrow).await?, making_amount, "src escrow was not funded by the fill");
+
+    // NEAR leg
+    let order_hash = format!("0x{}", hex::encode(signed.order_hash));
+    let near_now_ms = harness.wait_for_near_time(src_deployed_at_ms).await?;
+    let dst_timelocks = align_dst_timelocks(&timelocks, src_deployed_at_ms, near_now_ms).map_err(anyhow::Error::msg)?;
+    let dst_immutables = Immutables {
+        order_hash: order_hash.clone(),
+        hashlock: hex::encode(hashlock),
+        maker: harness.maker.id().as_str().parse()?,
+        taker: harness.funding_account.as_str().parse()?,
+        token: "near".parse()?,
+        amount,
+        safety_deposit,
+        timelocks: dst_timelocks,
+        public_withdrawal_allowlist: None,
+        chain_context: None,
+        hash_algorithm: HashAlgo::Keccak256,
+    };
+    let expected_immutables = serde_json::to_value(&dst_immutables)?;
+    SWAP_STORE.insert(SwapRecord {
+        order_hash: order_hash.clone(),
+        fill_index: 0,
+        escrow_account: None,
+        status: SwapStatus::Deployed,
+        correlation_id: new_correlation_id(),
+    });
+    let swap_id = swap_id(&order_hash, 0);
+
+    let deadlines = deploy_dst_or_cancel_src(
+        &swap_id,
+        dst_immutables,
+        src_cancellation_timestamp(&timelocks, src_deployed_at_ms),
+        src_escrow,
+        src_immutables.clone(),
+    )
+    .await
+    .map_err(anyhow::Error::msg)?;
+    assert_eq!(deadlines.withdrawal, src_deployed_at_ms + DST_WITHDRAWAL_SECS as u64 * 1000);
+
+    let escrow: AccountId = harness
+        .factory
+        .view("get_escrow_address")
+        .args_json(json!({ "order_hash": order_hash }))
+        .await?
+        .json::<Option<AccountId>>()?
+        .expect("dst escrow not recorded by the factory");
+
+    // The native principal came with the factory's create transfer. The escrow pays out
+    // everything it holds, leave it enough to cover its own storage
+    harness.root.transfer_near(&escrow, NearToken::from_near(1)).await?.into_result()?;
+
+    // accept_secret_reveal refuses while the dst escrow is in B1_FINALITY_LOCK
+    harness.worker.fast_forward(DST_WITHDRAWAL_SECS as u64 * 2).await?;
+    accept_secret_reveal(&swap_id, escrow.as_str(), &hex::encode(secret)).await.map_err(anyhow::Error::msg)?;
+
+    let maker_before = harness.maker.view_account().await?.balance;
+    send_funding_actions(
+        harness.funding_account.as_str(),
+        vec![function_call(
+            "withdraw",
+            json!({ "escrow": escrow, "secret": hex::encode(secret), "immutables": expected_immutables }),
+        )],
+    )
+    .await
+    .map_err(anyhow::Error::msg)?;
+    settle().await;
+
+    assert_eq!(confirm_withdrawal(escrow.as_str(), &swap_id).await, SwapStatus::Completed);
+    let maker_after = harness.maker.view_account().await?.balance;
+    assert_eq!(maker_after.as_yoctonear() - maker_before.as_yoctonear(), amount);
+
+    // Back on ETH: the revealed secret releases the maker's tokens to the resolver contract
+    harness.eth_fast_forward(DST_WITHDRAWAL_SECS as u64 * 2).await?;
+    let tx_hashes = withdraw_eth_escrow(src_escrow, secret, src_immutables, signed.maker_traits, Address::zero())
+        .await
+        .map_err(anyhow::Error::msg)?;
+    assert_eq!(tx_hashes.len(), 1);
+    assert_eq!(harness.evm.token_balance(src_escrow).await?, U256::zero());
+    assert_eq!(harness.evm.token_balance(eth_resolver).await?, making_amount);
+    Ok(())
+}